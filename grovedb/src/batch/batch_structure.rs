@@ -143,9 +143,62 @@ where
         let mut ops_by_qualified_paths: BTreeMap<Vec<Vec<u8>>, Op> = BTreeMap::new();
 
         for op in ops.into_iter() {
+            // A tree inserted with contents is inserted empty, its elements go
+            // straight to the ops on its new subtree
+            let (op, tree_elements) = match op.op {
+                Op::InsertTreeWithContents {
+                    is_sum_tree,
+                    elements,
+                } => {
+                    let element = if is_sum_tree {
+                        Element::empty_sum_tree()
+                    } else {
+                        Element::empty_tree()
+                    };
+                    let op = GroveDbOp {
+                        op: Op::Insert { element },
+                        ..op
+                    };
+                    (op, elements)
+                }
+                _ => (op, Vec::new()),
+            };
+            if !tree_elements.is_empty() {
+                let mut tree_path = op.path.clone();
+                tree_path.push(op.key.clone());
+                let qualified_path = tree_path.to_path();
+                let tree_ops: BTreeMap<KeyInfo, Op> = tree_elements
+                    .into_iter()
+                    .map(|(key, element)| {
+                        let mut qualified_path = qualified_path.clone();
+                        qualified_path.push(key.clone());
+                        let op = Op::Insert { element };
+                        ops_by_qualified_paths.insert(qualified_path, op.clone());
+                        (KeyInfo::KnownKey(key), op)
+                    })
+                    .collect();
+                let level = tree_path.len();
+                ops_by_level_paths
+                    .entry(level)
+                    .or_default()
+                    .entry(tree_path)
+                    .or_default()
+                    .extend(tree_ops);
+                if current_last_level < level {
+                    current_last_level = level;
+                }
+            }
+
             let mut path = op.path.clone();
             path.push(op.key.clone());
-            ops_by_qualified_paths.insert(path.to_path_consume(), op.op.clone());
+            if let Op::DeleteRange { .. } = op.op {
+                // an op on the lower bound of a range is applied after the range
+                ops_by_qualified_paths
+                    .entry(path.to_path_consume())
+                    .or_insert_with(|| op.op.clone());
+            } else {
+                ops_by_qualified_paths.insert(path.to_path_consume(), op.op.clone());
+            }
             let op_cost = OperationCost::default();
             let op_result = match &op.op {
                 Op::Insert { element } | Op::Replace { element } | Op::Patch { element, .. } => {
@@ -156,9 +209,17 @@ where
                     }
                     Ok(())
                 }
-                Op::RefreshReference { .. } | Op::Delete | Op::DeleteTree | Op::DeleteSumTree => {
-                    Ok(())
-                }
+                // Moves are only left in estimated batches, at their destination,
+                // as applied batches relink moved subtrees beforehand
+                Op::RefreshReference { .. }
+                | Op::Delete
+                | Op::DeleteTree
+                | Op::DeleteSumTree
+                | Op::DeleteRange { .. }
+                | Op::Move { .. } => Ok(()),
+                Op::InsertTreeWithContents { .. } => Err(Error::CorruptedCodeExecution(
+                    "trees inserted with contents are inserted empty beforehand",
+                )),
                 Op::ReplaceTreeRootKey { .. } | Op::InsertTreeWithRootHash { .. } => {
                     Err(Error::InvalidBatchOperation(
                         "replace and insert tree hash are internal operations only",
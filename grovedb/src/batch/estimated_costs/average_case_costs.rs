@@ -146,6 +146,20 @@ impl Op {
                 layer_element_estimates,
                 propagate,
             ),
+            // Estimated batches keep their moves at their destination
+            Op::Move { .. } => GroveDb::average_case_merk_insert_estimated_element(
+                key,
+                layer_element_estimates,
+                propagate,
+            ),
+            Op::DeleteRange { .. } => Err(Error::InvalidBatchOperation(
+                "range delete operations can not be estimated",
+            ))
+            .wrap_with_cost(OperationCost::default()),
+            Op::InsertTreeWithContents { .. } => Err(Error::CorruptedCodeExecution(
+                "trees inserted with contents are inserted empty before the batch is applied",
+            ))
+            .wrap_with_cost(OperationCost::default()),
         }
     }
 }
@@ -783,4 +797,69 @@ mod tests {
             cost.storage_cost.added_bytes
         );
     }
+
+    #[test]
+    fn test_batch_move_op_average_case_costs() {
+        let db = make_empty_grovedb();
+        let tx = db.start_transaction();
+
+        for key in [b"a", b"b"] {
+            db.insert(EMPTY_PATH, key, Element::empty_tree(), None, Some(&tx))
+                .unwrap()
+                .expect("successful root tree leaf insert");
+        }
+        db.insert(
+            [b"a"].as_ref(),
+            b"key1",
+            Element::new_item(b"cat".to_vec()),
+            None,
+            Some(&tx),
+        )
+        .unwrap()
+        .expect("successful item insert");
+
+        let ops = vec![GroveDbOp::move_op(
+            vec![b"a".to_vec()],
+            b"key1".to_vec(),
+            vec![b"b".to_vec()],
+            b"key2".to_vec(),
+        )];
+        let mut paths = HashMap::new();
+        paths.insert(
+            KeyInfoPath(vec![]),
+            EstimatedLayerInformation {
+                is_sum_tree: false,
+                estimated_layer_count: EstimatedLevel(1, false),
+                estimated_layer_sizes: AllSubtrees(1, NoSumTrees, None),
+            },
+        );
+        for key in [b"a", b"b"] {
+            paths.insert(
+                KeyInfoPath(vec![KeyInfo::KnownKey(key.to_vec())]),
+                EstimatedLayerInformation {
+                    is_sum_tree: false,
+                    estimated_layer_count: ApproximateElements(1),
+                    estimated_layer_sizes: AllItems(4, 3, None),
+                },
+            );
+        }
+
+        let average_case_cost = GroveDb::estimated_case_operations_for_batch(
+            AverageCaseCostsType(paths),
+            ops.clone(),
+            None,
+            |_cost, _old_flags, _new_flags| Ok(false),
+            |_flags, _removed_key_bytes, _removed_value_bytes| {
+                Ok((NoStorageRemoval, NoStorageRemoval))
+            },
+        )
+        .cost_as_result()
+        .expect("expected to estimate costs");
+        let cost = db.apply_batch(ops, None, Some(&tx)).cost;
+        // the moved item is inserted at its destination as estimated
+        assert_eq!(
+            average_case_cost.storage_cost.added_bytes,
+            cost.storage_cost.added_bytes
+        );
+    }
 }
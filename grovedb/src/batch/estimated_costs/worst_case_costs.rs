@@ -142,6 +142,21 @@ impl Op {
                 worst_case_layer_element_estimates,
                 propagate,
             ),
+            // Estimated batches keep their moves at their destination
+            Op::Move { .. } => GroveDb::worst_case_merk_insert_estimated_element(
+                key,
+                is_in_parent_sum_tree,
+                worst_case_layer_element_estimates,
+                propagate,
+            ),
+            Op::DeleteRange { .. } => Err(Error::InvalidBatchOperation(
+                "range delete operations can not be estimated",
+            ))
+            .wrap_with_cost(OperationCost::default()),
+            Op::InsertTreeWithContents { .. } => Err(Error::CorruptedCodeExecution(
+                "trees inserted with contents are inserted empty before the batch is applied",
+            ))
+            .wrap_with_cost(OperationCost::default()),
         }
     }
 }
@@ -575,4 +590,60 @@ mod tests {
             cost.storage_cost.added_bytes
         );
     }
+
+    #[test]
+    fn test_batch_move_op_worst_case_costs() {
+        let db = make_empty_grovedb();
+        let tx = db.start_transaction();
+
+        for key in [b"a", b"b"] {
+            db.insert(EMPTY_PATH, key, Element::empty_tree(), None, Some(&tx))
+                .unwrap()
+                .expect("successful root tree leaf insert");
+        }
+        db.insert(
+            [b"a"].as_ref(),
+            b"key1",
+            Element::new_item(b"cat".to_vec()),
+            None,
+            Some(&tx),
+        )
+        .unwrap()
+        .expect("successful item insert");
+
+        let ops = vec![GroveDbOp::move_op(
+            vec![b"a".to_vec()],
+            b"key1".to_vec(),
+            vec![b"b".to_vec()],
+            b"key2".to_vec(),
+        )];
+        let mut paths = HashMap::new();
+        paths.insert(KeyInfoPath(vec![]), MaxElementsNumber(2));
+        for key in [b"a", b"b"] {
+            paths.insert(
+                KeyInfoPath(vec![KeyInfo::KnownKey(key.to_vec())]),
+                MaxElementsNumber(1),
+            );
+        }
+
+        let worst_case_cost = GroveDb::estimated_case_operations_for_batch(
+            WorstCaseCostsType(paths),
+            ops.clone(),
+            None,
+            |_cost, _old_flags, _new_flags| Ok(false),
+            |_flags, _removed_key_bytes, _removed_value_bytes| {
+                Ok((NoStorageRemoval, NoStorageRemoval))
+            },
+        )
+        .cost_as_result()
+        .expect("expected to get worst case costs");
+
+        let cost = db.apply_batch(ops, None, Some(&tx)).cost;
+        assert!(
+            worst_case_cost.worse_or_eq_than(&cost),
+            "not worse {:?} \n than {:?}",
+            worst_case_cost,
+            cost
+        );
+    }
 }
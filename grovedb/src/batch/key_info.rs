@@ -40,7 +40,9 @@ use grovedb_storage::worst_case_costs::WorstKeyLength;
 use grovedb_visualize::{Drawer, Visualize};
 
 #[cfg(feature = "full")]
-use crate::batch::key_info::KeyInfo::{KnownKey, MaxKeySize};
+use crate::batch::key_info::KeyInfo::{KnownKey, MaxKeySize, Range};
+#[cfg(feature = "full")]
+use crate::QueryItem;
 
 /// Key info
 #[cfg(feature = "full")]
@@ -55,13 +57,14 @@ pub enum KeyInfo {
         /// Max size
         max_size: u8,
     },
+    /// Range of keys, the key of range deletes
+    Range(QueryItem),
 }
 
 #[cfg(feature = "full")]
 impl PartialEq for KeyInfo {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
-            (KnownKey(..), MaxKeySize { .. }) | (MaxKeySize { .. }, KnownKey(..)) => false,
             (KnownKey(a), KnownKey(b)) => a == b,
             (
                 MaxKeySize {
@@ -73,6 +76,8 @@ impl PartialEq for KeyInfo {
                     max_size: max_size_b,
                 },
             ) => unique_id_a == unique_id_b && max_size_a == max_size_b,
+            (Range(a), Range(b)) => a == b,
+            _ => false,
         }
     }
 }
@@ -100,20 +105,7 @@ impl PartialEq<&[u8]> for KeyInfo {
 #[cfg(feature = "full")]
 impl PartialOrd<Self> for KeyInfo {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        match self.as_slice().partial_cmp(other.as_slice()) {
-            None => None,
-            Some(ord) => match ord {
-                Ordering::Less => Some(Ordering::Less),
-                Ordering::Equal => {
-                    let other_len = other.max_length();
-                    match self.max_length().partial_cmp(&other_len) {
-                        None => Some(Ordering::Equal),
-                        Some(ord) => Some(ord),
-                    }
-                }
-                Ordering::Greater => Some(Ordering::Greater),
-            },
-        }
+        Some(self.cmp(other))
     }
 }
 
@@ -124,7 +116,15 @@ impl Ord for KeyInfo {
             Ordering::Less => Ordering::Less,
             Ordering::Equal => {
                 let other_len = other.max_length();
-                self.max_length().cmp(&other_len)
+                // ranges come after the keys they start at
+                self.max_length()
+                    .cmp(&other_len)
+                    .then_with(|| match (self, other) {
+                        (Range(a), Range(b)) => a.cmp(b),
+                        (Range(_), _) => Ordering::Greater,
+                        (_, Range(_)) => Ordering::Less,
+                        _ => Ordering::Equal,
+                    })
             }
             Ordering::Greater => Ordering::Greater,
         }
@@ -143,6 +143,7 @@ impl Hash for KeyInfo {
                 unique_id.hash(state);
                 max_size.hash(state);
             }
+            Range(range) => range.hash(state),
         }
     }
 }
@@ -153,25 +154,28 @@ impl WorstKeyLength for KeyInfo {
         match self {
             Self::KnownKey(key) => key.len() as u8,
             Self::MaxKeySize { max_size, .. } => *max_size,
+            Self::Range(_) => self.as_slice().len() as u8,
         }
     }
 }
 
 #[cfg(feature = "full")]
 impl KeyInfo {
-    /// Return self as slice
+    /// Return self as slice, ranges give their lower bound
     pub fn as_slice(&self) -> &[u8] {
         match self {
             KnownKey(key) => key.as_slice(),
             MaxKeySize { unique_id, .. } => unique_id.as_slice(),
+            Range(range) => range.lower_bound().0.unwrap_or_default(),
         }
     }
 
-    /// Return key
+    /// Return key, ranges give their lower bound
     pub fn get_key(self) -> Vec<u8> {
         match self {
             KnownKey(key) => key,
             MaxKeySize { unique_id, .. } => unique_id,
+            Range(_) => self.get_key_clone(),
         }
     }
 
@@ -180,6 +184,17 @@ impl KeyInfo {
         match self {
             KnownKey(key) => key.clone(),
             MaxKeySize { unique_id, .. } => unique_id.clone(),
+            Range(_) => self.as_slice().to_vec(),
+        }
+    }
+
+    /// Whether both key infos can stand for a same key, a range standing for
+    /// every key it contains
+    pub fn overlaps(&self, other: &KeyInfo) -> bool {
+        match (self, other) {
+            (Range(a), Range(b)) => a.collides_with(b),
+            (Range(range), KnownKey(key)) | (KnownKey(key), Range(range)) => range.contains(key),
+            _ => self == other,
         }
     }
 }
@@ -200,6 +215,9 @@ impl Visualize for KeyInfo {
                 drawer = unique_id.visualize(drawer)?;
                 drawer.write(format!(", max_size: {max_size}").as_bytes())?;
             }
+            Range(range) => {
+                drawer.write(format!("range: {range:?}").as_bytes())?;
+            }
         }
         Ok(drawer)
     }
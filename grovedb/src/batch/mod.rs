@@ -42,6 +42,11 @@ mod multi_insert_cost_tests;
 #[cfg(test)]
 mod just_in_time_cost_tests;
 mod options;
+mod parallel;
+mod precondition;
+mod report;
+mod serialize;
+mod simulate;
 #[cfg(test)]
 mod single_deletion_cost_tests;
 #[cfg(test)]
@@ -50,13 +55,14 @@ mod single_insert_cost_tests;
 mod single_sum_item_deletion_cost_tests;
 #[cfg(test)]
 mod single_sum_item_insert_cost_tests;
+mod undo;
 
 use core::fmt;
 use std::{
     cmp::Ordering,
     collections::{btree_map::Entry, hash_map::Entry as HashMapEntry, BTreeMap, HashMap},
     hash::{Hash, Hasher},
-    ops::{Add, AddAssign},
+    ops::{Add, AddAssign, Bound},
     slice::Iter,
     vec::IntoIter,
 };
@@ -83,27 +89,33 @@ use grovedb_merk::{
     TreeFeatureType::{BasicMerk, SummedMerk},
 };
 use grovedb_path::SubtreePath;
-use grovedb_storage::{
-    rocksdb_storage::{PrefixedRocksDbStorageContext, PrefixedRocksDbTransactionContext},
-    Storage, StorageBatch, StorageContext,
-};
+use grovedb_storage::{CovariantStorage, PreviousRecords, Storage, StorageBatch, StorageContext};
 use grovedb_visualize::{Drawer, Visualize};
 use integer_encoding::VarInt;
 use itertools::Itertools;
 use key_info::{KeyInfo, KeyInfo::KnownKey};
 pub use options::BatchApplyOptions;
+pub(crate) use parallel::BatchThreadPool;
+pub use precondition::Precondition;
+pub use report::{BatchApplyReport, BatchOpReport, BatchOpStatus};
+pub use serialize::BATCH_SERIALIZATION_VERSION;
+pub use simulate::{SimulatedBatch, SimulatedOpDiff};
+pub use undo::UndoBatch;
 
 pub use crate::batch::batch_structure::{OpsByLevelPath, OpsByPath};
 #[cfg(feature = "estimated_costs")]
 use crate::batch::estimated_costs::EstimatedCostsType;
 use crate::{
-    batch::{batch_structure::BatchStructure, mode::BatchRunMode},
+    batch::{
+        batch_structure::BatchStructure, mode::BatchRunMode, parallel::ParallelTreeCacheMerkByPath,
+        report::BatchTrace,
+    },
     element::{MaxReferenceHop, SUM_ITEM_COST_SIZE, SUM_TREE_COST_SIZE, TREE_COST_SIZE},
     operations::get::MAX_REFERENCE_HOPS,
     reference_path::{
         path_from_reference_path_type, path_from_reference_qualified_path_type, ReferencePathType,
     },
-    Element, ElementFlags, Error, GroveDb, Transaction, TransactionArg,
+    Element, ElementFlags, Error, GroveDb, QueryItem, Transaction, TransactionArg,
 };
 
 /// Operations
@@ -164,6 +176,29 @@ pub enum Op {
     DeleteTree,
     /// Delete sum tree
     DeleteSumTree,
+    /// Move the element, and everything below it if it is a tree, to a new
+    /// path and key
+    Move {
+        /// Path to move to
+        to_path: Vec<Vec<u8>>,
+        /// Key to move to
+        to_key: Vec<u8>,
+    },
+    /// Delete every element in a range of keys, before the other operations
+    /// at the path are applied
+    DeleteRange {
+        /// Range of keys to delete
+        query_item: QueryItem,
+    },
+    /// Insert a new tree together with the elements it holds, which are
+    /// loaded into the new subtree in one pass. Subtrees of the new tree come
+    /// with operations of their own.
+    InsertTreeWithContents {
+        /// Whether the tree is a sum tree
+        is_sum_tree: bool,
+        /// Elements of the tree that are not trees, sorted by key
+        elements: Vec<(Vec<u8>, Element)>,
+    },
 }
 
 impl PartialOrd for Op {
@@ -349,6 +384,21 @@ impl KeyInfoPath {
     }
 }
 
+/// Content of a subtree inserted with
+/// [`GroveDbOp::insert_tree_with_contents`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubtreeContent {
+    /// An element that is not a tree
+    Element(Element),
+    /// A tree or sum tree and its own contents
+    Tree {
+        /// Whether the tree is a sum tree
+        is_sum_tree: bool,
+        /// Contents of the tree
+        contents: Vec<(Vec<u8>, SubtreeContent)>,
+    },
+}
+
 /// Batch operation
 #[derive(Clone, PartialEq, Eq)]
 pub struct GroveDbOp {
@@ -358,6 +408,8 @@ pub struct GroveDbOp {
     pub key: KeyInfo,
     /// Operation to perform on the key
     pub op: Op,
+    /// Condition on the current state of the key checked before applying
+    pub precondition: Option<Precondition>,
 }
 
 impl fmt::Debug for GroveDbOp {
@@ -395,6 +447,14 @@ impl fmt::Debug for GroveDbOp {
             Op::Delete => "Delete",
             Op::DeleteTree => "Delete Tree",
             Op::DeleteSumTree => "Delete Sum Tree",
+            Op::Move { .. } => "Move",
+            Op::DeleteRange { .. } => "Delete Range",
+            Op::InsertTreeWithContents {
+                is_sum_tree: false, ..
+            } => "Insert Tree With Contents",
+            Op::InsertTreeWithContents {
+                is_sum_tree: true, ..
+            } => "Insert Sum Tree With Contents",
             Op::ReplaceTreeRootKey { .. } => "Replace Tree Hash and Root Key",
             Op::InsertTreeWithRootHash { .. } => "Insert Tree Hash and Root Key",
         };
@@ -403,6 +463,7 @@ impl fmt::Debug for GroveDbOp {
             .field("path", &String::from_utf8_lossy(&path_out))
             .field("key", &String::from_utf8_lossy(&key_out))
             .field("op", &op_dbg)
+            .field("precondition", &self.precondition)
             .finish()
     }
 }
@@ -415,6 +476,7 @@ impl GroveDbOp {
             path,
             key: KnownKey(key),
             op: Op::Insert { element },
+            precondition: None,
         }
     }
 
@@ -424,6 +486,7 @@ impl GroveDbOp {
             path,
             key,
             op: Op::Insert { element },
+            precondition: None,
         }
     }
 
@@ -434,6 +497,7 @@ impl GroveDbOp {
             path,
             key: KnownKey(key),
             op: Op::Replace { element },
+            precondition: None,
         }
     }
 
@@ -443,6 +507,7 @@ impl GroveDbOp {
             path,
             key,
             op: Op::Replace { element },
+            precondition: None,
         }
     }
 
@@ -461,6 +526,7 @@ impl GroveDbOp {
                 element,
                 change_in_bytes,
             },
+            precondition: None,
         }
     }
 
@@ -478,6 +544,7 @@ impl GroveDbOp {
                 element,
                 change_in_bytes,
             },
+            precondition: None,
         }
     }
 
@@ -500,6 +567,7 @@ impl GroveDbOp {
                 flags,
                 trust_refresh_reference,
             },
+            precondition: None,
         }
     }
 
@@ -510,6 +578,7 @@ impl GroveDbOp {
             path,
             key: KnownKey(key),
             op: Op::Delete,
+            precondition: None,
         }
     }
 
@@ -524,6 +593,7 @@ impl GroveDbOp {
             } else {
                 Op::DeleteTree
             },
+            precondition: None,
         }
     }
 
@@ -533,6 +603,7 @@ impl GroveDbOp {
             path,
             key,
             op: Op::Delete,
+            precondition: None,
         }
     }
 
@@ -546,7 +617,113 @@ impl GroveDbOp {
             } else {
                 Op::DeleteTree
             },
+            precondition: None,
+        }
+    }
+
+    /// A delete range op using a known owned path
+    pub fn delete_range_op(path: Vec<Vec<u8>>, query_item: QueryItem) -> Self {
+        let path = KeyInfoPath::from_known_owned_path(path);
+        Self {
+            path,
+            key: KeyInfo::Range(query_item.clone()),
+            op: Op::DeleteRange { query_item },
+            precondition: None,
+        }
+    }
+
+    /// A move op using known owned paths and known keys
+    pub fn move_op(
+        path: Vec<Vec<u8>>,
+        key: Vec<u8>,
+        to_path: Vec<Vec<u8>>,
+        to_key: Vec<u8>,
+    ) -> Self {
+        let path = KeyInfoPath::from_known_owned_path(path);
+        Self {
+            path,
+            key: KnownKey(key),
+            op: Op::Move { to_path, to_key },
+            precondition: None,
+        }
+    }
+
+    /// Insert ops for a tree or sum tree together with all of its contents,
+    /// one op per tree. The elements of each tree are loaded into its new
+    /// subtree in one pass, bottom-up, instead of being inserted one by one.
+    /// Trees can't be given as elements, they come with their contents.
+    pub fn insert_tree_with_contents(
+        path: Vec<Vec<u8>>,
+        key: Vec<u8>,
+        is_sum_tree: bool,
+        contents: Vec<(Vec<u8>, SubtreeContent)>,
+    ) -> Result<Vec<Self>, Error> {
+        let mut ops = Vec::new();
+        Self::push_tree_with_contents_ops(&mut ops, path, key, is_sum_tree, contents)?;
+        Ok(ops)
+    }
+
+    fn push_tree_with_contents_ops(
+        ops: &mut Vec<Self>,
+        path: Vec<Vec<u8>>,
+        key: Vec<u8>,
+        is_sum_tree: bool,
+        contents: Vec<(Vec<u8>, SubtreeContent)>,
+    ) -> Result<(), Error> {
+        let mut keys: Vec<&[u8]> = contents.iter().map(|(key, _)| key.as_slice()).collect();
+        keys.sort_unstable();
+        if keys.windows(2).any(|pair| pair[0] == pair[1]) {
+            return Err(Error::InvalidBatchOperation(
+                "tree contents have a repeated key",
+            ));
+        }
+
+        let mut tree_path = path.clone();
+        tree_path.push(key.clone());
+        let mut elements = Vec::new();
+        let mut subtree_ops = Vec::new();
+        for (child_key, child) in contents {
+            match child {
+                SubtreeContent::Element(Element::Tree(..) | Element::SumTree(..)) => {
+                    return Err(Error::InvalidBatchOperation(
+                        "trees inserted with contents must be given as subtree contents",
+                    ))
+                }
+                SubtreeContent::Element(element) => elements.push((child_key, element)),
+                SubtreeContent::Tree {
+                    is_sum_tree,
+                    contents,
+                } => Self::push_tree_with_contents_ops(
+                    &mut subtree_ops,
+                    tree_path.clone(),
+                    child_key,
+                    is_sum_tree,
+                    contents,
+                )?,
+            }
         }
+        elements.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        ops.push(Self {
+            path: KeyInfoPath::from_known_owned_path(path),
+            key: KnownKey(key),
+            op: Op::InsertTreeWithContents {
+                is_sum_tree,
+                elements,
+            },
+            precondition: None,
+        });
+        ops.append(&mut subtree_ops);
+        Ok(())
+    }
+
+    /// Sets a precondition that must hold before the batch is applied.
+    /// Committing the batch, or the transaction it is applied in, fails with
+    /// `Error::TransactionConflict` if the record the precondition reads is
+    /// changed by someone else meanwhile.
+    pub fn with_precondition(mut self, precondition: Precondition) -> Self {
+        self.precondition = Some(precondition);
+        self
     }
 
     /// Verify consistency of operations
@@ -581,7 +758,7 @@ impl GroveDbOp {
                 .1
                 .iter()
                 .filter_map(|current_op| {
-                    if current_op.path == op.path && current_op.key == op.key {
+                    if current_op.path == op.path && current_op.key.overlaps(&op.key) {
                         Some(current_op.op.clone())
                     } else {
                         None
@@ -597,7 +774,9 @@ impl GroveDbOp {
         let inserts = ops
             .iter()
             .filter_map(|current_op| match current_op.op {
-                Op::Insert { .. } | Op::Replace { .. } => Some(current_op.clone()),
+                Op::Insert { .. } | Op::Replace { .. } | Op::InsertTreeWithContents { .. } => {
+                    Some(current_op.clone())
+                }
                 _ => None,
             })
             .collect::<Vec<GroveDbOp>>();
@@ -605,7 +784,7 @@ impl GroveDbOp {
         let deletes = ops
             .iter()
             .filter_map(|current_op| {
-                if let Op::Delete = current_op.op {
+                if let Op::Delete | Op::DeleteRange { .. } = current_op.op {
                     Some(current_op.clone())
                 } else {
                     None
@@ -617,16 +796,18 @@ impl GroveDbOp {
 
         // No inserts under a deleted path
         for deleted_op in deletes.iter() {
-            let mut deleted_qualified_path = deleted_op.path.clone();
-            deleted_qualified_path.push(deleted_op.key.clone());
             let inserts_with_deleted_ops_above = inserts
                 .iter()
                 .filter_map(|inserted_op| {
                     if deleted_op.path.len() < inserted_op.path.len()
-                        && deleted_qualified_path
+                        && deleted_op
+                            .path
                             .iterator()
                             .zip(inserted_op.path.iterator())
                             .all(|(a, b)| a == b)
+                        && deleted_op
+                            .key
+                            .overlaps(&inserted_op.path.0[deleted_op.path.0.len()])
                     {
                         Some(inserted_op.clone())
                     } else {
@@ -667,12 +848,14 @@ impl GroveDbOpConsistencyResults {
 }
 
 /// Cache for Merk trees by their paths.
-struct TreeCacheMerkByPath<S, F> {
+struct TreeCacheMerkByPath<'t, S, F> {
     merks: HashMap<Vec<Vec<u8>>, Merk<S>>,
     get_merk_fn: F,
+    /// Collects path costs and failures when a batch report is requested
+    trace: Option<&'t mut BatchTrace>,
 }
 
-impl<S, F> fmt::Debug for TreeCacheMerkByPath<S, F> {
+impl<S, F> fmt::Debug for TreeCacheMerkByPath<'_, S, F> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("TreeCacheMerkByPath").finish()
     }
@@ -694,10 +877,62 @@ trait TreeCache<G, SR> {
         split_removal_bytes: &mut SR,
     ) -> CostResult<RootHashKeyAndSum, Error>;
 
+    /// Executes the ops of several paths of the same level, results are given
+    /// in the order of the paths
+    fn execute_ops_on_paths(
+        &mut self,
+        ops_by_path: Vec<(KeyInfoPath, BTreeMap<KeyInfo, Op>)>,
+        ops_by_qualified_paths: &BTreeMap<Vec<Vec<u8>>, Op>,
+        batch_apply_options: &BatchApplyOptions,
+        flags_update: &mut G,
+        split_removal_bytes: &mut SR,
+    ) -> CostResult<Vec<(KeyInfoPath, RootHashKeyAndSum)>, Error> {
+        let mut cost = OperationCost::default();
+        let mut results = Vec::with_capacity(ops_by_path.len());
+        for (path, ops_at_path) in ops_by_path {
+            let result = cost_return_on_error!(
+                &mut cost,
+                self.execute_ops_on_path(
+                    &path,
+                    ops_at_path,
+                    ops_by_qualified_paths,
+                    batch_apply_options,
+                    flags_update,
+                    split_removal_bytes,
+                )
+            );
+            results.push((path, result));
+        }
+        Ok(results).wrap_with_cost(cost)
+    }
+
     fn update_base_merk_root_key(&mut self, root_key: Option<Vec<u8>>) -> CostResult<(), Error>;
+
+    /// Records the op the batch failed at for batch reports
+    fn record_failed_op(&mut self, _path: &KeyInfoPath, _key: &KeyInfo) {}
+}
+
+/// Whether a range delete of the batch removes the element at a qualified
+/// path. Range deletes are keyed by their lower bound, so they come before
+/// the keys they contain.
+fn is_deleted_by_range(
+    qualified_path: &[Vec<u8>],
+    ops_by_qualified_paths: &BTreeMap<Vec<Vec<u8>>, Op>,
+) -> bool {
+    let Some((key, path)) = qualified_path.split_last() else {
+        return false;
+    };
+    ops_by_qualified_paths
+        .range::<[Vec<u8>], _>((Bound::Unbounded, Bound::Included(qualified_path)))
+        .rev()
+        .take_while(|(other_path, _)| other_path.starts_with(path))
+        .any(|(other_path, op)| {
+            other_path.len() == qualified_path.len()
+                && matches!(op, Op::DeleteRange { query_item } if query_item.contains(key))
+        })
 }
 
-impl<'db, S, F> TreeCacheMerkByPath<S, F>
+impl<'db, S, F> TreeCacheMerkByPath<'_, S, F>
 where
     F: FnMut(&[Vec<u8>], bool) -> CostResult<Merk<S>, Error>,
     S: StorageContext<'db>,
@@ -883,9 +1118,11 @@ where
         if let Some(op) = ops_by_qualified_paths.get(qualified_path) {
             // the path is being modified, inserted or deleted in the batch of operations
             match op {
-                Op::ReplaceTreeRootKey { .. } | Op::InsertTreeWithRootHash { .. } => Err(
-                    Error::InvalidBatchOperation("references can not point to trees being updated"),
-                )
+                Op::ReplaceTreeRootKey { .. }
+                | Op::InsertTreeWithRootHash { .. }
+                | Op::InsertTreeWithContents { .. } => Err(Error::InvalidBatchOperation(
+                    "references can not point to trees being updated",
+                ))
                 .wrap_with_cost(cost),
                 Op::Insert { element } | Op::Replace { element } | Op::Patch { element, .. } => {
                     match element {
@@ -935,13 +1172,22 @@ where
                         reference_info,
                     )
                 }
-                Op::Delete | Op::DeleteTree | Op::DeleteSumTree => {
+                Op::Delete | Op::DeleteTree | Op::DeleteSumTree | Op::DeleteRange { .. } => {
                     Err(Error::InvalidBatchOperation(
                         "references can not point to something currently being deleted",
                     ))
                     .wrap_with_cost(cost)
                 }
+                Op::Move { .. } => Err(Error::CorruptedCodeExecution(
+                    "move operations are expanded before the batch is applied",
+                ))
+                .wrap_with_cost(cost),
             }
+        } else if is_deleted_by_range(qualified_path, ops_by_qualified_paths) {
+            Err(Error::InvalidBatchOperation(
+                "references can not point to something currently being deleted",
+            ))
+            .wrap_with_cost(cost)
         } else {
             self.process_reference(
                 qualified_path,
@@ -951,72 +1197,38 @@ where
             )
         }
     }
-}
-
-impl<'db, S, F, G, SR> TreeCache<G, SR> for TreeCacheMerkByPath<S, F>
-where
-    G: FnMut(&StorageCost, Option<ElementFlags>, &mut ElementFlags) -> Result<bool, Error>,
-    SR: FnMut(
-        &mut ElementFlags,
-        u32,
-        u32,
-    ) -> Result<(StorageRemovedBytes, StorageRemovedBytes), Error>,
-    F: FnMut(&[Vec<u8>], bool) -> CostResult<Merk<S>, Error>,
-    S: StorageContext<'db>,
-{
-    fn insert(&mut self, op: &GroveDbOp, is_sum_tree: bool) -> CostResult<(), Error> {
-        let mut cost = OperationCost::default();
-
-        let mut inserted_path = op.path.to_path();
-        inserted_path.push(op.key.get_key_clone());
-        if let HashMapEntry::Vacant(e) = self.merks.entry(inserted_path.clone()) {
-            let mut merk =
-                cost_return_on_error!(&mut cost, (self.get_merk_fn)(&inserted_path, true));
-            merk.is_sum_tree = is_sum_tree;
-            e.insert(merk);
-        }
-
-        Ok(()).wrap_with_cost(cost)
-    }
-
-    fn update_base_merk_root_key(&mut self, root_key: Option<Vec<u8>>) -> CostResult<(), Error> {
-        let mut cost = OperationCost::default();
-        let base_path = vec![];
-        let merk_wrapped = self
-            .merks
-            .remove(&base_path)
-            .map(|x| Ok(x).wrap_with_cost(Default::default()))
-            .unwrap_or_else(|| (self.get_merk_fn)(&[], false));
-        let mut merk = cost_return_on_error!(&mut cost, merk_wrapped);
-        merk.set_base_root_key(root_key)
-            .add_cost(cost)
-            .map_err(|_| Error::InternalError("unable to set base root key"))
-    }
 
-    fn execute_ops_on_path(
-        &mut self,
-        path: &KeyInfoPath,
+    /// Applies the operations at a path on its Merk, references are resolved
+    /// with `follow_reference`. If `failed_key` is given it's set to the key of
+    /// the op that failed, it stays `None` for failures of the whole path.
+    fn execute_ops_on_merk<G, SR>(
+        merk: &mut Merk<S>,
+        path: &[Vec<u8>],
         ops_at_path_by_key: BTreeMap<KeyInfo, Op>,
-        ops_by_qualified_paths: &BTreeMap<Vec<Vec<u8>>, Op>,
         batch_apply_options: &BatchApplyOptions,
+        follow_reference: &mut impl FnMut(&[Vec<u8>], u8) -> CostResult<CryptoHash, Error>,
         flags_update: &mut G,
         split_removal_bytes: &mut SR,
-    ) -> CostResult<RootHashKeyAndSum, Error> {
+        mut failed_key: Option<&mut Option<Vec<u8>>>,
+    ) -> CostResult<RootHashKeyAndSum, Error>
+    where
+        G: FnMut(&StorageCost, Option<ElementFlags>, &mut ElementFlags) -> Result<bool, Error>,
+        SR: FnMut(
+            &mut ElementFlags,
+            u32,
+            u32,
+        ) -> Result<(StorageRemovedBytes, StorageRemovedBytes), Error>,
+    {
         let mut cost = OperationCost::default();
-        // todo: fix this
-        let p = path.to_path();
-        let path = &p;
 
-        let merk_wrapped = self
-            .merks
-            .remove(path)
-            .map(|x| Ok(x).wrap_with_cost(Default::default()))
-            .unwrap_or_else(|| (self.get_merk_fn)(path, false));
-        let mut merk = cost_return_on_error!(&mut cost, merk_wrapped);
         let is_sum_tree = merk.is_sum_tree;
 
         let mut batch_operations: Vec<(Vec<u8>, _)> = vec![];
+        let mut delete_ranges: Vec<QueryItem> = vec![];
         for (key_info, op) in ops_at_path_by_key.into_iter() {
+            if let Some(failed_key) = failed_key.as_deref_mut() {
+                *failed_key = Some(key_info.get_key_clone());
+            }
             match op {
                 Op::Insert { element } | Op::Replace { element } | Op::Patch { element, .. } => {
                     match &element {
@@ -1045,9 +1257,8 @@ where
 
                             let referenced_element_value_hash = cost_return_on_error!(
                                 &mut cost,
-                                self.follow_reference_get_value_hash(
+                                (follow_reference)(
                                     path_reference.as_slice(),
-                                    ops_by_qualified_paths,
                                     element_max_reference_hop.unwrap_or(MAX_REFERENCE_HOPS as u8)
                                 )
                             );
@@ -1091,7 +1302,7 @@ where
                                 let inserted = cost_return_on_error!(
                                     &mut cost,
                                     element.insert_if_not_exists_into_batch_operations(
-                                        &mut merk,
+                                        merk,
                                         key_info.get_key(),
                                         &mut batch_operations,
                                         merk_feature_type
@@ -1150,7 +1361,8 @@ where
                     let Element::Reference(path_reference, max_reference_hop, _) = &element else {
                         return Err(Error::InvalidInput(
                             "trying to refresh a an element that is not a reference",
-                        )).wrap_with_cost(cost)
+                        ))
+                        .wrap_with_cost(cost);
                     };
 
                     let merk_feature_type = if is_sum_tree {
@@ -1177,9 +1389,8 @@ where
 
                     let referenced_element_value_hash = cost_return_on_error!(
                         &mut cost,
-                        self.follow_reference_get_value_hash(
+                        (follow_reference)(
                             path_reference.as_slice(),
-                            ops_by_qualified_paths,
                             max_reference_hop.unwrap_or(MAX_REFERENCE_HOPS as u8)
                         )
                     );
@@ -1236,7 +1447,7 @@ where
                     cost_return_on_error!(
                         &mut cost,
                         GroveDb::update_tree_item_preserve_flag_into_batch_operations(
-                            &merk,
+                            merk,
                             key_info.get_key(),
                             root_key,
                             hash,
@@ -1271,11 +1482,31 @@ where
                         )
                     );
                 }
+                Op::DeleteRange { query_item } => {
+                    delete_ranges.push(query_item);
+                }
+                Op::Move { .. } => {
+                    return Err(Error::CorruptedCodeExecution(
+                        "move operations are expanded before the batch is applied",
+                    ))
+                    .wrap_with_cost(cost);
+                }
+                Op::InsertTreeWithContents { .. } => {
+                    return Err(Error::CorruptedCodeExecution(
+                        "trees inserted with contents are inserted empty before the batch is \
+                         applied",
+                    ))
+                    .wrap_with_cost(cost);
+                }
             }
         }
+        if let Some(failed_key) = failed_key {
+            *failed_key = None;
+        }
         cost_return_on_error!(
             &mut cost,
-            merk.apply_unchecked::<_, Vec<u8>, _, _, _>(
+            merk.apply_with_range_deletes_unchecked::<_, Vec<u8>, _, _, _>(
+                &delete_ranges,
                 &batch_operations,
                 &[],
                 Some(batch_apply_options.as_merk_options()),
@@ -1358,21 +1589,122 @@ where
             )
             .map_err(|e| Error::CorruptedData(e.to_string()))
         );
-        let r = merk
-            .root_hash_key_and_sum()
+        merk.root_hash_key_and_sum()
+            .add_cost(cost)
+            .map_err(Error::MerkError)
+    }
+}
+
+impl<'db, S, F, G, SR> TreeCache<G, SR> for TreeCacheMerkByPath<'_, S, F>
+where
+    G: FnMut(&StorageCost, Option<ElementFlags>, &mut ElementFlags) -> Result<bool, Error>,
+    SR: FnMut(
+        &mut ElementFlags,
+        u32,
+        u32,
+    ) -> Result<(StorageRemovedBytes, StorageRemovedBytes), Error>,
+    F: FnMut(&[Vec<u8>], bool) -> CostResult<Merk<S>, Error>,
+    S: StorageContext<'db>,
+{
+    fn insert(&mut self, op: &GroveDbOp, is_sum_tree: bool) -> CostResult<(), Error> {
+        let mut cost = OperationCost::default();
+
+        let mut inserted_path = op.path.to_path();
+        inserted_path.push(op.key.get_key_clone());
+        if let HashMapEntry::Vacant(e) = self.merks.entry(inserted_path.clone()) {
+            let mut merk =
+                cost_return_on_error!(&mut cost, (self.get_merk_fn)(&inserted_path, true));
+            merk.is_sum_tree = is_sum_tree;
+            e.insert(merk);
+        }
+
+        Ok(()).wrap_with_cost(cost)
+    }
+
+    fn update_base_merk_root_key(&mut self, root_key: Option<Vec<u8>>) -> CostResult<(), Error> {
+        let mut cost = OperationCost::default();
+        let base_path = vec![];
+        let merk_wrapped = self
+            .merks
+            .remove(&base_path)
+            .map(|x| Ok(x).wrap_with_cost(Default::default()))
+            .unwrap_or_else(|| (self.get_merk_fn)(&[], false));
+        let mut merk = cost_return_on_error!(&mut cost, merk_wrapped);
+        merk.set_base_root_key(root_key)
             .add_cost(cost)
-            .map_err(Error::MerkError);
+            .map_err(|_| Error::InternalError("unable to set base root key"))
+    }
+
+    fn execute_ops_on_path(
+        &mut self,
+        path: &KeyInfoPath,
+        ops_at_path_by_key: BTreeMap<KeyInfo, Op>,
+        ops_by_qualified_paths: &BTreeMap<Vec<Vec<u8>>, Op>,
+        batch_apply_options: &BatchApplyOptions,
+        flags_update: &mut G,
+        split_removal_bytes: &mut SR,
+    ) -> CostResult<RootHashKeyAndSum, Error> {
+        let mut cost = OperationCost::default();
+        // todo: fix this
+        let p = path.to_path();
+        let path = &p;
+
+        let merk_wrapped = self
+            .merks
+            .remove(path)
+            .map(|x| Ok(x).wrap_with_cost(Default::default()))
+            .unwrap_or_else(|| (self.get_merk_fn)(path, false));
+        let mut merk = match merk_wrapped.unwrap_add_cost(&mut cost) {
+            Ok(merk) => merk,
+            Err(e) => {
+                if let Some(trace) = self.trace.as_deref_mut() {
+                    trace.record_failure(path, None);
+                }
+                return Err(e).wrap_with_cost(cost);
+            }
+        };
+        let mut failed_key = None;
+        let tracing = self.trace.is_some();
+        let r = Self::execute_ops_on_merk(
+            &mut merk,
+            path,
+            ops_at_path_by_key,
+            batch_apply_options,
+            &mut |reference_path, max_reference_hop| {
+                self.follow_reference_get_value_hash(
+                    reference_path,
+                    ops_by_qualified_paths,
+                    max_reference_hop,
+                )
+            },
+            flags_update,
+            split_removal_bytes,
+            tracing.then_some(&mut failed_key),
+        )
+        .add_cost(cost);
+        if let Some(trace) = self.trace.as_deref_mut() {
+            trace.record_path(path, failed_key, &r);
+            if let (true, Ok((root_hash, ..))) = (path.is_empty(), &r.value) {
+                trace.record_root_hash(*root_hash);
+            }
+        }
         // We need to reinsert the merk
         self.merks.insert(path.clone(), merk);
         r
     }
 
+    fn record_failed_op(&mut self, path: &KeyInfoPath, key: &KeyInfo) {
+        if let Some(trace) = self.trace.as_deref_mut() {
+            trace.record_failure(&path.to_path(), Some(key.get_key_clone()));
+        }
+    }
+
     fn get_batch_run_mode(&self) -> BatchRunMode {
         BatchRunMode::Execute
     }
 }
 
-impl GroveDb {
+impl<S: CovariantStorage> GroveDb<S> {
     /// Method to propagate updated subtree root hashes up to GroveDB root
     /// If the stop level is set in the apply options the remaining operations
     /// are returned
@@ -1404,8 +1736,8 @@ impl GroveDb {
 
         // We will update up the tree
         while let Some(ops_at_level) = ops_by_level_paths.remove(&current_level) {
-            for (path, ops_at_path) in ops_at_level.into_iter() {
-                if current_level == 0 {
+            if current_level == 0 {
+                for (path, ops_at_path) in ops_at_level.into_iter() {
                     // execute the ops at this path
                     // ignoring sum as root tree cannot be summed
                     let (_root_hash, calculated_root_key, _sum) = cost_return_on_error!(
@@ -1435,114 +1767,137 @@ impl GroveDb {
                             merk_tree_cache.update_base_merk_root_key(calculated_root_key)
                         );
                     }
-                } else {
-                    let (root_hash, calculated_root_key, sum_value) = cost_return_on_error!(
-                        &mut cost,
-                        merk_tree_cache.execute_ops_on_path(
-                            &path,
-                            ops_at_path,
-                            &ops_by_qualified_paths,
-                            &batch_apply_options,
-                            &mut flags_update,
-                            &mut split_removal_bytes,
-                        )
-                    );
-
-                    if current_level > 0 {
-                        // We need to propagate up this root hash, this means adding grove_db
-                        // operations up for the level above
-                        if let Some((key, parent_path)) = path.split_last() {
-                            if let Some(ops_at_level_above) =
-                                ops_by_level_paths.get_mut(&(current_level - 1))
-                            {
-                                // todo: fix this hack
-                                let parent_path = KeyInfoPath(parent_path.to_vec());
-                                if let Some(ops_on_path) = ops_at_level_above.get_mut(&parent_path)
-                                {
-                                    match ops_on_path.entry(key.clone()) {
-                                        Entry::Vacant(vacant_entry) => {
-                                            vacant_entry.insert(Op::ReplaceTreeRootKey {
-                                                hash: root_hash,
-                                                root_key: calculated_root_key,
-                                                sum: sum_value,
-                                            });
+                }
+            } else {
+                let executed_paths = cost_return_on_error!(
+                    &mut cost,
+                    merk_tree_cache.execute_ops_on_paths(
+                        ops_at_level.into_iter().collect(),
+                        &ops_by_qualified_paths,
+                        &batch_apply_options,
+                        &mut flags_update,
+                        &mut split_removal_bytes,
+                    )
+                );
+                for (path, (root_hash, calculated_root_key, sum_value)) in executed_paths {
+                    // We need to propagate up this root hash, this means adding grove_db
+                    // operations up for the level above
+                    if let Some((key, parent_path)) = path.split_last() {
+                        if let Some(ops_at_level_above) =
+                            ops_by_level_paths.get_mut(&(current_level - 1))
+                        {
+                            // todo: fix this hack
+                            let parent_path = KeyInfoPath(parent_path.to_vec());
+                            if let Some(ops_on_path) = ops_at_level_above.get_mut(&parent_path) {
+                                let deleted_by_range = ops_on_path.values().any(|op| {
+                                    matches!(op, Op::DeleteRange { query_item }
+                                        if query_item.contains(key.as_slice()))
+                                });
+                                match ops_on_path.entry(key.clone()) {
+                                    Entry::Vacant(_) if deleted_by_range => {
+                                        if calculated_root_key.is_some() {
+                                            merk_tree_cache.record_failed_op(&parent_path, key);
+                                            return Err(Error::InvalidBatchOperation(
+                                                "modification of tree when it will be deleted",
+                                            ))
+                                            .wrap_with_cost(cost);
                                         }
-                                        Entry::Occupied(occupied_entry) => {
-                                            let mutable_occupied_entry = occupied_entry.into_mut();
-                                            match mutable_occupied_entry {
-                                                Op::ReplaceTreeRootKey {
-                                                    hash,
-                                                    root_key,
-                                                    sum,
-                                                } => {
-                                                    *hash = root_hash;
-                                                    *root_key = calculated_root_key;
-                                                    *sum = sum_value;
-                                                }
-                                                Op::InsertTreeWithRootHash { .. } => {
-                                                    return Err(Error::CorruptedCodeExecution(
-                                                        "we can not do this operation twice",
+                                    }
+                                    Entry::Vacant(vacant_entry) => {
+                                        vacant_entry.insert(Op::ReplaceTreeRootKey {
+                                            hash: root_hash,
+                                            root_key: calculated_root_key,
+                                            sum: sum_value,
+                                        });
+                                    }
+                                    Entry::Occupied(occupied_entry) => {
+                                        let mutable_occupied_entry = occupied_entry.into_mut();
+                                        match mutable_occupied_entry {
+                                            Op::ReplaceTreeRootKey {
+                                                hash,
+                                                root_key,
+                                                sum,
+                                            } => {
+                                                *hash = root_hash;
+                                                *root_key = calculated_root_key;
+                                                *sum = sum_value;
+                                            }
+                                            Op::InsertTreeWithRootHash { .. } => {
+                                                return Err(Error::CorruptedCodeExecution(
+                                                    "we can not do this operation twice",
+                                                ))
+                                                .wrap_with_cost(cost);
+                                            }
+                                            Op::Insert { element }
+                                            | Op::Replace { element }
+                                            | Op::Patch { element, .. } => {
+                                                if let Element::Tree(_, flags) = element {
+                                                    *mutable_occupied_entry =
+                                                        Op::InsertTreeWithRootHash {
+                                                            hash: root_hash,
+                                                            root_key: calculated_root_key,
+                                                            flags: flags.clone(),
+                                                            sum: None,
+                                                        };
+                                                } else if let Element::SumTree(.., flags) = element
+                                                {
+                                                    *mutable_occupied_entry =
+                                                        Op::InsertTreeWithRootHash {
+                                                            hash: root_hash,
+                                                            root_key: calculated_root_key,
+                                                            flags: flags.clone(),
+                                                            sum: sum_value,
+                                                        };
+                                                } else {
+                                                    merk_tree_cache
+                                                        .record_failed_op(&parent_path, key);
+                                                    return Err(Error::InvalidBatchOperation(
+                                                        "insertion of element under a non tree",
                                                     ))
                                                     .wrap_with_cost(cost);
                                                 }
-                                                Op::Insert { element }
-                                                | Op::Replace { element }
-                                                | Op::Patch { element, .. } => {
-                                                    if let Element::Tree(_, flags) = element {
-                                                        *mutable_occupied_entry =
-                                                            Op::InsertTreeWithRootHash {
-                                                                hash: root_hash,
-                                                                root_key: calculated_root_key,
-                                                                flags: flags.clone(),
-                                                                sum: None,
-                                                            };
-                                                    } else if let Element::SumTree(.., flags) =
-                                                        element
-                                                    {
-                                                        *mutable_occupied_entry =
-                                                            Op::InsertTreeWithRootHash {
-                                                                hash: root_hash,
-                                                                root_key: calculated_root_key,
-                                                                flags: flags.clone(),
-                                                                sum: sum_value,
-                                                            };
-                                                    } else {
-                                                        return Err(Error::InvalidBatchOperation(
-                                                            "insertion of element under a non tree",
-                                                        ))
-                                                        .wrap_with_cost(cost);
-                                                    }
-                                                }
-                                                Op::RefreshReference { .. } => {
+                                            }
+                                            Op::RefreshReference { .. } => {
+                                                merk_tree_cache.record_failed_op(&parent_path, key);
+                                                return Err(Error::InvalidBatchOperation(
+                                                    "insertion of element under a refreshed \
+                                                     reference",
+                                                ))
+                                                .wrap_with_cost(cost);
+                                            }
+                                            Op::Delete | Op::DeleteTree | Op::DeleteSumTree => {
+                                                if calculated_root_key.is_some() {
+                                                    merk_tree_cache
+                                                        .record_failed_op(&parent_path, key);
                                                     return Err(Error::InvalidBatchOperation(
-                                                        "insertion of element under a refreshed \
-                                                         reference",
+                                                        "modification of tree when it will be \
+                                                         deleted",
                                                     ))
                                                     .wrap_with_cost(cost);
                                                 }
-                                                Op::Delete | Op::DeleteTree | Op::DeleteSumTree => {
-                                                    if calculated_root_key.is_some() {
-                                                        return Err(Error::InvalidBatchOperation(
-                                                            "modification of tree when it will be \
-                                                             deleted",
-                                                        ))
-                                                        .wrap_with_cost(cost);
-                                                    }
-                                                }
+                                            }
+                                            Op::Move { .. } => {
+                                                return Err(Error::CorruptedCodeExecution(
+                                                    "move operations are expanded before the \
+                                                     batch is applied",
+                                                ))
+                                                .wrap_with_cost(cost);
+                                            }
+                                            Op::InsertTreeWithContents { .. } => {
+                                                return Err(Error::CorruptedCodeExecution(
+                                                    "trees inserted with contents are inserted \
+                                                     empty before the batch is applied",
+                                                ))
+                                                .wrap_with_cost(cost);
+                                            }
+                                            Op::DeleteRange { .. } => {
+                                                return Err(Error::CorruptedCodeExecution(
+                                                    "range deletes are not keyed by a tree key",
+                                                ))
+                                                .wrap_with_cost(cost);
                                             }
                                         }
                                     }
-                                } else {
-                                    let mut ops_on_path: BTreeMap<KeyInfo, Op> = BTreeMap::new();
-                                    ops_on_path.insert(
-                                        key.clone(),
-                                        Op::ReplaceTreeRootKey {
-                                            hash: root_hash,
-                                            root_key: calculated_root_key,
-                                            sum: sum_value,
-                                        },
-                                    );
-                                    ops_at_level_above.insert(parent_path, ops_on_path);
                                 }
                             } else {
                                 let mut ops_on_path: BTreeMap<KeyInfo, Op> = BTreeMap::new();
@@ -1554,11 +1909,22 @@ impl GroveDb {
                                         sum: sum_value,
                                     },
                                 );
-                                let mut ops_on_level: BTreeMap<KeyInfoPath, BTreeMap<KeyInfo, Op>> =
-                                    BTreeMap::new();
-                                ops_on_level.insert(KeyInfoPath(parent_path.to_vec()), ops_on_path);
-                                ops_by_level_paths.insert(current_level - 1, ops_on_level);
+                                ops_at_level_above.insert(parent_path, ops_on_path);
                             }
+                        } else {
+                            let mut ops_on_path: BTreeMap<KeyInfo, Op> = BTreeMap::new();
+                            ops_on_path.insert(
+                                key.clone(),
+                                Op::ReplaceTreeRootKey {
+                                    hash: root_hash,
+                                    root_key: calculated_root_key,
+                                    sum: sum_value,
+                                },
+                            );
+                            let mut ops_on_level: BTreeMap<KeyInfoPath, BTreeMap<KeyInfo, Op>> =
+                                BTreeMap::new();
+                            ops_on_level.insert(KeyInfoPath(parent_path.to_vec()), ops_on_path);
+                            ops_by_level_paths.insert(current_level - 1, ops_on_level);
                         }
                     }
                 }
@@ -1577,7 +1943,7 @@ impl GroveDb {
     /// Method to propagate updated subtree root hashes up to GroveDB root
     /// If the pause height is set in the batch apply options
     /// Then return the list of leftover operations
-    fn apply_body<'db, S: StorageContext<'db>>(
+    fn apply_body<'db, C: StorageContext<'db>>(
         &self,
         ops: Vec<GroveDbOp>,
         batch_apply_options: Option<BatchApplyOptions>,
@@ -1594,7 +1960,8 @@ impl GroveDb {
             (StorageRemovedBytes, StorageRemovedBytes),
             Error,
         >,
-        get_merk_fn: impl FnMut(&[Vec<u8>], bool) -> CostResult<Merk<S>, Error>,
+        get_merk_fn: impl FnMut(&[Vec<u8>], bool) -> CostResult<Merk<C>, Error>,
+        trace: Option<&mut BatchTrace>,
     ) -> CostResult<Option<OpsByLevelPath>, Error> {
         let mut cost = OperationCost::default();
         let batch_structure = cost_return_on_error!(
@@ -1606,18 +1973,72 @@ impl GroveDb {
                 TreeCacheMerkByPath {
                     merks: Default::default(),
                     get_merk_fn,
+                    trace,
                 }
             )
         );
         Self::apply_batch_structure(batch_structure, batch_apply_options).add_cost(cost)
     }
 
-    /// Method to propagate updated subtree root hashes up to GroveDB root
-    /// If the pause height is set in the batch apply options
-    /// Then return the list of leftover operations
-    fn continue_partial_apply_body<'db, S: StorageContext<'db>>(
+    /// Same as `apply_body`, but the subtrees of a level are spread over the
+    /// batch thread pool if it's asked for and no other batch uses it. Only
+    /// used without a transaction, as a transaction can't be read by several
+    /// threads.
+    fn apply_body_on_threads<'db, C: StorageContext<'db> + Send>(
         &self,
-        previous_leftover_operations: Option<OpsByLevelPath>,
+        ops: Vec<GroveDbOp>,
+        batch_apply_options: Option<BatchApplyOptions>,
+        update_element_flags_function: impl FnMut(
+            &StorageCost,
+            Option<ElementFlags>,
+            &mut ElementFlags,
+        ) -> Result<bool, Error>,
+        split_removed_bytes_function: impl FnMut(
+            &mut ElementFlags,
+            u32, // key removed bytes
+            u32, // value removed bytes
+        ) -> Result<
+            (StorageRemovedBytes, StorageRemovedBytes),
+            Error,
+        >,
+        get_merk_fn: impl FnMut(&[Vec<u8>], bool) -> CostResult<Merk<C>, Error>,
+        trace: Option<&mut BatchTrace>,
+    ) -> CostResult<Option<OpsByLevelPath>, Error> {
+        let mut cost = OperationCost::default();
+        let threads = batch_apply_options
+            .as_ref()
+            .and_then(|options| options.batch_thread_pool_size)
+            .unwrap_or(1);
+        let pool = if threads > 1 {
+            cost_return_on_error_no_add!(&cost, self.batch_thread_pool.take(threads))
+        } else {
+            None
+        };
+        let batch_structure = cost_return_on_error!(
+            &mut cost,
+            BatchStructure::from_ops(
+                ops,
+                update_element_flags_function,
+                split_removed_bytes_function,
+                ParallelTreeCacheMerkByPath {
+                    cache: TreeCacheMerkByPath {
+                        merks: Default::default(),
+                        get_merk_fn,
+                        trace,
+                    },
+                    pool: pool.as_ref().and_then(|pool| pool.as_ref()),
+                }
+            )
+        );
+        Self::apply_batch_structure(batch_structure, batch_apply_options).add_cost(cost)
+    }
+
+    /// Method to propagate updated subtree root hashes up to GroveDB root
+    /// If the pause height is set in the batch apply options
+    /// Then return the list of leftover operations
+    fn continue_partial_apply_body<'db, C: StorageContext<'db>>(
+        &self,
+        previous_leftover_operations: Option<OpsByLevelPath>,
         additional_ops: Vec<GroveDbOp>,
         batch_apply_options: Option<BatchApplyOptions>,
         update_element_flags_function: impl FnMut(
@@ -1633,7 +2054,7 @@ impl GroveDb {
             (StorageRemovedBytes, StorageRemovedBytes),
             Error,
         >,
-        get_merk_fn: impl FnMut(&[Vec<u8>], bool) -> CostResult<Merk<S>, Error>,
+        get_merk_fn: impl FnMut(&[Vec<u8>], bool) -> CostResult<Merk<C>, Error>,
     ) -> CostResult<Option<OpsByLevelPath>, Error> {
         let mut cost = OperationCost::default();
         let batch_structure = cost_return_on_error!(
@@ -1646,6 +2067,7 @@ impl GroveDb {
                 TreeCacheMerkByPath {
                     merks: Default::default(),
                     get_merk_fn,
+                    trace: None,
                 }
             )
         );
@@ -1657,9 +2079,23 @@ impl GroveDb {
         &self,
         ops: Vec<GroveDbOp>,
         options: Option<BatchApplyOptions>,
-        transaction: TransactionArg,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<(), Error> {
+        self.with_precondition_transaction(ops, transaction, |ops, transaction| {
+            self.apply_operations_one_by_one(ops, options, transaction)
+        })
+    }
+
+    /// Checks the preconditions of the operations then applies them one by
+    /// one
+    fn apply_operations_one_by_one(
+        &self,
+        ops: Vec<GroveDbOp>,
+        options: Option<BatchApplyOptions>,
+        transaction: TransactionArg<S>,
     ) -> CostResult<(), Error> {
         let mut cost = OperationCost::default();
+        cost_return_on_error!(&mut cost, self.check_batch_preconditions(&ops, transaction));
         for op in ops.into_iter() {
             match op.op {
                 Op::Insert { element } | Op::Replace { element } => {
@@ -1690,6 +2126,80 @@ impl GroveDb {
                         )
                     );
                 }
+                Op::Move { to_path, to_key } => {
+                    let path_slices: Vec<&[u8]> =
+                        op.path.iterator().map(|p| p.as_slice()).collect();
+                    cost_return_on_error!(
+                        &mut cost,
+                        self.move_element(
+                            path_slices.as_slice(),
+                            op.key.as_slice(),
+                            to_path.as_slice(),
+                            to_key.as_slice(),
+                            options.clone(),
+                            transaction
+                        )
+                    );
+                }
+                Op::InsertTreeWithContents {
+                    is_sum_tree,
+                    elements,
+                } => {
+                    let path_slices: Vec<&[u8]> =
+                        op.path.iterator().map(|p| p.as_slice()).collect();
+                    let element = if is_sum_tree {
+                        Element::empty_sum_tree()
+                    } else {
+                        Element::empty_tree()
+                    };
+                    cost_return_on_error!(
+                        &mut cost,
+                        self.insert(
+                            path_slices.as_slice(),
+                            op.key.as_slice(),
+                            element,
+                            options.clone().map(|o| o.as_insert_options()),
+                            transaction,
+                        )
+                    );
+                    let mut tree_path = path_slices;
+                    tree_path.push(op.key.as_slice());
+                    for (key, element) in elements {
+                        cost_return_on_error!(
+                            &mut cost,
+                            self.insert(
+                                tree_path.as_slice(),
+                                key.as_slice(),
+                                element,
+                                options.clone().map(|o| o.as_insert_options()),
+                                transaction,
+                            )
+                        );
+                    }
+                }
+                Op::DeleteRange { query_item } => {
+                    let path_slices: Vec<&[u8]> =
+                        op.path.iterator().map(|p| p.as_slice()).collect();
+                    let delete_options = options
+                        .as_ref()
+                        .map(|o| o.as_delete_options())
+                        .unwrap_or_default();
+                    let elements_in_range = cost_return_on_error!(
+                        &mut cost,
+                        self.elements_in_range(&op.path.to_path(), query_item, transaction)
+                    );
+                    for (key, _) in elements_in_range {
+                        cost_return_on_error!(
+                            &mut cost,
+                            self.delete(
+                                path_slices.as_slice(),
+                                key.as_slice(),
+                                Some(delete_options.clone()),
+                                transaction
+                            )
+                        );
+                    }
+                }
                 _ => {}
             }
         }
@@ -1701,7 +2211,7 @@ impl GroveDb {
         &self,
         ops: Vec<GroveDbOp>,
         batch_apply_options: Option<BatchApplyOptions>,
-        transaction: TransactionArg,
+        transaction: TransactionArg<S>,
     ) -> CostResult<(), Error> {
         self.apply_batch_with_element_flags_update(
             ops,
@@ -1717,42 +2227,17 @@ impl GroveDb {
         )
     }
 
-    /// Applies batch on GroveDB
-    pub fn apply_partial_batch(
-        &self,
-        ops: Vec<GroveDbOp>,
-        batch_apply_options: Option<BatchApplyOptions>,
-        cost_based_add_on_operations: impl FnMut(
-            &OperationCost,
-            &Option<OpsByLevelPath>,
-        ) -> Result<Vec<GroveDbOp>, Error>,
-        transaction: TransactionArg,
-    ) -> CostResult<(), Error> {
-        self.apply_partial_batch_with_element_flags_update(
-            ops,
-            batch_apply_options,
-            |_cost, _old_flags, _new_flags| Ok(false),
-            |_flags, key_bytes_to_remove, value_bytes_to_remove| {
-                Ok((
-                    BasicStorageRemoval(key_bytes_to_remove),
-                    BasicStorageRemoval(value_bytes_to_remove),
-                ))
-            },
-            cost_based_add_on_operations,
-            transaction,
-        )
-    }
-
     /// Opens transactional merk at path with given storage batch context.
     /// Returns CostResult.
     pub fn open_batch_transactional_merk_at_path<'db, B: AsRef<[u8]>>(
         &'db self,
         storage_batch: &'db StorageBatch,
         path: SubtreePath<B>,
-        tx: &'db Transaction,
+        tx: &'db Transaction<S>,
         new_merk: bool,
-    ) -> CostResult<Merk<PrefixedRocksDbTransactionContext<'db>>, Error> {
+    ) -> CostResult<Merk<<S as Storage<'db>>::BatchTransactionalStorageContext>, Error> {
         let mut cost = OperationCost::default();
+        let tx = self.db.narrow_transaction(tx);
         let storage = self
             .db
             .get_transactional_storage_context(path.clone(), Some(storage_batch), tx)
@@ -1809,7 +2294,7 @@ impl GroveDb {
         storage_batch: &'a StorageBatch,
         path: SubtreePath<B>,
         new_merk: bool,
-    ) -> CostResult<Merk<PrefixedRocksDbStorageContext>, Error> {
+    ) -> CostResult<Merk<<S as Storage<'a>>::BatchStorageContext>, Error> {
         let mut local_cost = OperationCost::default();
         let storage = self
             .db
@@ -1870,19 +2355,49 @@ impl GroveDb {
             (StorageRemovedBytes, StorageRemovedBytes),
             Error,
         >,
-        transaction: TransactionArg,
+        transaction: TransactionArg<S>,
     ) -> CostResult<(), Error> {
-        let mut cost = OperationCost::default();
+        let cost = OperationCost::default();
 
         if ops.is_empty() {
             return Ok(()).wrap_with_cost(cost);
         }
 
+        self.with_precondition_transaction(ops, transaction, |ops, transaction| {
+            let mut cost = OperationCost::default();
+            let ops = cost_return_on_error!(
+                &mut cost,
+                self.expand_batch(ops, batch_apply_options.as_ref(), transaction)
+            );
+
+            self.apply_expanded_batch(
+                ops,
+                batch_apply_options,
+                update_element_flags_function,
+                split_removal_bytes_function,
+                transaction,
+                None,
+                None,
+            )
+            .add_cost(cost)
+        })
+        .add_cost(cost)
+    }
+
+    /// Checks the consistency and preconditions of a batch, then expands its
+    /// moves into the ops they are made of and checks its range deletes
+    fn expand_batch(
+        &self,
+        ops: Vec<GroveDbOp>,
+        batch_apply_options: Option<&BatchApplyOptions>,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<Vec<GroveDbOp>, Error> {
+        let mut cost = OperationCost::default();
+
         // Determines whether to check batch operation consistency
         // return false if the disable option is set to true, returns true for any other
         // case
         let check_batch_operation_consistency = batch_apply_options
-            .as_ref()
             .map(|batch_options| !batch_options.disable_operation_consistency_check)
             .unwrap_or(true);
 
@@ -1896,69 +2411,270 @@ impl GroveDb {
             }
         }
 
-        // `StorageBatch` allows us to collect operations on different subtrees before
-        // execution
-        let storage_batch = StorageBatch::new();
-
-        // With the only one difference (if there is a transaction) do the following:
-        // 2. If nothing left to do and we were on a non-leaf subtree or we're done with
-        //    one subtree and moved to another then add propagation operation to the
-        //    operations tree and drop Merk handle;
-        // 3. Take Merk from temp subtrees or open a new one with batched storage_cost
-        //    context;
-        // 4. Apply operation to the Merk;
-        // 5. Remove operation from the tree, repeat until there are operations to do;
-        // 6. Add root leaves save operation to the batch
-        // 7. Apply storage_cost batch
-        if let Some(tx) = transaction {
-            cost_return_on_error!(
-                &mut cost,
-                self.apply_body(
-                    ops,
-                    batch_apply_options,
-                    update_element_flags_function,
-                    split_removal_bytes_function,
-                    |path, new_merk| {
-                        self.open_batch_transactional_merk_at_path(
-                            &storage_batch,
-                            path.into(),
-                            tx,
-                            new_merk,
-                        )
-                    }
-                )
-            );
+        // Preconditions are checked before moves are expanded into the ops they
+        // are made of and range deletes are checked, as those ops don't carry them
+        cost_return_on_error!(&mut cost, self.check_batch_preconditions(&ops, transaction));
+        let ops = cost_return_on_error!(&mut cost, self.expand_move_ops(ops, transaction));
+        self.check_delete_range_ops(
+            ops,
+            &batch_apply_options
+                .map(|o| o.as_delete_options())
+                .unwrap_or_default(),
+            transaction,
+        )
+        .add_cost(cost)
+    }
 
-            // TODO: compute batch costs
-            cost_return_on_error!(
-                &mut cost,
-                self.db
-                    .commit_multi_context_batch(storage_batch, Some(tx))
-                    .map_err(|e| e.into())
-            );
+    /// Applies and commits a batch whose moves were already expanded and range
+    /// deletes checked, optionally tracing it for a batch report and reading
+    /// the previous values of the records it writes
+    #[allow(clippy::too_many_arguments)]
+    fn apply_expanded_batch(
+        &self,
+        ops: Vec<GroveDbOp>,
+        batch_apply_options: Option<BatchApplyOptions>,
+        update_element_flags_function: impl FnMut(
+            &StorageCost,
+            Option<ElementFlags>,
+            &mut ElementFlags,
+        ) -> Result<bool, Error>,
+        split_removal_bytes_function: impl FnMut(
+            &mut ElementFlags,
+            u32, // key removed bytes
+            u32, // value removed bytes
+        ) -> Result<
+            (StorageRemovedBytes, StorageRemovedBytes),
+            Error,
+        >,
+        transaction: TransactionArg<S>,
+        trace: Option<&mut BatchTrace>,
+        previous_records: Option<&mut PreviousRecords>,
+    ) -> CostResult<(), Error> {
+        let mut cost = OperationCost::default();
+
+        let changed_elements = if self.change_feed {
+            cost_return_on_error!(&mut cost, self.changed_elements(&ops, transaction))
         } else {
-            cost_return_on_error!(
-                &mut cost,
-                self.apply_body(
-                    ops,
-                    batch_apply_options,
-                    update_element_flags_function,
-                    split_removal_bytes_function,
-                    |path, new_merk| {
-                        self.open_batch_merk_at_path(&storage_batch, path.into(), new_merk)
+            Vec::new()
+        };
+        let (tree_moves, ops): (Vec<_>, Vec<_>) = ops
+            .into_iter()
+            .partition(|op| matches!(op.op, Op::Move { .. }));
+
+        self.capture_changes(changed_elements, transaction, |transaction| {
+            self.with_moved_subtrees(
+                tree_moves,
+                transaction,
+                trace,
+                previous_records,
+                |transaction, mut trace, previous_records| {
+                    let mut cost = OperationCost::default();
+
+                    // `StorageBatch` allows us to collect operations on different subtrees before
+                    // execution
+                    let storage_batch = StorageBatch::new();
+                    cost_return_on_error!(
+                        &mut cost,
+                        self.delete_prefixes_of_deleted_trees(&ops, &storage_batch, transaction)
+                    );
+
+                    // With the only one difference (if there is a transaction) do the following:
+                    // 2. If nothing left to do and we were on a non-leaf subtree or we're done with
+                    //    one subtree and moved to another then add propagation operation to the
+                    //    operations tree and drop Merk handle;
+                    // 3. Take Merk from temp subtrees or open a new one with batched storage_cost
+                    //    context;
+                    // 4. Apply operation to the Merk;
+                    // 5. Remove operation from the tree, repeat until there are operations to do;
+                    // 6. Add root leaves save operation to the batch
+                    // 7. Apply storage_cost batch
+                    if let Some(tx) = transaction {
+                        cost_return_on_error!(
+                            &mut cost,
+                            self.apply_body(
+                                ops,
+                                batch_apply_options,
+                                update_element_flags_function,
+                                split_removal_bytes_function,
+                                |path, new_merk| {
+                                    self.open_batch_transactional_merk_at_path(
+                                        &storage_batch,
+                                        path.into(),
+                                        tx,
+                                        new_merk,
+                                    )
+                                },
+                                trace.as_deref_mut()
+                            )
+                        );
+                        if let Some(trace) = trace {
+                            cost_return_on_error_no_add!(
+                                &cost,
+                                trace.record_storage_costs(&storage_batch)
+                            );
+                        }
+
+                        let tx = self.db.narrow_transaction(tx);
+                        if let Some(previous_records) = previous_records {
+                            *previous_records = cost_return_on_error!(
+                                &mut cost,
+                                self.db
+                                    .previous_records(&storage_batch, Some(tx))
+                                    .map_err(|e| e.into())
+                            );
+                        }
+
+                        // TODO: compute batch costs
+                        cost_return_on_error!(
+                            &mut cost,
+                            self.db
+                                .commit_multi_context_batch(storage_batch, Some(tx))
+                                .map_err(|e| e.into())
+                        );
+                    } else {
+                        cost_return_on_error!(
+                            &mut cost,
+                            self.apply_body_on_threads(
+                                ops,
+                                batch_apply_options,
+                                update_element_flags_function,
+                                split_removal_bytes_function,
+                                |path, new_merk| {
+                                    self.open_batch_merk_at_path(
+                                        &storage_batch,
+                                        path.into(),
+                                        new_merk,
+                                    )
+                                },
+                                trace.as_deref_mut()
+                            )
+                        );
+                        if let Some(trace) = trace {
+                            cost_return_on_error_no_add!(
+                                &cost,
+                                trace.record_storage_costs(&storage_batch)
+                            );
+                        }
+
+                        if let Some(previous_records) = previous_records {
+                            *previous_records = cost_return_on_error!(
+                                &mut cost,
+                                self.db
+                                    .previous_records(&storage_batch, None)
+                                    .map_err(|e| e.into())
+                            );
+                        }
+
+                        // TODO: compute batch costs
+                        cost_return_on_error!(
+                            &mut cost,
+                            self.db
+                                .commit_multi_context_batch(storage_batch, None)
+                                .map_err(|e| e.into())
+                        );
                     }
-                )
-            );
+                    Ok(()).wrap_with_cost(cost)
+                },
+            )
+        })
+        .add_cost(cost)
+    }
 
-            // TODO: compute batch costs
-            cost_return_on_error!(
+    /// Runs `apply` once the subtrees moved by the move ops kept in an
+    /// expanded batch are relinked at their destinations. They are relinked
+    /// in a transaction of their own if none is given, and the transaction is
+    /// rolled back to before the relinking if `apply` fails. The previous
+    /// values of the records the relinking writes take precedence over those
+    /// `apply` reads.
+    fn with_moved_subtrees<T>(
+        &self,
+        tree_moves: Vec<GroveDbOp>,
+        transaction: TransactionArg<S>,
+        mut trace: Option<&mut BatchTrace>,
+        mut previous_records: Option<&mut PreviousRecords>,
+        apply: impl FnOnce(
+            TransactionArg<S>,
+            Option<&mut BatchTrace>,
+            Option<&mut PreviousRecords>,
+        ) -> CostResult<T, Error>,
+    ) -> CostResult<T, Error> {
+        let mut cost = OperationCost::default();
+
+        if tree_moves.is_empty() {
+            return apply(transaction, trace, previous_records);
+        }
+        let Some(tx) = transaction else {
+            let tx = self.start_transaction();
+            let result = cost_return_on_error!(
                 &mut cost,
-                self.db
-                    .commit_multi_context_batch(storage_batch, None)
-                    .map_err(|e| e.into())
+                self.with_moved_subtrees(tree_moves, Some(&tx), trace, previous_records, apply)
             );
+            cost_return_on_error!(&mut cost, self.commit_transaction(tx));
+            return Ok(result).wrap_with_cost(cost);
+        };
+
+        self.set_savepoint(tx);
+        let mut relink_records = previous_records.is_some().then(PreviousRecords::default);
+        let mut result = Ok(());
+        for op in tree_moves.iter() {
+            let relinked = self.relink_moved_subtree(op, tx, relink_records.as_mut());
+            if let Some(trace) = trace.as_deref_mut() {
+                // Charged to the path of the moved subtree, as the move op is
+                trace.record_path(&op.path.to_path(), Some(op.key.get_key_clone()), &relinked);
+            }
+            result = relinked.unwrap_add_cost(&mut cost);
+            if result.is_err() {
+                break;
+            }
         }
-        Ok(()).wrap_with_cost(cost)
+        let result = result.and_then(|_| {
+            apply(Some(tx), trace, previous_records.as_deref_mut()).unwrap_add_cost(&mut cost)
+        });
+
+        match result {
+            Ok(value) => {
+                cost_return_on_error_no_add!(&cost, self.release_savepoint(tx));
+                if let (Some(previous_records), Some(mut relink_records)) =
+                    (previous_records, relink_records)
+                {
+                    relink_records.append(std::mem::take(previous_records));
+                    *previous_records = relink_records;
+                }
+                Ok(value).wrap_with_cost(cost)
+            }
+            Err(e) => {
+                cost_return_on_error_no_add!(&cost, self.rollback_to_savepoint(tx));
+                Err(e).wrap_with_cost(cost)
+            }
+        }
+    }
+}
+
+impl GroveDb {
+    /// Applies batch on GroveDB
+    pub fn apply_partial_batch(
+        &self,
+        ops: Vec<GroveDbOp>,
+        batch_apply_options: Option<BatchApplyOptions>,
+        cost_based_add_on_operations: impl FnMut(
+            &OperationCost,
+            &Option<OpsByLevelPath>,
+        ) -> Result<Vec<GroveDbOp>, Error>,
+        transaction: TransactionArg,
+    ) -> CostResult<(), Error> {
+        self.apply_partial_batch_with_element_flags_update(
+            ops,
+            batch_apply_options,
+            |_cost, _old_flags, _new_flags| Ok(false),
+            |_flags, key_bytes_to_remove, value_bytes_to_remove| {
+                Ok((
+                    BasicStorageRemoval(key_bytes_to_remove),
+                    BasicStorageRemoval(value_bytes_to_remove),
+                ))
+            },
+            cost_based_add_on_operations,
+            transaction,
+        )
     }
 
     /// Applies a partial batch of operations on GroveDB
@@ -1969,12 +2685,12 @@ impl GroveDb {
         &self,
         ops: Vec<GroveDbOp>,
         batch_apply_options: Option<BatchApplyOptions>,
-        mut update_element_flags_function: impl FnMut(
+        update_element_flags_function: impl FnMut(
             &StorageCost,
             Option<ElementFlags>,
             &mut ElementFlags,
         ) -> Result<bool, Error>,
-        mut split_removal_bytes_function: impl FnMut(
+        split_removal_bytes_function: impl FnMut(
             &mut ElementFlags,
             u32, // key removed bytes
             u32, // value removed bytes
@@ -1982,18 +2698,27 @@ impl GroveDb {
             (StorageRemovedBytes, StorageRemovedBytes),
             Error,
         >,
-        mut add_on_operations: impl FnMut(
+        add_on_operations: impl FnMut(
             &OperationCost,
             &Option<OpsByLevelPath>,
         ) -> Result<Vec<GroveDbOp>, Error>,
         transaction: TransactionArg,
     ) -> CostResult<(), Error> {
-        let mut cost = OperationCost::default();
+        let cost = OperationCost::default();
 
         if ops.is_empty() {
             return Ok(()).wrap_with_cost(cost);
         }
 
+        // add on operations are only known once the batch is applied, so their
+        // changes can't be recorded
+        if self.change_feed {
+            return Err(Error::NotSupported(
+                "partial batches can't be applied with the change feed enabled",
+            ))
+            .wrap_with_cost(cost);
+        }
+
         let mut batch_apply_options = batch_apply_options.unwrap_or_default();
         if batch_apply_options.batch_pause_height.is_none() {
             // we default to pausing at the root tree, which is the most common case
@@ -2016,175 +2741,273 @@ impl GroveDb {
             }
         }
 
-        // `StorageBatch` allows us to collect operations on different subtrees before
-        // execution
-        let storage_batch = StorageBatch::new();
-
-        // With the only one difference (if there is a transaction) do the following:
-        // 2. If nothing left to do and we were on a non-leaf subtree or we're done with
-        //    one subtree and moved to another then add propagation operation to the
-        //    operations tree and drop Merk handle;
-        // 3. Take Merk from temp subtrees or open a new one with batched storage_cost
-        //    context;
-        // 4. Apply operation to the Merk;
-        // 5. Remove operation from the tree, repeat until there are operations to do;
-        // 6. Add root leaves save operation to the batch
-        // 7. Apply storage_cost batch
-        if let Some(tx) = transaction {
-            let left_over_operations = cost_return_on_error!(
-                &mut cost,
-                self.apply_body(
-                    ops,
-                    Some(batch_apply_options.clone()),
-                    &mut update_element_flags_function,
-                    &mut split_removal_bytes_function,
-                    |path, new_merk| {
-                        self.open_batch_transactional_merk_at_path(
-                            &storage_batch,
-                            path.into(),
-                            tx,
-                            new_merk,
-                        )
-                    }
-                )
-            );
-            // if we paused at the root height, the left over operations would be to replace
-            // a lot of leaf nodes in the root tree
+        self.with_precondition_transaction(ops, transaction, |ops, transaction| {
+            self.apply_consistent_partial_batch(
+                ops,
+                batch_apply_options,
+                update_element_flags_function,
+                split_removal_bytes_function,
+                add_on_operations,
+                transaction,
+            )
+        })
+        .add_cost(cost)
+    }
+
+    /// Applies a partial batch whose consistency was already checked
+    fn apply_consistent_partial_batch(
+        &self,
+        ops: Vec<GroveDbOp>,
+        mut batch_apply_options: BatchApplyOptions,
+        mut update_element_flags_function: impl FnMut(
+            &StorageCost,
+            Option<ElementFlags>,
+            &mut ElementFlags,
+        ) -> Result<bool, Error>,
+        mut split_removal_bytes_function: impl FnMut(
+            &mut ElementFlags,
+            u32, // key removed bytes
+            u32, // value removed bytes
+        ) -> Result<
+            (StorageRemovedBytes, StorageRemovedBytes),
+            Error,
+        >,
+        mut add_on_operations: impl FnMut(
+            &OperationCost,
+            &Option<OpsByLevelPath>,
+        ) -> Result<Vec<GroveDbOp>, Error>,
+        transaction: TransactionArg,
+    ) -> CostResult<(), Error> {
+        let mut cost = OperationCost::default();
+
+        cost_return_on_error!(&mut cost, self.check_batch_preconditions(&ops, transaction));
+        let ops = cost_return_on_error!(&mut cost, self.expand_move_ops(ops, transaction));
+        let ops = cost_return_on_error!(
+            &mut cost,
+            self.check_delete_range_ops(ops, &batch_apply_options.as_delete_options(), transaction)
+        );
+        let (tree_moves, ops): (Vec<_>, Vec<_>) = ops
+            .into_iter()
+            .partition(|op| matches!(op.op, Op::Move { .. }));
 
-            // let's build the write batch
-            let (mut write_batch, mut pending_costs) = cost_return_on_error!(
+        self.with_moved_subtrees(tree_moves, transaction, None, None, |transaction, _, _| {
+            let mut cost = OperationCost::default();
+
+            // `StorageBatch` allows us to collect operations on different subtrees before
+            // execution
+            let storage_batch = StorageBatch::new();
+            cost_return_on_error!(
                 &mut cost,
-                self.db
-                    .build_write_batch(storage_batch)
-                    .map_err(|e| e.into())
+                self.delete_prefixes_of_deleted_trees(&ops, &storage_batch, transaction)
             );
 
-            let total_current_costs = cost.clone().add(pending_costs.clone());
+            // With the only one difference (if there is a transaction) do the following:
+            // 2. If nothing left to do and we were on a non-leaf subtree or we're done with
+            //    one subtree and moved to another then add propagation operation to the
+            //    operations tree and drop Merk handle;
+            // 3. Take Merk from temp subtrees or open a new one with batched storage_cost
+            //    context;
+            // 4. Apply operation to the Merk;
+            // 5. Remove operation from the tree, repeat until there are operations to do;
+            // 6. Add root leaves save operation to the batch
+            // 7. Apply storage_cost batch
+            if let Some(tx) = transaction {
+                let left_over_operations = cost_return_on_error!(
+                    &mut cost,
+                    self.apply_body(
+                        ops,
+                        Some(batch_apply_options.clone()),
+                        &mut update_element_flags_function,
+                        &mut split_removal_bytes_function,
+                        |path, new_merk| {
+                            self.open_batch_transactional_merk_at_path(
+                                &storage_batch,
+                                path.into(),
+                                tx,
+                                new_merk,
+                            )
+                        },
+                        None
+                    )
+                );
+                // if we paused at the root height, the left over operations would be to replace
+                // a lot of leaf nodes in the root tree
 
-            // todo: estimate root costs
+                // let's build the write batch
+                let (mut write_batch, mut pending_costs) = cost_return_on_error!(
+                    &mut cost,
+                    self.db
+                        .build_write_batch(storage_batch, Some(tx))
+                        .map_err(|e| e.into())
+                );
 
-            // at this point we need to send the pending costs back
-            // we will get GroveDB a new set of GroveDBOps
+                let total_current_costs = cost.clone().add(pending_costs.clone());
 
-            let new_operations = cost_return_on_error_no_add!(
-                &cost,
-                add_on_operations(&total_current_costs, &left_over_operations)
-            );
+                // todo: estimate root costs
 
-            // we are trying to finalize
-            batch_apply_options.batch_pause_height = None;
+                // at this point we need to send the pending costs back
+                // we will get GroveDB a new set of GroveDBOps
 
-            let continue_storage_batch = StorageBatch::new();
+                let new_operations = cost_return_on_error_no_add!(
+                    &cost,
+                    add_on_operations(&total_current_costs, &left_over_operations)
+                );
+                if new_operations
+                    .iter()
+                    .any(|op| matches!(op.op, Op::Move { .. }))
+                {
+                    return Err(Error::InvalidBatchOperation(
+                        "move operations can not be added to a partial batch",
+                    ))
+                    .wrap_with_cost(cost);
+                }
 
-            cost_return_on_error!(
-                &mut cost,
-                self.continue_partial_apply_body(
-                    left_over_operations,
-                    new_operations,
-                    Some(batch_apply_options),
-                    update_element_flags_function,
-                    split_removal_bytes_function,
-                    |path, new_merk| {
-                        self.open_batch_transactional_merk_at_path(
-                            &continue_storage_batch,
-                            path.into(),
-                            tx,
-                            new_merk,
-                        )
-                    }
-                )
-            );
+                // we are trying to finalize
+                batch_apply_options.batch_pause_height = None;
 
-            // let's build the write batch
-            let continued_pending_costs = cost_return_on_error!(
-                &mut cost,
-                self.db
-                    .continue_write_batch(&mut write_batch, continue_storage_batch)
-                    .map_err(|e| e.into())
-            );
+                let continue_storage_batch = StorageBatch::new();
+                cost_return_on_error!(
+                    &mut cost,
+                    self.delete_prefixes_of_deleted_trees(
+                        &new_operations,
+                        &continue_storage_batch,
+                        transaction
+                    )
+                );
 
-            pending_costs.add_assign(continued_pending_costs);
+                cost_return_on_error!(
+                    &mut cost,
+                    self.continue_partial_apply_body(
+                        left_over_operations,
+                        new_operations,
+                        Some(batch_apply_options),
+                        update_element_flags_function,
+                        split_removal_bytes_function,
+                        |path, new_merk| {
+                            self.open_batch_transactional_merk_at_path(
+                                &continue_storage_batch,
+                                path.into(),
+                                tx,
+                                new_merk,
+                            )
+                        }
+                    )
+                );
 
-            // TODO: compute batch costs
-            cost_return_on_error!(
-                &mut cost,
-                self.db
-                    .commit_db_write_batch(write_batch, pending_costs, Some(tx))
-                    .map_err(|e| e.into())
-            );
-        } else {
-            let left_over_operations = cost_return_on_error!(
-                &mut cost,
-                self.apply_body(
-                    ops,
-                    Some(batch_apply_options.clone()),
-                    &mut update_element_flags_function,
-                    &mut split_removal_bytes_function,
-                    |path, new_merk| {
-                        self.open_batch_merk_at_path(&storage_batch, path.into(), new_merk)
-                    }
-                )
-            );
+                // let's build the write batch
+                let continued_pending_costs = cost_return_on_error!(
+                    &mut cost,
+                    self.db
+                        .continue_write_batch(&mut write_batch, continue_storage_batch, Some(tx))
+                        .map_err(|e| e.into())
+                );
 
-            // if we paused at the root height, the left over operations would be to replace
-            // a lot of leaf nodes in the root tree
+                pending_costs.add_assign(continued_pending_costs);
 
-            // let's build the write batch
-            let (mut write_batch, mut pending_costs) = cost_return_on_error!(
-                &mut cost,
-                self.db
-                    .build_write_batch(storage_batch)
-                    .map_err(|e| e.into())
-            );
+                // TODO: compute batch costs
+                cost_return_on_error!(
+                    &mut cost,
+                    self.db
+                        .commit_db_write_batch(write_batch, pending_costs, Some(tx))
+                        .map_err(|e| e.into())
+                );
+            } else {
+                let left_over_operations = cost_return_on_error!(
+                    &mut cost,
+                    self.apply_body_on_threads(
+                        ops,
+                        Some(batch_apply_options.clone()),
+                        &mut update_element_flags_function,
+                        &mut split_removal_bytes_function,
+                        |path, new_merk| {
+                            self.open_batch_merk_at_path(&storage_batch, path.into(), new_merk)
+                        },
+                        None
+                    )
+                );
 
-            let total_current_costs = cost.clone().add(pending_costs.clone());
+                // if we paused at the root height, the left over operations would be to replace
+                // a lot of leaf nodes in the root tree
+
+                // let's build the write batch
+                let (mut write_batch, mut pending_costs) = cost_return_on_error!(
+                    &mut cost,
+                    self.db
+                        .build_write_batch(storage_batch, None)
+                        .map_err(|e| e.into())
+                );
 
-            // at this point we need to send the pending costs back
-            // we will get GroveDB a new set of GroveDBOps
+                let total_current_costs = cost.clone().add(pending_costs.clone());
 
-            let new_operations = cost_return_on_error_no_add!(
-                &cost,
-                add_on_operations(&total_current_costs, &left_over_operations)
-            );
+                // at this point we need to send the pending costs back
+                // we will get GroveDB a new set of GroveDBOps
 
-            // we are trying to finalize
-            batch_apply_options.batch_pause_height = None;
+                let new_operations = cost_return_on_error_no_add!(
+                    &cost,
+                    add_on_operations(&total_current_costs, &left_over_operations)
+                );
+                if new_operations
+                    .iter()
+                    .any(|op| matches!(op.op, Op::Move { .. }))
+                {
+                    return Err(Error::InvalidBatchOperation(
+                        "move operations can not be added to a partial batch",
+                    ))
+                    .wrap_with_cost(cost);
+                }
 
-            let continue_storage_batch = StorageBatch::new();
+                // we are trying to finalize
+                batch_apply_options.batch_pause_height = None;
 
-            cost_return_on_error!(
-                &mut cost,
-                self.continue_partial_apply_body(
-                    left_over_operations,
-                    new_operations,
-                    Some(batch_apply_options),
-                    update_element_flags_function,
-                    split_removal_bytes_function,
-                    |path, new_merk| {
-                        self.open_batch_merk_at_path(&continue_storage_batch, path.into(), new_merk)
-                    }
-                )
-            );
+                let continue_storage_batch = StorageBatch::new();
+                cost_return_on_error!(
+                    &mut cost,
+                    self.delete_prefixes_of_deleted_trees(
+                        &new_operations,
+                        &continue_storage_batch,
+                        transaction
+                    )
+                );
 
-            // let's build the write batch
-            let continued_pending_costs = cost_return_on_error!(
-                &mut cost,
-                self.db
-                    .continue_write_batch(&mut write_batch, continue_storage_batch)
-                    .map_err(|e| e.into())
-            );
+                cost_return_on_error!(
+                    &mut cost,
+                    self.continue_partial_apply_body(
+                        left_over_operations,
+                        new_operations,
+                        Some(batch_apply_options),
+                        update_element_flags_function,
+                        split_removal_bytes_function,
+                        |path, new_merk| {
+                            self.open_batch_merk_at_path(
+                                &continue_storage_batch,
+                                path.into(),
+                                new_merk,
+                            )
+                        }
+                    )
+                );
 
-            pending_costs.add_assign(continued_pending_costs);
+                // let's build the write batch
+                let continued_pending_costs = cost_return_on_error!(
+                    &mut cost,
+                    self.db
+                        .continue_write_batch(&mut write_batch, continue_storage_batch, None)
+                        .map_err(|e| e.into())
+                );
 
-            // TODO: compute batch costs
-            cost_return_on_error!(
-                &mut cost,
-                self.db
-                    .commit_db_write_batch(write_batch, pending_costs, None)
-                    .map_err(|e| e.into())
-            );
-        }
-        Ok(()).wrap_with_cost(cost)
+                pending_costs.add_assign(continued_pending_costs);
+
+                // TODO: compute batch costs
+                cost_return_on_error!(
+                    &mut cost,
+                    self.db
+                        .commit_db_write_batch(write_batch, pending_costs, None)
+                        .map_err(|e| e.into())
+                );
+            }
+            Ok(()).wrap_with_cost(cost)
+        })
+        .add_cost(cost)
     }
 
     #[cfg(feature = "estimated_costs")]
@@ -2214,6 +3037,30 @@ impl GroveDb {
             return Ok(()).wrap_with_cost(cost);
         }
 
+        // A move is estimated as the delete of the moved element and, kept at
+        // its destination, the insert of an element of the size estimated there
+        let ops = ops
+            .into_iter()
+            .flat_map(|op| match &op.op {
+                Op::Move { to_path, to_key } => {
+                    let destination_op = GroveDbOp {
+                        path: KeyInfoPath::from_known_owned_path(to_path.clone()),
+                        key: KnownKey(to_key.clone()),
+                        op: op.op.clone(),
+                        precondition: None,
+                    };
+                    vec![
+                        GroveDbOp {
+                            op: Op::Delete,
+                            ..op
+                        },
+                        destination_op,
+                    ]
+                }
+                _ => vec![op],
+            })
+            .collect();
+
         match estimated_costs_type {
             EstimatedCostsType::AverageCaseCostsType(estimated_layer_information) => {
                 let batch_structure = cost_return_on_error!(
@@ -2258,7 +3105,11 @@ impl GroveDb {
 
 #[cfg(test)]
 mod tests {
-    use grovedb_costs::storage_cost::removal::StorageRemovedBytes::NoStorageRemoval;
+    use std::cell::RefCell;
+
+    use grovedb_costs::storage_cost::removal::StorageRemovedBytes::{
+        BasicStorageRemoval, NoStorageRemoval,
+    };
     use grovedb_merk::proofs::Query;
 
     use super::*;
@@ -2407,6 +3258,7 @@ mod tests {
                     disable_operation_consistency_check: true,
                     base_root_storage_is_free: true,
                     batch_pause_height: None,
+                    batch_thread_pool_size: None,
                 }),
                 None
             )
@@ -3157,6 +4009,7 @@ mod tests {
                     disable_operation_consistency_check: false,
                     base_root_storage_is_free: true,
                     batch_pause_height: None,
+                    batch_thread_pool_size: None,
                 }),
                 None
             )
@@ -3196,6 +4049,7 @@ mod tests {
                     deleting_non_empty_trees_returns_error: true,
                     base_root_storage_is_free: true,
                     batch_pause_height: None,
+                    batch_thread_pool_size: None,
                 }),
                 None
             )
@@ -3229,6 +4083,7 @@ mod tests {
                     disable_operation_consistency_check: false,
                     base_root_storage_is_free: true,
                     batch_pause_height: None,
+                    batch_thread_pool_size: None,
                 }),
                 None
             )
@@ -3506,4 +4361,1126 @@ mod tests {
             Err(Error::ReferenceLimit)
         ));
     }
+
+    #[test]
+    fn test_batch_preconditions() {
+        let db = make_test_grovedb();
+        let item = Element::new_item(b"value".to_vec());
+        db.insert([TEST_LEAF].as_ref(), b"key1", item.clone(), None, None)
+            .unwrap()
+            .expect("successful insert");
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            b"sums",
+            Element::empty_sum_tree(),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+        db.insert(
+            [TEST_LEAF, b"sums"].as_ref(),
+            b"a",
+            Element::new_sum_item(5),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+
+        let item_value_hash =
+            value_hash(&item.serialize().expect("expected to serialize")).unwrap();
+
+        // All preconditions hold, so the batch is applied
+        let ops = vec![
+            GroveDbOp::replace_op(
+                vec![TEST_LEAF.to_vec()],
+                b"key1".to_vec(),
+                Element::new_item(b"value2".to_vec()),
+            )
+            .with_precondition(Precondition::ValueHash(item_value_hash)),
+            GroveDbOp::insert_op(
+                vec![TEST_LEAF.to_vec()],
+                b"key2".to_vec(),
+                Element::new_item(b"new".to_vec()),
+            )
+            .with_precondition(Precondition::DoesNotExist),
+            GroveDbOp::replace_op(
+                vec![TEST_LEAF.to_vec(), b"sums".to_vec()],
+                b"a".to_vec(),
+                Element::new_sum_item(5),
+            )
+            .with_precondition(Precondition::SumInRange(0..=5)),
+            GroveDbOp::insert_op(
+                vec![TEST_LEAF.to_vec(), b"sums".to_vec()],
+                b"b".to_vec(),
+                Element::new_sum_item(3),
+            ),
+            GroveDbOp::insert_op(
+                vec![ANOTHER_TEST_LEAF.to_vec(), b"missing".to_vec()],
+                b"key".to_vec(),
+                Element::new_item(b"value".to_vec()),
+            )
+            .with_precondition(Precondition::DoesNotExist),
+            GroveDbOp::insert_op(
+                vec![ANOTHER_TEST_LEAF.to_vec()],
+                b"missing".to_vec(),
+                Element::empty_tree(),
+            ),
+        ];
+        db.apply_batch(ops, None, None)
+            .unwrap()
+            .expect("expected preconditions to hold");
+        assert_eq!(
+            db.get([TEST_LEAF].as_ref(), b"key1", None)
+                .unwrap()
+                .expect("expected element"),
+            Element::new_item(b"value2".to_vec())
+        );
+
+        // The second operation's precondition fails, so nothing is applied
+        let ops = vec![
+            GroveDbOp::delete_op(vec![TEST_LEAF.to_vec()], b"key2".to_vec())
+                .with_precondition(Precondition::Exists),
+            GroveDbOp::insert_op(
+                vec![TEST_LEAF.to_vec()],
+                b"key1".to_vec(),
+                Element::new_item(b"value3".to_vec()),
+            )
+            .with_precondition(Precondition::Element(item.clone())),
+        ];
+        assert!(matches!(
+            db.apply_batch(ops, None, None).unwrap(),
+            Err(Error::BatchPreconditionFailed(1, _))
+        ));
+        assert!(db.get([TEST_LEAF].as_ref(), b"key2", None).unwrap().is_ok());
+
+        // The old value hash no longer matches
+        let ops = vec![
+            GroveDbOp::delete_op(vec![TEST_LEAF.to_vec()], b"key1".to_vec())
+                .with_precondition(Precondition::ValueHash(item_value_hash)),
+        ];
+        assert!(matches!(
+            db.apply_batch(ops, None, None).unwrap(),
+            Err(Error::BatchPreconditionFailed(0, _))
+        ));
+
+        // Earlier preconditions hold but the last key is missing
+        let ops = vec![
+            GroveDbOp::insert_op(
+                vec![TEST_LEAF.to_vec()],
+                b"key3".to_vec(),
+                Element::new_item(b"value".to_vec()),
+            )
+            .with_precondition(Precondition::DoesNotExist),
+            GroveDbOp::insert_op(
+                vec![TEST_LEAF.to_vec()],
+                b"key4".to_vec(),
+                Element::new_item(b"value".to_vec()),
+            ),
+            GroveDbOp::delete_op(vec![TEST_LEAF.to_vec()], b"key2".to_vec())
+                .with_precondition(Precondition::Exists),
+            GroveDbOp::insert_op(
+                vec![TEST_LEAF.to_vec(), b"sums".to_vec()],
+                b"c".to_vec(),
+                Element::new_sum_item(1),
+            )
+            .with_precondition(Precondition::Exists),
+        ];
+        assert!(matches!(
+            db.apply_batch(ops, None, None).unwrap(),
+            Err(Error::BatchPreconditionFailed(3, _))
+        ));
+        let sum_tree = db
+            .get([TEST_LEAF].as_ref(), b"sums", None)
+            .unwrap()
+            .expect("expected sum tree");
+        assert_eq!(sum_tree.sum_value_or_default(), 8);
+
+        // Preconditions are checked against the transaction's state
+        let tx = db.start_transaction();
+        let ops = vec![GroveDbOp::insert_op(
+            vec![TEST_LEAF.to_vec()],
+            b"key3".to_vec(),
+            Element::new_item(b"value".to_vec()),
+        )
+        .with_precondition(Precondition::DoesNotExist)];
+        db.apply_batch(ops.clone(), None, Some(&tx))
+            .unwrap()
+            .expect("expected key to be absent");
+        assert!(matches!(
+            db.apply_batch(ops.clone(), None, Some(&tx)).unwrap(),
+            Err(Error::BatchPreconditionFailed(0, _))
+        ));
+        db.apply_batch(ops, None, None)
+            .unwrap()
+            .expect("expected key to be absent outside the transaction");
+        let ops =
+            vec![
+                GroveDbOp::delete_op(vec![TEST_LEAF.to_vec(), b"sums".to_vec()], b"a".to_vec())
+                    .with_precondition(Precondition::SumInRange(6..=10)),
+            ];
+        assert!(matches!(
+            db.apply_operations_without_batching(ops, None, None)
+                .unwrap(),
+            Err(Error::BatchPreconditionFailed(0, _))
+        ));
+    }
+
+    fn check_precondition_conflict<S: CovariantStorage>(db: &GroveDb<S>) {
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            b"key",
+            Element::new_item(b"value".to_vec()),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+
+        let tx = db.start_transaction();
+        let ops = vec![GroveDbOp::replace_op(
+            vec![TEST_LEAF.to_vec()],
+            b"key".to_vec(),
+            Element::new_item(b"tx".to_vec()),
+        )
+        .with_precondition(Precondition::Element(Element::new_item(b"value".to_vec())))];
+        db.apply_batch(ops, None, Some(&tx))
+            .unwrap()
+            .expect("expected precondition to hold");
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            b"key",
+            Element::new_item(b"other".to_vec()),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+        assert!(matches!(
+            db.commit_transaction(tx).unwrap(),
+            Err(Error::TransactionConflict)
+        ));
+    }
+
+    #[test]
+    fn test_batch_precondition_conflict() {
+        check_precondition_conflict(&*make_test_grovedb());
+        let db = GroveDb::with_storage(grovedb_storage::memory_storage::MemoryStorage::new());
+        db.insert(EMPTY_PATH, TEST_LEAF, Element::empty_tree(), None, None)
+            .unwrap()
+            .expect("successful insert");
+        check_precondition_conflict(&db);
+    }
+
+    #[test]
+    fn test_batch_precondition_conflict_without_transaction() {
+        let db = make_test_grovedb();
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            b"key",
+            Element::new_item(b"value".to_vec()),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+
+        // The key changes after its precondition is checked and before the
+        // batch is committed
+        let ops = vec![GroveDbOp::replace_op(
+            vec![TEST_LEAF.to_vec()],
+            b"key".to_vec(),
+            Element::new_item(b"batch".to_vec()),
+        )
+        .with_precondition(Precondition::Exists)];
+        let result = db
+            .apply_partial_batch(
+                ops,
+                None,
+                |_cost, _left_over_ops| {
+                    db.insert(
+                        [TEST_LEAF].as_ref(),
+                        b"key",
+                        Element::new_item(b"other".to_vec()),
+                        None,
+                        None,
+                    )
+                    .unwrap()
+                    .expect("successful insert");
+                    Ok(vec![])
+                },
+                None,
+            )
+            .unwrap();
+        assert!(matches!(result, Err(Error::TransactionConflict)));
+        assert_eq!(
+            db.get([TEST_LEAF].as_ref(), b"key", None)
+                .unwrap()
+                .expect("expected element"),
+            Element::new_item(b"other".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_simulate_batch_matches_apply_batch() {
+        let db = make_test_grovedb();
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            b"key1",
+            Element::new_item(b"value".to_vec()),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            b"sums",
+            Element::empty_sum_tree(),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+
+        let ops = vec![
+            GroveDbOp::replace_op(
+                vec![TEST_LEAF.to_vec()],
+                b"key1".to_vec(),
+                Element::new_item(b"value2".to_vec()),
+            ),
+            GroveDbOp::insert_op(
+                vec![TEST_LEAF.to_vec(), b"sums".to_vec()],
+                b"a".to_vec(),
+                Element::new_sum_item(7),
+            ),
+            GroveDbOp::insert_op(
+                vec![ANOTHER_TEST_LEAF.to_vec()],
+                b"ref".to_vec(),
+                Element::new_reference(ReferencePathType::AbsolutePathReference(vec![
+                    TEST_LEAF.to_vec(),
+                    b"key1".to_vec(),
+                ])),
+            ),
+        ];
+
+        let root_hash_before = db.root_hash(None).unwrap().expect("expected root hash");
+        let simulated = db
+            .simulate_batch(ops.clone(), None, None)
+            .unwrap()
+            .expect("expected to simulate batch");
+        assert_eq!(
+            db.root_hash(None).unwrap().expect("expected root hash"),
+            root_hash_before
+        );
+        assert_eq!(
+            simulated.op_diffs[0],
+            SimulatedOpDiff {
+                previous_element: Some(Element::new_item(b"value".to_vec())),
+                new_element: Some(Element::new_item(b"value2".to_vec())),
+            }
+        );
+        assert_eq!(simulated.op_diffs[1].previous_element, None);
+        assert_eq!(simulated.op_diffs[2].previous_element, None);
+
+        // Simulating inside a transaction leaves the transaction untouched
+        let tx = db.start_transaction();
+        db.simulate_batch(ops.clone(), None, Some(&tx))
+            .unwrap()
+            .expect("expected to simulate batch");
+        assert_eq!(
+            db.root_hash(Some(&tx))
+                .unwrap()
+                .expect("expected root hash"),
+            root_hash_before
+        );
+
+        let cost = db
+            .apply_batch(ops, None, None)
+            .cost_as_result()
+            .expect("expected to apply batch");
+        assert_eq!(simulated.cost, cost);
+        assert_eq!(
+            db.root_hash(None).unwrap().expect("expected root hash"),
+            simulated.root_hash
+        );
+        assert_eq!(
+            simulated.op_diffs[1].new_element,
+            Some(Element::new_sum_item(7))
+        );
+        assert_eq!(
+            simulated.op_diffs[2].new_element,
+            Some(
+                db.get_raw([ANOTHER_TEST_LEAF].as_ref().into(), b"ref", None)
+                    .unwrap()
+                    .expect("expected reference")
+            )
+        );
+    }
+
+    #[test]
+    fn test_simulate_batch_in_memory_transaction() {
+        let db = GroveDb::with_storage(grovedb_storage::memory_storage::MemoryStorage::new());
+        db.insert(EMPTY_PATH, TEST_LEAF, Element::empty_tree(), None, None)
+            .unwrap()
+            .expect("successful insert");
+        let tx = db.start_transaction();
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            b"key",
+            Element::new_item(b"value".to_vec()),
+            None,
+            Some(&tx),
+        )
+        .unwrap()
+        .expect("successful insert");
+        let root_hash_before = db
+            .root_hash(Some(&tx))
+            .unwrap()
+            .expect("expected root hash");
+
+        let ops = vec![GroveDbOp::delete_op(
+            vec![TEST_LEAF.to_vec()],
+            b"key".to_vec(),
+        )];
+        let simulated = db
+            .simulate_batch(ops.clone(), None, Some(&tx))
+            .unwrap()
+            .expect("expected to simulate batch");
+        assert_eq!(
+            simulated.op_diffs,
+            vec![SimulatedOpDiff {
+                previous_element: Some(Element::new_item(b"value".to_vec())),
+                new_element: None,
+            }]
+        );
+        assert_eq!(
+            db.root_hash(Some(&tx))
+                .unwrap()
+                .expect("expected root hash"),
+            root_hash_before
+        );
+
+        db.apply_batch(ops, None, Some(&tx))
+            .unwrap()
+            .expect("expected to apply batch");
+        assert_eq!(
+            db.root_hash(Some(&tx))
+                .unwrap()
+                .expect("expected root hash"),
+            simulated.root_hash
+        );
+    }
+
+    #[test]
+    fn test_simulate_batch_rejects_subtree_moves() {
+        let db = make_test_grovedb();
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            b"key",
+            Element::empty_tree(),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+
+        let ops = vec![GroveDbOp::move_op(
+            vec![TEST_LEAF.to_vec()],
+            b"key".to_vec(),
+            vec![ANOTHER_TEST_LEAF.to_vec()],
+            b"key".to_vec(),
+        )];
+        assert!(matches!(
+            db.simulate_batch(ops, None, None).unwrap(),
+            Err(Error::NotSupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_apply_batch_on_threads_matches_single_thread() {
+        let single_thread_db = make_test_grovedb();
+        let threads_db = make_test_grovedb();
+        for db in [&single_thread_db, &threads_db] {
+            for i in 0u8..8 {
+                db.insert(
+                    [TEST_LEAF].as_ref(),
+                    &[i],
+                    Element::empty_tree(),
+                    None,
+                    None,
+                )
+                .unwrap()
+                .expect("successful insert");
+            }
+        }
+
+        let mut ops = vec![];
+        for i in 0u8..8 {
+            ops.push(GroveDbOp::insert_op(
+                vec![TEST_LEAF.to_vec(), vec![i]],
+                b"key".to_vec(),
+                Element::new_item(vec![i]),
+            ));
+            ops.push(GroveDbOp::insert_op(
+                vec![TEST_LEAF.to_vec(), vec![i]],
+                b"tree".to_vec(),
+                Element::empty_tree(),
+            ));
+        }
+        // A subtree in the middle of the level follows a reference
+        ops.push(GroveDbOp::insert_op(
+            vec![TEST_LEAF.to_vec(), vec![4]],
+            b"ref".to_vec(),
+            Element::new_reference(ReferencePathType::AbsolutePathReference(vec![
+                TEST_LEAF.to_vec(),
+                vec![1],
+                b"key".to_vec(),
+            ])),
+        ));
+
+        let single_thread_cost = single_thread_db
+            .apply_batch(ops.clone(), None, None)
+            .cost_as_result()
+            .expect("expected to apply batch");
+        let threads_cost = threads_db
+            .apply_batch(
+                ops,
+                Some(BatchApplyOptions {
+                    batch_thread_pool_size: Some(4),
+                    ..Default::default()
+                }),
+                None,
+            )
+            .cost_as_result()
+            .expect("expected to apply batch");
+
+        assert_eq!(single_thread_cost, threads_cost);
+        assert_eq!(
+            single_thread_db
+                .root_hash(None)
+                .unwrap()
+                .expect("expected root hash"),
+            threads_db
+                .root_hash(None)
+                .unwrap()
+                .expect("expected root hash")
+        );
+        assert_eq!(
+            threads_db
+                .get([TEST_LEAF, &[4]].as_ref(), b"ref", None)
+                .unwrap()
+                .expect("expected to follow reference"),
+            Element::new_item(vec![1])
+        );
+        assert!(threads_db.verify_grovedb().is_empty());
+    }
+
+    #[test]
+    fn test_apply_batch_on_threads_runs_callbacks_in_op_order() {
+        let mut calls_by_db = vec![];
+        let mut root_hashes = vec![];
+        for batch_thread_pool_size in [None, Some(4)] {
+            let db = make_test_grovedb();
+            for i in 0u8..8 {
+                db.insert(
+                    [TEST_LEAF].as_ref(),
+                    &[i],
+                    Element::empty_tree(),
+                    None,
+                    None,
+                )
+                .unwrap()
+                .expect("successful insert");
+                for key in [b"key", b"old"] {
+                    db.insert(
+                        [TEST_LEAF, &[i]].as_ref(),
+                        key,
+                        Element::new_item_with_flags(vec![i], Some(vec![i])),
+                        None,
+                        None,
+                    )
+                    .unwrap()
+                    .expect("successful insert");
+                }
+            }
+
+            let mut ops = vec![];
+            for i in 0u8..8 {
+                ops.push(GroveDbOp::replace_op(
+                    vec![TEST_LEAF.to_vec(), vec![i]],
+                    b"key".to_vec(),
+                    Element::new_item_with_flags(vec![i; 40], Some(vec![i])),
+                ));
+                ops.push(GroveDbOp::delete_op(
+                    vec![TEST_LEAF.to_vec(), vec![i]],
+                    b"old".to_vec(),
+                ));
+            }
+
+            let calls = RefCell::new(vec![]);
+            db.apply_batch_with_element_flags_update(
+                ops,
+                Some(BatchApplyOptions {
+                    batch_thread_pool_size,
+                    ..Default::default()
+                }),
+                |_cost, old_flags, new_flags| {
+                    calls
+                        .borrow_mut()
+                        .push(("update", old_flags, new_flags.clone()));
+                    Ok(false)
+                },
+                |flags, key_removed_bytes, value_removed_bytes| {
+                    calls.borrow_mut().push(("removal", None, flags.clone()));
+                    Ok((
+                        BasicStorageRemoval(key_removed_bytes),
+                        BasicStorageRemoval(value_removed_bytes),
+                    ))
+                },
+                None,
+            )
+            .unwrap()
+            .expect("expected to apply batch");
+
+            calls_by_db.push(calls.into_inner());
+            root_hashes.push(db.root_hash(None).unwrap().expect("expected root hash"));
+            assert!(db.verify_grovedb().is_empty());
+        }
+
+        assert!(!calls_by_db[0].is_empty());
+        assert_eq!(calls_by_db[0], calls_by_db[1]);
+        assert_eq!(root_hashes[0], root_hashes[1]);
+    }
+
+    #[test]
+    fn test_replay_serialized_batches() {
+        let db = make_test_grovedb();
+        let batches = vec![
+            vec![
+                GroveDbOp::insert_op(
+                    vec![TEST_LEAF.to_vec()],
+                    b"tree".to_vec(),
+                    Element::empty_tree(),
+                ),
+                GroveDbOp::insert_op(
+                    vec![TEST_LEAF.to_vec(), b"tree".to_vec()],
+                    b"a".to_vec(),
+                    Element::new_item_with_flags(b"value".to_vec(), Some(vec![1])),
+                ),
+                GroveDbOp::insert_op(
+                    vec![TEST_LEAF.to_vec(), b"tree".to_vec()],
+                    b"b".to_vec(),
+                    Element::new_item(b"value".to_vec()),
+                ),
+                GroveDbOp::insert_op(
+                    vec![ANOTHER_TEST_LEAF.to_vec()],
+                    b"ref".to_vec(),
+                    Element::new_reference(ReferencePathType::AbsolutePathReference(vec![
+                        TEST_LEAF.to_vec(),
+                        b"tree".to_vec(),
+                        b"a".to_vec(),
+                    ])),
+                ),
+            ],
+            vec![
+                GroveDbOp::replace_op(
+                    vec![TEST_LEAF.to_vec(), b"tree".to_vec()],
+                    b"a".to_vec(),
+                    Element::new_item(b"new value".to_vec()),
+                )
+                .with_precondition(Precondition::Exists),
+                GroveDbOp::delete_range_op(
+                    vec![TEST_LEAF.to_vec(), b"tree".to_vec()],
+                    QueryItem::RangeAfter(b"a".to_vec()..),
+                ),
+            ],
+            vec![GroveDbOp::move_op(
+                vec![TEST_LEAF.to_vec(), b"tree".to_vec()],
+                b"a".to_vec(),
+                vec![TEST_LEAF.to_vec()],
+                b"moved".to_vec(),
+            )],
+        ];
+
+        // Write the log while applying the batches
+        let mut log = vec![];
+        for ops in batches {
+            let serialized = GroveDbOp::serialize_batch(&ops).expect("expected to serialize");
+            assert_eq!(
+                GroveDbOp::deserialize_batch(&serialized).expect("expected to deserialize"),
+                ops
+            );
+            log.push(serialized);
+            db.apply_batch(ops, None, None)
+                .unwrap()
+                .expect("expected to apply batch");
+        }
+
+        // Replaying the log on an empty GroveDB gives the same root hash
+        let replayed_db = make_test_grovedb();
+        for serialized in log.iter() {
+            replayed_db
+                .apply_serialized_batch(serialized, None, None)
+                .unwrap()
+                .expect("expected to apply serialized batch");
+        }
+        assert_eq!(
+            replayed_db
+                .root_hash(None)
+                .unwrap()
+                .expect("expected root hash"),
+            db.root_hash(None).unwrap().expect("expected root hash")
+        );
+
+        let mut unknown_version = log[0].clone();
+        unknown_version[0] = 0;
+        assert!(matches!(
+            GroveDbOp::deserialize_batch(&unknown_version),
+            Err(Error::CorruptedData(_))
+        ));
+    }
+
+    #[test]
+    fn test_serialized_batch_encoding_is_stable() {
+        let ops = vec![
+            GroveDbOp::insert_op(
+                vec![b"a".to_vec()],
+                b"b".to_vec(),
+                Element::new_item_with_flags(b"c".to_vec(), Some(vec![1])),
+            ),
+            GroveDbOp::replace_op(
+                vec![],
+                b"d".to_vec(),
+                Element::new_reference(ReferencePathType::SiblingReference(b"e".to_vec())),
+            )
+            .with_precondition(Precondition::Exists),
+        ];
+        let serialized = GroveDbOp::serialize_batch(&ops).expect("expected to serialize");
+        // Logs written with version 1 must keep decoding to the same operations
+        assert_eq!(
+            serialized,
+            vec![
+                1, 2, 1, 1, 97, 1, 98, 0, 0, 1, 99, 1, 1, 1, 0, 0, 1, 100, 1, 1, 5, 1, 101, 0, 0,
+                1, 0
+            ]
+        );
+        assert_eq!(
+            GroveDbOp::deserialize_batch(&serialized).expect("expected to deserialize"),
+            ops
+        );
+    }
+
+    #[test]
+    fn test_insert_tree_with_contents() {
+        let db = make_test_grovedb();
+        let ops = GroveDbOp::insert_tree_with_contents(
+            vec![TEST_LEAF.to_vec()],
+            b"tree".to_vec(),
+            false,
+            vec![
+                (
+                    b"b".to_vec(),
+                    SubtreeContent::Element(Element::new_item(b"value_b".to_vec())),
+                ),
+                (
+                    b"sums".to_vec(),
+                    SubtreeContent::Tree {
+                        is_sum_tree: true,
+                        contents: vec![
+                            (
+                                b"y".to_vec(),
+                                SubtreeContent::Element(Element::new_sum_item(6)),
+                            ),
+                            (
+                                b"x".to_vec(),
+                                SubtreeContent::Element(Element::new_sum_item(4)),
+                            ),
+                        ],
+                    },
+                ),
+                (
+                    b"a".to_vec(),
+                    SubtreeContent::Element(Element::new_item(b"value_a".to_vec())),
+                ),
+            ],
+        )
+        .expect("expected valid tree contents");
+        assert_eq!(ops.len(), 2);
+        assert_eq!(
+            ops[1].op,
+            Op::InsertTreeWithContents {
+                is_sum_tree: true,
+                elements: vec![
+                    (b"x".to_vec(), Element::new_sum_item(4)),
+                    (b"y".to_vec(), Element::new_sum_item(6)),
+                ],
+            }
+        );
+        let serialized = GroveDbOp::serialize_batch(&ops).expect("expected to serialize");
+        assert_eq!(
+            GroveDbOp::deserialize_batch(&serialized).expect("expected to deserialize"),
+            ops
+        );
+        db.apply_batch(ops.clone(), None, None)
+            .unwrap()
+            .expect("expected to insert tree with contents");
+
+        assert_eq!(
+            db.get([TEST_LEAF, b"tree"].as_ref(), b"a", None)
+                .unwrap()
+                .expect("expected item"),
+            Element::new_item(b"value_a".to_vec())
+        );
+        let sum_tree = db
+            .get([TEST_LEAF, b"tree"].as_ref(), b"sums", None)
+            .unwrap()
+            .expect("expected sum tree");
+        assert_eq!(sum_tree.sum_value_or_default(), 10);
+        assert!(db.verify_grovedb().is_empty());
+
+        // Inserting every element with an op of its own gives the same tree
+        let tree_path = vec![TEST_LEAF.to_vec(), b"tree".to_vec()];
+        let sums_path = vec![TEST_LEAF.to_vec(), b"tree".to_vec(), b"sums".to_vec()];
+        let element_ops = vec![
+            GroveDbOp::insert_op(
+                vec![TEST_LEAF.to_vec()],
+                b"tree".to_vec(),
+                Element::empty_tree(),
+            ),
+            GroveDbOp::insert_op(
+                tree_path.clone(),
+                b"a".to_vec(),
+                Element::new_item(b"value_a".to_vec()),
+            ),
+            GroveDbOp::insert_op(
+                tree_path.clone(),
+                b"b".to_vec(),
+                Element::new_item(b"value_b".to_vec()),
+            ),
+            GroveDbOp::insert_op(tree_path, b"sums".to_vec(), Element::empty_sum_tree()),
+            GroveDbOp::insert_op(sums_path.clone(), b"x".to_vec(), Element::new_sum_item(4)),
+            GroveDbOp::insert_op(sums_path, b"y".to_vec(), Element::new_sum_item(6)),
+        ];
+        let other_db = make_test_grovedb();
+        other_db
+            .apply_batch(element_ops, None, None)
+            .unwrap()
+            .expect("expected to insert elements");
+        assert_eq!(
+            other_db
+                .root_hash(None)
+                .unwrap()
+                .expect("expected root hash"),
+            db.root_hash(None).unwrap().expect("expected root hash")
+        );
+
+        let other_db = make_test_grovedb();
+        other_db
+            .apply_operations_without_batching(ops, None, None)
+            .unwrap()
+            .expect("expected to insert tree with contents");
+        let sum_tree = other_db
+            .get([TEST_LEAF, b"tree"].as_ref(), b"sums", None)
+            .unwrap()
+            .expect("expected sum tree");
+        assert_eq!(sum_tree.sum_value_or_default(), 10);
+        assert_eq!(
+            other_db
+                .get([TEST_LEAF, b"tree"].as_ref(), b"b", None)
+                .unwrap()
+                .expect("expected item"),
+            Element::new_item(b"value_b".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_insert_tree_with_contents_rejects_invalid_trees() {
+        assert!(matches!(
+            GroveDbOp::insert_tree_with_contents(
+                vec![TEST_LEAF.to_vec()],
+                b"tree".to_vec(),
+                false,
+                vec![(
+                    b"sums".to_vec(),
+                    SubtreeContent::Element(Element::empty_sum_tree()),
+                )],
+            ),
+            Err(Error::InvalidBatchOperation(_))
+        ));
+        assert!(matches!(
+            GroveDbOp::insert_tree_with_contents(
+                vec![TEST_LEAF.to_vec()],
+                b"tree".to_vec(),
+                false,
+                vec![
+                    (
+                        b"a".to_vec(),
+                        SubtreeContent::Element(Element::new_item(b"value".to_vec())),
+                    ),
+                    (
+                        b"a".to_vec(),
+                        SubtreeContent::Tree {
+                            is_sum_tree: false,
+                            contents: vec![],
+                        },
+                    ),
+                ],
+            ),
+            Err(Error::InvalidBatchOperation(_))
+        ));
+    }
+
+    #[test]
+    fn test_apply_batch_with_undo_restores_root_hash() {
+        let db = make_test_grovedb();
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            b"item",
+            Element::new_item(b"old".to_vec()),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+        let root_hash = db.root_hash(None).unwrap().expect("expected root hash");
+
+        let ops = vec![
+            GroveDbOp::replace_op(
+                vec![TEST_LEAF.to_vec()],
+                b"item".to_vec(),
+                Element::new_item(b"new".to_vec()),
+            ),
+            GroveDbOp::insert_op(
+                vec![ANOTHER_TEST_LEAF.to_vec()],
+                b"fresh".to_vec(),
+                Element::empty_tree(),
+            ),
+            GroveDbOp::insert_op(
+                vec![ANOTHER_TEST_LEAF.to_vec(), b"fresh".to_vec()],
+                b"b".to_vec(),
+                Element::new_item(b"value_b".to_vec()),
+            ),
+        ];
+        let undo_batch = db
+            .apply_batch_with_undo(ops, None, None)
+            .unwrap()
+            .expect("expected to apply batch with undo");
+        assert_ne!(
+            db.root_hash(None).unwrap().expect("expected root hash"),
+            root_hash
+        );
+
+        let bytes = undo_batch.serialize().expect("expected to serialize");
+        assert_eq!(
+            UndoBatch::deserialize(&bytes).expect("expected to deserialize"),
+            undo_batch
+        );
+        db.apply_undo_batch(undo_batch, None)
+            .unwrap()
+            .expect("expected to apply undo batch");
+        assert_eq!(
+            db.root_hash(None).unwrap().expect("expected root hash"),
+            root_hash
+        );
+        assert!(db.verify_grovedb().is_empty());
+    }
+
+    #[test]
+    fn test_apply_batch_with_undo_restores_deleted_subtree() {
+        let db = make_test_grovedb();
+        let mut ops = GroveDbOp::insert_tree_with_contents(
+            vec![TEST_LEAF.to_vec()],
+            b"tree".to_vec(),
+            false,
+            vec![(
+                b"a".to_vec(),
+                SubtreeContent::Element(Element::new_item(b"value_a".to_vec())),
+            )],
+        )
+        .expect("expected valid tree contents");
+        ops.push(GroveDbOp::insert_op(
+            vec![TEST_LEAF.to_vec(), b"tree".to_vec()],
+            b"sums".to_vec(),
+            Element::new_sum_tree_with_flags(None, Some(vec![7])),
+        ));
+        ops.push(GroveDbOp::insert_op(
+            vec![TEST_LEAF.to_vec(), b"tree".to_vec(), b"sums".to_vec()],
+            b"x".to_vec(),
+            Element::new_sum_item(4),
+        ));
+        ops.push(GroveDbOp::insert_op(
+            vec![TEST_LEAF.to_vec()],
+            b"item".to_vec(),
+            Element::new_item(b"old".to_vec()),
+        ));
+        db.apply_batch(ops, None, None)
+            .unwrap()
+            .expect("expected to apply batch");
+        let root_hash = db.root_hash(None).unwrap().expect("expected root hash");
+
+        let options = BatchApplyOptions {
+            allow_deleting_non_empty_trees: true,
+            deleting_non_empty_trees_returns_error: false,
+            ..Default::default()
+        };
+        let ops = vec![GroveDbOp::delete_tree_op(
+            vec![TEST_LEAF.to_vec()],
+            b"tree".to_vec(),
+            false,
+        )];
+        let undo_batch = db
+            .apply_batch_with_undo(ops, Some(options), None)
+            .unwrap()
+            .expect("expected to apply batch with undo");
+        db.get([TEST_LEAF].as_ref(), b"tree", None)
+            .unwrap()
+            .expect_err("expected tree to be deleted");
+
+        db.apply_undo_batch(undo_batch, None)
+            .unwrap()
+            .expect("expected to apply undo batch");
+        assert_eq!(
+            db.root_hash(None).unwrap().expect("expected root hash"),
+            root_hash
+        );
+        assert_eq!(
+            db.get([TEST_LEAF, b"tree", b"sums"].as_ref(), b"x", None)
+                .unwrap()
+                .expect("expected restored sum item"),
+            Element::new_sum_item(4)
+        );
+        assert!(db.verify_grovedb().is_empty());
+    }
+
+    #[test]
+    fn test_apply_batch_with_report() {
+        let db = make_test_grovedb();
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            b"item",
+            Element::new_item(b"old".to_vec()),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("expected to insert item");
+        db.insert(
+            [ANOTHER_TEST_LEAF].as_ref(),
+            b"gone",
+            Element::new_item(b"old".to_vec()),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("expected to insert item");
+
+        let report = db
+            .apply_batch_with_report(
+                vec![
+                    GroveDbOp::insert_op(
+                        vec![TEST_LEAF.to_vec()],
+                        b"new".to_vec(),
+                        Element::new_item(b"value".to_vec()),
+                    ),
+                    GroveDbOp::replace_op(
+                        vec![TEST_LEAF.to_vec()],
+                        b"item".to_vec(),
+                        Element::new_item(b"new".to_vec()),
+                    ),
+                    GroveDbOp::insert_op(
+                        vec![ANOTHER_TEST_LEAF.to_vec()],
+                        b"other".to_vec(),
+                        Element::new_item(b"value".to_vec()),
+                    ),
+                    GroveDbOp::delete_op(vec![ANOTHER_TEST_LEAF.to_vec()], b"gone".to_vec()),
+                ],
+                None,
+                None,
+            )
+            .unwrap();
+        assert!(report.result.is_ok());
+        assert_eq!(report.failed_op_index, None);
+        assert!(report
+            .op_reports
+            .iter()
+            .all(|op_report| op_report.status == BatchOpStatus::Applied));
+        assert_eq!(report.op_reports[0].previous_element, None);
+        assert_eq!(
+            report.op_reports[1].previous_element,
+            Some(Element::new_item(b"old".to_vec()))
+        );
+        assert!(report.op_reports[1].cost.storage_cost.replaced_bytes > 0);
+        assert!(report.op_reports[2].cost.hash_node_calls > 0);
+        assert!(report.op_reports[2].cost.storage_cost.added_bytes > 0);
+        assert!(
+            report.op_reports[3]
+                .cost
+                .storage_cost
+                .removed_bytes
+                .total_removed_bytes()
+                > 0
+        );
+        // Ops only pay for the node they write, the inserted item is larger
+        assert!(
+            report.op_reports[0].cost.storage_cost.added_bytes
+                > report.op_reports[1].cost.storage_cost.added_bytes
+        );
+
+        // The insert over an existing item is the op the batch fails at
+        let report = db
+            .apply_batch_with_report(
+                vec![
+                    GroveDbOp::insert_op(
+                        vec![ANOTHER_TEST_LEAF.to_vec()],
+                        b"a".to_vec(),
+                        Element::new_item(b"value".to_vec()),
+                    ),
+                    GroveDbOp::insert_op(
+                        vec![TEST_LEAF.to_vec()],
+                        b"new".to_vec(),
+                        Element::new_item(b"other value".to_vec()),
+                    ),
+                ],
+                Some(BatchApplyOptions {
+                    validate_insertion_does_not_override: true,
+                    ..Default::default()
+                }),
+                None,
+            )
+            .unwrap();
+        assert!(matches!(
+            report.result,
+            Err(Error::InvalidBatchOperation(_))
+        ));
+        assert_eq!(report.failed_op_index, Some(1));
+        assert_eq!(report.op_reports[0].status, BatchOpStatus::NotApplied);
+        assert_eq!(report.op_reports[1].status, BatchOpStatus::Failed);
+        db.get([ANOTHER_TEST_LEAF].as_ref(), b"a", None)
+            .unwrap()
+            .expect_err("expected the batch not to be applied");
+
+        // So is an insert under a missing subtree
+        let report = db
+            .apply_batch_with_report(
+                vec![
+                    GroveDbOp::insert_op(
+                        vec![ANOTHER_TEST_LEAF.to_vec()],
+                        b"a".to_vec(),
+                        Element::new_item(b"value".to_vec()),
+                    ),
+                    GroveDbOp::insert_op(
+                        vec![TEST_LEAF.to_vec(), b"missing".to_vec()],
+                        b"b".to_vec(),
+                        Element::new_item(b"value".to_vec()),
+                    ),
+                ],
+                None,
+                None,
+            )
+            .unwrap();
+        assert!(report.result.is_err());
+        assert_eq!(report.failed_op_index, Some(1));
+    }
 }
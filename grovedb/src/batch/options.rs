@@ -53,6 +53,15 @@ pub struct BatchApplyOptions {
     /// At what height do we want to pause applying batch operations
     /// Most of the time this should be not set
     pub batch_pause_height: Option<u8>,
+    /// Number of threads applying the subtrees of a level that don't follow
+    /// references, `None` applies everything on the calling thread. Only
+    /// batches applied without a transaction are spread over threads. GroveDb
+    /// keeps the threads between batches, a batch applied while another one
+    /// uses them is applied on the calling thread. Element flags callbacks
+    /// are run on the calling thread in the order of the ops, as without
+    /// threads, so subtrees calling them are in effect applied one after the
+    /// other.
+    pub batch_thread_pool_size: Option<usize>,
 }
 
 #[cfg(feature = "full")]
@@ -66,6 +75,7 @@ impl Default for BatchApplyOptions {
             disable_operation_consistency_check: false,
             base_root_storage_is_free: true,
             batch_pause_height: None,
+            batch_thread_pool_size: None,
         }
     }
 }
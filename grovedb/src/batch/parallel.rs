@@ -0,0 +1,498 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Parallel batch application
+
+use std::{
+    collections::BTreeMap,
+    sync::{mpsc, Mutex, MutexGuard, TryLockError},
+};
+
+use grovedb_costs::{
+    cost_return_on_error,
+    storage_cost::{removal::StorageRemovedBytes, StorageCost},
+    CostResult, CostsExt, OperationCost,
+};
+use grovedb_merk::{Merk, RootHashKeyAndSum};
+use grovedb_storage::StorageContext;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::{
+    batch::{
+        key_info::KeyInfo, mode::BatchRunMode, BatchApplyOptions, GroveDbOp, KeyInfoPath, Op,
+        TreeCache, TreeCacheMerkByPath,
+    },
+    Element, ElementFlags, Error,
+};
+
+/// Result of a removed bytes split callback with the flags it updated
+type SplitRemovedBytesResponse = (
+    Result<(StorageRemovedBytes, StorageRemovedBytes), Error>,
+    ElementFlags,
+);
+
+/// Element flags callback a worker needs the calling thread to run
+enum CallbackRequest {
+    UpdateFlags {
+        storage_cost: StorageCost,
+        old_flags: Option<ElementFlags>,
+        new_flags: ElementFlags,
+        response: mpsc::Sender<(Result<bool, Error>, ElementFlags)>,
+    },
+    SplitRemovedBytes {
+        flags: ElementFlags,
+        removed_key_bytes: u32,
+        removed_value_bytes: u32,
+        response: mpsc::Sender<SplitRemovedBytesResponse>,
+    },
+}
+
+/// Path with its Merk opened on the calling thread
+struct OpenedPath<S> {
+    path: KeyInfoPath,
+    merk_path: Vec<Vec<u8>>,
+    merk: Merk<S>,
+    ops_at_path: BTreeMap<KeyInfo, Op>,
+    open_cost: OperationCost,
+}
+
+/// Path after its ops were applied by a worker
+struct ExecutedPath<S> {
+    path: KeyInfoPath,
+    merk_path: Vec<Vec<u8>>,
+    merk: Merk<S>,
+    result: CostResult<RootHashKeyAndSum, Error>,
+    failed_key: Option<Vec<u8>>,
+}
+
+/// Thread pool kept by GroveDb between batches, used by one batch at a time
+#[derive(Default)]
+pub(crate) struct BatchThreadPool {
+    pool: Mutex<Option<ThreadPool>>,
+}
+
+impl BatchThreadPool {
+    /// Takes the pool for a batch, building it again if it doesn't have
+    /// `threads` threads. Returns `None` while another batch uses it.
+    pub(crate) fn take(
+        &self,
+        threads: usize,
+    ) -> Result<Option<MutexGuard<'_, Option<ThreadPool>>>, Error> {
+        let mut pool = match self.pool.try_lock() {
+            Ok(pool) => pool,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return Ok(None),
+        };
+        if pool.as_ref().map(ThreadPool::current_num_threads) != Some(threads) {
+            *pool = Some(
+                ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .thread_name(|i| format!("grovedb-batch-{i}"))
+                    .build()
+                    .map_err(|_| Error::InternalError("batch thread pool could not be built"))?,
+            );
+        }
+        Ok(Some(pool))
+    }
+}
+
+/// Merk cache applying the subtrees of a level on the threads of a pool.
+/// Subtrees that follow references may read any other subtree, so they are
+/// applied on the calling thread in between, which keeps root hashes and
+/// costs the same as applying the level on a single thread. Without a pool
+/// everything is applied on the calling thread.
+///
+/// Element flags callbacks are run on the calling thread one chunk after the
+/// other, so a worker calling one waits for the chunks before its own to be
+/// done. Levels whose ops call them fall back to serial execution.
+pub(super) struct ParallelTreeCacheMerkByPath<'t, 'p, S, F> {
+    pub(super) cache: TreeCacheMerkByPath<'t, S, F>,
+    pub(super) pool: Option<&'p ThreadPool>,
+}
+
+/// Whether applying the ops needs to read other subtrees to follow references
+fn follows_references(ops_at_path: &BTreeMap<KeyInfo, Op>) -> bool {
+    ops_at_path.values().any(|op| {
+        matches!(
+            op,
+            Op::Insert {
+                element: Element::Reference(..)
+            } | Op::Replace {
+                element: Element::Reference(..)
+            } | Op::Patch {
+                element: Element::Reference(..),
+                ..
+            } | Op::RefreshReference { .. }
+        )
+    })
+}
+
+impl<'db, S, F> ParallelTreeCacheMerkByPath<'_, '_, S, F>
+where
+    F: FnMut(&[Vec<u8>], bool) -> CostResult<Merk<S>, Error>,
+    S: StorageContext<'db> + Send,
+{
+    /// Applies paths that don't follow references. Their Merks are opened in
+    /// order on the calling thread, then consecutive chunks of paths are
+    /// applied by the pool.
+    fn execute_independent_paths<G, SR>(
+        &mut self,
+        ops_by_path: Vec<(KeyInfoPath, BTreeMap<KeyInfo, Op>)>,
+        ops_by_qualified_paths: &BTreeMap<Vec<Vec<u8>>, Op>,
+        batch_apply_options: &BatchApplyOptions,
+        flags_update: &mut G,
+        split_removal_bytes: &mut SR,
+    ) -> CostResult<Vec<(KeyInfoPath, RootHashKeyAndSum)>, Error>
+    where
+        G: FnMut(&StorageCost, Option<ElementFlags>, &mut ElementFlags) -> Result<bool, Error>,
+        SR: FnMut(
+            &mut ElementFlags,
+            u32,
+            u32,
+        ) -> Result<(StorageRemovedBytes, StorageRemovedBytes), Error>,
+    {
+        let pool = match self.pool {
+            Some(pool) if pool.current_num_threads() > 1 && ops_by_path.len() > 1 => pool,
+            _ => {
+                return self.cache.execute_ops_on_paths(
+                    ops_by_path,
+                    ops_by_qualified_paths,
+                    batch_apply_options,
+                    flags_update,
+                    split_removal_bytes,
+                )
+            }
+        };
+
+        let mut cost = OperationCost::default();
+
+        let mut opened_paths = Vec::with_capacity(ops_by_path.len());
+        let mut open_error = None;
+        for (path, ops_at_path) in ops_by_path {
+            let merk_path = path.to_path();
+            let mut open_cost = OperationCost::default();
+            let merk_wrapped = self
+                .cache
+                .merks
+                .remove(&merk_path)
+                .map(|x| Ok(x).wrap_with_cost(Default::default()))
+                .unwrap_or_else(|| (self.cache.get_merk_fn)(&merk_path, false));
+            match merk_wrapped.unwrap_add_cost(&mut open_cost) {
+                Ok(merk) => opened_paths.push(OpenedPath {
+                    path,
+                    merk_path,
+                    merk,
+                    ops_at_path,
+                    open_cost,
+                }),
+                Err(e) => {
+                    open_error = Some((merk_path, e, open_cost));
+                    break;
+                }
+            }
+        }
+
+        let threads = pool.current_num_threads();
+        let chunk_size = opened_paths.len().div_ceil(threads);
+        let mut chunks = Vec::with_capacity(threads);
+        while opened_paths.len() > chunk_size {
+            let rest = opened_paths.split_off(chunk_size);
+            chunks.push(opened_paths);
+            opened_paths = rest;
+        }
+        chunks.push(opened_paths);
+
+        let tracing = self.cache.trace.is_some();
+        let mut executed_chunks: Vec<Vec<ExecutedPath<S>>> =
+            chunks.iter().map(|_| Vec::new()).collect();
+        pool.in_place_scope(|scope| {
+            let mut request_receivers = Vec::with_capacity(chunks.len());
+            for (chunk, executed_paths) in chunks.into_iter().zip(executed_chunks.iter_mut()) {
+                let (requests, request_receiver) = mpsc::channel();
+                request_receivers.push(request_receiver);
+                scope.spawn(move |_| {
+                    *executed_paths =
+                        execute_chunk::<S, F>(chunk, batch_apply_options, tracing, requests)
+                });
+            }
+
+            // Callbacks are run here one chunk after the other, so in the order
+            // of the ops as on a single thread. This serializes the workers
+            // calling them. A chunk has no more requests once its worker is
+            // done, a panic of the worker being raised when the scope ends.
+            for request in request_receivers.into_iter().flatten() {
+                match request {
+                    CallbackRequest::UpdateFlags {
+                        storage_cost,
+                        old_flags,
+                        mut new_flags,
+                        response,
+                    } => {
+                        let result = flags_update(&storage_cost, old_flags, &mut new_flags);
+                        let _ = response.send((result, new_flags));
+                    }
+                    CallbackRequest::SplitRemovedBytes {
+                        mut flags,
+                        removed_key_bytes,
+                        removed_value_bytes,
+                        response,
+                    } => {
+                        let result =
+                            split_removal_bytes(&mut flags, removed_key_bytes, removed_value_bytes);
+                        let _ = response.send((result, flags));
+                    }
+                }
+            }
+        });
+
+        let mut results = Vec::new();
+        for ExecutedPath {
+            path,
+            merk_path,
+            merk,
+            result,
+            failed_key,
+        } in executed_chunks.into_iter().flatten()
+        {
+            if let Some(trace) = self.cache.trace.as_deref_mut() {
+                trace.record_path(&merk_path, failed_key, &result);
+            }
+            // We need to reinsert the merk
+            self.cache.merks.insert(merk_path, merk);
+            let root_hash_key_and_sum = cost_return_on_error!(&mut cost, result);
+            results.push((path, root_hash_key_and_sum));
+        }
+
+        if let Some((merk_path, e, open_cost)) = open_error {
+            if let Some(trace) = self.cache.trace.as_deref_mut() {
+                trace.record_failure(&merk_path, None);
+            }
+            cost += open_cost;
+            return Err(e).wrap_with_cost(cost);
+        }
+
+        Ok(results).wrap_with_cost(cost)
+    }
+}
+
+/// Applies the ops of a chunk of paths in order on a worker, stopping at the
+/// first error
+fn execute_chunk<'db, S, F>(
+    chunk: Vec<OpenedPath<S>>,
+    batch_apply_options: &BatchApplyOptions,
+    tracing: bool,
+    requests: mpsc::Sender<CallbackRequest>,
+) -> Vec<ExecutedPath<S>>
+where
+    F: FnMut(&[Vec<u8>], bool) -> CostResult<Merk<S>, Error>,
+    S: StorageContext<'db>,
+{
+    let mut flags_update = |storage_cost: &StorageCost,
+                            old_flags: Option<ElementFlags>,
+                            new_flags: &mut ElementFlags| {
+        let (response, response_receiver) = mpsc::channel();
+        requests
+            .send(CallbackRequest::UpdateFlags {
+                storage_cost: storage_cost.clone(),
+                old_flags,
+                new_flags: new_flags.clone(),
+                response,
+            })
+            .map_err(|_| Error::InternalError("batch callbacks are no longer run"))?;
+        let (result, updated_flags) = response_receiver
+            .recv()
+            .map_err(|_| Error::InternalError("batch callbacks are no longer run"))?;
+        *new_flags = updated_flags;
+        result
+    };
+    let mut split_removal_bytes =
+        |flags: &mut ElementFlags, removed_key_bytes: u32, removed_value_bytes: u32| {
+            let (response, response_receiver) = mpsc::channel();
+            requests
+                .send(CallbackRequest::SplitRemovedBytes {
+                    flags: flags.clone(),
+                    removed_key_bytes,
+                    removed_value_bytes,
+                    response,
+                })
+                .map_err(|_| Error::InternalError("batch callbacks are no longer run"))?;
+            let (result, updated_flags) = response_receiver
+                .recv()
+                .map_err(|_| Error::InternalError("batch callbacks are no longer run"))?;
+            *flags = updated_flags;
+            result
+        };
+
+    let mut executed_paths = Vec::with_capacity(chunk.len());
+    for OpenedPath {
+        path,
+        merk_path,
+        mut merk,
+        ops_at_path,
+        open_cost,
+    } in chunk
+    {
+        let mut failed_key = None;
+        let result = TreeCacheMerkByPath::<S, F>::execute_ops_on_merk(
+            &mut merk,
+            &merk_path,
+            ops_at_path,
+            batch_apply_options,
+            &mut |_, _| {
+                Err(Error::CorruptedCodeExecution(
+                    "references are only followed on the calling thread",
+                ))
+                .wrap_with_cost(OperationCost::default())
+            },
+            &mut flags_update,
+            &mut split_removal_bytes,
+            tracing.then_some(&mut failed_key),
+        )
+        .add_cost(open_cost);
+        let failed = result.value.is_err();
+        executed_paths.push(ExecutedPath {
+            path,
+            merk_path,
+            merk,
+            result,
+            failed_key,
+        });
+        if failed {
+            break;
+        }
+    }
+    executed_paths
+}
+
+impl<'db, S, F, G, SR> TreeCache<G, SR> for ParallelTreeCacheMerkByPath<'_, '_, S, F>
+where
+    G: FnMut(&StorageCost, Option<ElementFlags>, &mut ElementFlags) -> Result<bool, Error>,
+    SR: FnMut(
+        &mut ElementFlags,
+        u32,
+        u32,
+    ) -> Result<(StorageRemovedBytes, StorageRemovedBytes), Error>,
+    F: FnMut(&[Vec<u8>], bool) -> CostResult<Merk<S>, Error>,
+    S: StorageContext<'db> + Send,
+{
+    fn insert(&mut self, op: &GroveDbOp, is_sum_tree: bool) -> CostResult<(), Error> {
+        <TreeCacheMerkByPath<S, F> as TreeCache<G, SR>>::insert(&mut self.cache, op, is_sum_tree)
+    }
+
+    fn get_batch_run_mode(&self) -> BatchRunMode {
+        BatchRunMode::Execute
+    }
+
+    fn execute_ops_on_path(
+        &mut self,
+        path: &KeyInfoPath,
+        ops_at_path_by_key: BTreeMap<KeyInfo, Op>,
+        ops_by_qualified_paths: &BTreeMap<Vec<Vec<u8>>, Op>,
+        batch_apply_options: &BatchApplyOptions,
+        flags_update: &mut G,
+        split_removal_bytes: &mut SR,
+    ) -> CostResult<RootHashKeyAndSum, Error> {
+        self.cache.execute_ops_on_path(
+            path,
+            ops_at_path_by_key,
+            ops_by_qualified_paths,
+            batch_apply_options,
+            flags_update,
+            split_removal_bytes,
+        )
+    }
+
+    fn execute_ops_on_paths(
+        &mut self,
+        ops_by_path: Vec<(KeyInfoPath, BTreeMap<KeyInfo, Op>)>,
+        ops_by_qualified_paths: &BTreeMap<Vec<Vec<u8>>, Op>,
+        batch_apply_options: &BatchApplyOptions,
+        flags_update: &mut G,
+        split_removal_bytes: &mut SR,
+    ) -> CostResult<Vec<(KeyInfoPath, RootHashKeyAndSum)>, Error> {
+        let mut cost = OperationCost::default();
+        let mut results = Vec::with_capacity(ops_by_path.len());
+        let mut independent_paths = Vec::new();
+        for (path, ops_at_path) in ops_by_path {
+            if !follows_references(&ops_at_path) {
+                independent_paths.push((path, ops_at_path));
+                continue;
+            }
+            // Paths before this one are applied first, as they would be on a
+            // single thread
+            results.extend(cost_return_on_error!(
+                &mut cost,
+                self.execute_independent_paths(
+                    std::mem::take(&mut independent_paths),
+                    ops_by_qualified_paths,
+                    batch_apply_options,
+                    flags_update,
+                    split_removal_bytes,
+                )
+            ));
+            let result = cost_return_on_error!(
+                &mut cost,
+                self.cache.execute_ops_on_path(
+                    &path,
+                    ops_at_path,
+                    ops_by_qualified_paths,
+                    batch_apply_options,
+                    flags_update,
+                    split_removal_bytes,
+                )
+            );
+            results.push((path, result));
+        }
+        results.extend(cost_return_on_error!(
+            &mut cost,
+            self.execute_independent_paths(
+                independent_paths,
+                ops_by_qualified_paths,
+                batch_apply_options,
+                flags_update,
+                split_removal_bytes,
+            )
+        ));
+        Ok(results).wrap_with_cost(cost)
+    }
+
+    fn update_base_merk_root_key(&mut self, root_key: Option<Vec<u8>>) -> CostResult<(), Error> {
+        <TreeCacheMerkByPath<S, F> as TreeCache<G, SR>>::update_base_merk_root_key(
+            &mut self.cache,
+            root_key,
+        )
+    }
+
+    fn record_failed_op(&mut self, path: &KeyInfoPath, key: &KeyInfo) {
+        <TreeCacheMerkByPath<S, F> as TreeCache<G, SR>>::record_failed_op(
+            &mut self.cache,
+            path,
+            key,
+        )
+    }
+}
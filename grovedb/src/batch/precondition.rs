@@ -0,0 +1,189 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Batch operation preconditions
+
+use std::ops::RangeInclusive;
+
+use grovedb_costs::{cost_return_on_error, CostResult, CostsExt, OperationCost};
+use grovedb_merk::{CryptoHash, Merk};
+use grovedb_path::SubtreePath;
+use grovedb_storage::{build_prefix, CovariantStorage, StorageContext};
+
+use crate::{batch::GroveDbOp, Element, Error, GroveDb, TransactionArg};
+
+/// A condition on the current state of an operation's key that must hold for
+/// the batch to be applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Precondition {
+    /// An element must exist under the key
+    Exists,
+    /// No element may exist under the key
+    DoesNotExist,
+    /// The element under the key must have this value hash
+    ValueHash(CryptoHash),
+    /// The element under the key must be equal to this one
+    Element(Element),
+    /// The element under the key must be a sum item or a sum tree with a sum
+    /// in this range
+    SumInRange(RangeInclusive<i64>),
+}
+
+impl Precondition {
+    /// Reason reported when the precondition does not hold
+    fn failure_reason(&self) -> &'static str {
+        match self {
+            Precondition::Exists => "element does not exist",
+            Precondition::DoesNotExist => "element already exists",
+            Precondition::ValueHash(_) => "value hash does not match",
+            Precondition::Element(_) => "element does not match",
+            Precondition::SumInRange(_) => "sum is not in the expected range",
+        }
+    }
+}
+
+impl<S: CovariantStorage> GroveDb<S> {
+    /// Runs `apply` on the operations in a transaction of its own if none is
+    /// given and some operations have preconditions, so the records the
+    /// preconditions read are tracked for conflicts until the batch is
+    /// committed
+    pub(crate) fn with_precondition_transaction<T>(
+        &self,
+        ops: Vec<GroveDbOp>,
+        transaction: TransactionArg<S>,
+        apply: impl FnOnce(Vec<GroveDbOp>, TransactionArg<S>) -> CostResult<T, Error>,
+    ) -> CostResult<T, Error> {
+        if transaction.is_some() || ops.iter().all(|op| op.precondition.is_none()) {
+            return apply(ops, transaction);
+        }
+
+        let mut cost = OperationCost::default();
+        let tx = self.start_transaction();
+        let result = cost_return_on_error!(&mut cost, apply(ops, Some(&tx)));
+        cost_return_on_error!(&mut cost, self.commit_transaction(tx));
+        Ok(result).wrap_with_cost(cost)
+    }
+
+    /// Checks the preconditions of all operations against the state before
+    /// the batch is applied, returning the index of the first failing
+    /// operation in the error. Within a transaction the records they read are
+    /// tracked, so the transaction fails to commit with
+    /// `Error::TransactionConflict` if another one changes them meanwhile.
+    pub(crate) fn check_batch_preconditions(
+        &self,
+        ops: &[GroveDbOp],
+        transaction: TransactionArg<S>,
+    ) -> CostResult<(), Error> {
+        let mut cost = OperationCost::default();
+
+        for (index, op) in ops.iter().enumerate() {
+            let Some(precondition) = &op.precondition else {
+                continue;
+            };
+            let path = op.path.to_path();
+            let subtree_path: SubtreePath<_> = path.as_slice().into();
+            let key = op.key.as_slice();
+
+            if let Some(tx) = transaction {
+                let prefix = build_prefix(subtree_path.clone()).unwrap_add_cost(&mut cost);
+                cost_return_on_error!(
+                    &mut cost,
+                    self.db
+                        .get_for_update(tx, &prefix, key)
+                        .map_err(|e| e.into())
+                );
+            }
+
+            let holds = if let Precondition::ValueHash(expected_value_hash) = precondition {
+                let value_hash = cost_return_on_error!(
+                    &mut cost,
+                    self.get_value_hash_optional(subtree_path, key, transaction)
+                );
+                value_hash.as_ref() == Some(expected_value_hash)
+            } else {
+                let element = cost_return_on_error!(
+                    &mut cost,
+                    self.get_raw_optional(subtree_path, key, transaction)
+                );
+                match (precondition, element) {
+                    (Precondition::Exists, element) => element.is_some(),
+                    (Precondition::DoesNotExist, element) => element.is_none(),
+                    (Precondition::Element(expected_element), element) => {
+                        element.as_ref() == Some(expected_element)
+                    }
+                    (
+                        Precondition::SumInRange(range),
+                        Some(Element::SumItem(sum, _) | Element::SumTree(_, sum, _)),
+                    ) => range.contains(&sum),
+                    _ => false,
+                }
+            };
+
+            if !holds {
+                return Err(Error::BatchPreconditionFailed(
+                    index,
+                    precondition.failure_reason(),
+                ))
+                .wrap_with_cost(cost);
+            }
+        }
+
+        Ok(()).wrap_with_cost(cost)
+    }
+
+    /// Get the value hash of the element at a path and key, `None` if either
+    /// doesn't exist
+    fn get_value_hash_optional<B: AsRef<[u8]>>(
+        &self,
+        path: SubtreePath<B>,
+        key: &[u8],
+        transaction: TransactionArg<S>,
+    ) -> CostResult<Option<CryptoHash>, Error> {
+        if let Some(tx) = transaction {
+            let merk = self.open_transactional_merk_at_path(path, tx, None);
+            value_hash_in_optional_merk(merk, key)
+        } else {
+            let merk = self.open_non_transactional_merk_at_path(path, None);
+            value_hash_in_optional_merk(merk, key)
+        }
+    }
+}
+
+/// Get the value hash of a key in a Merk, treating a missing Merk as a
+/// missing key
+fn value_hash_in_optional_merk<'db, S: StorageContext<'db>>(
+    merk: CostResult<Merk<S>, Error>,
+    key: &[u8],
+) -> CostResult<Option<CryptoHash>, Error> {
+    let mut cost = OperationCost::default();
+    match merk.unwrap_add_cost(&mut cost) {
+        Ok(merk) => Element::get_value_hash(&merk, key, true).add_cost(cost),
+        Err(Error::InvalidParentLayerPath(_)) => Ok(None).wrap_with_cost(cost),
+        Err(e) => Err(e).wrap_with_cost(cost),
+    }
+}
@@ -0,0 +1,453 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Batch apply reports
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use grovedb_costs::{
+    storage_cost::{removal::StorageRemovedBytes::BasicStorageRemoval, StorageCost},
+    CostContext, CostResult, CostsExt, OperationCost,
+};
+use grovedb_merk::CryptoHash;
+use grovedb_path::SubtreePath;
+use grovedb_storage::{build_prefix, CovariantStorage, StorageBatch, SubtreePrefix};
+
+use crate::{
+    batch::{key_info::KeyInfo, BatchApplyOptions, GroveDbOp, GroveDbOpConsistencyResults, Op},
+    Element, Error, GroveDb, TransactionArg,
+};
+
+/// Length of the prefix storage keys of a subtree start with
+const SUBTREE_PREFIX_LENGTH: usize = std::mem::size_of::<SubtreePrefix>();
+
+/// Outcome of an operation of a batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchOpStatus {
+    /// The operation was applied
+    Applied,
+    /// The batch failed at this operation
+    Failed,
+    /// The batch failed at another operation so nothing was applied
+    NotApplied,
+}
+
+/// Report of an operation of a batch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchOpReport {
+    /// Outcome of the operation
+    pub status: BatchOpStatus,
+    /// Element under the operation's key before the batch, `None` if there
+    /// was none, it couldn't be read or the operation deletes a range
+    pub previous_element: Option<Element>,
+    /// Storage cost of the Merk node the operation writes, plus the cost of
+    /// applying the batch at the operation's path and propagating it up to
+    /// the root. Path costs are included whole in the cost of every operation
+    /// going through the path. Records of deleted subtrees are removed on
+    /// commit and only part of the batch cost.
+    pub cost: OperationCost,
+}
+
+/// Report of a batch applied with `apply_batch_with_report`
+#[derive(Debug)]
+pub struct BatchApplyReport {
+    /// Reports in the same order as the batch operations
+    pub op_reports: Vec<BatchOpReport>,
+    /// Index of the operation the batch failed at, if the error can be tied
+    /// to one
+    pub failed_op_index: Option<usize>,
+    /// Result of applying the batch
+    pub result: Result<(), Error>,
+}
+
+/// Path and key of an op a batch failed at, the key is `None` when the
+/// failure concerns the whole path
+type FailedAt = (Vec<Vec<u8>>, Option<Vec<u8>>);
+
+/// Cost of every path, the op the batch failed at and the root hash it leads
+/// to, collected while a batch is applied for a report or a simulation
+#[derive(Debug, Default)]
+pub(super) struct BatchTrace {
+    /// Cost of executing the ops at each path, in execution order
+    path_costs: Vec<(Vec<Vec<u8>>, OperationCost)>,
+    /// Path and key of the op the batch failed at
+    failed_at: Option<FailedAt>,
+    /// Storage cost of every Merk node written by the batch, by prefixed key
+    record_costs: BTreeMap<Vec<u8>, StorageCost>,
+    /// Root hash of the root tree once its ops are executed
+    root_hash: Option<CryptoHash>,
+}
+
+impl BatchTrace {
+    /// Records the root hash of the root tree once its ops are executed
+    pub(super) fn record_root_hash(&mut self, root_hash: CryptoHash) {
+        self.root_hash = Some(root_hash);
+    }
+
+    /// Root hash of the root tree, `None` if the batch didn't reach it
+    pub(super) fn root_hash(&self) -> Option<CryptoHash> {
+        self.root_hash
+    }
+
+    /// Records the cost of executing the ops at a path and whether it failed
+    pub(super) fn record_path<T>(
+        &mut self,
+        path: &[Vec<u8>],
+        failed_key: Option<Vec<u8>>,
+        result: &CostResult<T, Error>,
+    ) {
+        self.path_costs.push((path.to_vec(), result.cost.clone()));
+        if result.value.is_err() {
+            self.record_failure(path, failed_key);
+        }
+    }
+
+    /// Records the storage cost of the Merk nodes written by the batch before
+    /// it is committed
+    pub(super) fn record_storage_costs(&mut self, batch: &StorageBatch) -> Result<(), Error> {
+        self.record_costs = batch.data_storage_costs()?;
+        Ok(())
+    }
+
+    /// Adds to the report of every op the storage cost of the node it writes
+    /// and the cost of applying the batch at its path and at every path
+    /// above it. Expanded ops are given with the index of the op they come
+    /// from.
+    fn add_op_costs(
+        &self,
+        expanded_paths_and_keys: &[(Vec<Vec<u8>>, KeyInfo)],
+        origins: &[usize],
+        op_reports: &mut [BatchOpReport],
+    ) {
+        let prefix = |path: &[Vec<u8>]| build_prefix(SubtreePath::from(path)).unwrap().to_vec();
+
+        let mut op_records = BTreeSet::new();
+        for ((path, key), origin) in expanded_paths_and_keys.iter().zip(origins.iter()) {
+            let path_prefix = prefix(path);
+            let records: Vec<Vec<u8>> = if let KeyInfo::Range(range) = key {
+                // a range delete writes the records of every key it removed
+                self.record_costs
+                    .range(path_prefix.clone()..)
+                    .map(|(record, _)| record)
+                    .take_while(|record| record.starts_with(&path_prefix))
+                    .filter(|record| range.contains(&record[path_prefix.len()..]))
+                    .cloned()
+                    .collect()
+            } else {
+                vec![[path_prefix, key.get_key_clone()].concat()]
+            };
+            for record in records {
+                if let Some(record_cost) = self.record_costs.get(&record) {
+                    op_reports[*origin].cost.storage_cost += record_cost.clone();
+                }
+                op_records.insert(record);
+            }
+        }
+
+        // Applying the batch at a path costs executing its ops and writing the
+        // nodes no op targets, those moved by rebalancing or holding the root
+        // hash of a changed subtree
+        let mut path_costs: BTreeMap<&[Vec<u8>], OperationCost> = BTreeMap::new();
+        for (path, path_cost) in self.path_costs.iter() {
+            *path_costs.entry(path.as_slice()).or_default() += path_cost.clone();
+        }
+        let paths_by_prefix: BTreeMap<Vec<u8>, &[Vec<u8>]> = path_costs
+            .keys()
+            .map(|path| (prefix(path), *path))
+            .collect();
+        for (record, record_cost) in self.record_costs.iter() {
+            if op_records.contains(record) {
+                continue;
+            }
+            let path = record
+                .get(..SUBTREE_PREFIX_LENGTH)
+                .and_then(|record_prefix| paths_by_prefix.get(record_prefix));
+            if let Some(path_cost) = path.and_then(|path| path_costs.get_mut(path)) {
+                path_cost.storage_cost += record_cost.clone();
+            }
+        }
+
+        // An op pays once for every path its changes propagate through
+        let mut op_paths = BTreeSet::new();
+        for ((path, _), origin) in expanded_paths_and_keys.iter().zip(origins.iter()) {
+            for length in 0..=path.len() {
+                op_paths.insert((*origin, &path[..length]));
+            }
+        }
+        for (origin, path) in op_paths {
+            if let Some(path_cost) = path_costs.get(path) {
+                op_reports[origin].cost += path_cost.clone();
+            }
+        }
+    }
+
+    /// Records the op the batch failed at, only the first failure is kept as
+    /// the batch stops there
+    pub(super) fn record_failure(&mut self, path: &[Vec<u8>], key: Option<Vec<u8>>) {
+        if self.failed_at.is_none() {
+            self.failed_at = Some((path.to_vec(), key));
+        }
+    }
+}
+
+impl<S: CovariantStorage> GroveDb<S> {
+    /// Applies a batch as `apply_batch` does and reports, for every
+    /// operation, its outcome, the element it replaced and its share of the
+    /// cost. When the batch fails the report gives the index of the operation
+    /// it failed at, if the error can be tied to one.
+    pub fn apply_batch_with_report(
+        &self,
+        ops: Vec<GroveDbOp>,
+        batch_apply_options: Option<BatchApplyOptions>,
+        transaction: TransactionArg<S>,
+    ) -> CostContext<BatchApplyReport> {
+        let mut cost = OperationCost::default();
+
+        let mut op_reports = Vec::with_capacity(ops.len());
+        for op in ops.iter() {
+            let previous_element = if let Op::DeleteRange { .. } = op.op {
+                None
+            } else {
+                let path = op.path.to_path();
+                self.get_raw_optional(
+                    SubtreePath::from(path.as_slice()),
+                    op.key.as_slice(),
+                    transaction,
+                )
+                .unwrap_add_cost(&mut cost)
+                .ok()
+                .flatten()
+            };
+            op_reports.push(BatchOpReport {
+                status: BatchOpStatus::NotApplied,
+                previous_element,
+                cost: OperationCost::default(),
+            });
+        }
+
+        // Preconditions are checked in a transaction of their own if none is
+        // given, as `with_precondition_transaction` does
+        let result = if transaction.is_none() && ops.iter().any(|op| op.precondition.is_some()) {
+            let tx = self.start_transaction();
+            self.apply_batch_with_op_costs(ops, batch_apply_options, Some(&tx), &mut op_reports)
+                .unwrap_add_cost(&mut cost)
+                .and_then(|_| {
+                    self.commit_transaction(tx)
+                        .unwrap_add_cost(&mut cost)
+                        .map_err(|e| (None, e))
+                })
+        } else {
+            self.apply_batch_with_op_costs(ops, batch_apply_options, transaction, &mut op_reports)
+                .unwrap_add_cost(&mut cost)
+        };
+
+        let (failed_op_index, result) = match result {
+            Ok(()) => {
+                for op_report in op_reports.iter_mut() {
+                    op_report.status = BatchOpStatus::Applied;
+                }
+                (None, Ok(()))
+            }
+            Err((failed_op_index, e)) => {
+                if let Some(index) = failed_op_index {
+                    op_reports[index].status = BatchOpStatus::Failed;
+                }
+                (failed_op_index, Err(e))
+            }
+        };
+
+        BatchApplyReport {
+            op_reports,
+            failed_op_index,
+            result,
+        }
+        .wrap_with_cost(cost)
+    }
+
+    /// Applies the batch, adding the cost shares to the op reports. Errors
+    /// come with the index of the op they are tied to.
+    fn apply_batch_with_op_costs(
+        &self,
+        ops: Vec<GroveDbOp>,
+        batch_apply_options: Option<BatchApplyOptions>,
+        transaction: TransactionArg<S>,
+        op_reports: &mut [BatchOpReport],
+    ) -> CostResult<(), (Option<usize>, Error)> {
+        let mut cost = OperationCost::default();
+
+        if ops.is_empty() {
+            return Ok(()).wrap_with_cost(cost);
+        }
+
+        let check_batch_operation_consistency = batch_apply_options
+            .as_ref()
+            .map(|batch_options| !batch_options.disable_operation_consistency_check)
+            .unwrap_or(true);
+        if check_batch_operation_consistency {
+            let consistency_result = GroveDbOp::verify_consistency_of_operations(&ops);
+            if !consistency_result.is_empty() {
+                return Err((
+                    first_inconsistent_op_index(&ops, &consistency_result),
+                    Error::InvalidBatchOperation("batch operations fail consistency checks"),
+                ))
+                .wrap_with_cost(cost);
+            }
+        }
+
+        if let Err(e) = self
+            .check_batch_preconditions(&ops, transaction)
+            .unwrap_add_cost(&mut cost)
+        {
+            let failed_op_index = match e {
+                Error::BatchPreconditionFailed(index, _) => Some(index),
+                _ => None,
+            };
+            return Err((failed_op_index, e)).wrap_with_cost(cost);
+        }
+
+        // Moves are expanded and range deletes checked one by one to know which
+        // op every resulting op comes from
+        let delete_options = batch_apply_options
+            .as_ref()
+            .map(|o| o.as_delete_options())
+            .unwrap_or_default();
+        let mut expanded_ops = Vec::with_capacity(ops.len());
+        let mut origins = Vec::with_capacity(ops.len());
+        for (index, op) in ops.into_iter().enumerate() {
+            let expanded = match op.op {
+                Op::Move { to_path, to_key } => self.push_move_ops(
+                    &mut expanded_ops,
+                    op.path.to_path_consume(),
+                    op.key.get_key(),
+                    to_path,
+                    to_key,
+                    transaction,
+                ),
+                Op::DeleteRange { query_item } => self.push_delete_range_ops(
+                    &mut expanded_ops,
+                    op.path.to_path_consume(),
+                    query_item,
+                    &delete_options,
+                    transaction,
+                ),
+                _ => {
+                    expanded_ops.push(op);
+                    Ok(()).wrap_with_cost(OperationCost::default())
+                }
+            };
+            if let Err(e) = expanded.unwrap_add_cost(&mut cost) {
+                return Err((Some(index), e)).wrap_with_cost(cost);
+            }
+            origins.resize(expanded_ops.len(), index);
+        }
+
+        let expanded_paths_and_keys: Vec<(Vec<Vec<u8>>, KeyInfo)> = expanded_ops
+            .iter()
+            .map(|op| (op.path.to_path(), op.key.clone()))
+            .collect();
+
+        let mut trace = BatchTrace::default();
+        let result = self
+            .apply_expanded_batch(
+                expanded_ops,
+                batch_apply_options,
+                |_cost, _old_flags, _new_flags| Ok(false),
+                |_flags, key_bytes_to_remove, value_bytes_to_remove| {
+                    Ok((
+                        BasicStorageRemoval(key_bytes_to_remove),
+                        BasicStorageRemoval(value_bytes_to_remove),
+                    ))
+                },
+                transaction,
+                Some(&mut trace),
+                None,
+            )
+            .unwrap_add_cost(&mut cost);
+
+        trace.add_op_costs(&expanded_paths_and_keys, &origins, op_reports);
+
+        if let Err(e) = result {
+            let failed_op_index = trace.failed_at.and_then(|(failed_path, failed_key)| {
+                expanded_paths_and_keys
+                    .iter()
+                    .position(|(path, key)| {
+                        *path == failed_path
+                            && failed_key.as_ref().is_none_or(|failed_key| {
+                                key.overlaps(&KeyInfo::KnownKey(failed_key.clone()))
+                            })
+                    })
+                    .map(|position| origins[position])
+            });
+            return Err((failed_op_index, e)).wrap_with_cost(cost);
+        }
+
+        Ok(()).wrap_with_cost(cost)
+    }
+}
+
+/// Index of the first op making the batch inconsistent, that is a repeated
+/// op, an op on an already used path and key, or an insert below a deleted
+/// tree
+fn first_inconsistent_op_index(
+    ops: &[GroveDbOp],
+    consistency_result: &GroveDbOpConsistencyResults,
+) -> Option<usize> {
+    let repeated = consistency_result
+        .repeated_ops
+        .iter()
+        .filter_map(|(op, _)| {
+            ops.iter()
+                .enumerate()
+                .filter(|(_, other_op)| *other_op == op)
+                .nth(1)
+                .map(|(index, _)| index)
+        });
+    let same_path_key = consistency_result
+        .same_path_key_ops
+        .iter()
+        .filter_map(|(path, key, _)| {
+            ops.iter()
+                .enumerate()
+                .filter(|(_, op)| op.path == *path && op.key.overlaps(key))
+                .nth(1)
+                .map(|(index, _)| index)
+        });
+    let inserts_below_deleted = consistency_result
+        .insert_ops_below_deleted_ops
+        .iter()
+        .filter_map(|(_, inserts)| {
+            inserts
+                .iter()
+                .filter_map(|insert| ops.iter().position(|op| op == insert))
+                .min()
+        });
+    repeated
+        .chain(same_path_key)
+        .chain(inserts_below_deleted)
+        .min()
+}
@@ -0,0 +1,459 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Batch serialization
+//!
+//! A serialized batch is the encoding version as a varint followed by the
+//! operations. The operations, elements and reference paths are encoded
+//! through types of this module rather than the types themselves, so those can
+//! change without changing the encoding of a version.
+
+use bincode::Options;
+use grovedb_costs::{CostResult, CostsExt, OperationCost};
+use grovedb_merk::CryptoHash;
+use grovedb_storage::CovariantStorage;
+use integer_encoding::VarInt;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    batch::{key_info::KeyInfo, BatchApplyOptions, GroveDbOp, KeyInfoPath, Op, Precondition},
+    element::{MaxReferenceHop, SumValue},
+    reference_path::ReferencePathType,
+    Element, ElementFlags, Error, GroveDb, QueryItem, TransactionArg,
+};
+
+/// Version of the encoding written by `GroveDbOp::serialize_batch`
+pub const BATCH_SERIALIZATION_VERSION: u16 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct SerializedOpV1 {
+    path: Vec<Vec<u8>>,
+    key: Vec<u8>,
+    op: SerializedOpKindV1,
+    precondition: Option<SerializedPreconditionV1>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum SerializedOpKindV1 {
+    Insert(SerializedElementV1),
+    Replace(SerializedElementV1),
+    Patch(SerializedElementV1, i32),
+    RefreshReference {
+        reference_path_type: SerializedReferencePathTypeV1,
+        max_reference_hop: MaxReferenceHop,
+        flags: Option<ElementFlags>,
+        trust_refresh_reference: bool,
+    },
+    Delete,
+    DeleteTree,
+    DeleteSumTree,
+    Move {
+        to_path: Vec<Vec<u8>>,
+        to_key: Vec<u8>,
+    },
+    DeleteRange(SerializedQueryItemV1),
+    InsertTreeWithContents {
+        is_sum_tree: bool,
+        elements: Vec<(Vec<u8>, SerializedElementV1)>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+enum SerializedElementV1 {
+    Item(Vec<u8>, Option<ElementFlags>),
+    Reference(
+        SerializedReferencePathTypeV1,
+        MaxReferenceHop,
+        Option<ElementFlags>,
+    ),
+    Tree(Option<Vec<u8>>, Option<ElementFlags>),
+    SumItem(SumValue, Option<ElementFlags>),
+    SumTree(Option<Vec<u8>>, SumValue, Option<ElementFlags>),
+}
+
+// Variants mirror those of `ReferencePathType` so the two read side by side
+#[allow(clippy::enum_variant_names)]
+#[derive(Serialize, Deserialize)]
+enum SerializedReferencePathTypeV1 {
+    AbsolutePathReference(Vec<Vec<u8>>),
+    UpstreamRootHeightReference(u8, Vec<Vec<u8>>),
+    UpstreamFromElementHeightReference(u8, Vec<Vec<u8>>),
+    CousinReference(Vec<u8>),
+    RemovedCousinReference(Vec<Vec<u8>>),
+    SiblingReference(Vec<u8>),
+}
+
+#[derive(Serialize, Deserialize)]
+enum SerializedQueryItemV1 {
+    Key(Vec<u8>),
+    Range(Vec<u8>, Vec<u8>),
+    RangeInclusive(Vec<u8>, Vec<u8>),
+    RangeFull,
+    RangeFrom(Vec<u8>),
+    RangeTo(Vec<u8>),
+    RangeToInclusive(Vec<u8>),
+    RangeAfter(Vec<u8>),
+    RangeAfterTo(Vec<u8>, Vec<u8>),
+    RangeAfterToInclusive(Vec<u8>, Vec<u8>),
+}
+
+#[derive(Serialize, Deserialize)]
+enum SerializedPreconditionV1 {
+    Exists,
+    DoesNotExist,
+    ValueHash(CryptoHash),
+    Element(SerializedElementV1),
+    SumInRange(i64, i64),
+}
+
+pub(super) fn bincode_options() -> impl Options {
+    bincode::DefaultOptions::default()
+        .with_varint_encoding()
+        .reject_trailing_bytes()
+}
+
+fn known_path(path: &KeyInfoPath) -> Result<Vec<Vec<u8>>, Error> {
+    path.iterator().map(known_key).collect()
+}
+
+fn known_key(key: &KeyInfo) -> Result<Vec<u8>, Error> {
+    match key {
+        KeyInfo::KnownKey(key) => Ok(key.clone()),
+        KeyInfo::MaxKeySize { .. } | KeyInfo::Range(_) => Err(Error::InvalidInput(
+            "only operations on known paths and keys can be serialized",
+        )),
+    }
+}
+
+impl From<&ReferencePathType> for SerializedReferencePathTypeV1 {
+    fn from(reference_path_type: &ReferencePathType) -> Self {
+        match reference_path_type.clone() {
+            ReferencePathType::AbsolutePathReference(path) => Self::AbsolutePathReference(path),
+            ReferencePathType::UpstreamRootHeightReference(height, path) => {
+                Self::UpstreamRootHeightReference(height, path)
+            }
+            ReferencePathType::UpstreamFromElementHeightReference(height, path) => {
+                Self::UpstreamFromElementHeightReference(height, path)
+            }
+            ReferencePathType::CousinReference(key) => Self::CousinReference(key),
+            ReferencePathType::RemovedCousinReference(path) => Self::RemovedCousinReference(path),
+            ReferencePathType::SiblingReference(key) => Self::SiblingReference(key),
+        }
+    }
+}
+
+impl From<SerializedReferencePathTypeV1> for ReferencePathType {
+    fn from(reference_path_type: SerializedReferencePathTypeV1) -> Self {
+        match reference_path_type {
+            SerializedReferencePathTypeV1::AbsolutePathReference(path) => {
+                ReferencePathType::AbsolutePathReference(path)
+            }
+            SerializedReferencePathTypeV1::UpstreamRootHeightReference(height, path) => {
+                ReferencePathType::UpstreamRootHeightReference(height, path)
+            }
+            SerializedReferencePathTypeV1::UpstreamFromElementHeightReference(height, path) => {
+                ReferencePathType::UpstreamFromElementHeightReference(height, path)
+            }
+            SerializedReferencePathTypeV1::CousinReference(key) => {
+                ReferencePathType::CousinReference(key)
+            }
+            SerializedReferencePathTypeV1::RemovedCousinReference(path) => {
+                ReferencePathType::RemovedCousinReference(path)
+            }
+            SerializedReferencePathTypeV1::SiblingReference(key) => {
+                ReferencePathType::SiblingReference(key)
+            }
+        }
+    }
+}
+
+impl From<&Element> for SerializedElementV1 {
+    fn from(element: &Element) -> Self {
+        match element.clone() {
+            Element::Item(value, flags) => Self::Item(value, flags),
+            Element::Reference(reference_path_type, max_reference_hop, flags) => {
+                Self::Reference((&reference_path_type).into(), max_reference_hop, flags)
+            }
+            Element::Tree(root_key, flags) => Self::Tree(root_key, flags),
+            Element::SumItem(value, flags) => Self::SumItem(value, flags),
+            Element::SumTree(root_key, sum, flags) => Self::SumTree(root_key, sum, flags),
+        }
+    }
+}
+
+impl From<SerializedElementV1> for Element {
+    fn from(element: SerializedElementV1) -> Self {
+        match element {
+            SerializedElementV1::Item(value, flags) => Element::Item(value, flags),
+            SerializedElementV1::Reference(reference_path_type, max_reference_hop, flags) => {
+                Element::Reference(reference_path_type.into(), max_reference_hop, flags)
+            }
+            SerializedElementV1::Tree(root_key, flags) => Element::Tree(root_key, flags),
+            SerializedElementV1::SumItem(value, flags) => Element::SumItem(value, flags),
+            SerializedElementV1::SumTree(root_key, sum, flags) => {
+                Element::SumTree(root_key, sum, flags)
+            }
+        }
+    }
+}
+
+impl From<&QueryItem> for SerializedQueryItemV1 {
+    fn from(query_item: &QueryItem) -> Self {
+        match query_item.clone() {
+            QueryItem::Key(key) => Self::Key(key),
+            QueryItem::Range(range) => Self::Range(range.start, range.end),
+            QueryItem::RangeInclusive(range) => {
+                let (start, end) = range.into_inner();
+                Self::RangeInclusive(start, end)
+            }
+            QueryItem::RangeFull(_) => Self::RangeFull,
+            QueryItem::RangeFrom(range) => Self::RangeFrom(range.start),
+            QueryItem::RangeTo(range) => Self::RangeTo(range.end),
+            QueryItem::RangeToInclusive(range) => Self::RangeToInclusive(range.end),
+            QueryItem::RangeAfter(range) => Self::RangeAfter(range.start),
+            QueryItem::RangeAfterTo(range) => Self::RangeAfterTo(range.start, range.end),
+            QueryItem::RangeAfterToInclusive(range) => {
+                let (start, end) = range.into_inner();
+                Self::RangeAfterToInclusive(start, end)
+            }
+        }
+    }
+}
+
+impl From<SerializedQueryItemV1> for QueryItem {
+    fn from(query_item: SerializedQueryItemV1) -> Self {
+        match query_item {
+            SerializedQueryItemV1::Key(key) => QueryItem::Key(key),
+            SerializedQueryItemV1::Range(start, end) => QueryItem::Range(start..end),
+            SerializedQueryItemV1::RangeInclusive(start, end) => {
+                QueryItem::RangeInclusive(start..=end)
+            }
+            SerializedQueryItemV1::RangeFull => QueryItem::RangeFull(..),
+            SerializedQueryItemV1::RangeFrom(start) => QueryItem::RangeFrom(start..),
+            SerializedQueryItemV1::RangeTo(end) => QueryItem::RangeTo(..end),
+            SerializedQueryItemV1::RangeToInclusive(end) => QueryItem::RangeToInclusive(..=end),
+            SerializedQueryItemV1::RangeAfter(start) => QueryItem::RangeAfter(start..),
+            SerializedQueryItemV1::RangeAfterTo(start, end) => QueryItem::RangeAfterTo(start..end),
+            SerializedQueryItemV1::RangeAfterToInclusive(start, end) => {
+                QueryItem::RangeAfterToInclusive(start..=end)
+            }
+        }
+    }
+}
+
+impl From<&Precondition> for SerializedPreconditionV1 {
+    fn from(precondition: &Precondition) -> Self {
+        match precondition {
+            Precondition::Exists => Self::Exists,
+            Precondition::DoesNotExist => Self::DoesNotExist,
+            Precondition::ValueHash(hash) => Self::ValueHash(*hash),
+            Precondition::Element(element) => Self::Element(element.into()),
+            Precondition::SumInRange(range) => Self::SumInRange(*range.start(), *range.end()),
+        }
+    }
+}
+
+impl From<SerializedPreconditionV1> for Precondition {
+    fn from(precondition: SerializedPreconditionV1) -> Self {
+        match precondition {
+            SerializedPreconditionV1::Exists => Precondition::Exists,
+            SerializedPreconditionV1::DoesNotExist => Precondition::DoesNotExist,
+            SerializedPreconditionV1::ValueHash(hash) => Precondition::ValueHash(hash),
+            SerializedPreconditionV1::Element(element) => Precondition::Element(element.into()),
+            SerializedPreconditionV1::SumInRange(start, end) => {
+                Precondition::SumInRange(start..=end)
+            }
+        }
+    }
+}
+
+impl TryFrom<&GroveDbOp> for SerializedOpV1 {
+    type Error = Error;
+
+    fn try_from(op: &GroveDbOp) -> Result<Self, Self::Error> {
+        let kind = match &op.op {
+            Op::Insert { element } => SerializedOpKindV1::Insert(element.into()),
+            Op::Replace { element } => SerializedOpKindV1::Replace(element.into()),
+            Op::Patch {
+                element,
+                change_in_bytes,
+            } => SerializedOpKindV1::Patch(element.into(), *change_in_bytes),
+            Op::RefreshReference {
+                reference_path_type,
+                max_reference_hop,
+                flags,
+                trust_refresh_reference,
+            } => SerializedOpKindV1::RefreshReference {
+                reference_path_type: reference_path_type.into(),
+                max_reference_hop: *max_reference_hop,
+                flags: flags.clone(),
+                trust_refresh_reference: *trust_refresh_reference,
+            },
+            Op::Delete => SerializedOpKindV1::Delete,
+            Op::DeleteTree => SerializedOpKindV1::DeleteTree,
+            Op::DeleteSumTree => SerializedOpKindV1::DeleteSumTree,
+            Op::Move { to_path, to_key } => SerializedOpKindV1::Move {
+                to_path: to_path.clone(),
+                to_key: to_key.clone(),
+            },
+            Op::DeleteRange { query_item } => SerializedOpKindV1::DeleteRange(query_item.into()),
+            Op::InsertTreeWithContents {
+                is_sum_tree,
+                elements,
+            } => SerializedOpKindV1::InsertTreeWithContents {
+                is_sum_tree: *is_sum_tree,
+                elements: elements
+                    .iter()
+                    .map(|(key, element)| (key.clone(), element.into()))
+                    .collect(),
+            },
+            Op::ReplaceTreeRootKey { .. } | Op::InsertTreeWithRootHash { .. } => {
+                return Err(Error::InvalidInput(
+                    "internal batch operations can not be serialized",
+                ))
+            }
+        };
+        Ok(SerializedOpV1 {
+            path: known_path(&op.path)?,
+            key: match &op.key {
+                // the range is serialized with the op
+                KeyInfo::Range(_) => Vec::new(),
+                key => known_key(key)?,
+            },
+            op: kind,
+            precondition: op.precondition.as_ref().map(|p| p.into()),
+        })
+    }
+}
+
+impl From<SerializedOpV1> for GroveDbOp {
+    fn from(op: SerializedOpV1) -> Self {
+        let kind = match op.op {
+            SerializedOpKindV1::Insert(element) => Op::Insert {
+                element: element.into(),
+            },
+            SerializedOpKindV1::Replace(element) => Op::Replace {
+                element: element.into(),
+            },
+            SerializedOpKindV1::Patch(element, change_in_bytes) => Op::Patch {
+                element: element.into(),
+                change_in_bytes,
+            },
+            SerializedOpKindV1::RefreshReference {
+                reference_path_type,
+                max_reference_hop,
+                flags,
+                trust_refresh_reference,
+            } => Op::RefreshReference {
+                reference_path_type: reference_path_type.into(),
+                max_reference_hop,
+                flags,
+                trust_refresh_reference,
+            },
+            SerializedOpKindV1::Delete => Op::Delete,
+            SerializedOpKindV1::DeleteTree => Op::DeleteTree,
+            SerializedOpKindV1::DeleteSumTree => Op::DeleteSumTree,
+            SerializedOpKindV1::Move { to_path, to_key } => Op::Move { to_path, to_key },
+            SerializedOpKindV1::DeleteRange(query_item) => Op::DeleteRange {
+                query_item: query_item.into(),
+            },
+            SerializedOpKindV1::InsertTreeWithContents {
+                is_sum_tree,
+                elements,
+            } => Op::InsertTreeWithContents {
+                is_sum_tree,
+                elements: elements
+                    .into_iter()
+                    .map(|(key, element)| (key, element.into()))
+                    .collect(),
+            },
+        };
+        let key = match &kind {
+            Op::DeleteRange { query_item } => KeyInfo::Range(query_item.clone()),
+            _ => KeyInfo::KnownKey(op.key),
+        };
+        GroveDbOp {
+            path: KeyInfoPath::from_known_owned_path(op.path),
+            key,
+            op: kind,
+            precondition: op.precondition.map(|p| p.into()),
+        }
+    }
+}
+
+impl GroveDbOp {
+    /// Serializes a batch with the current encoding version. Only operations
+    /// on known paths and keys can be serialized.
+    pub fn serialize_batch(ops: &[GroveDbOp]) -> Result<Vec<u8>, Error> {
+        let serialized_ops = ops
+            .iter()
+            .map(SerializedOpV1::try_from)
+            .collect::<Result<Vec<_>, Error>>()?;
+        let mut bytes = BATCH_SERIALIZATION_VERSION.encode_var_vec();
+        bincode_options()
+            .serialize_into(&mut bytes, &serialized_ops)
+            .map_err(|_| Error::CorruptedData(String::from("unable to serialize batch")))?;
+        Ok(bytes)
+    }
+
+    /// Deserializes a batch written with any supported encoding version
+    pub fn deserialize_batch(bytes: &[u8]) -> Result<Vec<GroveDbOp>, Error> {
+        let (version, version_length) = u16::decode_var(bytes).ok_or_else(|| {
+            Error::CorruptedData(String::from("unable to read batch encoding version"))
+        })?;
+        match version {
+            1 => {
+                let serialized_ops: Vec<SerializedOpV1> = bincode_options()
+                    .deserialize(&bytes[version_length..])
+                    .map_err(|_| {
+                        Error::CorruptedData(String::from("unable to deserialize batch"))
+                    })?;
+                Ok(serialized_ops.into_iter().map(GroveDbOp::from).collect())
+            }
+            _ => Err(Error::CorruptedData(format!(
+                "unsupported batch encoding version {version}"
+            ))),
+        }
+    }
+}
+
+impl<S: CovariantStorage> GroveDb<S> {
+    /// Deserializes a batch written by `GroveDbOp::serialize_batch` and
+    /// applies it
+    pub fn apply_serialized_batch(
+        &self,
+        bytes: &[u8],
+        batch_apply_options: Option<BatchApplyOptions>,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<(), Error> {
+        match GroveDbOp::deserialize_batch(bytes) {
+            Ok(ops) => self.apply_batch(ops, batch_apply_options, transaction),
+            Err(e) => Err(e).wrap_with_cost(OperationCost::default()),
+        }
+    }
+}
@@ -0,0 +1,229 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Batch simulation
+
+use grovedb_costs::{
+    cost_return_on_error,
+    storage_cost::{removal::StorageRemovedBytes::BasicStorageRemoval, StorageCost},
+    CostResult, CostsExt, OperationCost,
+};
+use grovedb_merk::CryptoHash;
+use grovedb_path::SubtreePath;
+use grovedb_storage::{build_prefix, CovariantStorage, StorageBatch};
+
+use crate::{
+    batch::{report::BatchTrace, BatchApplyOptions, GroveDbOp, Op},
+    element::helpers::raw_decode,
+    Element, ElementFlags, Error, GroveDb, TransactionArg,
+};
+
+/// Outcome of a simulated batch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulatedBatch {
+    /// Root hash GroveDB would have after applying the batch
+    pub root_hash: CryptoHash,
+    /// Cost of applying the batch
+    pub cost: OperationCost,
+    /// Element changes in the same order as the batch operations
+    pub op_diffs: Vec<SimulatedOpDiff>,
+}
+
+/// Element under an operation's key before and after the batch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulatedOpDiff {
+    /// Element before the batch, `None` if there was none
+    pub previous_element: Option<Element>,
+    /// Element after the batch, `None` if there is none
+    pub new_element: Option<Element>,
+}
+
+impl<S: CovariantStorage> GroveDb<S> {
+    /// Runs the whole batch pipeline as `apply_batch` would, including
+    /// reference and sum propagation, against a storage batch that is
+    /// discarded instead of committed, so GroveDB and the transaction are left
+    /// unchanged. Returns the resulting root hash, the cost of applying the
+    /// batch and the previous and new element for every operation. Moves of
+    /// subtrees can't be simulated, as they are relinked before the rest of
+    /// the batch is applied.
+    pub fn simulate_batch(
+        &self,
+        ops: Vec<GroveDbOp>,
+        batch_apply_options: Option<BatchApplyOptions>,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<SimulatedBatch, Error> {
+        let mut cost = OperationCost::default();
+
+        let paths_and_keys: Vec<(Vec<Vec<u8>>, Vec<u8>)> = ops
+            .iter()
+            .map(|op| (op.path.to_path(), op.key.get_key_clone()))
+            .collect();
+
+        let mut previous_elements = Vec::with_capacity(paths_and_keys.len());
+        for (path, key) in paths_and_keys.iter() {
+            previous_elements.push(cost_return_on_error!(
+                &mut cost,
+                self.get_raw_optional(SubtreePath::from(path.as_slice()), key, transaction)
+            ));
+        }
+
+        let storage_batch = StorageBatch::new();
+        let mut trace = BatchTrace::default();
+        let batch_result = self.simulate_batch_on_storage_batch(
+            ops,
+            batch_apply_options,
+            &storage_batch,
+            &mut trace,
+            transaction,
+        );
+        let mut batch_cost = batch_result.cost.clone();
+        cost_return_on_error!(&mut cost, batch_result);
+
+        let root_hash = match trace.root_hash() {
+            Some(root_hash) => root_hash,
+            None => cost_return_on_error!(&mut cost, self.root_hash(transaction)),
+        };
+
+        let mut op_diffs = Vec::with_capacity(paths_and_keys.len());
+        for ((path, key), previous_element) in paths_and_keys.iter().zip(previous_elements) {
+            let new_element = cost_return_on_error!(
+                &mut cost,
+                self.get_raw_optional_after_batch(path, key, &storage_batch, transaction)
+            );
+            op_diffs.push(SimulatedOpDiff {
+                previous_element,
+                new_element,
+            });
+        }
+
+        let commit_result = self.db.multi_context_batch_cost(
+            storage_batch,
+            transaction.map(|tx| self.db.narrow_transaction(tx)),
+        );
+        batch_cost += commit_result.cost.clone();
+        cost_return_on_error!(&mut cost, commit_result.map_err(|e| e.into()));
+
+        Ok(SimulatedBatch {
+            root_hash,
+            cost: batch_cost,
+            op_diffs,
+        })
+        .wrap_with_cost(cost)
+    }
+
+    /// Runs the batch pipeline writing into `storage_batch`, which is left to
+    /// the caller to discard
+    fn simulate_batch_on_storage_batch(
+        &self,
+        ops: Vec<GroveDbOp>,
+        batch_apply_options: Option<BatchApplyOptions>,
+        storage_batch: &StorageBatch,
+        trace: &mut BatchTrace,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<(), Error> {
+        let mut cost = OperationCost::default();
+
+        if ops.is_empty() {
+            return Ok(()).wrap_with_cost(cost);
+        }
+
+        let ops = cost_return_on_error!(
+            &mut cost,
+            self.expand_batch(ops, batch_apply_options.as_ref(), transaction)
+        );
+        if ops.iter().any(|op| matches!(op.op, Op::Move { .. })) {
+            return Err(Error::NotSupported("moves of subtrees can't be simulated"))
+                .wrap_with_cost(cost);
+        }
+        cost_return_on_error!(
+            &mut cost,
+            self.delete_prefixes_of_deleted_trees(&ops, storage_batch, transaction)
+        );
+
+        let update_element_flags_function =
+            |_: &StorageCost, _: Option<ElementFlags>, _: &mut ElementFlags| Ok(false);
+        let split_removal_bytes_function =
+            |_: &mut ElementFlags, key_bytes_to_remove, value_bytes_to_remove| {
+                Ok((
+                    BasicStorageRemoval(key_bytes_to_remove),
+                    BasicStorageRemoval(value_bytes_to_remove),
+                ))
+            };
+        if let Some(tx) = transaction {
+            self.apply_body(
+                ops,
+                batch_apply_options,
+                update_element_flags_function,
+                split_removal_bytes_function,
+                |path, new_merk| {
+                    self.open_batch_transactional_merk_at_path(
+                        storage_batch,
+                        path.into(),
+                        tx,
+                        new_merk,
+                    )
+                },
+                Some(trace),
+            )
+            .map_ok(|_| ())
+            .add_cost(cost)
+        } else {
+            self.apply_body(
+                ops,
+                batch_apply_options,
+                update_element_flags_function,
+                split_removal_bytes_function,
+                |path, new_merk| self.open_batch_merk_at_path(storage_batch, path.into(), new_merk),
+                Some(trace),
+            )
+            .map_ok(|_| ())
+            .add_cost(cost)
+        }
+    }
+
+    /// Get the element at a path and key as it is once a storage batch is
+    /// committed, `None` if either doesn't exist
+    fn get_raw_optional_after_batch(
+        &self,
+        path: &[Vec<u8>],
+        key: &[u8],
+        storage_batch: &StorageBatch,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<Option<Element>, Error> {
+        let subtree_path = SubtreePath::from(path);
+        let mut cost = OperationCost::default();
+        let prefix = build_prefix(subtree_path.clone()).unwrap_add_cost(&mut cost);
+        match storage_batch.data_value_after_commit(&[prefix.as_slice(), key].concat()) {
+            Some(Some(node)) => raw_decode(&node).map(Some).wrap_with_cost(cost),
+            Some(None) => Ok(None).wrap_with_cost(cost),
+            None => self
+                .get_raw_optional(subtree_path, key, transaction)
+                .add_cost(cost),
+        }
+    }
+}
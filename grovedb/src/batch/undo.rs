@@ -0,0 +1,191 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Undo batches
+//!
+//! An undo batch holds the values the storage records written by a batch had
+//! before it, so applying it restores the previous state exactly, Merk tree
+//! shapes and root hashes included.
+
+use bincode::Options;
+use grovedb_costs::{
+    cost_return_on_error, storage_cost::removal::StorageRemovedBytes::BasicStorageRemoval,
+    CostResult, CostsExt, OperationCost,
+};
+use grovedb_storage::{CovariantStorage, PreviousRecords, StorageBatch};
+use integer_encoding::VarInt;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    batch::{serialize::bincode_options, BatchApplyOptions, GroveDbOp},
+    Error, GroveDb, TransactionArg,
+};
+
+/// Version of the encoding written by `UndoBatch::serialize`
+const UNDO_BATCH_SERIALIZATION_VERSION: u16 = 1;
+
+/// Batch restoring the storage records a batch wrote to their previous
+/// values, made by `GroveDb::apply_batch_with_undo`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UndoBatch {
+    /// Paths and keys of the elements changed by the batch
+    changed_elements: Vec<(Vec<Vec<u8>>, Vec<u8>)>,
+    /// Previous values of the records written by the batch
+    records: PreviousRecords,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerializedUndoBatchV1 {
+    changed_elements: Vec<(Vec<Vec<u8>>, Vec<u8>)>,
+    data: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+    aux: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+    roots: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+    meta: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+}
+
+impl UndoBatch {
+    /// Serializes the undo batch, prefixed with the encoding version
+    pub fn serialize(&self) -> Result<Vec<u8>, Error> {
+        let serialized = SerializedUndoBatchV1 {
+            changed_elements: self.changed_elements.clone(),
+            data: self.records.data.clone(),
+            aux: self.records.aux.clone(),
+            roots: self.records.roots.clone(),
+            meta: self.records.meta.clone(),
+        };
+        let mut bytes = UNDO_BATCH_SERIALIZATION_VERSION.encode_var_vec();
+        bincode_options()
+            .serialize_into(&mut bytes, &serialized)
+            .map_err(|_| Error::CorruptedData(String::from("unable to serialize undo batch")))?;
+        Ok(bytes)
+    }
+
+    /// Deserializes an undo batch written with any supported encoding version
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
+        let (version, version_length) = u16::decode_var(bytes).ok_or_else(|| {
+            Error::CorruptedData(String::from("unable to read undo batch encoding version"))
+        })?;
+        match version {
+            1 => {
+                let serialized: SerializedUndoBatchV1 = bincode_options()
+                    .deserialize(&bytes[version_length..])
+                    .map_err(|_| {
+                        Error::CorruptedData(String::from("unable to deserialize undo batch"))
+                    })?;
+                Ok(UndoBatch {
+                    changed_elements: serialized.changed_elements,
+                    records: PreviousRecords {
+                        data: serialized.data,
+                        aux: serialized.aux,
+                        roots: serialized.roots,
+                        meta: serialized.meta,
+                    },
+                })
+            }
+            _ => Err(Error::CorruptedData(format!(
+                "unsupported undo batch encoding version {version}"
+            ))),
+        }
+    }
+}
+
+impl<S: CovariantStorage> GroveDb<S> {
+    /// Applies a batch and returns the batch undoing it. The undo batch holds
+    /// the previous values of every storage record the batch wrote, including
+    /// the contents of deleted or replaced subtrees, so applying it on the new
+    /// state restores the previous root hash.
+    pub fn apply_batch_with_undo(
+        &self,
+        ops: Vec<GroveDbOp>,
+        batch_apply_options: Option<BatchApplyOptions>,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<UndoBatch, Error> {
+        let mut cost = OperationCost::default();
+
+        if ops.is_empty() {
+            return Ok(UndoBatch::default()).wrap_with_cost(cost);
+        }
+
+        let ops = cost_return_on_error!(
+            &mut cost,
+            self.expand_batch(ops, batch_apply_options.as_ref(), transaction)
+        );
+        let changed_elements =
+            cost_return_on_error!(&mut cost, self.changed_elements(&ops, transaction));
+
+        let mut records = PreviousRecords::default();
+        cost_return_on_error!(
+            &mut cost,
+            self.apply_expanded_batch(
+                ops,
+                batch_apply_options,
+                |_cost, _old_flags, _new_flags| Ok(false),
+                |_flags, key_bytes_to_remove, value_bytes_to_remove| {
+                    Ok((
+                        BasicStorageRemoval(key_bytes_to_remove),
+                        BasicStorageRemoval(value_bytes_to_remove),
+                    ))
+                },
+                transaction,
+                None,
+                Some(&mut records),
+            )
+        );
+
+        Ok(UndoBatch {
+            changed_elements,
+            records,
+        })
+        .wrap_with_cost(cost)
+    }
+
+    /// Applies an undo batch, writing back the records its batch wrote. It
+    /// must be applied on the state its batch left, undo batches of successive
+    /// batches being applied in reverse order.
+    pub fn apply_undo_batch(
+        &self,
+        undo_batch: UndoBatch,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<(), Error> {
+        let UndoBatch {
+            changed_elements,
+            records,
+        } = undo_batch;
+
+        self.capture_changes(changed_elements, transaction, |transaction| {
+            let storage_batch = StorageBatch::new();
+            storage_batch.restore_previous_records(records);
+            self.db
+                .commit_multi_context_batch(
+                    storage_batch,
+                    transaction.map(|tx| self.db.narrow_transaction(tx)),
+                )
+                .map_err(|e| e.into())
+        })
+    }
+}
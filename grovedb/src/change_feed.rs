@@ -0,0 +1,746 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Change data capture feed of the writes committed to GroveDb
+
+use std::collections::VecDeque;
+
+use grovedb_costs::{
+    cost_return_on_error, cost_return_on_error_no_add, CostResult, CostsExt, OperationCost,
+};
+use grovedb_path::SubtreePath;
+use grovedb_storage::{CovariantStorage, StorageContext};
+use serde::{Deserialize, Serialize};
+
+use crate::{Element, Error, GroveDb, Hash, Transaction, TransactionArg};
+
+/// Paths and keys of the elements a write changes
+pub(crate) type ChangedElements = Vec<(Vec<Vec<u8>>, Vec<u8>)>;
+
+/// Meta key prefix of a change made in a transaction and not committed yet,
+/// followed by the encoded path and key of the changed element
+const PENDING_CHANGE_KEY_PREFIX: &[u8] = b"change_feed_pending_change";
+/// Meta key prefix of the pending change keys in the order they were first
+/// made, followed by their position
+const PENDING_ORDER_KEY_PREFIX: &[u8] = b"change_feed_pending_order";
+/// Meta key of the number of pending changes
+const PENDING_COUNT_KEY: &[u8] = b"change_feed_pending_count";
+/// Meta key of the sequence number of the last commit record
+const LAST_SEQUENCE_KEY: &[u8] = b"change_feed_sequence";
+/// Meta key prefix of commit records, followed by their sequence number
+const COMMIT_RECORD_KEY_PREFIX: &[u8] = b"change_feed_record";
+
+/// Element change made by a committed write
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeEvent {
+    /// Sequence number of the commit the change is part of
+    pub sequence: u64,
+    /// Path to the subtree of the changed element
+    pub path: Vec<Vec<u8>>,
+    /// Key of the changed element
+    pub key: Vec<u8>,
+    /// Element before the commit, `None` if it was inserted
+    pub old_element: Option<Element>,
+    /// Element after the commit, `None` if it was deleted
+    pub new_element: Option<Element>,
+    /// Root hash of GroveDb right after the commit
+    pub root_hash: Hash,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PendingChange {
+    path: Vec<Vec<u8>>,
+    key: Vec<u8>,
+    old_element: Option<Element>,
+    new_element: Option<Element>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CommitRecord {
+    root_hash: Hash,
+    changes: Vec<PendingChange>,
+}
+
+fn commit_record_key(sequence: u64) -> Vec<u8> {
+    let mut key = COMMIT_RECORD_KEY_PREFIX.to_vec();
+    key.extend_from_slice(&sequence.to_be_bytes());
+    key
+}
+
+fn pending_change_key(path: &[Vec<u8>], key: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut change_key = PENDING_CHANGE_KEY_PREFIX.to_vec();
+    change_key.extend(serialize(&(path, key))?);
+    Ok(change_key)
+}
+
+fn pending_order_key(position: u64) -> Vec<u8> {
+    let mut key = PENDING_ORDER_KEY_PREFIX.to_vec();
+    key.extend_from_slice(&position.to_be_bytes());
+    key
+}
+
+/// Reads a record of the changes pending in a transaction, which must exist
+fn get_pending_record<'db, C: StorageContext<'db>>(
+    storage: &C,
+    key: &[u8],
+) -> CostResult<Vec<u8>, Error> {
+    storage.get_meta(key).map_err(Into::into).map(|result| {
+        result.and_then(|value| {
+            value.ok_or_else(|| {
+                Error::CorruptedData("change feed pending change is missing".to_owned())
+            })
+        })
+    })
+}
+
+fn decode_counter(bytes: &[u8]) -> Result<u64, Error> {
+    bytes
+        .try_into()
+        .map(u64::from_be_bytes)
+        .map_err(|_| Error::CorruptedData("change feed counter must be 8 bytes".to_owned()))
+}
+
+fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+    bincode::serialize(value)
+        .map_err(|_| Error::CorruptedData("unable to serialize change feed record".to_owned()))
+}
+
+fn deserialize<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, Error> {
+    bincode::deserialize(bytes)
+        .map_err(|_| Error::CorruptedData("unable to deserialize change feed record".to_owned()))
+}
+
+impl<S: CovariantStorage> GroveDb<S> {
+    /// Starts recording the writes committed through this handle, so they can
+    /// be read back with `change_feed`. Recording stays off by default as it
+    /// reads every written element before and after the write. Transactions
+    /// have to be committed with `GroveDb::commit_transaction` for their
+    /// changes to be recorded. Deleting a non empty tree records the change
+    /// of the tree element only, not of the elements below it.
+    pub fn enable_change_feed(&mut self) {
+        self.change_feed = true;
+    }
+
+    /// Returns the feed of changes committed after the commit with
+    /// `from_sequence - 1` sequence number, optionally limited to elements
+    /// under `path_prefix`. The first commit has sequence number 1.
+    pub fn change_feed(
+        &self,
+        from_sequence: u64,
+        path_prefix: Option<Vec<Vec<u8>>>,
+    ) -> ChangeFeed<'_, S> {
+        ChangeFeed {
+            db: self,
+            next_sequence: from_sequence.max(1),
+            path_prefix,
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Returns the sequence number of the last recorded commit, 0 if there
+    /// is none
+    pub fn last_change_sequence(&self) -> Result<u64, Error> {
+        let storage = self
+            .db
+            .get_storage_context(SubtreePath::empty(), None)
+            .unwrap();
+        storage
+            .get_meta(LAST_SEQUENCE_KEY)
+            .unwrap()?
+            .map_or(Ok(0), |bytes| decode_counter(&bytes))
+    }
+
+    /// Runs `write` recording the changes of the given elements when the
+    /// change feed is enabled. Without a transaction the write runs in one of
+    /// its own, committed right after it.
+    pub(crate) fn capture_changes<T>(
+        &self,
+        changed_elements: ChangedElements,
+        transaction: TransactionArg<S>,
+        write: impl FnOnce(TransactionArg<S>) -> CostResult<T, Error>,
+    ) -> CostResult<T, Error> {
+        if !self.change_feed {
+            return write(transaction);
+        }
+        if let Some(tx) = transaction {
+            return self.capture_changes_on_transaction(changed_elements, tx, write);
+        }
+
+        let mut cost = OperationCost::default();
+        let tx = self.start_transaction();
+        let result = cost_return_on_error!(
+            &mut cost,
+            self.capture_changes_on_transaction(changed_elements, &tx, write)
+        );
+        cost_return_on_error!(&mut cost, self.commit_transaction(tx));
+        Ok(result).wrap_with_cost(cost)
+    }
+
+    fn capture_changes_on_transaction<T>(
+        &self,
+        changed_elements: ChangedElements,
+        transaction: &Transaction<S>,
+        write: impl FnOnce(TransactionArg<S>) -> CostResult<T, Error>,
+    ) -> CostResult<T, Error> {
+        let mut cost = OperationCost::default();
+
+        let mut changes = Vec::with_capacity(changed_elements.len());
+        for (path, key) in changed_elements {
+            let old_element = cost_return_on_error!(
+                &mut cost,
+                self.get_raw_optional(path.as_slice().into(), &key, Some(transaction))
+            );
+            changes.push(PendingChange {
+                path,
+                key,
+                old_element,
+                new_element: None,
+            });
+        }
+
+        let result = cost_return_on_error!(&mut cost, write(Some(transaction)));
+
+        for change in changes.iter_mut() {
+            change.new_element = cost_return_on_error!(
+                &mut cost,
+                self.get_raw_optional(
+                    change.path.as_slice().into(),
+                    &change.key,
+                    Some(transaction)
+                )
+            );
+        }
+        cost_return_on_error!(&mut cost, self.add_pending_changes(changes, transaction));
+
+        Ok(result).wrap_with_cost(cost)
+    }
+
+    /// Merges `changes` into the changes pending in the transaction, keeping
+    /// the first old element and the last new element of each changed
+    /// element. Every changed element has a pending record of its own, so
+    /// the cost of a write doesn't grow with the changes made before it.
+    fn add_pending_changes(
+        &self,
+        changes: Vec<PendingChange>,
+        transaction: &Transaction<S>,
+    ) -> CostResult<(), Error> {
+        let mut cost = OperationCost::default();
+
+        let storage = self
+            .db
+            .get_immediate_storage_context(
+                SubtreePath::empty(),
+                self.db.narrow_transaction(transaction),
+            )
+            .unwrap_add_cost(&mut cost);
+        let mut count = match cost_return_on_error!(
+            &mut cost,
+            storage.get_meta(PENDING_COUNT_KEY).map_err(Into::into)
+        ) {
+            Some(bytes) => cost_return_on_error_no_add!(&cost, decode_counter(&bytes)),
+            None => 0,
+        };
+        let initial_count = count;
+
+        for change in changes {
+            let change_key =
+                cost_return_on_error_no_add!(&cost, pending_change_key(&change.path, &change.key));
+            let pending = match cost_return_on_error!(
+                &mut cost,
+                storage.get_meta(&change_key).map_err(Into::into)
+            ) {
+                Some(bytes) => {
+                    let mut existing: PendingChange =
+                        cost_return_on_error_no_add!(&cost, deserialize(&bytes));
+                    existing.new_element = change.new_element;
+                    existing
+                }
+                None => {
+                    cost_return_on_error!(
+                        &mut cost,
+                        storage
+                            .put_meta(pending_order_key(count), &change_key, None)
+                            .map_err(Into::into)
+                    );
+                    count += 1;
+                    change
+                }
+            };
+            let bytes = cost_return_on_error_no_add!(&cost, serialize(&pending));
+            cost_return_on_error!(
+                &mut cost,
+                storage
+                    .put_meta(&change_key, &bytes, None)
+                    .map_err(Into::into)
+            );
+        }
+
+        if count != initial_count {
+            cost_return_on_error!(
+                &mut cost,
+                storage
+                    .put_meta(PENDING_COUNT_KEY, &count.to_be_bytes(), None)
+                    .map_err(Into::into)
+            );
+        }
+        Ok(()).wrap_with_cost(cost)
+    }
+
+    /// Turns the changes pending in the transaction into the next commit
+    /// record, called right before the transaction is committed. Changes
+    /// are recorded in the order they were first made, elements changed back
+    /// to what they were are left out.
+    pub(crate) fn record_pending_changes(
+        &self,
+        transaction: &Transaction<S>,
+    ) -> CostResult<(), Error> {
+        let mut cost = OperationCost::default();
+
+        let storage = self
+            .db
+            .get_immediate_storage_context(
+                SubtreePath::empty(),
+                self.db.narrow_transaction(transaction),
+            )
+            .unwrap_add_cost(&mut cost);
+        let Some(count) = cost_return_on_error!(
+            &mut cost,
+            storage.get_meta(PENDING_COUNT_KEY).map_err(Into::into)
+        ) else {
+            return Ok(()).wrap_with_cost(cost);
+        };
+        let count = cost_return_on_error_no_add!(&cost, decode_counter(&count));
+
+        let mut changes = Vec::new();
+        for position in 0..count {
+            let order_key = pending_order_key(position);
+            let change_key =
+                cost_return_on_error!(&mut cost, get_pending_record(&storage, &order_key));
+            let bytes = cost_return_on_error!(&mut cost, get_pending_record(&storage, &change_key));
+            let change: PendingChange = cost_return_on_error_no_add!(&cost, deserialize(&bytes));
+            if change.old_element != change.new_element {
+                changes.push(change);
+            }
+            cost_return_on_error!(
+                &mut cost,
+                storage.delete_meta(&order_key, None).map_err(Into::into)
+            );
+            cost_return_on_error!(
+                &mut cost,
+                storage.delete_meta(&change_key, None).map_err(Into::into)
+            );
+        }
+        cost_return_on_error!(
+            &mut cost,
+            storage
+                .delete_meta(PENDING_COUNT_KEY, None)
+                .map_err(Into::into)
+        );
+        if changes.is_empty() {
+            return Ok(()).wrap_with_cost(cost);
+        }
+
+        let root_hash = cost_return_on_error!(&mut cost, self.root_hash(Some(transaction)));
+        let last_sequence = match cost_return_on_error!(
+            &mut cost,
+            storage.get_meta(LAST_SEQUENCE_KEY).map_err(Into::into)
+        ) {
+            Some(bytes) => cost_return_on_error_no_add!(&cost, decode_counter(&bytes)),
+            None => 0,
+        };
+        let sequence = last_sequence + 1;
+
+        let record =
+            cost_return_on_error_no_add!(&cost, serialize(&CommitRecord { root_hash, changes }));
+        cost_return_on_error!(
+            &mut cost,
+            storage
+                .put_meta(commit_record_key(sequence), &record, None)
+                .map_err(Into::into)
+        );
+        storage
+            .put_meta(LAST_SEQUENCE_KEY, &sequence.to_be_bytes(), None)
+            .map_err(Into::into)
+            .add_cost(cost)
+    }
+}
+
+/// Iterator over committed changes in commit order, see
+/// `GroveDb::change_feed`. It ends once all recorded commits are read and can
+/// be polled again later to get the commits made meanwhile.
+pub struct ChangeFeed<'db, S: CovariantStorage> {
+    db: &'db GroveDb<S>,
+    next_sequence: u64,
+    path_prefix: Option<Vec<Vec<u8>>>,
+    events: VecDeque<ChangeEvent>,
+}
+
+impl<'db, S: CovariantStorage> ChangeFeed<'db, S> {
+    /// Returns the sequence number of the first commit not read yet, a feed
+    /// created with it resumes where this one stopped
+    pub fn next_sequence(&self) -> u64 {
+        self.events
+            .front()
+            .map_or(self.next_sequence, |event| event.sequence)
+    }
+
+    /// Reads the next commit record into `events`, returns false if there is
+    /// none
+    fn read_next_record(&mut self) -> Result<bool, Error> {
+        let storage = self
+            .db
+            .db
+            .get_storage_context(SubtreePath::empty(), None)
+            .unwrap();
+        let Some(bytes) = storage
+            .get_meta(commit_record_key(self.next_sequence))
+            .unwrap()?
+        else {
+            return Ok(false);
+        };
+        let record: CommitRecord = deserialize(&bytes)?;
+
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        let path_prefix = self.path_prefix.as_ref();
+        self.events.extend(
+            record
+                .changes
+                .into_iter()
+                .filter(|change| match path_prefix {
+                    Some(prefix) => change.path.starts_with(prefix),
+                    None => true,
+                })
+                .map(|change| ChangeEvent {
+                    sequence,
+                    path: change.path,
+                    key: change.key,
+                    old_element: change.old_element,
+                    new_element: change.new_element,
+                    root_hash: record.root_hash,
+                }),
+        );
+        Ok(true)
+    }
+}
+
+impl<'db, S: CovariantStorage> Iterator for ChangeFeed<'db, S> {
+    type Item = Result<ChangeEvent, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Some(Ok(event));
+            }
+            match self.read_next_record() {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use grovedb_storage::Storage;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        batch::GroveDbOp,
+        tests::{make_test_grovedb, ANOTHER_TEST_LEAF, TEST_LEAF},
+    };
+
+    fn read_feed<S: CovariantStorage>(feed: ChangeFeed<S>) -> Vec<ChangeEvent> {
+        feed.collect::<Result<_, _>>()
+            .expect("should read change feed")
+    }
+
+    #[test]
+    fn test_change_feed_records_committed_writes() {
+        let mut db = make_test_grovedb();
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            b"ignored",
+            Element::new_item(b"a".to_vec()),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("should insert item");
+        db.enable_change_feed();
+
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            b"key",
+            Element::new_item(b"a".to_vec()),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("should insert item");
+        let first_root_hash = db.root_hash(None).unwrap().unwrap();
+        db.apply_batch(
+            vec![
+                GroveDbOp::insert_op(
+                    vec![TEST_LEAF.to_vec()],
+                    b"key".to_vec(),
+                    Element::new_item(b"b".to_vec()),
+                ),
+                GroveDbOp::insert_op(
+                    vec![ANOTHER_TEST_LEAF.to_vec()],
+                    b"other".to_vec(),
+                    Element::new_item(b"c".to_vec()),
+                ),
+            ],
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("should apply batch");
+        let second_root_hash = db.root_hash(None).unwrap().unwrap();
+        db.delete([TEST_LEAF].as_ref(), b"key", None, None)
+            .unwrap()
+            .expect("should delete item");
+        let third_root_hash = db.root_hash(None).unwrap().unwrap();
+
+        assert_eq!(db.last_change_sequence().unwrap(), 3);
+        let events = read_feed(db.change_feed(0, None));
+        assert_eq!(
+            events,
+            vec![
+                ChangeEvent {
+                    sequence: 1,
+                    path: vec![TEST_LEAF.to_vec()],
+                    key: b"key".to_vec(),
+                    old_element: None,
+                    new_element: Some(Element::new_item(b"a".to_vec())),
+                    root_hash: first_root_hash,
+                },
+                ChangeEvent {
+                    sequence: 2,
+                    path: vec![TEST_LEAF.to_vec()],
+                    key: b"key".to_vec(),
+                    old_element: Some(Element::new_item(b"a".to_vec())),
+                    new_element: Some(Element::new_item(b"b".to_vec())),
+                    root_hash: second_root_hash,
+                },
+                ChangeEvent {
+                    sequence: 2,
+                    path: vec![ANOTHER_TEST_LEAF.to_vec()],
+                    key: b"other".to_vec(),
+                    old_element: None,
+                    new_element: Some(Element::new_item(b"c".to_vec())),
+                    root_hash: second_root_hash,
+                },
+                ChangeEvent {
+                    sequence: 3,
+                    path: vec![TEST_LEAF.to_vec()],
+                    key: b"key".to_vec(),
+                    old_element: Some(Element::new_item(b"b".to_vec())),
+                    new_element: None,
+                    root_hash: third_root_hash,
+                },
+            ]
+        );
+
+        let filtered = read_feed(db.change_feed(2, Some(vec![ANOTHER_TEST_LEAF.to_vec()])));
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].key, b"other".to_vec());
+    }
+
+    #[test]
+    fn test_change_feed_records_transactions_on_commit() {
+        let mut db = make_test_grovedb();
+        db.enable_change_feed();
+
+        let tx = db.start_transaction();
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            b"key",
+            Element::new_item(b"a".to_vec()),
+            None,
+            Some(&tx),
+        )
+        .unwrap()
+        .expect("should insert item");
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            b"key",
+            Element::new_item(b"b".to_vec()),
+            None,
+            Some(&tx),
+        )
+        .unwrap()
+        .expect("should insert item");
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            b"temp",
+            Element::new_item(b"c".to_vec()),
+            None,
+            Some(&tx),
+        )
+        .unwrap()
+        .expect("should insert item");
+        db.delete([TEST_LEAF].as_ref(), b"temp", None, Some(&tx))
+            .unwrap()
+            .expect("should delete item");
+
+        let mut feed = db.change_feed(1, None);
+        assert!(feed.next().is_none());
+
+        db.commit_transaction(tx)
+            .unwrap()
+            .expect("should commit transaction");
+        let events = read_feed(feed);
+        assert_eq!(
+            events,
+            vec![ChangeEvent {
+                sequence: 1,
+                path: vec![TEST_LEAF.to_vec()],
+                key: b"key".to_vec(),
+                old_element: None,
+                new_element: Some(Element::new_item(b"b".to_vec())),
+                root_hash: db.root_hash(None).unwrap().unwrap(),
+            }]
+        );
+
+        let tx = db.start_transaction();
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            b"key",
+            Element::new_item(b"c".to_vec()),
+            None,
+            Some(&tx),
+        )
+        .unwrap()
+        .expect("should insert item");
+        db.rollback_transaction(&tx)
+            .expect("should rollback transaction");
+        db.commit_transaction(tx)
+            .unwrap()
+            .expect("should commit transaction");
+        assert_eq!(db.last_change_sequence().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_change_feed_merges_pending_changes_in_first_write_order() {
+        let mut db = make_test_grovedb();
+        db.enable_change_feed();
+
+        let tx = db.start_transaction();
+        for (key, value) in [(b"b", b"1"), (b"a", b"2"), (b"b", b"3")] {
+            db.insert(
+                [TEST_LEAF].as_ref(),
+                key,
+                Element::new_item(value.to_vec()),
+                None,
+                Some(&tx),
+            )
+            .unwrap()
+            .expect("should insert item");
+        }
+        db.commit_transaction(tx)
+            .unwrap()
+            .expect("should commit transaction");
+
+        let events = read_feed(db.change_feed(1, None));
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| (event.key.clone(), event.new_element.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (b"b".to_vec(), Some(Element::new_item(b"3".to_vec()))),
+                (b"a".to_vec(), Some(Element::new_item(b"2".to_vec()))),
+            ]
+        );
+
+        // Pending records are removed on commit
+        let storage = db
+            .db
+            .get_storage_context(SubtreePath::empty(), None)
+            .unwrap();
+        assert_eq!(storage.get_meta(PENDING_COUNT_KEY).unwrap().unwrap(), None);
+        assert_eq!(
+            storage
+                .get_meta(pending_change_key(&[TEST_LEAF.to_vec()], b"b").unwrap())
+                .unwrap()
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_change_feed_sequence_survives_reopening() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut feed_position = 1;
+        for (key, sequence) in [(b"a", 1), (b"b", 2)] {
+            let mut db = GroveDb::open(tmp_dir.path()).unwrap();
+            db.enable_change_feed();
+            db.insert(
+                SubtreePath::empty(),
+                key,
+                Element::new_item(key.to_vec()),
+                None,
+                None,
+            )
+            .unwrap()
+            .expect("should insert item");
+
+            let mut feed = db.change_feed(feed_position, None);
+            let event = feed.next().unwrap().expect("should read change feed");
+            assert_eq!(event.sequence, sequence);
+            assert_eq!(event.key, key.to_vec());
+            assert!(feed.next().is_none());
+            feed_position = feed.next_sequence();
+        }
+        assert_eq!(feed_position, 3);
+    }
+
+    #[test]
+    fn test_change_feed_rejects_partial_batches() {
+        let mut db = make_test_grovedb();
+        db.enable_change_feed();
+
+        let result = db
+            .apply_partial_batch(
+                vec![GroveDbOp::insert_op(
+                    vec![TEST_LEAF.to_vec()],
+                    b"key".to_vec(),
+                    Element::new_item(b"a".to_vec()),
+                )],
+                None,
+                |_, _| Ok(vec![]),
+                None,
+            )
+            .unwrap();
+        assert!(matches!(result, Err(Error::NotSupported(_))));
+    }
+}
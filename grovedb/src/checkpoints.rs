@@ -0,0 +1,339 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Checkpoints of GroveDb tagged with a version and root hash, kept under a
+//! retention policy and opened read only for queries and proofs.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use grovedb_storage::rocksdb_storage::ReadOnlyRocksDbStorage;
+
+use crate::{Error, GroveDb, Hash};
+
+/// Directory holding RocksDB checkpoint files within a checkpoint directory
+const DB_DIR_NAME: &str = "db";
+/// File with checkpoint info within a checkpoint directory
+const INFO_FILE_NAME: &str = "info";
+/// Encoded info length: version, root hash and creation time
+const INFO_LEN: usize = 8 + 32 + 8;
+
+/// Checkpoint of GroveDb managed by `CheckpointManager`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointInfo {
+    /// Version the checkpoint was tagged with, like a block height
+    pub version: u64,
+    /// Root hash of GroveDb at the checkpoint
+    pub root_hash: Hash,
+    /// Creation time in seconds since the Unix epoch
+    pub created_at: u64,
+}
+
+impl CheckpointInfo {
+    fn encode(&self) -> [u8; INFO_LEN] {
+        let mut bytes = [0; INFO_LEN];
+        bytes[..8].copy_from_slice(&self.version.to_be_bytes());
+        bytes[8..40].copy_from_slice(&self.root_hash);
+        bytes[40..].copy_from_slice(&self.created_at.to_be_bytes());
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() != INFO_LEN {
+            return Err(Error::CorruptedData(
+                "checkpoint info has wrong length".to_owned(),
+            ));
+        }
+        Ok(CheckpointInfo {
+            version: u64::from_be_bytes(bytes[..8].try_into().expect("length checked above")),
+            root_hash: bytes[8..40].try_into().expect("length checked above"),
+            created_at: u64::from_be_bytes(bytes[40..].try_into().expect("length checked above")),
+        })
+    }
+}
+
+/// Which checkpoints to keep, checkpoints matching any limit are deleted
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicy {
+    /// Keep only this many checkpoints with the highest versions
+    pub keep_last: Option<usize>,
+    /// Keep only checkpoints younger than this
+    pub max_age: Option<Duration>,
+}
+
+/// Manages checkpoints of GroveDb in a directory, one subdirectory per
+/// version
+pub struct CheckpointManager {
+    dir: PathBuf,
+}
+
+impl CheckpointManager {
+    /// Manages checkpoints in `dir`, creating it if missing
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<Self, Error> {
+        fs::create_dir_all(&dir).map_err(checkpoint_error)?;
+        Ok(CheckpointManager {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    fn checkpoint_dir(&self, version: u64) -> PathBuf {
+        // Zero padded so directories sort by version
+        self.dir.join(format!("{version:020}"))
+    }
+
+    /// Creates a checkpoint of `db` tagged with `version`. The root hash is
+    /// read from the checkpoint itself, so it matches the checkpointed data
+    /// even if `db` is written meanwhile. An incomplete checkpoint left for
+    /// `version` is replaced.
+    pub fn create(&self, db: &GroveDb, version: u64) -> Result<CheckpointInfo, Error> {
+        let checkpoint_dir = self.checkpoint_dir(version);
+        if checkpoint_dir.exists() {
+            if checkpoint_dir.join(INFO_FILE_NAME).exists() {
+                return Err(Error::CheckpointError(format!(
+                    "checkpoint for version {version} already exists"
+                )));
+            }
+            fs::remove_dir_all(&checkpoint_dir).map_err(checkpoint_error)?;
+        }
+        fs::create_dir(&checkpoint_dir).map_err(checkpoint_error)?;
+
+        let result = Self::write_checkpoint(db, version, &checkpoint_dir);
+        if result.is_err() {
+            // Don't leave an incomplete checkpoint behind, the original error
+            // matters more than a failure to clean up
+            let _ = fs::remove_dir_all(&checkpoint_dir);
+        }
+        result
+    }
+
+    fn write_checkpoint(
+        db: &GroveDb,
+        version: u64,
+        checkpoint_dir: &Path,
+    ) -> Result<CheckpointInfo, Error> {
+        db.create_checkpoint(checkpoint_dir.join(DB_DIR_NAME))?;
+
+        let root_hash = GroveDb::open_read_only(checkpoint_dir.join(DB_DIR_NAME))?
+            .root_hash(None)
+            .unwrap()?;
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+        let info = CheckpointInfo {
+            version,
+            root_hash,
+            created_at,
+        };
+
+        // The info file is written last and atomically, checkpoints without it
+        // are incomplete and not listed
+        let tmp_info_path = checkpoint_dir.join(format!("{INFO_FILE_NAME}.tmp"));
+        fs::write(&tmp_info_path, info.encode()).map_err(checkpoint_error)?;
+        fs::rename(&tmp_info_path, checkpoint_dir.join(INFO_FILE_NAME))
+            .map_err(checkpoint_error)?;
+        Ok(info)
+    }
+
+    /// Lists complete checkpoints ordered by version
+    pub fn list(&self) -> Result<Vec<CheckpointInfo>, Error> {
+        let mut checkpoints = Vec::new();
+        for entry in fs::read_dir(&self.dir).map_err(checkpoint_error)? {
+            let info_path = entry.map_err(checkpoint_error)?.path().join(INFO_FILE_NAME);
+            if info_path.exists() {
+                let bytes = fs::read(info_path).map_err(checkpoint_error)?;
+                checkpoints.push(CheckpointInfo::decode(&bytes)?);
+            }
+        }
+        checkpoints.sort_by_key(|info| info.version);
+        Ok(checkpoints)
+    }
+
+    /// Returns the checkpoint tagged with `version`
+    pub fn get(&self, version: u64) -> Result<Option<CheckpointInfo>, Error> {
+        let info_path = self.checkpoint_dir(version).join(INFO_FILE_NAME);
+        if !info_path.exists() {
+            return Ok(None);
+        }
+        let bytes = fs::read(info_path).map_err(checkpoint_error)?;
+        CheckpointInfo::decode(&bytes).map(Some)
+    }
+
+    /// Opens the checkpoint tagged with `version` as read only GroveDb
+    pub fn open(&self, version: u64) -> Result<GroveDb<ReadOnlyRocksDbStorage>, Error> {
+        if self.get(version)?.is_none() {
+            return Err(Error::CheckpointError(format!(
+                "no checkpoint for version {version}"
+            )));
+        }
+        GroveDb::open_read_only(self.checkpoint_dir(version).join(DB_DIR_NAME))
+    }
+
+    /// Deletes the checkpoint tagged with `version`, including an incomplete
+    /// one. Returns whether there was one.
+    pub fn delete(&self, version: u64) -> Result<bool, Error> {
+        let checkpoint_dir = self.checkpoint_dir(version);
+        if !checkpoint_dir.exists() {
+            return Ok(false);
+        }
+        // Remove the info first so a partially deleted checkpoint isn't listed
+        let info_path = checkpoint_dir.join(INFO_FILE_NAME);
+        if info_path.exists() {
+            fs::remove_file(info_path).map_err(checkpoint_error)?;
+        }
+        fs::remove_dir_all(checkpoint_dir).map_err(checkpoint_error)?;
+        Ok(true)
+    }
+
+    /// Deletes checkpoints not kept by `policy`, returning the deleted ones
+    pub fn apply_retention(&self, policy: &RetentionPolicy) -> Result<Vec<CheckpointInfo>, Error> {
+        let checkpoints = self.list()?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+        let first_kept = policy
+            .keep_last
+            .map(|keep_last| checkpoints.len().saturating_sub(keep_last))
+            .unwrap_or_default();
+
+        let mut deleted = Vec::new();
+        for (i, info) in checkpoints.into_iter().enumerate() {
+            let expired = policy
+                .max_age
+                .map(|max_age| now.saturating_sub(info.created_at) > max_age.as_secs())
+                .unwrap_or_default();
+            if i < first_kept || expired {
+                self.delete(info.version)?;
+                deleted.push(info);
+            }
+        }
+        Ok(deleted)
+    }
+}
+
+fn checkpoint_error(error: std::io::Error) -> Error {
+    Error::CheckpointError(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        tests::{make_test_grovedb, TEST_LEAF},
+        Element, PathQuery, Query,
+    };
+
+    #[test]
+    fn test_checkpoint_lifecycle() {
+        let db = make_test_grovedb();
+        let checkpoints_dir = TempDir::new().unwrap();
+        let manager = CheckpointManager::new(checkpoints_dir.path()).unwrap();
+
+        let mut hashes = Vec::new();
+        for version in 1..=3u64 {
+            db.insert(
+                [TEST_LEAF].as_ref(),
+                &version.to_be_bytes(),
+                Element::new_item(b"value".to_vec()),
+                None,
+                None,
+            )
+            .unwrap()
+            .expect("cannot insert item");
+            let info = manager.create(&db, version).unwrap();
+            assert_eq!(info.version, version);
+            assert_eq!(info.root_hash, db.root_hash(None).unwrap().unwrap());
+            hashes.push(info.root_hash);
+        }
+        assert!(matches!(
+            manager.create(&db, 3),
+            Err(Error::CheckpointError(_))
+        ));
+        assert_eq!(
+            manager
+                .list()
+                .unwrap()
+                .iter()
+                .map(|info| info.version)
+                .collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+
+        // Checkpoint serves queries and proofs against its own root
+        let checkpoint_db = manager.open(1).unwrap();
+        let mut query = Query::new();
+        query.insert_all();
+        let path_query = PathQuery::new_unsized(vec![TEST_LEAF.to_vec()], query);
+        let proof = checkpoint_db.prove_query(&path_query).unwrap().unwrap();
+        let (root_hash, result_set) = GroveDb::verify_query(&proof, &path_query).unwrap();
+        assert_eq!(root_hash, hashes[0]);
+        assert_eq!(result_set.len(), 1);
+        assert_ne!(root_hash, db.root_hash(None).unwrap().unwrap());
+        drop(checkpoint_db);
+
+        let deleted = manager
+            .apply_retention(&RetentionPolicy {
+                keep_last: Some(2),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(deleted[0].version, 1);
+        assert!(manager.get(1).unwrap().is_none());
+        assert!(manager.open(1).is_err());
+        assert_eq!(manager.get(2).unwrap().unwrap().root_hash, hashes[1]);
+
+        assert!(manager.delete(3).unwrap());
+        assert!(!manager.delete(3).unwrap());
+        assert_eq!(manager.list().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_create_replaces_incomplete_checkpoint() {
+        let db = make_test_grovedb();
+        let checkpoints_dir = TempDir::new().unwrap();
+        let manager = CheckpointManager::new(checkpoints_dir.path()).unwrap();
+
+        // Leftover of a creation that failed before the info was written
+        let incomplete_dir = manager.checkpoint_dir(1);
+        fs::create_dir_all(incomplete_dir.join(DB_DIR_NAME)).unwrap();
+        fs::write(incomplete_dir.join(DB_DIR_NAME).join("CURRENT"), b"junk").unwrap();
+        assert!(manager.get(1).unwrap().is_none());
+
+        let info = manager.create(&db, 1).unwrap();
+        assert_eq!(info.root_hash, db.root_hash(None).unwrap().unwrap());
+        assert_eq!(manager.get(1).unwrap(), Some(info));
+        assert!(manager.open(1).is_ok());
+    }
+}
@@ -48,6 +48,9 @@ pub enum Error {
     #[error("invalid proof: {0}")]
     /// Invalid proof
     InvalidProof(&'static str),
+    #[error("invalid proof at index {0} of the batch: {1}")]
+    /// A proof of a batch is invalid
+    InvalidBatchProof(usize, Box<Error>),
     #[error("invalid input: {0}")]
     /// Invalid input
     InvalidInput(&'static str),
@@ -107,12 +110,16 @@ pub enum Error {
     // Irrecoverable errors
     #[error("storage_cost error: {0}")]
     /// Storage error
-    StorageError(#[from] grovedb_storage::error::Error),
+    StorageError(grovedb_storage::error::Error),
 
     #[error("data corruption error: {0}")]
     /// Corrupted data
     CorruptedData(String),
 
+    #[error("checkpoint error: {0}")]
+    /// Checkpoint management error
+    CheckpointError(String),
+
     #[error("invalid code execution error: {0}")]
     /// Invalid code execution
     InvalidCodeExecution(&'static str),
@@ -124,6 +131,10 @@ pub enum Error {
     /// Invalid batch operation
     InvalidBatchOperation(&'static str),
 
+    #[error("precondition of batch operation {0} failed: {1}")]
+    /// Precondition of a batch operation failed
+    BatchPreconditionFailed(usize, &'static str),
+
     #[error("delete up tree stop height more than initial path size error: {0}")]
     /// Delete up tree stop height more than initial path size
     DeleteUpTreeStopHeightMoreThanInitialPathSize(String),
@@ -158,8 +169,25 @@ pub enum Error {
     /// Not supported
     NotSupported(&'static str),
 
+    // Transaction errors
+    #[error("transaction conflict")]
+    /// Transaction conflicts with writes committed since it made its own, it
+    /// can be retried
+    TransactionConflict,
+
     // Merk errors
     #[error("merk error: {0}")]
     /// Merk error
     MerkError(grovedb_merk::error::Error),
 }
+
+#[cfg(feature = "full")]
+impl From<grovedb_storage::error::Error> for Error {
+    fn from(value: grovedb_storage::error::Error) -> Self {
+        match value {
+            grovedb_storage::error::Error::NotSupported(reason) => Error::NotSupported(reason),
+            grovedb_storage::error::Error::TransactionConflict => Error::TransactionConflict,
+            e => Error::StorageError(e),
+        }
+    }
+}
@@ -220,6 +220,37 @@ impl GroveDb {
         .wrap_with_cost(cost)
     }
 
+    /// Add average case for insertion into merk of an element of the size the
+    /// layer estimates, as moves insert at their destination
+    pub fn average_case_merk_insert_estimated_element(
+        key: &KeyInfo,
+        estimated_layer_information: &EstimatedLayerInformation,
+        propagate: bool,
+    ) -> CostResult<(), Error> {
+        let mut cost = OperationCost::default();
+        let key_len = key.max_length() as u32;
+        let value_size = cost_return_on_error_no_add!(
+            &cost,
+            estimated_layer_information
+                .estimated_layer_sizes
+                .value_with_feature_and_flags_size()
+                .map_err(Error::MerkError)
+        );
+        add_cost_case_merk_insert(
+            &mut cost,
+            key_len,
+            value_size,
+            estimated_layer_information.is_sum_tree,
+        );
+        if propagate {
+            add_average_case_merk_propagate(&mut cost, estimated_layer_information)
+                .map_err(Error::MerkError)
+        } else {
+            Ok(())
+        }
+        .wrap_with_cost(cost)
+    }
+
     /// Add average case for replacement into merk
     /// This only propagates on 1 level
     /// As higher level propagation is done in batching
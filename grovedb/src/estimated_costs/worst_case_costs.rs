@@ -207,6 +207,31 @@ impl GroveDb {
         .wrap_with_cost(cost)
     }
 
+    /// Add worst case for insertion into merk of an element of the biggest
+    /// size, as moves insert at their destination
+    pub fn worst_case_merk_insert_estimated_element(
+        key: &KeyInfo,
+        in_parent_tree_using_sums: bool,
+        worst_case_layer_information: &WorstCaseLayerInformation,
+        propagate: bool,
+    ) -> CostResult<(), Error> {
+        let mut cost = OperationCost::default();
+        let key_len = key.max_length() as u32;
+        add_cost_case_merk_insert(
+            &mut cost,
+            key_len,
+            MERK_BIGGEST_VALUE_SIZE,
+            in_parent_tree_using_sums,
+        );
+        if propagate {
+            add_worst_case_merk_propagate(&mut cost, worst_case_layer_information)
+                .map_err(Error::MerkError)
+        } else {
+            Ok(())
+        }
+        .wrap_with_cost(cost)
+    }
+
     /// Add worst case for replacement in merk
     /// This only propagates on 1 level
     /// As higher level propagation is done in batching
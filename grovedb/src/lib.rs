@@ -145,6 +145,10 @@ extern crate core;
 
 #[cfg(feature = "full")]
 pub mod batch;
+#[cfg(feature = "full")]
+mod change_feed;
+#[cfg(feature = "full")]
+mod checkpoints;
 #[cfg(any(feature = "full", feature = "verify"))]
 pub mod element;
 #[cfg(any(feature = "full", feature = "verify"))]
@@ -161,6 +165,12 @@ pub mod query_result_type;
 pub mod reference_path;
 #[cfg(feature = "full")]
 mod replication;
+#[cfg(feature = "full")]
+mod retry;
+#[cfg(feature = "full")]
+mod snapshot;
+#[cfg(feature = "full")]
+mod sub_transaction;
 #[cfg(all(test, feature = "full"))]
 mod tests;
 #[cfg(feature = "full")]
@@ -172,6 +182,10 @@ mod visualize;
 #[cfg(feature = "full")]
 use std::{collections::HashMap, option::Option::None, path::Path};
 
+#[cfg(feature = "full")]
+pub use change_feed::{ChangeEvent, ChangeFeed};
+#[cfg(feature = "full")]
+pub use checkpoints::{CheckpointInfo, CheckpointManager, RetentionPolicy};
 #[cfg(any(feature = "full", feature = "verify"))]
 use element::helpers;
 #[cfg(any(feature = "full", feature = "verify"))]
@@ -201,23 +215,30 @@ use grovedb_merk::{
 };
 use grovedb_path::SubtreePath;
 #[cfg(feature = "full")]
-use grovedb_storage::rocksdb_storage::PrefixedRocksDbImmediateStorageContext;
+pub use grovedb_storage::rocksdb_storage::{
+    ColumnFamilyConfig, DBCompressionType, RocksDbConfig as GroveDbConfig,
+};
 #[cfg(feature = "full")]
-use grovedb_storage::rocksdb_storage::RocksDbStorage;
+use grovedb_storage::rocksdb_storage::{ReadOnlyRocksDbStorage, RocksDbStorage};
 #[cfg(feature = "full")]
-use grovedb_storage::{
-    rocksdb_storage::{PrefixedRocksDbStorageContext, PrefixedRocksDbTransactionContext},
-    StorageBatch,
-};
+use grovedb_storage::StorageBatch;
 #[cfg(feature = "full")]
-use grovedb_storage::{Storage, StorageContext};
+use grovedb_storage::{CovariantStorage, Storage, StorageContext};
 #[cfg(feature = "full")]
 use grovedb_visualize::DebugByteVectors;
 #[cfg(any(feature = "full", feature = "verify"))]
 pub use query::{PathQuery, SizedQuery};
 #[cfg(feature = "full")]
 pub use replication::{BufferedRestorer, Restorer, SiblingsChunkProducer, SubtreeChunkProducer};
+#[cfg(feature = "full")]
+pub use retry::RetryPolicy;
+#[cfg(feature = "full")]
+pub use snapshot::Snapshot;
+#[cfg(feature = "full")]
+pub use sub_transaction::SubTransaction;
 
+#[cfg(feature = "full")]
+use crate::batch::BatchThreadPool;
 #[cfg(any(feature = "full", feature = "verify"))]
 pub use crate::error::Error;
 #[cfg(feature = "full")]
@@ -228,38 +249,116 @@ use crate::util::{root_merk_optional_tx, storage_context_optional_tx};
 #[cfg(feature = "full")]
 type Hash = [u8; 32];
 
-/// GroveDb
-pub struct GroveDb {
-    #[cfg(feature = "full")]
-    db: RocksDbStorage,
+/// GroveDb, generic over the storage backend it runs on (RocksDB by default)
+#[cfg(feature = "full")]
+pub struct GroveDb<S = RocksDbStorage> {
+    db: S,
+    /// Whether committed writes are recorded for the change feed
+    change_feed: bool,
+    /// Threads applying the subtrees of batches
+    batch_thread_pool: BatchThreadPool,
 }
 
+/// GroveDb
+#[cfg(not(feature = "full"))]
+pub struct GroveDb;
+
 /// Transaction
 #[cfg(feature = "full")]
-pub type Transaction<'db> = <RocksDbStorage as Storage<'db>>::Transaction;
+pub type Transaction<'db, S = RocksDbStorage> = <S as Storage<'db>>::Transaction;
 /// TransactionArg
 #[cfg(feature = "full")]
-pub type TransactionArg<'db, 'a> = Option<&'a Transaction<'db>>;
+pub type TransactionArg<'db, 'a, S = RocksDbStorage> = Option<&'a Transaction<'db, S>>;
 
 #[cfg(feature = "full")]
 impl GroveDb {
     /// Opens a given path
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
         let db = RocksDbStorage::default_rocksdb_with_path(path)?;
-        Ok(GroveDb { db })
+        Ok(GroveDb {
+            db,
+            change_feed: false,
+            batch_thread_pool: Default::default(),
+        })
+    }
+
+    /// Opens a given path with RocksDB tuned by `config`
+    pub fn open_with_options<P: AsRef<Path>>(
+        path: P,
+        config: &GroveDbConfig,
+    ) -> Result<Self, Error> {
+        let db = RocksDbStorage::rocksdb_with_config(path, config)?;
+        Ok(GroveDb {
+            db,
+            change_feed: false,
+            batch_thread_pool: Default::default(),
+        })
+    }
+
+    /// Opens a given path in read only mode. Write operations fail with
+    /// `Error::NotSupported`.
+    pub fn open_read_only<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<GroveDb<ReadOnlyRocksDbStorage>, Error> {
+        let db = ReadOnlyRocksDbStorage::read_only_with_path(path, &GroveDbConfig::default())?;
+        Ok(GroveDb {
+            db,
+            change_feed: false,
+            batch_thread_pool: Default::default(),
+        })
+    }
+
+    /// Opens GroveDb at `primary_path` as a secondary instance next to a
+    /// writing process, keeping the secondary instance logs in
+    /// `secondary_path`. Write operations fail with `Error::NotSupported`.
+    pub fn open_as_secondary<P: AsRef<Path>, Q: AsRef<Path>>(
+        primary_path: P,
+        secondary_path: Q,
+    ) -> Result<GroveDb<ReadOnlyRocksDbStorage>, Error> {
+        let db = ReadOnlyRocksDbStorage::secondary_with_path(
+            primary_path,
+            secondary_path,
+            &GroveDbConfig::default(),
+        )?;
+        Ok(GroveDb {
+            db,
+            change_feed: false,
+            batch_thread_pool: Default::default(),
+        })
+    }
+}
+
+#[cfg(feature = "full")]
+impl GroveDb<ReadOnlyRocksDbStorage> {
+    /// Makes writes of the primary instance visible to the secondary one
+    pub fn try_catch_up_with_primary(&self) -> Result<(), Error> {
+        self.db.try_catch_up_with_primary().map_err(Into::into)
+    }
+}
+
+#[cfg(feature = "full")]
+impl<S: CovariantStorage> GroveDb<S> {
+    /// Creates GroveDb on top of an already opened storage
+    pub fn with_storage(db: S) -> Self {
+        GroveDb {
+            db,
+            change_feed: false,
+            batch_thread_pool: Default::default(),
+        }
     }
 
     /// Opens the transactional Merk at the given path. Returns CostResult.
     fn open_transactional_merk_at_path<'db, 'b, B>(
         &'db self,
         path: SubtreePath<'b, B>,
-        tx: &'db Transaction,
+        tx: &'db Transaction<S>,
         batch: Option<&'db StorageBatch>,
-    ) -> CostResult<Merk<PrefixedRocksDbTransactionContext<'db>>, Error>
+    ) -> CostResult<Merk<<S as Storage<'db>>::BatchTransactionalStorageContext>, Error>
     where
         B: AsRef<[u8]> + 'b,
     {
         let mut cost = OperationCost::default();
+        let tx = self.db.narrow_transaction(tx);
 
         let storage = self
             .db
@@ -306,12 +405,13 @@ impl GroveDb {
     fn open_merk_for_replication<'db, 'b, B>(
         &'db self,
         path: SubtreePath<'b, B>,
-        tx: &'db Transaction,
-    ) -> Result<Merk<PrefixedRocksDbImmediateStorageContext<'db>>, Error>
+        tx: &'db Transaction<S>,
+    ) -> Result<Merk<<S as Storage<'db>>::ImmediateStorageContext>, Error>
     where
         B: AsRef<[u8]> + 'b,
     {
         let mut cost = OperationCost::default();
+        let tx = self.db.narrow_transaction(tx);
 
         let storage = self
             .db
@@ -356,7 +456,7 @@ impl GroveDb {
         &'db self,
         path: SubtreePath<'b, B>,
         batch: Option<&'db StorageBatch>,
-    ) -> CostResult<Merk<PrefixedRocksDbStorageContext>, Error>
+    ) -> CostResult<Merk<<S as Storage<'db>>::BatchStorageContext>, Error>
     where
         B: AsRef<[u8]> + 'b,
     {
@@ -410,7 +510,7 @@ impl GroveDb {
 
     /// Returns root key of GroveDb.
     /// Will be `None` if GroveDb is empty.
-    pub fn root_key(&self, transaction: TransactionArg) -> CostResult<Vec<u8>, Error> {
+    pub fn root_key(&self, transaction: TransactionArg<S>) -> CostResult<Vec<u8>, Error> {
         let mut cost = OperationCost {
             ..Default::default()
         };
@@ -423,7 +523,7 @@ impl GroveDb {
 
     /// Returns root hash of GroveDb.
     /// Will be `None` if GroveDb is empty.
-    pub fn root_hash(&self, transaction: TransactionArg) -> CostResult<Hash, Error> {
+    pub fn root_hash(&self, transaction: TransactionArg<S>) -> CostResult<Hash, Error> {
         let mut cost = OperationCost {
             ..Default::default()
         };
@@ -436,12 +536,15 @@ impl GroveDb {
 
     /// Method to propagate updated subtree key changes one level up inside a
     /// transaction
-    fn propagate_changes_with_batch_transaction<'b, B: AsRef<[u8]>>(
-        &self,
-        storage_batch: &StorageBatch,
-        mut merk_cache: HashMap<SubtreePath<'b, B>, Merk<PrefixedRocksDbTransactionContext>>,
+    fn propagate_changes_with_batch_transaction<'db, 'b, B: AsRef<[u8]>>(
+        &'db self,
+        storage_batch: &'db StorageBatch,
+        mut merk_cache: HashMap<
+            SubtreePath<'b, B>,
+            Merk<<S as Storage<'db>>::BatchTransactionalStorageContext>,
+        >,
         path: &SubtreePath<'b, B>,
-        transaction: &Transaction,
+        transaction: &'db Transaction<S>,
     ) -> CostResult<(), Error> {
         let mut cost = OperationCost::default();
 
@@ -470,7 +573,7 @@ impl GroveDb {
             );
             cost_return_on_error!(
                 &mut cost,
-                Self::update_tree_item_preserve_flag(
+                GroveDb::update_tree_item_preserve_flag(
                     &mut parent_tree,
                     parent_key,
                     root_key,
@@ -486,12 +589,15 @@ impl GroveDb {
 
     /// Method to propagate updated subtree key changes one level up inside a
     /// transaction
-    fn propagate_changes_with_transaction<'b, B: AsRef<[u8]>>(
-        &self,
-        mut merk_cache: HashMap<SubtreePath<'b, B>, Merk<PrefixedRocksDbTransactionContext>>,
+    fn propagate_changes_with_transaction<'db, 'b, B: AsRef<[u8]>>(
+        &'db self,
+        mut merk_cache: HashMap<
+            SubtreePath<'b, B>,
+            Merk<<S as Storage<'db>>::BatchTransactionalStorageContext>,
+        >,
         path: SubtreePath<'b, B>,
-        transaction: &Transaction,
-        batch: &StorageBatch,
+        transaction: &'db Transaction<S>,
+        batch: &'db StorageBatch,
     ) -> CostResult<(), Error> {
         let mut cost = OperationCost::default();
 
@@ -507,7 +613,7 @@ impl GroveDb {
         let mut current_path = path.clone();
 
         while let Some((parent_path, parent_key)) = current_path.derive_parent() {
-            let mut parent_tree: Merk<PrefixedRocksDbTransactionContext> = cost_return_on_error!(
+            let mut parent_tree = cost_return_on_error!(
                 &mut cost,
                 self.open_transactional_merk_at_path(parent_path.clone(), transaction, Some(batch))
             );
@@ -517,7 +623,7 @@ impl GroveDb {
             );
             cost_return_on_error!(
                 &mut cost,
-                Self::update_tree_item_preserve_flag(
+                GroveDb::update_tree_item_preserve_flag(
                     &mut parent_tree,
                     parent_key,
                     root_key,
@@ -532,11 +638,11 @@ impl GroveDb {
     }
 
     /// Method to propagate updated subtree key changes one level up
-    fn propagate_changes_without_transaction<'b, B: AsRef<[u8]>>(
-        &self,
-        mut merk_cache: HashMap<SubtreePath<'b, B>, Merk<PrefixedRocksDbStorageContext>>,
+    fn propagate_changes_without_transaction<'db, 'b, B: AsRef<[u8]>>(
+        &'db self,
+        mut merk_cache: HashMap<SubtreePath<'b, B>, Merk<<S as Storage<'db>>::BatchStorageContext>>,
         path: SubtreePath<'b, B>,
-        batch: &StorageBatch,
+        batch: &'db StorageBatch,
     ) -> CostResult<(), Error> {
         let mut cost = OperationCost::default();
 
@@ -552,7 +658,7 @@ impl GroveDb {
         let mut current_path: SubtreePath<B> = path;
 
         while let Some((parent_path, parent_key)) = current_path.derive_parent() {
-            let mut parent_tree: Merk<PrefixedRocksDbStorageContext> = cost_return_on_error!(
+            let mut parent_tree = cost_return_on_error!(
                 &mut cost,
                 self.open_non_transactional_merk_at_path(parent_path.clone(), Some(batch))
             );
@@ -562,7 +668,7 @@ impl GroveDb {
             );
             cost_return_on_error!(
                 &mut cost,
-                Self::update_tree_item_preserve_flag(
+                GroveDb::update_tree_item_preserve_flag(
                     &mut parent_tree,
                     parent_key,
                     root_key,
@@ -576,129 +682,6 @@ impl GroveDb {
         Ok(()).wrap_with_cost(cost)
     }
 
-    /// Updates a tree item and preserves flags. Returns CostResult.
-    pub(crate) fn update_tree_item_preserve_flag<'db, K: AsRef<[u8]>, S: StorageContext<'db>>(
-        parent_tree: &mut Merk<S>,
-        key: K,
-        maybe_root_key: Option<Vec<u8>>,
-        root_tree_hash: Hash,
-        sum: Option<i64>,
-    ) -> CostResult<(), Error> {
-        let key_ref = key.as_ref();
-
-        Self::get_element_from_subtree(parent_tree, key_ref).flat_map_ok(|element| {
-            if let Element::Tree(_, flag) = element {
-                let tree = Element::new_tree_with_flags(maybe_root_key, flag);
-                tree.insert_subtree(parent_tree, key_ref, root_tree_hash, None)
-            } else if let Element::SumTree(.., flag) = element {
-                let tree = Element::new_sum_tree_with_flags_and_sum_value(
-                    maybe_root_key,
-                    sum.unwrap_or_default(),
-                    flag,
-                );
-                tree.insert_subtree(parent_tree, key.as_ref(), root_tree_hash, None)
-            } else {
-                Err(Error::InvalidPath(
-                    "can only propagate on tree items".to_owned(),
-                ))
-                .wrap_with_cost(Default::default())
-            }
-        })
-    }
-
-    /// Pushes to batch an operation which updates a tree item and preserves
-    /// flags. Returns CostResult.
-    pub(crate) fn update_tree_item_preserve_flag_into_batch_operations<
-        'db,
-        K: AsRef<[u8]>,
-        S: StorageContext<'db>,
-    >(
-        parent_tree: &Merk<S>,
-        key: K,
-        maybe_root_key: Option<Vec<u8>>,
-        root_tree_hash: Hash,
-        sum: Option<i64>,
-        batch_operations: &mut Vec<BatchEntry<K>>,
-    ) -> CostResult<(), Error> {
-        let mut cost = OperationCost::default();
-        Self::get_element_from_subtree(parent_tree, key.as_ref()).flat_map_ok(|element| {
-            if let Element::Tree(_, flag) = element {
-                let tree = Element::new_tree_with_flags(maybe_root_key, flag);
-                let merk_feature_type = cost_return_on_error!(
-                    &mut cost,
-                    tree.get_feature_type(parent_tree.is_sum_tree)
-                        .wrap_with_cost(OperationCost::default())
-                );
-                tree.insert_subtree_into_batch_operations(
-                    key,
-                    root_tree_hash,
-                    true,
-                    batch_operations,
-                    merk_feature_type,
-                )
-            } else if let Element::SumTree(.., flag) = element {
-                let tree = Element::new_sum_tree_with_flags_and_sum_value(
-                    maybe_root_key,
-                    sum.unwrap_or_default(),
-                    flag,
-                );
-                let merk_feature_type = cost_return_on_error!(
-                    &mut cost,
-                    tree.get_feature_type(parent_tree.is_sum_tree)
-                        .wrap_with_cost(OperationCost::default())
-                );
-                tree.insert_subtree_into_batch_operations(
-                    key,
-                    root_tree_hash,
-                    true,
-                    batch_operations,
-                    merk_feature_type,
-                )
-            } else {
-                Err(Error::InvalidPath(
-                    "can only propagate on tree items".to_owned(),
-                ))
-                .wrap_with_cost(Default::default())
-            }
-        })
-    }
-
-    /// Get element from subtree. Return CostResult.
-    fn get_element_from_subtree<'db, K: AsRef<[u8]>, S: StorageContext<'db>>(
-        subtree: &Merk<S>,
-        key: K,
-    ) -> CostResult<Element, Error> {
-        subtree
-            .get(key.as_ref(), true)
-            .map_err(|_| {
-                Error::InvalidPath("can't find subtree in parent during propagation".to_owned())
-            })
-            .map_ok(|subtree_opt| {
-                subtree_opt.ok_or_else(|| {
-                    let key = hex::encode(key.as_ref());
-                    Error::PathKeyNotFound(format!(
-                        "can't find subtree with key {} in parent during propagation (subtree is \
-                         {})",
-                        key,
-                        if subtree.root_key().is_some() {
-                            "not empty"
-                        } else {
-                            "empty"
-                        }
-                    ))
-                })
-            })
-            .flatten()
-            .map_ok(|element_bytes| {
-                Element::deserialize(&element_bytes).map_err(|_| {
-                    Error::CorruptedData(
-                        "failed to deserialized parent during propagation".to_owned(),
-                    )
-                })
-            })
-            .flatten()
-    }
-
     /// Flush memory table to disk.
     pub fn flush(&self) -> Result<(), Error> {
         Ok(self.db.flush()?)
@@ -759,23 +742,61 @@ impl GroveDb {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn start_transaction(&self) -> Transaction {
+    pub fn start_transaction(&self) -> Transaction<'_, S> {
         self.db.start_transaction()
     }
 
     /// Commits previously started db transaction. For more details on the
     /// transaction usage, please check [`GroveDb::start_transaction`]
-    pub fn commit_transaction(&self, transaction: Transaction) -> CostResult<(), Error> {
-        self.db.commit_transaction(transaction).map_err(Into::into)
+    pub fn commit_transaction(&self, transaction: Transaction<S>) -> CostResult<(), Error> {
+        let mut cost = OperationCost::default();
+        if self.change_feed {
+            cost_return_on_error!(&mut cost, self.record_pending_changes(&transaction));
+        }
+        self.db
+            .commit_transaction(transaction)
+            .map_err(Into::into)
+            .add_cost(cost)
+    }
+
+    /// Fails with `Error::TransactionConflict` if records the transaction
+    /// wrote were committed meanwhile. With RocksDB they are compared to the
+    /// records as of the start of the transaction, so a record committed
+    /// after the start but before the transaction wrote it is reported too,
+    /// though the commit would accept it.
+    pub fn validate_transaction(&self, transaction: &Transaction<S>) -> Result<(), Error> {
+        Ok(self.db.validate_transaction(transaction)?)
     }
 
     /// Rollbacks previously started db transaction to initial state.
     /// For more details on the transaction usage, please check
     /// [`GroveDb::start_transaction`]
-    pub fn rollback_transaction(&self, transaction: &Transaction) -> Result<(), Error> {
+    pub fn rollback_transaction(&self, transaction: &Transaction<S>) -> Result<(), Error> {
         Ok(self.db.rollback_transaction(transaction)?)
     }
 
+    /// Sets a savepoint in the transaction to roll back to with
+    /// [`GroveDb::rollback_to_savepoint`]. Savepoints stack, for sub steps
+    /// that commit or roll back on their own see
+    /// [`GroveDb::start_sub_transaction`]
+    pub fn set_savepoint(&self, transaction: &Transaction<S>) {
+        self.db.set_savepoint(transaction)
+    }
+
+    /// Discards the writes made in the transaction since its last savepoint
+    /// and removes the savepoint. Each write propagates root hashes up to the
+    /// GroveDb root before it returns, so the transaction keeps consistent
+    /// Merk trees after a partial rollback.
+    pub fn rollback_to_savepoint(&self, transaction: &Transaction<S>) -> Result<(), Error> {
+        Ok(self.db.rollback_to_savepoint(transaction)?)
+    }
+
+    /// Removes the last savepoint of the transaction keeping the writes made
+    /// since it was set
+    pub fn release_savepoint(&self, transaction: &Transaction<S>) -> Result<(), Error> {
+        Ok(self.db.release_savepoint(transaction)?)
+    }
+
     /// Method to visualize hash mismatch after verification
     pub fn visualize_verify_grovedb(&self) -> HashMap<String, (String, String, String)> {
         self.verify_grovedb()
@@ -810,7 +831,7 @@ impl GroveDb {
     /// those of the merk and submerks at the given path. Returns any issues.
     fn verify_merk_and_submerks<'db, B: AsRef<[u8]>>(
         &'db self,
-        merk: Merk<PrefixedRocksDbStorageContext>,
+        merk: Merk<<S as Storage<'db>>::BatchStorageContext>,
         path: &SubtreePath<B>,
         batch: Option<&'db StorageBatch>,
     ) -> HashMap<Vec<Vec<u8>>, (CryptoHash, CryptoHash, CryptoHash)> {
@@ -853,3 +874,129 @@ impl GroveDb {
         issues
     }
 }
+
+#[cfg(feature = "full")]
+impl GroveDb {
+    /// Updates a tree item and preserves flags. Returns CostResult.
+    pub(crate) fn update_tree_item_preserve_flag<'db, K: AsRef<[u8]>, C: StorageContext<'db>>(
+        parent_tree: &mut Merk<C>,
+        key: K,
+        maybe_root_key: Option<Vec<u8>>,
+        root_tree_hash: Hash,
+        sum: Option<i64>,
+    ) -> CostResult<(), Error> {
+        let key_ref = key.as_ref();
+
+        Self::get_element_from_subtree(parent_tree, key_ref).flat_map_ok(|element| {
+            if let Element::Tree(_, flag) = element {
+                let tree = Element::new_tree_with_flags(maybe_root_key, flag);
+                tree.insert_subtree(parent_tree, key_ref, root_tree_hash, None)
+            } else if let Element::SumTree(.., flag) = element {
+                let tree = Element::new_sum_tree_with_flags_and_sum_value(
+                    maybe_root_key,
+                    sum.unwrap_or_default(),
+                    flag,
+                );
+                tree.insert_subtree(parent_tree, key.as_ref(), root_tree_hash, None)
+            } else {
+                Err(Error::InvalidPath(
+                    "can only propagate on tree items".to_owned(),
+                ))
+                .wrap_with_cost(Default::default())
+            }
+        })
+    }
+
+    /// Pushes to batch an operation which updates a tree item and preserves
+    /// flags. Returns CostResult.
+    pub(crate) fn update_tree_item_preserve_flag_into_batch_operations<
+        'db,
+        K: AsRef<[u8]>,
+        C: StorageContext<'db>,
+    >(
+        parent_tree: &Merk<C>,
+        key: K,
+        maybe_root_key: Option<Vec<u8>>,
+        root_tree_hash: Hash,
+        sum: Option<i64>,
+        batch_operations: &mut Vec<BatchEntry<K>>,
+    ) -> CostResult<(), Error> {
+        let mut cost = OperationCost::default();
+        Self::get_element_from_subtree(parent_tree, key.as_ref()).flat_map_ok(|element| {
+            if let Element::Tree(_, flag) = element {
+                let tree = Element::new_tree_with_flags(maybe_root_key, flag);
+                let merk_feature_type = cost_return_on_error!(
+                    &mut cost,
+                    tree.get_feature_type(parent_tree.is_sum_tree)
+                        .wrap_with_cost(OperationCost::default())
+                );
+                tree.insert_subtree_into_batch_operations(
+                    key,
+                    root_tree_hash,
+                    true,
+                    batch_operations,
+                    merk_feature_type,
+                )
+            } else if let Element::SumTree(.., flag) = element {
+                let tree = Element::new_sum_tree_with_flags_and_sum_value(
+                    maybe_root_key,
+                    sum.unwrap_or_default(),
+                    flag,
+                );
+                let merk_feature_type = cost_return_on_error!(
+                    &mut cost,
+                    tree.get_feature_type(parent_tree.is_sum_tree)
+                        .wrap_with_cost(OperationCost::default())
+                );
+                tree.insert_subtree_into_batch_operations(
+                    key,
+                    root_tree_hash,
+                    true,
+                    batch_operations,
+                    merk_feature_type,
+                )
+            } else {
+                Err(Error::InvalidPath(
+                    "can only propagate on tree items".to_owned(),
+                ))
+                .wrap_with_cost(Default::default())
+            }
+        })
+    }
+
+    /// Get element from subtree. Return CostResult.
+    fn get_element_from_subtree<'db, K: AsRef<[u8]>, C: StorageContext<'db>>(
+        subtree: &Merk<C>,
+        key: K,
+    ) -> CostResult<Element, Error> {
+        subtree
+            .get(key.as_ref(), true)
+            .map_err(|_| {
+                Error::InvalidPath("can't find subtree in parent during propagation".to_owned())
+            })
+            .map_ok(|subtree_opt| {
+                subtree_opt.ok_or_else(|| {
+                    let key = hex::encode(key.as_ref());
+                    Error::PathKeyNotFound(format!(
+                        "can't find subtree with key {} in parent during propagation (subtree is \
+                         {})",
+                        key,
+                        if subtree.root_key().is_some() {
+                            "not empty"
+                        } else {
+                            "empty"
+                        }
+                    ))
+                })
+            })
+            .flatten()
+            .map_ok(|element_bytes| {
+                Element::deserialize(&element_bytes).map_err(|_| {
+                    Error::CorruptedData(
+                        "failed to deserialized parent during propagation".to_owned(),
+                    )
+                })
+            })
+            .flatten()
+    }
+}
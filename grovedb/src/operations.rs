@@ -33,10 +33,14 @@ pub(crate) mod auxiliary;
 #[cfg(feature = "full")]
 pub mod delete;
 #[cfg(feature = "full")]
+mod fork;
+#[cfg(feature = "full")]
 pub(crate) mod get;
 #[cfg(feature = "full")]
 pub mod insert;
 #[cfg(feature = "full")]
 pub(crate) mod is_empty_tree;
+#[cfg(feature = "full")]
+mod move_element;
 #[cfg(any(feature = "full", feature = "verify"))]
 pub mod proof;
@@ -35,20 +35,20 @@ use grovedb_costs::{
 };
 #[cfg(feature = "full")]
 use grovedb_storage::StorageContext;
-use grovedb_storage::{Storage, StorageBatch};
+use grovedb_storage::{CovariantStorage, StorageBatch};
 
 #[cfg(feature = "full")]
 use crate::{util::meta_storage_context_optional_tx, Error, GroveDb, TransactionArg};
 
 #[cfg(feature = "full")]
-impl GroveDb {
+impl<S: CovariantStorage> GroveDb<S> {
     /// Put op for aux storage
     pub fn put_aux<K: AsRef<[u8]>>(
         &self,
         key: K,
         value: &[u8],
         cost_info: Option<KeyValueStorageCost>,
-        transaction: TransactionArg,
+        transaction: TransactionArg<S>,
     ) -> CostResult<(), Error> {
         let mut cost = OperationCost::default();
         let batch = StorageBatch::new();
@@ -65,7 +65,7 @@ impl GroveDb {
         });
 
         self.db
-            .commit_multi_context_batch(batch, transaction)
+            .commit_multi_context_batch(batch, transaction.map(|tx| self.db.narrow_transaction(tx)))
             .add_cost(cost)
             .map_err(Into::into)
     }
@@ -75,7 +75,7 @@ impl GroveDb {
         &self,
         key: K,
         cost_info: Option<KeyValueStorageCost>,
-        transaction: TransactionArg,
+        transaction: TransactionArg<S>,
     ) -> CostResult<(), Error> {
         let mut cost = OperationCost::default();
         let batch = StorageBatch::new();
@@ -92,7 +92,7 @@ impl GroveDb {
         });
 
         self.db
-            .commit_multi_context_batch(batch, transaction)
+            .commit_multi_context_batch(batch, transaction.map(|tx| self.db.narrow_transaction(tx)))
             .add_cost(cost)
             .map_err(Into::into)
     }
@@ -101,7 +101,7 @@ impl GroveDb {
     pub fn get_aux<K: AsRef<[u8]>>(
         &self,
         key: K,
-        transaction: TransactionArg,
+        transaction: TransactionArg<S>,
     ) -> CostResult<Option<Vec<u8>>, Error> {
         let mut cost = OperationCost::default();
 
@@ -0,0 +1,398 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Delete range
+
+use grovedb_costs::{
+    cost_return_on_error, cost_return_on_error_no_add, CostResult, CostsExt, OperationCost,
+};
+use grovedb_merk::{proofs::Query, KVIterator};
+use grovedb_path::SubtreePath;
+use grovedb_storage::{CovariantStorage, StorageContext};
+
+use crate::{
+    batch::{GroveDbOp, Op},
+    change_feed::ChangedElements,
+    element::helpers::raw_decode,
+    operations::delete::DeleteOptions,
+    util::storage_context_optional_tx,
+    Element, Error, GroveDb, QueryItem, TransactionArg,
+};
+
+impl<S: CovariantStorage> GroveDb<S> {
+    /// Checks every range delete operation against the current state. Trees
+    /// in a range are handled according to the delete options, as for a
+    /// single delete, and a range is split around the trees it must keep.
+    pub(crate) fn check_delete_range_ops(
+        &self,
+        ops: Vec<GroveDbOp>,
+        options: &DeleteOptions,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<Vec<GroveDbOp>, Error> {
+        let mut cost = OperationCost::default();
+
+        if !ops.iter().any(|op| matches!(op.op, Op::DeleteRange { .. })) {
+            return Ok(ops).wrap_with_cost(cost);
+        }
+
+        let mut checked_ops = Vec::with_capacity(ops.len());
+        for op in ops {
+            let Op::DeleteRange { query_item } = op.op else {
+                checked_ops.push(op);
+                continue;
+            };
+            cost_return_on_error!(
+                &mut cost,
+                self.push_delete_range_ops(
+                    &mut checked_ops,
+                    op.path.to_path_consume(),
+                    query_item,
+                    options,
+                    transaction
+                )
+            );
+        }
+
+        Ok(checked_ops).wrap_with_cost(cost)
+    }
+
+    /// Pushes deletes of the runs of consecutive elements currently in the
+    /// range, split around the trees the range must keep. The ops already
+    /// pushed are taken into account when deleting trees.
+    pub(crate) fn push_delete_range_ops(
+        &self,
+        ops: &mut Vec<GroveDbOp>,
+        path: Vec<Vec<u8>>,
+        query_item: QueryItem,
+        options: &DeleteOptions,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<(), Error> {
+        let mut cost = OperationCost::default();
+
+        let elements_in_range = cost_return_on_error!(
+            &mut cost,
+            self.elements_in_range(&path, query_item, transaction)
+        );
+
+        let mut runs: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+        let mut extends_run = false;
+        for (key, element) in elements_in_range {
+            let is_subtree_with_sum = match element {
+                Element::Tree(..) => (true, false),
+                Element::SumTree(..) => (true, true),
+                _ => (false, false),
+            };
+            let delete_op = cost_return_on_error!(
+                &mut cost,
+                self.delete_operation_for_delete_internal(
+                    SubtreePath::from(path.as_slice()),
+                    key.as_slice(),
+                    options,
+                    Some(is_subtree_with_sum),
+                    ops,
+                    transaction
+                )
+            );
+            if delete_op.is_none() {
+                extends_run = false;
+                continue;
+            }
+            match runs.last_mut() {
+                Some((_, last)) if extends_run => *last = key,
+                _ => runs.push((key.clone(), key)),
+            }
+            extends_run = true;
+        }
+
+        ops.extend(runs.into_iter().map(|(first, last)| {
+            GroveDbOp::delete_range_op(path.clone(), QueryItem::RangeInclusive(first..=last))
+        }));
+
+        Ok(()).wrap_with_cost(cost)
+    }
+
+    /// Paths and keys of the elements changed by the ops, those of a range
+    /// delete being the elements currently in its range and that of a kept
+    /// move being its destination
+    pub(crate) fn changed_elements(
+        &self,
+        ops: &[GroveDbOp],
+        transaction: TransactionArg<S>,
+    ) -> CostResult<ChangedElements, Error> {
+        let mut cost = OperationCost::default();
+
+        let mut changed_elements = Vec::with_capacity(ops.len());
+        for op in ops {
+            let path = op.path.to_path();
+            if let Op::DeleteRange { query_item } = &op.op {
+                let elements_in_range = cost_return_on_error!(
+                    &mut cost,
+                    self.elements_in_range(&path, query_item.clone(), transaction)
+                );
+                changed_elements.extend(
+                    elements_in_range
+                        .into_iter()
+                        .map(|(key, _)| (path.clone(), key)),
+                );
+            } else if let Op::Move { to_path, to_key } = &op.op {
+                changed_elements.push((to_path.clone(), to_key.clone()));
+            } else if let Op::InsertTreeWithContents { elements, .. } = &op.op {
+                let key = op.key.get_key_clone();
+                let mut tree_path = path.clone();
+                tree_path.push(key.clone());
+                changed_elements.push((path, key));
+                changed_elements.extend(
+                    elements
+                        .iter()
+                        .map(|(key, _)| (tree_path.clone(), key.clone())),
+                );
+            } else {
+                changed_elements.push((path, op.key.get_key_clone()));
+            }
+        }
+
+        Ok(changed_elements).wrap_with_cost(cost)
+    }
+
+    /// Elements currently in a range of a subtree, in key order
+    pub(crate) fn elements_in_range(
+        &self,
+        path: &[Vec<u8>],
+        query_item: QueryItem,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<Vec<(Vec<u8>, Element)>, Error> {
+        let mut cost = OperationCost::default();
+
+        let query = Query::new_single_query_item(query_item);
+
+        let mut elements_in_range = Vec::new();
+        let subtree_path: SubtreePath<Vec<u8>> = path.into();
+        storage_context_optional_tx!(self.db, subtree_path, None, transaction, storage, {
+            let storage = storage.unwrap_add_cost(&mut cost);
+            let mut kv_iter =
+                KVIterator::new(storage.raw_iter(), &query).unwrap_add_cost(&mut cost);
+            while let Some((key, value)) = kv_iter.next_kv().unwrap_add_cost(&mut cost) {
+                let element = cost_return_on_error_no_add!(&cost, raw_decode(&value));
+                elements_in_range.push((key, element));
+            }
+        });
+
+        Ok(elements_in_range).wrap_with_cost(cost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        batch::{BatchApplyOptions, GroveDbOp},
+        reference_path::ReferencePathType,
+        tests::{make_test_grovedb, ANOTHER_TEST_LEAF, TEST_LEAF},
+        Element, Error, QueryItem,
+    };
+
+    #[test]
+    fn test_delete_range_op() {
+        let db = make_test_grovedb();
+        for i in 0u8..10 {
+            db.insert(
+                [TEST_LEAF].as_ref(),
+                &[i],
+                Element::new_item(vec![i]),
+                None,
+                None,
+            )
+            .unwrap()
+            .expect("successful insert");
+        }
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            &[20],
+            Element::empty_tree(),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+        db.insert(
+            [TEST_LEAF, &[20]].as_ref(),
+            b"inner",
+            Element::new_item(b"value".to_vec()),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+
+        let ops = vec![GroveDbOp::delete_range_op(
+            vec![TEST_LEAF.to_vec()],
+            QueryItem::Range(vec![2]..vec![7]),
+        )];
+        db.apply_batch(ops, None, None)
+            .unwrap()
+            .expect("expected to delete range");
+        for i in 0u8..10 {
+            let result = db.get([TEST_LEAF].as_ref(), &[i], None).unwrap();
+            if (2..7).contains(&i) {
+                assert!(matches!(result, Err(Error::PathKeyNotFound(_))));
+            } else {
+                assert_eq!(result.expect("expected item"), Element::new_item(vec![i]));
+            }
+        }
+        assert!(db.verify_grovedb().is_empty());
+
+        // The range contains a non empty tree
+        let ops = vec![GroveDbOp::delete_range_op(
+            vec![TEST_LEAF.to_vec()],
+            QueryItem::RangeFrom(vec![8]..),
+        )];
+        assert!(matches!(
+            db.apply_batch(ops.clone(), None, None).unwrap(),
+            Err(Error::DeletingNonEmptyTree(_))
+        ));
+
+        // Non empty trees are skipped when they may not be deleted without an
+        // error
+        db.apply_batch(
+            ops,
+            Some(BatchApplyOptions {
+                deleting_non_empty_trees_returns_error: false,
+                ..Default::default()
+            }),
+            None,
+        )
+        .unwrap()
+        .expect("expected to delete range");
+        assert!(matches!(
+            db.get([TEST_LEAF].as_ref(), &[9], None).unwrap(),
+            Err(Error::PathKeyNotFound(_))
+        ));
+        assert!(db.get([TEST_LEAF].as_ref(), &[20], None).unwrap().is_ok());
+        assert!(db.verify_grovedb().is_empty());
+    }
+
+    #[test]
+    fn test_delete_range_op_removes_empty_trees_and_storage() {
+        let db = make_test_grovedb();
+        for i in 0u8..10 {
+            db.insert(
+                [TEST_LEAF].as_ref(),
+                &[i],
+                Element::new_item(vec![i]),
+                None,
+                None,
+            )
+            .unwrap()
+            .expect("successful insert");
+        }
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            &[5, 0],
+            Element::empty_tree(),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+
+        let ops = vec![GroveDbOp::delete_range_op(
+            vec![TEST_LEAF.to_vec()],
+            QueryItem::RangeInclusive(vec![3]..=vec![6]),
+        )];
+        let cost = db
+            .apply_batch(ops, None, None)
+            .cost_as_result()
+            .expect("expected to delete range");
+        assert!(cost.storage_cost.removed_bytes.total_removed_bytes() > 0);
+
+        for key in [vec![3], vec![4], vec![5], vec![5, 0], vec![6]] {
+            assert!(matches!(
+                db.get([TEST_LEAF].as_ref(), &key, None).unwrap(),
+                Err(Error::PathKeyNotFound(_))
+            ));
+        }
+        assert!(db.get([TEST_LEAF].as_ref(), &[2], None).unwrap().is_ok());
+        assert!(db.get([TEST_LEAF].as_ref(), &[7], None).unwrap().is_ok());
+        assert!(db.verify_grovedb().is_empty());
+    }
+
+    #[test]
+    fn test_delete_range_op_with_reference_into_range() {
+        let db = make_test_grovedb();
+        for i in 0u8..10 {
+            db.insert(
+                [TEST_LEAF].as_ref(),
+                &[i],
+                Element::new_item(vec![i]),
+                None,
+                None,
+            )
+            .unwrap()
+            .expect("successful insert");
+        }
+
+        let ops = vec![
+            GroveDbOp::delete_range_op(
+                vec![TEST_LEAF.to_vec()],
+                QueryItem::Range(vec![2]..vec![7]),
+            ),
+            GroveDbOp::insert_op(
+                vec![ANOTHER_TEST_LEAF.to_vec()],
+                b"reference".to_vec(),
+                Element::new_reference(ReferencePathType::AbsolutePathReference(vec![
+                    TEST_LEAF.to_vec(),
+                    vec![4],
+                ])),
+            ),
+        ];
+        assert!(matches!(
+            db.apply_batch(ops, None, None).unwrap(),
+            Err(Error::InvalidBatchOperation(
+                "references can not point to something currently being deleted"
+            ))
+        ));
+
+        // An op on a key of the range is an op on the same path and key
+        let ops = vec![
+            GroveDbOp::delete_range_op(
+                vec![TEST_LEAF.to_vec()],
+                QueryItem::Range(vec![2]..vec![7]),
+            ),
+            GroveDbOp::insert_op(
+                vec![TEST_LEAF.to_vec()],
+                vec![4],
+                Element::new_item(b"new".to_vec()),
+            ),
+        ];
+        assert!(matches!(
+            db.apply_batch(ops, None, None).unwrap(),
+            Err(Error::InvalidBatchOperation(
+                "batch operations fail consistency checks"
+            ))
+        ));
+    }
+}
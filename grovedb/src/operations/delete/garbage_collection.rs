@@ -0,0 +1,405 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Garbage collection of subtree prefixes no tree element leads to
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use grovedb_costs::{cost_return_on_error, CostResult, CostsExt, OperationCost};
+use grovedb_path::SubtreePath;
+use grovedb_storage::{build_prefix, CovariantStorage, StorageBatch};
+
+use crate::{Error, GroveDb, Transaction, TransactionArg};
+
+/// Outcome of a garbage collection pass
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GarbageCollectionReport {
+    /// Prefixes of the removed orphaned subtrees
+    pub removed_prefixes: Vec<Vec<u8>>,
+    /// Number of key and value bytes removed from storage
+    pub freed_bytes: u64,
+}
+
+/// Number of orphaned prefixes removed by each write of a garbage collection
+/// pass, bounding the size of its write batches
+const GARBAGE_COLLECTION_CHUNK_SIZE: usize = 64;
+
+impl<S: CovariantStorage> GroveDb<S> {
+    /// Returns subtree prefixes that still have records in storage while no
+    /// tree element leads to them, with the number of key and value bytes
+    /// stored under each of them.
+    pub fn find_orphaned_prefixes(
+        &self,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<BTreeMap<Vec<u8>, u64>, Error> {
+        let mut cost = OperationCost::default();
+
+        let live_prefixes = cost_return_on_error!(&mut cost, self.live_prefixes(transaction));
+        self.orphaned_prefixes(&live_prefixes, &[], usize::MAX, transaction)
+            .add_cost(cost)
+    }
+
+    /// Removes all records of orphaned subtree prefixes. Without a transaction
+    /// the pass runs in a transaction of its own and fails with
+    /// `Error::TransactionConflict` if a concurrent write touched the removed
+    /// records or linked one of the removed prefixes again, so it can run in
+    /// the background next to other writers.
+    ///
+    /// Orphans are removed in chunks of bounded size. On RocksDB every record
+    /// of an orphan is deleted on its own as optimistic transaction databases
+    /// have no range deletion, so space is only reclaimed on compaction.
+    pub fn collect_garbage(
+        &self,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<GarbageCollectionReport, Error> {
+        if let Some(tx) = transaction {
+            return self.collect_garbage_on_transaction(tx);
+        }
+
+        let mut cost = OperationCost::default();
+        let tx = self.db.start_transaction();
+        let report = cost_return_on_error!(&mut cost, self.collect_garbage_on_transaction(&tx));
+
+        // A subtree created at a removed prefix after the orphans were found
+        // would have lost its records, so the removed prefixes must still be
+        // unlinked before committing
+        let live_prefixes = cost_return_on_error!(&mut cost, self.live_prefixes(Some(&tx)));
+        if report
+            .removed_prefixes
+            .iter()
+            .any(|removed| live_prefixes.contains(removed))
+        {
+            return Err(Error::TransactionConflict).wrap_with_cost(cost);
+        }
+
+        cost_return_on_error!(&mut cost, self.commit_transaction(tx));
+        Ok(report).wrap_with_cost(cost)
+    }
+
+    fn collect_garbage_on_transaction(
+        &self,
+        transaction: &Transaction<S>,
+    ) -> CostResult<GarbageCollectionReport, Error> {
+        let mut cost = OperationCost::default();
+
+        let live_prefixes = cost_return_on_error!(&mut cost, self.live_prefixes(Some(transaction)));
+
+        let mut report = GarbageCollectionReport::default();
+        let mut from = Vec::new();
+        loop {
+            let orphaned_prefixes = cost_return_on_error!(
+                &mut cost,
+                self.orphaned_prefixes(
+                    &live_prefixes,
+                    &from,
+                    GARBAGE_COLLECTION_CHUNK_SIZE,
+                    Some(transaction)
+                )
+            );
+            let Some(last) = orphaned_prefixes.keys().next_back().cloned() else {
+                break;
+            };
+
+            let batch = StorageBatch::new();
+            for (prefix, stored_bytes) in orphaned_prefixes {
+                batch.delete_prefix(prefix.clone());
+                report.removed_prefixes.push(prefix);
+                report.freed_bytes += stored_bytes;
+            }
+            cost_return_on_error!(
+                &mut cost,
+                self.db
+                    .commit_multi_context_batch(
+                        batch,
+                        Some(self.db.narrow_transaction(transaction))
+                    )
+                    .map_err(Into::into)
+            );
+
+            // the removed prefixes have no records left, so the next chunk
+            // starts right after them
+            from = last;
+        }
+
+        Ok(report).wrap_with_cost(cost)
+    }
+
+    /// Prefixes of every subtree a tree element leads to
+    fn live_prefixes(
+        &self,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<BTreeSet<Vec<u8>>, Error> {
+        let mut cost = OperationCost::default();
+
+        let subtree_paths = cost_return_on_error!(
+            &mut cost,
+            self.find_subtrees(&SubtreePath::empty(), transaction)
+        );
+        let live_prefixes = subtree_paths
+            .iter()
+            .map(|path| {
+                build_prefix(SubtreePath::from(path.as_slice()))
+                    .unwrap_add_cost(&mut cost)
+                    .to_vec()
+            })
+            .collect();
+
+        Ok(live_prefixes).wrap_with_cost(cost)
+    }
+
+    /// First `limit` orphaned prefixes from `from` on, live prefixes being
+    /// skipped in storage without reading their records
+    fn orphaned_prefixes(
+        &self,
+        live_prefixes: &BTreeSet<Vec<u8>>,
+        from: &[u8],
+        limit: usize,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<BTreeMap<Vec<u8>, u64>, Error> {
+        self.db
+            .stored_prefixes(
+                from,
+                limit,
+                &|prefix| live_prefixes.contains(prefix),
+                transaction.map(|tx| self.db.narrow_transaction(tx)),
+            )
+            .map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use grovedb_storage::{memory_storage::MemoryStorage, StorageContext};
+
+    use super::*;
+    use crate::{
+        batch::{BatchApplyOptions, GroveDbOp},
+        operations::delete::DeleteOptions,
+        tests::{common::EMPTY_PATH, make_test_grovedb, TEST_LEAF},
+        Element,
+    };
+
+    fn prefix_of(path: &[&[u8]]) -> Vec<u8> {
+        build_prefix(path.into()).unwrap().to_vec()
+    }
+
+    fn stored_prefixes<S: CovariantStorage>(db: &GroveDb<S>) -> BTreeMap<Vec<u8>, u64> {
+        db.db
+            .stored_prefixes(&[], usize::MAX, &|_| false, None)
+            .unwrap()
+            .unwrap()
+    }
+
+    /// Writes an item and an auxiliary record under the prefix of a subtree
+    /// no tree element leads to
+    fn insert_orphaned_records<S: CovariantStorage>(db: &GroveDb<S>, path: &[&[u8]]) {
+        let batch = StorageBatch::new();
+        {
+            let context = db
+                .db
+                .get_storage_context(path.into(), Some(&batch))
+                .unwrap();
+            context.put(b"key", b"value", None, None).unwrap().unwrap();
+            context.put_aux(b"aux", b"value", None).unwrap().unwrap();
+        }
+        db.db
+            .commit_multi_context_batch(batch, None)
+            .unwrap()
+            .unwrap();
+    }
+
+    /// Inserts a tree with an item and a nested tree with an item into the
+    /// test leaf
+    fn insert_nested_trees<S: CovariantStorage>(
+        db: &GroveDb<S>,
+        key: &[u8],
+        transaction: TransactionArg<S>,
+    ) {
+        for (path, key, element) in [
+            (vec![TEST_LEAF], key, Element::empty_tree()),
+            (
+                vec![TEST_LEAF, key],
+                b"item",
+                Element::new_item(b"a".to_vec()),
+            ),
+            (vec![TEST_LEAF, key], b"inner", Element::empty_tree()),
+            (
+                vec![TEST_LEAF, key, b"inner"],
+                b"item",
+                Element::new_item(b"b".to_vec()),
+            ),
+        ] {
+            db.insert(path.as_slice(), key, element, None, transaction)
+                .unwrap()
+                .expect("cannot insert");
+        }
+    }
+
+    fn delete_non_empty_tree<S: CovariantStorage>(
+        db: &GroveDb<S>,
+        key: &[u8],
+        transaction: TransactionArg<S>,
+    ) {
+        let options = DeleteOptions {
+            allow_deleting_non_empty_trees: true,
+            deleting_non_empty_trees_returns_error: false,
+            ..Default::default()
+        };
+        db.delete([TEST_LEAF].as_ref(), key, Some(options), transaction)
+            .unwrap()
+            .expect("cannot delete tree");
+    }
+
+    /// Checks that deleting a non empty tree removes every record of it and
+    /// of its subtrees
+    fn check_deleting_tree_removes_its_prefixes<S: CovariantStorage>(db: &GroveDb<S>) {
+        insert_nested_trees(db, b"tree", None);
+        delete_non_empty_tree(db, b"tree", None);
+
+        let transaction = db.start_transaction();
+        insert_nested_trees(db, b"tx_tree", Some(&transaction));
+        delete_non_empty_tree(db, b"tx_tree", Some(&transaction));
+        db.commit_transaction(transaction).unwrap().unwrap();
+
+        let stored_prefixes = stored_prefixes(db);
+        for key in [b"tree".as_slice(), b"tx_tree"] {
+            assert!(!stored_prefixes.contains_key(&prefix_of(&[TEST_LEAF, key])));
+            assert!(!stored_prefixes.contains_key(&prefix_of(&[TEST_LEAF, key, b"inner"])));
+        }
+        assert!(db.find_orphaned_prefixes(None).unwrap().unwrap().is_empty());
+    }
+
+    /// Checks that records written under prefixes of missing subtrees are
+    /// found and removed, leaving the grove intact
+    fn check_collect_garbage<S: CovariantStorage>(db: &GroveDb<S>) {
+        insert_nested_trees(db, b"tree", None);
+        let root_hash = db.root_hash(None).unwrap().unwrap();
+
+        let orphaned_path: [&[u8]; 2] = [TEST_LEAF, b"missing"];
+        insert_orphaned_records(db, &orphaned_path);
+
+        let orphaned_prefix = prefix_of(&orphaned_path);
+        let orphaned_prefixes = db.find_orphaned_prefixes(None).unwrap().unwrap();
+        // 32 bytes of prefix for both keys, 3 bytes keys and 5 bytes values
+        assert_eq!(
+            orphaned_prefixes,
+            BTreeMap::from([(orphaned_prefix.clone(), 2 * (32 + 3 + 5))])
+        );
+
+        let report = db.collect_garbage(None).unwrap().unwrap();
+        assert_eq!(report.removed_prefixes, vec![orphaned_prefix.clone()]);
+        assert_eq!(report.freed_bytes, 80);
+
+        let stored_prefixes = stored_prefixes(db);
+        assert!(!stored_prefixes.contains_key(&orphaned_prefix));
+        assert!(stored_prefixes.contains_key(&prefix_of(&[TEST_LEAF, b"tree", b"inner"])));
+        assert_eq!(db.root_hash(None).unwrap().unwrap(), root_hash);
+        assert!(db.verify_grovedb().is_empty());
+        assert_eq!(
+            db.collect_garbage(None).unwrap().unwrap(),
+            GarbageCollectionReport::default()
+        );
+    }
+
+    fn make_memory_grovedb() -> GroveDb<MemoryStorage> {
+        let db = GroveDb::with_storage(MemoryStorage::new());
+        db.insert(EMPTY_PATH, TEST_LEAF, Element::empty_tree(), None, None)
+            .unwrap()
+            .expect("cannot insert leaf");
+        db
+    }
+
+    #[test]
+    fn test_deleting_tree_removes_its_prefixes() {
+        check_deleting_tree_removes_its_prefixes(&make_test_grovedb());
+        check_deleting_tree_removes_its_prefixes(&make_memory_grovedb());
+    }
+
+    #[test]
+    fn test_collect_garbage() {
+        check_collect_garbage(&make_test_grovedb());
+        check_collect_garbage(&make_memory_grovedb());
+    }
+
+    #[test]
+    fn test_batch_tree_deletion_removes_its_prefixes() {
+        let db = make_test_grovedb();
+        insert_nested_trees(&db, b"tree", None);
+
+        let options = BatchApplyOptions {
+            allow_deleting_non_empty_trees: true,
+            deleting_non_empty_trees_returns_error: false,
+            ..Default::default()
+        };
+        let ops = vec![GroveDbOp::delete_tree_op(
+            vec![TEST_LEAF.to_vec()],
+            b"tree".to_vec(),
+            false,
+        )];
+        let cost = db
+            .apply_batch(ops, Some(options), None)
+            .cost_as_result()
+            .expect("cannot apply batch");
+        assert!(cost.storage_cost.removed_bytes.total_removed_bytes() > 0);
+
+        let stored_prefixes = stored_prefixes(&db);
+        assert!(!stored_prefixes.contains_key(&prefix_of(&[TEST_LEAF, b"tree"])));
+        assert!(!stored_prefixes.contains_key(&prefix_of(&[TEST_LEAF, b"tree", b"inner"])));
+        assert!(db.find_orphaned_prefixes(None).unwrap().unwrap().is_empty());
+        assert!(db.verify_grovedb().is_empty());
+    }
+
+    #[test]
+    fn test_collect_garbage_in_chunks() {
+        let db = make_memory_grovedb();
+        insert_nested_trees(&db, b"tree", None);
+        let root_hash = db.root_hash(None).unwrap().unwrap();
+
+        let orphaned_keys: Vec<[u8; 2]> = (0..2 * GARBAGE_COLLECTION_CHUNK_SIZE as u16 + 1)
+            .map(u16::to_be_bytes)
+            .collect();
+        for key in orphaned_keys.iter() {
+            insert_orphaned_records(&db, &[TEST_LEAF, key]);
+        }
+        let orphaned_prefixes = db.find_orphaned_prefixes(None).unwrap().unwrap();
+        assert_eq!(orphaned_prefixes.len(), orphaned_keys.len());
+
+        let transaction = db.start_transaction();
+        let report = db.collect_garbage(Some(&transaction)).unwrap().unwrap();
+        db.commit_transaction(transaction).unwrap().unwrap();
+        assert_eq!(
+            report.removed_prefixes,
+            orphaned_prefixes.keys().cloned().collect::<Vec<_>>()
+        );
+        assert_eq!(report.freed_bytes, orphaned_prefixes.values().sum());
+
+        assert!(db.find_orphaned_prefixes(None).unwrap().unwrap().is_empty());
+        assert_eq!(db.root_hash(None).unwrap().unwrap(), root_hash);
+        assert!(db.verify_grovedb().is_empty());
+    }
+}
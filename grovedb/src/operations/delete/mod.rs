@@ -31,7 +31,11 @@
 #[cfg(feature = "estimated_costs")]
 mod average_case;
 #[cfg(feature = "full")]
+mod delete_range;
+#[cfg(feature = "full")]
 mod delete_up_tree;
+#[cfg(feature = "full")]
+mod garbage_collection;
 #[cfg(feature = "estimated_costs")]
 mod worst_case;
 
@@ -41,6 +45,8 @@ use std::collections::{BTreeSet, HashMap};
 #[cfg(feature = "full")]
 pub use delete_up_tree::DeleteUpTreeOptions;
 #[cfg(feature = "full")]
+pub use garbage_collection::GarbageCollectionReport;
+#[cfg(feature = "full")]
 use grovedb_costs::{
     cost_return_on_error,
     storage_cost::removal::{StorageRemovedBytes, StorageRemovedBytes::BasicStorageRemoval},
@@ -50,10 +56,7 @@ use grovedb_costs::{
 use grovedb_merk::{Error as MerkError, Merk, MerkOptions};
 use grovedb_path::SubtreePath;
 #[cfg(feature = "full")]
-use grovedb_storage::{
-    rocksdb_storage::{PrefixedRocksDbStorageContext, PrefixedRocksDbTransactionContext},
-    Storage, StorageBatch, StorageContext,
-};
+use grovedb_storage::{build_prefix, CovariantStorage, StorageBatch, StorageContext};
 
 use crate::util::merk_optional_tx_path_not_empty;
 #[cfg(feature = "full")]
@@ -99,42 +102,54 @@ impl DeleteOptions {
 }
 
 #[cfg(feature = "full")]
-impl GroveDb {
+impl<S: CovariantStorage> GroveDb<S> {
     /// Delete an element at a specified subtree path and key.
     pub fn delete<'b, B, P>(
         &self,
         path: P,
         key: &[u8],
         options: Option<DeleteOptions>,
-        transaction: TransactionArg,
+        transaction: TransactionArg<S>,
     ) -> CostResult<(), Error>
     where
         B: AsRef<[u8]> + 'b,
         P: Into<SubtreePath<'b, B>>,
     {
         let options = options.unwrap_or_default();
-        let batch = StorageBatch::new();
+        let path: SubtreePath<B> = path.into();
+        let changed_elements = if self.change_feed {
+            vec![(path.to_vec(), key.to_vec())]
+        } else {
+            Vec::new()
+        };
 
-        let collect_costs = self
-            .delete_internal(
-                path.into(),
-                key,
-                &options,
-                transaction,
-                &mut |_, removed_key_bytes, removed_value_bytes| {
-                    Ok((
-                        BasicStorageRemoval(removed_key_bytes),
-                        BasicStorageRemoval(removed_value_bytes),
-                    ))
-                },
-                &batch,
-            )
-            .map_ok(|_| ());
+        self.capture_changes(changed_elements, transaction, |transaction| {
+            let batch = StorageBatch::new();
 
-        collect_costs.flat_map_ok(|_| {
-            self.db
-                .commit_multi_context_batch(batch, transaction)
-                .map_err(Into::into)
+            let collect_costs = self
+                .delete_internal(
+                    path,
+                    key,
+                    &options,
+                    transaction,
+                    &mut |_, removed_key_bytes, removed_value_bytes| {
+                        Ok((
+                            BasicStorageRemoval(removed_key_bytes),
+                            BasicStorageRemoval(removed_value_bytes),
+                        ))
+                    },
+                    &batch,
+                )
+                .map_ok(|_| ());
+
+            collect_costs.flat_map_ok(|_| {
+                self.db
+                    .commit_multi_context_batch(
+                        batch,
+                        transaction.map(|tx| self.db.narrow_transaction(tx)),
+                    )
+                    .map_err(Into::into)
+            })
         })
     }
 
@@ -144,7 +159,7 @@ impl GroveDb {
         path: SubtreePath<B>,
         key: &[u8],
         options: Option<DeleteOptions>,
-        transaction: TransactionArg,
+        transaction: TransactionArg<S>,
         split_removal_bytes_function: &mut impl FnMut(
             &mut ElementFlags,
             u32, // key removed bytes
@@ -155,39 +170,50 @@ impl GroveDb {
         >,
     ) -> CostResult<(), Error> {
         let options = options.unwrap_or_default();
-        let batch = StorageBatch::new();
+        let changed_elements = if self.change_feed {
+            vec![(path.to_vec(), key.to_vec())]
+        } else {
+            Vec::new()
+        };
 
-        let collect_costs = self
-            .delete_internal(
-                path,
-                key,
-                &options,
-                transaction,
-                &mut |value, removed_key_bytes, removed_value_bytes| {
-                    let mut element = Element::deserialize(value.as_slice())
-                        .map_err(|e| MerkError::ClientCorruptionError(e.to_string()))?;
-                    let maybe_flags = element.get_flags_mut();
-                    match maybe_flags {
-                        None => Ok((
-                            BasicStorageRemoval(removed_key_bytes),
-                            BasicStorageRemoval(removed_value_bytes),
-                        )),
-                        Some(flags) => (split_removal_bytes_function)(
-                            flags,
-                            removed_key_bytes,
-                            removed_value_bytes,
-                        )
-                        .map_err(|e| MerkError::ClientCorruptionError(e.to_string())),
-                    }
-                },
-                &batch,
-            )
-            .map_ok(|_| ());
+        self.capture_changes(changed_elements, transaction, |transaction| {
+            let batch = StorageBatch::new();
+
+            let collect_costs = self
+                .delete_internal(
+                    path,
+                    key,
+                    &options,
+                    transaction,
+                    &mut |value, removed_key_bytes, removed_value_bytes| {
+                        let mut element = Element::deserialize(value.as_slice())
+                            .map_err(|e| MerkError::ClientCorruptionError(e.to_string()))?;
+                        let maybe_flags = element.get_flags_mut();
+                        match maybe_flags {
+                            None => Ok((
+                                BasicStorageRemoval(removed_key_bytes),
+                                BasicStorageRemoval(removed_value_bytes),
+                            )),
+                            Some(flags) => (split_removal_bytes_function)(
+                                flags,
+                                removed_key_bytes,
+                                removed_value_bytes,
+                            )
+                            .map_err(|e| MerkError::ClientCorruptionError(e.to_string())),
+                        }
+                    },
+                    &batch,
+                )
+                .map_ok(|_| ());
 
-        collect_costs.flat_map_ok(|_| {
-            self.db
-                .commit_multi_context_batch(batch, transaction)
-                .map_err(Into::into)
+            collect_costs.flat_map_ok(|_| {
+                self.db
+                    .commit_multi_context_batch(
+                        batch,
+                        transaction.map(|tx| self.db.narrow_transaction(tx)),
+                    )
+                    .map_err(Into::into)
+            })
         })
     }
 
@@ -196,32 +222,44 @@ impl GroveDb {
         &self,
         path: P,
         key: &[u8],
-        transaction: TransactionArg,
+        transaction: TransactionArg<S>,
     ) -> CostResult<bool, Error>
     where
         B: AsRef<[u8]> + 'b,
         P: Into<SubtreePath<'b, B>>,
     {
-        let batch = StorageBatch::new();
+        let path: SubtreePath<B> = path.into();
+        let changed_elements = if self.change_feed {
+            vec![(path.to_vec(), key.to_vec())]
+        } else {
+            Vec::new()
+        };
 
-        let collect_costs = self.delete_if_empty_tree_with_sectional_storage_function(
-            path.into(),
-            key,
-            transaction,
-            &mut |_, removed_key_bytes, removed_value_bytes| {
-                Ok((
-                    BasicStorageRemoval(removed_key_bytes),
-                    (BasicStorageRemoval(removed_value_bytes)),
-                ))
-            },
-            &batch,
-        );
+        self.capture_changes(changed_elements, transaction, |transaction| {
+            let batch = StorageBatch::new();
 
-        collect_costs.flat_map_ok(|r| {
-            self.db
-                .commit_multi_context_batch(batch, transaction)
-                .map_err(Into::into)
-                .map_ok(|_| r)
+            let collect_costs = self.delete_if_empty_tree_with_sectional_storage_function(
+                path,
+                key,
+                transaction,
+                &mut |_, removed_key_bytes, removed_value_bytes| {
+                    Ok((
+                        BasicStorageRemoval(removed_key_bytes),
+                        (BasicStorageRemoval(removed_value_bytes)),
+                    ))
+                },
+                &batch,
+            );
+
+            collect_costs.flat_map_ok(|r| {
+                self.db
+                    .commit_multi_context_batch(
+                        batch,
+                        transaction.map(|tx| self.db.narrow_transaction(tx)),
+                    )
+                    .map_err(Into::into)
+                    .map_ok(|_| r)
+            })
         })
     }
 
@@ -230,7 +268,7 @@ impl GroveDb {
         &self,
         path: SubtreePath<B>,
         key: &[u8],
-        transaction: TransactionArg,
+        transaction: TransactionArg<S>,
         split_removal_bytes_function: &mut impl FnMut(
             &mut ElementFlags,
             u32, // key removed bytes
@@ -281,7 +319,7 @@ impl GroveDb {
         options: &DeleteOptions,
         is_known_to_be_subtree_with_sum: Option<(bool, bool)>,
         current_batch_operations: &[GroveDbOp],
-        transaction: TransactionArg,
+        transaction: TransactionArg<S>,
     ) -> CostResult<Option<GroveDbOp>, Error> {
         let mut cost = OperationCost::default();
 
@@ -385,7 +423,7 @@ impl GroveDb {
         path: SubtreePath<B>,
         key: &[u8],
         options: &DeleteOptions,
-        transaction: TransactionArg,
+        transaction: TransactionArg<S>,
         sectioned_removal: &mut impl FnMut(
             &Vec<u8>,
             u32,
@@ -415,7 +453,7 @@ impl GroveDb {
         path: SubtreePath<B>,
         key: &[u8],
         options: &DeleteOptions,
-        transaction: &Transaction,
+        transaction: &Transaction<S>,
         sectioned_removal: &mut impl FnMut(
             &Vec<u8>,
             u32,
@@ -427,6 +465,7 @@ impl GroveDb {
         batch: &StorageBatch,
     ) -> CostResult<bool, Error> {
         let mut cost = OperationCost::default();
+        let transaction = self.db.narrow_transaction(transaction);
 
         let element = cost_return_on_error!(
             &mut cost,
@@ -513,10 +552,7 @@ impl GroveDb {
                         sectioned_removal
                     )
                 );
-                let mut merk_cache: HashMap<
-                    SubtreePath<B>,
-                    Merk<PrefixedRocksDbTransactionContext>,
-                > = HashMap::default();
+                let mut merk_cache: HashMap<SubtreePath<B>, _> = HashMap::default();
                 merk_cache.insert(path.clone(), merk_to_delete_tree_from);
                 cost_return_on_error!(
                     &mut cost,
@@ -540,10 +576,7 @@ impl GroveDb {
                         sectioned_removal
                     )
                 );
-                let mut merk_cache: HashMap<
-                    SubtreePath<B>,
-                    Merk<PrefixedRocksDbTransactionContext>,
-                > = HashMap::default();
+                let mut merk_cache: HashMap<SubtreePath<B>, _> = HashMap::default();
                 merk_cache.insert(path.clone(), subtree_to_delete_from);
                 cost_return_on_error!(
                     &mut cost,
@@ -562,8 +595,7 @@ impl GroveDb {
                     sectioned_removal,
                 )
             );
-            let mut merk_cache: HashMap<SubtreePath<B>, Merk<PrefixedRocksDbTransactionContext>> =
-                HashMap::default();
+            let mut merk_cache: HashMap<SubtreePath<B>, _> = HashMap::default();
             merk_cache.insert(path.clone(), subtree_to_delete_from);
             cost_return_on_error!(
                 &mut cost,
@@ -593,8 +625,7 @@ impl GroveDb {
 
         let element =
             cost_return_on_error!(&mut cost, self.get_raw(path.clone(), key.as_ref(), None));
-        let mut merk_cache: HashMap<SubtreePath<B>, Merk<PrefixedRocksDbStorageContext>> =
-            HashMap::default();
+        let mut merk_cache: HashMap<SubtreePath<B>, _> = HashMap::default();
         let mut subtree_to_delete_from = cost_return_on_error!(
             &mut cost,
             self.open_non_transactional_merk_at_path(path.clone(), Some(batch))
@@ -687,7 +718,7 @@ impl GroveDb {
     pub(crate) fn find_subtrees<B: AsRef<[u8]>>(
         &self,
         path: &SubtreePath<B>,
-        transaction: TransactionArg,
+        transaction: TransactionArg<S>,
     ) -> CostResult<Vec<Vec<Vec<u8>>>, Error> {
         let mut cost = OperationCost::default();
 
@@ -725,6 +756,52 @@ impl GroveDb {
         }
         Ok(result).wrap_with_cost(cost)
     }
+
+    /// Adds to the storage batch deletions of the prefixes of the trees the
+    /// ops delete and of all their subtrees, for their records to be removed
+    /// along with the tree elements
+    pub(crate) fn delete_prefixes_of_deleted_trees(
+        &self,
+        ops: &[GroveDbOp],
+        batch: &StorageBatch,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<(), Error> {
+        let mut cost = OperationCost::default();
+
+        for op in ops {
+            let path = op.path.to_path();
+            let tree_keys = match &op.op {
+                Op::DeleteTree | Op::DeleteSumTree => vec![op.key.get_key_clone()],
+                Op::DeleteRange { query_item } => {
+                    let elements_in_range = cost_return_on_error!(
+                        &mut cost,
+                        self.elements_in_range(&path, query_item.clone(), transaction)
+                    );
+                    elements_in_range
+                        .into_iter()
+                        .filter(|(_, element)| element.is_tree())
+                        .map(|(key, _)| key)
+                        .collect()
+                }
+                _ => continue,
+            };
+            for key in tree_keys {
+                let mut tree_path = path.clone();
+                tree_path.push(key);
+                let subtree_paths = cost_return_on_error!(
+                    &mut cost,
+                    self.find_subtrees(&SubtreePath::from(tree_path.as_slice()), transaction)
+                );
+                for subtree_path in subtree_paths {
+                    let prefix = build_prefix(SubtreePath::from(subtree_path.as_slice()))
+                        .unwrap_add_cost(&mut cost);
+                    batch.delete_prefix(prefix.to_vec());
+                }
+            }
+        }
+
+        Ok(()).wrap_with_cost(cost)
+    }
 }
 
 #[cfg(feature = "full")]
@@ -824,7 +901,9 @@ mod tests {
             .unwrap(),
             Err(Error::PathParentLayerNotFound(_))
         ));
-        transaction.commit().expect("cannot commit transaction");
+        db.commit_transaction(transaction)
+            .unwrap()
+            .expect("cannot commit transaction");
         assert!(matches!(
             db.get([TEST_LEAF].as_ref(), b"key1", None).unwrap(),
             Err(Error::PathKeyNotFound(_))
@@ -1120,7 +1199,9 @@ mod tests {
             .unwrap(),
             Err(Error::PathParentLayerNotFound(_))
         ));
-        transaction.commit().expect("cannot commit transaction");
+        db.commit_transaction(transaction)
+            .unwrap()
+            .expect("cannot commit transaction");
         assert!(matches!(
             db.get([TEST_LEAF].as_ref(), b"key1", None).unwrap(),
             Err(Error::PathKeyNotFound(_))
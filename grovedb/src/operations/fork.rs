@@ -0,0 +1,410 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Subtree forks
+
+use std::collections::HashMap;
+
+use grovedb_costs::{
+    cost_return_on_error, cost_return_on_error_no_add, CostResult, CostsExt, OperationCost,
+};
+use grovedb_merk::tree::Tree;
+use grovedb_path::SubtreePath;
+use grovedb_storage::{CovariantStorage, RawIterator, StorageBatch, StorageContext};
+
+use crate::{
+    util::storage_context_optional_tx, Element, Error, GroveDb, Transaction, TransactionArg,
+};
+
+impl<S: CovariantStorage> GroveDb<S> {
+    /// Creates a new subtree at `dst_path` with the same contents and root
+    /// hash as the subtree at `src_path`, including all nested subtrees.
+    ///
+    /// Merk nodes are copied as raw key ranges under the new prefixes instead
+    /// of being reinserted, and are charged as the bytes written. Elements
+    /// are copied as they are: absolute references inside the fork still
+    /// point where the original ones do, while relative references now
+    /// resolve from the fork's path. Their stored value hashes are those of
+    /// the original targets either way.
+    pub fn fork_subtree<'b, 'c, B, C, P, Q>(
+        &self,
+        src_path: P,
+        dst_path: Q,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<(), Error>
+    where
+        B: AsRef<[u8]> + 'b,
+        C: AsRef<[u8]> + 'c,
+        P: Into<SubtreePath<'b, B>>,
+        Q: Into<SubtreePath<'c, C>>,
+    {
+        let src_path: SubtreePath<B> = src_path.into();
+        let dst_path: SubtreePath<C> = dst_path.into();
+        let changed_elements = match dst_path.derive_parent() {
+            Some((parent_path, key)) if self.change_feed => {
+                vec![(parent_path.to_vec(), key.to_vec())]
+            }
+            _ => Vec::new(),
+        };
+
+        self.capture_changes(changed_elements, transaction, |transaction| {
+            let batch = StorageBatch::new();
+
+            let collect_costs = if let Some(transaction) = transaction {
+                self.fork_subtree_on_transaction(src_path, dst_path, transaction, &batch)
+            } else {
+                self.fork_subtree_without_transaction(src_path, dst_path, &batch)
+            };
+
+            collect_costs.flat_map_ok(|_| {
+                self.db
+                    .commit_multi_context_batch(
+                        batch,
+                        transaction.map(|tx| self.db.narrow_transaction(tx)),
+                    )
+                    .map_err(Into::into)
+            })
+        })
+    }
+
+    pub(crate) fn fork_subtree_on_transaction<'b, 'c, B: AsRef<[u8]>, C: AsRef<[u8]>>(
+        &self,
+        src_path: SubtreePath<'b, B>,
+        dst_path: SubtreePath<'c, C>,
+        transaction: &Transaction<S>,
+        batch: &StorageBatch,
+    ) -> CostResult<(), Error> {
+        let mut cost = OperationCost::default();
+
+        // The forked element keeps the root key of the original subtree
+        let (element, dst_parent_path, dst_key) = cost_return_on_error!(
+            &mut cost,
+            self.copy_subtree_nodes(&src_path, &dst_path, Some(transaction), batch)
+        );
+        let src_root_hash = cost_return_on_error!(
+            &mut cost,
+            self.open_transactional_merk_at_path(src_path, transaction, None)
+                .flat_map_ok(|merk| merk.root_hash().map(Ok))
+        );
+
+        let mut parent_merk = cost_return_on_error!(
+            &mut cost,
+            self.open_transactional_merk_at_path(dst_parent_path.clone(), transaction, Some(batch))
+        );
+        cost_return_on_error!(
+            &mut cost,
+            element.insert_subtree(&mut parent_merk, dst_key, src_root_hash, None)
+        );
+
+        let mut merk_cache: HashMap<SubtreePath<'c, C>, _> = HashMap::default();
+        merk_cache.insert(dst_parent_path.clone(), parent_merk);
+        self.propagate_changes_with_transaction(merk_cache, dst_parent_path, transaction, batch)
+            .add_cost(cost)
+    }
+
+    fn fork_subtree_without_transaction<'b, 'c, B: AsRef<[u8]>, C: AsRef<[u8]>>(
+        &self,
+        src_path: SubtreePath<'b, B>,
+        dst_path: SubtreePath<'c, C>,
+        batch: &StorageBatch,
+    ) -> CostResult<(), Error> {
+        let mut cost = OperationCost::default();
+
+        // The forked element keeps the root key of the original subtree
+        let (element, dst_parent_path, dst_key) = cost_return_on_error!(
+            &mut cost,
+            self.copy_subtree_nodes(&src_path, &dst_path, None, batch)
+        );
+        let src_root_hash = cost_return_on_error!(
+            &mut cost,
+            self.open_non_transactional_merk_at_path(src_path, None)
+                .flat_map_ok(|merk| merk.root_hash().map(Ok))
+        );
+
+        let mut parent_merk = cost_return_on_error!(
+            &mut cost,
+            self.open_non_transactional_merk_at_path(dst_parent_path.clone(), Some(batch))
+        );
+        cost_return_on_error!(
+            &mut cost,
+            element.insert_subtree(&mut parent_merk, dst_key, src_root_hash, None)
+        );
+
+        let mut merk_cache: HashMap<SubtreePath<'c, C>, _> = HashMap::default();
+        merk_cache.insert(dst_parent_path.clone(), parent_merk);
+        self.propagate_changes_without_transaction(merk_cache, dst_parent_path, batch)
+            .add_cost(cost)
+    }
+
+    /// Checks that the fork is possible and copies the Merk nodes of the
+    /// subtree and all of its nested subtrees into the batch under their new
+    /// prefixes. Returns the tree element being forked together with the
+    /// parent path and key of the destination.
+    fn copy_subtree_nodes<'b, 'c, B: AsRef<[u8]>, C: AsRef<[u8]>>(
+        &self,
+        src_path: &SubtreePath<'b, B>,
+        dst_path: &SubtreePath<'c, C>,
+        transaction: TransactionArg<S>,
+        batch: &StorageBatch,
+    ) -> CostResult<(Element, SubtreePath<'c, C>, &'c [u8]), Error> {
+        let mut cost = OperationCost::default();
+
+        let Some((src_parent_path, src_key)) = src_path.derive_parent() else {
+            return Err(Error::InvalidPath(
+                "the root tree can not be forked".to_owned(),
+            ))
+            .wrap_with_cost(cost);
+        };
+        let Some((dst_parent_path, dst_key)) = dst_path.derive_parent() else {
+            return Err(Error::InvalidPath(
+                "the root tree can not be replaced by a fork".to_owned(),
+            ))
+            .wrap_with_cost(cost);
+        };
+
+        let src_path_vec = src_path.to_vec();
+        let dst_path_vec = dst_path.to_vec();
+        if dst_path_vec.starts_with(&src_path_vec) {
+            return Err(Error::InvalidInput(
+                "a subtree can not be forked into itself",
+            ))
+            .wrap_with_cost(cost);
+        }
+
+        let element = cost_return_on_error!(
+            &mut cost,
+            self.get_raw(src_parent_path, src_key, transaction)
+        );
+        if !element.is_tree() {
+            return Err(Error::InvalidPath(
+                "only trees and sum trees can be forked".to_owned(),
+            ))
+            .wrap_with_cost(cost);
+        }
+        if cost_return_on_error!(
+            &mut cost,
+            self.get_raw_optional(dst_parent_path.clone(), dst_key, transaction)
+        )
+        .is_some()
+        {
+            return Err(Error::OverrideNotAllowed("fork destination already exists"))
+                .wrap_with_cost(cost);
+        }
+
+        let subtree_paths =
+            cost_return_on_error!(&mut cost, self.find_subtrees(src_path, transaction));
+        for subtree_path in subtree_paths {
+            let mut nodes = Vec::new();
+            let subtree_path_ref: SubtreePath<Vec<u8>> = subtree_path.as_slice().into();
+            storage_context_optional_tx!(self.db, subtree_path_ref, None, transaction, storage, {
+                let storage = storage.unwrap_add_cost(&mut cost);
+                let mut raw_iter = storage.raw_iter();
+                raw_iter.seek_to_first().unwrap_add_cost(&mut cost);
+                while raw_iter.valid().unwrap_add_cost(&mut cost) {
+                    let key = raw_iter.key().unwrap_add_cost(&mut cost);
+                    let value = raw_iter.value().unwrap_add_cost(&mut cost);
+                    if let (Some(key), Some(value)) = (key, value) {
+                        nodes.push((key.to_vec(), value.to_vec()));
+                    }
+                    raw_iter.next().unwrap_add_cost(&mut cost);
+                }
+            });
+
+            let mut forked_path = dst_path_vec.clone();
+            forked_path.extend_from_slice(&subtree_path[src_path_vec.len()..]);
+            let forked_path_ref: SubtreePath<Vec<u8>> = forked_path.as_slice().into();
+            storage_context_optional_tx!(
+                self.db,
+                forked_path_ref,
+                Some(batch),
+                transaction,
+                storage,
+                {
+                    let storage = storage.unwrap_add_cost(&mut cost);
+                    for (key, value) in nodes {
+                        // Children sizes are only needed to charge for the node
+                        let node = cost_return_on_error_no_add!(
+                            &cost,
+                            Tree::decode_raw(&value, key.clone())
+                                .map_err(|e| Error::CorruptedData(e.to_string()))
+                        );
+                        let children_sizes = Some((
+                            node.feature_type().sum_length(),
+                            node.child_ref_and_sum_size(true),
+                            node.child_ref_and_sum_size(false),
+                        ));
+                        cost_return_on_error!(
+                            &mut cost,
+                            storage
+                                .put(key, &value, children_sizes, None)
+                                .map_err(Into::into)
+                        );
+                    }
+                }
+            );
+        }
+
+        Ok((element, dst_parent_path, dst_key)).wrap_with_cost(cost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        reference_path::ReferencePathType,
+        tests::{make_test_grovedb, ANOTHER_TEST_LEAF, TEST_LEAF},
+        Element, Error,
+    };
+
+    #[test]
+    fn test_fork_subtree() {
+        let db = make_test_grovedb();
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            b"tree",
+            Element::empty_tree(),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+        for i in 0u8..10 {
+            db.insert(
+                [TEST_LEAF, b"tree"].as_ref(),
+                &[i],
+                Element::new_item(vec![i; 5]),
+                None,
+                None,
+            )
+            .unwrap()
+            .expect("successful insert");
+        }
+        db.insert(
+            [TEST_LEAF, b"tree"].as_ref(),
+            b"sums",
+            Element::empty_sum_tree(),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+        db.insert(
+            [TEST_LEAF, b"tree", b"sums"].as_ref(),
+            b"a",
+            Element::new_sum_item(5),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+        db.insert(
+            [TEST_LEAF, b"tree"].as_ref(),
+            b"ref",
+            Element::new_reference(ReferencePathType::SiblingReference(vec![3])),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+
+        db.fork_subtree(
+            [TEST_LEAF, b"tree"].as_ref(),
+            [ANOTHER_TEST_LEAF, b"draft"].as_ref(),
+            None,
+        )
+        .unwrap()
+        .expect("expected to fork subtree");
+        assert!(db.verify_grovedb().is_empty());
+
+        let Element::Tree(src_root_key, _) = db
+            .get_raw([TEST_LEAF].as_ref().into(), b"tree", None)
+            .unwrap()
+            .expect("expected tree")
+        else {
+            panic!("expected a tree");
+        };
+        let Element::Tree(dst_root_key, _) = db
+            .get_raw([ANOTHER_TEST_LEAF].as_ref().into(), b"draft", None)
+            .unwrap()
+            .expect("expected tree")
+        else {
+            panic!("expected a tree");
+        };
+        assert_eq!(src_root_key, dst_root_key);
+        assert_eq!(
+            db.get([ANOTHER_TEST_LEAF, b"draft"].as_ref(), b"ref", None)
+                .unwrap()
+                .expect("expected reference to resolve"),
+            Element::new_item(vec![3; 5])
+        );
+        assert_eq!(
+            db.get([ANOTHER_TEST_LEAF, b"draft"].as_ref(), b"sums", None)
+                .unwrap()
+                .expect("expected sum tree")
+                .sum_value_or_default(),
+            5
+        );
+
+        // Editing the fork leaves the original untouched
+        db.insert(
+            [ANOTHER_TEST_LEAF, b"draft"].as_ref(),
+            &[1],
+            Element::new_item(b"edited".to_vec()),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+        assert_eq!(
+            db.get([TEST_LEAF, b"tree"].as_ref(), &[1], None)
+                .unwrap()
+                .expect("expected item"),
+            Element::new_item(vec![1; 5])
+        );
+        assert!(db.verify_grovedb().is_empty());
+
+        assert!(matches!(
+            db.fork_subtree(
+                [TEST_LEAF, b"tree"].as_ref(),
+                [ANOTHER_TEST_LEAF, b"draft"].as_ref(),
+                None,
+            )
+            .unwrap(),
+            Err(Error::OverrideNotAllowed(_))
+        ));
+        assert!(matches!(
+            db.fork_subtree(
+                [TEST_LEAF, b"tree"].as_ref(),
+                [TEST_LEAF, b"tree", b"inner"].as_ref(),
+                None,
+            )
+            .unwrap(),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+}
@@ -38,11 +38,7 @@ use grovedb_costs::{
 #[cfg(feature = "full")]
 use grovedb_merk::{tree::NULL_HASH, Merk, MerkOptions};
 use grovedb_path::SubtreePath;
-#[cfg(feature = "full")]
-use grovedb_storage::rocksdb_storage::{
-    PrefixedRocksDbStorageContext, PrefixedRocksDbTransactionContext,
-};
-use grovedb_storage::{Storage, StorageBatch};
+use grovedb_storage::{CovariantStorage, Storage, StorageBatch};
 
 #[cfg(feature = "full")]
 use crate::{
@@ -87,7 +83,7 @@ impl InsertOptions {
 }
 
 #[cfg(feature = "full")]
-impl GroveDb {
+impl<S: CovariantStorage> GroveDb<S> {
     /// Insert a GroveDB element given a path to the subtree and the key to
     /// insert at
     pub fn insert<'b, B, P>(
@@ -96,38 +92,49 @@ impl GroveDb {
         key: &[u8],
         element: Element,
         options: Option<InsertOptions>,
-        transaction: TransactionArg,
+        transaction: TransactionArg<S>,
     ) -> CostResult<(), Error>
     where
         B: AsRef<[u8]> + 'b,
         P: Into<SubtreePath<'b, B>>,
     {
         let subtree_path: SubtreePath<B> = path.into();
-        let batch = StorageBatch::new();
-
-        let collect_costs = if let Some(transaction) = transaction {
-            self.insert_on_transaction(
-                subtree_path,
-                key,
-                element,
-                options.unwrap_or_default(),
-                transaction,
-                &batch,
-            )
+        let changed_elements = if self.change_feed {
+            vec![(subtree_path.to_vec(), key.to_vec())]
         } else {
-            self.insert_without_transaction(
-                subtree_path,
-                key,
-                element,
-                options.unwrap_or_default(),
-                &batch,
-            )
+            Vec::new()
         };
 
-        collect_costs.flat_map_ok(|_| {
-            self.db
-                .commit_multi_context_batch(batch, transaction)
-                .map_err(Into::into)
+        self.capture_changes(changed_elements, transaction, |transaction| {
+            let batch = StorageBatch::new();
+
+            let collect_costs = if let Some(transaction) = transaction {
+                self.insert_on_transaction(
+                    subtree_path,
+                    key,
+                    element,
+                    options.unwrap_or_default(),
+                    transaction,
+                    &batch,
+                )
+            } else {
+                self.insert_without_transaction(
+                    subtree_path,
+                    key,
+                    element,
+                    options.unwrap_or_default(),
+                    &batch,
+                )
+            };
+
+            collect_costs.flat_map_ok(|_| {
+                self.db
+                    .commit_multi_context_batch(
+                        batch,
+                        transaction.map(|tx| self.db.narrow_transaction(tx)),
+                    )
+                    .map_err(Into::into)
+            })
         })
     }
 
@@ -137,13 +144,12 @@ impl GroveDb {
         key: &[u8],
         element: Element,
         options: InsertOptions,
-        transaction: &'db Transaction,
+        transaction: &'db Transaction<S>,
         batch: &StorageBatch,
     ) -> CostResult<(), Error> {
         let mut cost = OperationCost::default();
 
-        let mut merk_cache: HashMap<SubtreePath<'b, B>, Merk<PrefixedRocksDbTransactionContext>> =
-            HashMap::default();
+        let mut merk_cache: HashMap<SubtreePath<'b, B>, _> = HashMap::default();
 
         let merk = cost_return_on_error!(
             &mut cost,
@@ -175,8 +181,7 @@ impl GroveDb {
     ) -> CostResult<(), Error> {
         let mut cost = OperationCost::default();
 
-        let mut merk_cache: HashMap<SubtreePath<'b, B>, Merk<PrefixedRocksDbStorageContext>> =
-            HashMap::default();
+        let mut merk_cache: HashMap<SubtreePath<'b, B>, _> = HashMap::default();
 
         let merk = cost_return_on_error!(
             &mut cost,
@@ -203,9 +208,9 @@ impl GroveDb {
         key: &[u8],
         element: Element,
         options: InsertOptions,
-        transaction: &'db Transaction,
+        transaction: &'db Transaction<S>,
         batch: &'db StorageBatch,
-    ) -> CostResult<Merk<PrefixedRocksDbTransactionContext<'db>>, Error> {
+    ) -> CostResult<Merk<<S as Storage<'db>>::BatchTransactionalStorageContext>, Error> {
         let mut cost = OperationCost::default();
 
         let mut subtree_to_insert_into = cost_return_on_error!(
@@ -342,7 +347,7 @@ impl GroveDb {
         element: Element,
         options: InsertOptions,
         batch: &'db StorageBatch,
-    ) -> CostResult<Merk<PrefixedRocksDbStorageContext>, Error> {
+    ) -> CostResult<Merk<<S as Storage<'db>>::BatchStorageContext>, Error> {
         let mut cost = OperationCost::default();
         let mut subtree_to_insert_into = cost_return_on_error!(
             &mut cost,
@@ -468,7 +473,7 @@ impl GroveDb {
         path: P,
         key: &[u8],
         element: Element,
-        transaction: TransactionArg,
+        transaction: TransactionArg<S>,
     ) -> CostResult<bool, Error>
     where
         B: AsRef<[u8]> + 'b,
@@ -497,7 +502,7 @@ impl GroveDb {
         path: P,
         key: &[u8],
         element: Element,
-        transaction: TransactionArg,
+        transaction: TransactionArg<S>,
     ) -> CostResult<(bool, Option<Element>), Error>
     where
         B: AsRef<[u8]> + 'b,
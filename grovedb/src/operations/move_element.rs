@@ -0,0 +1,753 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Move operations
+
+use grovedb_costs::{
+    cost_return_on_error, cost_return_on_error_no_add, CostResult, CostsExt, OperationCost,
+};
+use grovedb_path::SubtreePath;
+use grovedb_storage::{CovariantStorage, PreviousRecords, StorageBatch, StorageContext};
+
+use crate::{
+    batch::{BatchApplyOptions, GroveDbOp, Op},
+    reference_path::{path_from_reference_qualified_path_type, ReferencePathType},
+    util::storage_context_optional_tx,
+    Element, Error, GroveDb, Transaction, TransactionArg,
+};
+
+/// Elements directly under a subtree, each with its path and key
+pub(crate) type SubtreeChildren = Vec<(Vec<Vec<u8>>, Vec<u8>, Element)>;
+
+impl<S: CovariantStorage> GroveDb<S> {
+    /// Moves an element to a new path and key. If the element is a tree its
+    /// whole subtree is moved along with it: the tree element is relinked at
+    /// the destination with the same root hash and the Merk nodes of the
+    /// subtree are copied under their new prefixes, so a subtree can't be
+    /// moved onto an existing element.
+    ///
+    /// References inside the moved subtree that would no longer resolve to
+    /// the same element are rewritten into absolute references. References
+    /// from outside the moved subtree are not updated.
+    pub fn move_element<'b, 'c, B, C, P, Q>(
+        &self,
+        path: P,
+        key: &[u8],
+        to_path: Q,
+        to_key: &[u8],
+        options: Option<BatchApplyOptions>,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<(), Error>
+    where
+        B: AsRef<[u8]> + 'b,
+        C: AsRef<[u8]> + 'c,
+        P: Into<SubtreePath<'b, B>>,
+        Q: Into<SubtreePath<'c, C>>,
+    {
+        let path: SubtreePath<B> = path.into();
+        let to_path: SubtreePath<C> = to_path.into();
+        let op = GroveDbOp::move_op(
+            path.to_vec(),
+            key.to_vec(),
+            to_path.to_vec(),
+            to_key.to_vec(),
+        );
+        self.apply_batch(vec![op], options, transaction)
+    }
+
+    /// Replaces every move operation with the ops it is made of, based on
+    /// the state before the batch is applied
+    pub(crate) fn expand_move_ops(
+        &self,
+        ops: Vec<GroveDbOp>,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<Vec<GroveDbOp>, Error> {
+        let mut cost = OperationCost::default();
+
+        if !ops.iter().any(|op| matches!(op.op, Op::Move { .. })) {
+            return Ok(ops).wrap_with_cost(cost);
+        }
+
+        let mut expanded_ops = Vec::with_capacity(ops.len());
+        for op in ops {
+            if let Op::Move { to_path, to_key } = op.op {
+                let path = op.path.to_path_consume();
+                let key = op.key.get_key();
+                cost_return_on_error!(
+                    &mut cost,
+                    self.push_move_ops(&mut expanded_ops, path, key, to_path, to_key, transaction)
+                );
+            } else {
+                expanded_ops.push(op);
+            }
+        }
+
+        Ok(expanded_ops).wrap_with_cost(cost)
+    }
+
+    /// Pushes the ops a move is made of. An element that isn't a tree is
+    /// inserted at the destination and deleted. A tree keeps the move op,
+    /// which relinks it at the destination when the batch is applied, and is
+    /// deleted along with its subtree, while the references inside it that
+    /// are rewritten are replaced at their new paths.
+    pub(crate) fn push_move_ops(
+        &self,
+        ops: &mut Vec<GroveDbOp>,
+        path: Vec<Vec<u8>>,
+        key: Vec<u8>,
+        to_path: Vec<Vec<u8>>,
+        to_key: Vec<u8>,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<(), Error> {
+        let mut cost = OperationCost::default();
+
+        let mut qualified_path = path.clone();
+        qualified_path.push(key.clone());
+        let mut to_qualified_path = to_path.clone();
+        to_qualified_path.push(to_key.clone());
+
+        if qualified_path.starts_with(&to_qualified_path)
+            || to_qualified_path.starts_with(&qualified_path)
+        {
+            return Err(Error::InvalidBatchOperation(
+                "an element can not be moved into itself or onto one of its ancestors",
+            ))
+            .wrap_with_cost(cost);
+        }
+
+        let element = cost_return_on_error!(
+            &mut cost,
+            self.get_raw(
+                SubtreePath::from(path.as_slice()),
+                key.as_slice(),
+                transaction
+            )
+        );
+
+        if !element.is_tree() {
+            let new_element = cost_return_on_error_no_add!(
+                &cost,
+                rewrite_moved_element(
+                    &element,
+                    &path,
+                    &key,
+                    &to_path,
+                    &to_key,
+                    &qualified_path,
+                    &to_qualified_path
+                )
+            );
+            ops.push(GroveDbOp::insert_op(to_path, to_key, new_element));
+            ops.push(GroveDbOp::delete_op(path, key));
+            return Ok(()).wrap_with_cost(cost);
+        }
+
+        // The subtree is relinked as it is, so its destination must be free
+        if cost_return_on_error!(
+            &mut cost,
+            self.get_raw_optional(
+                SubtreePath::from(to_path.as_slice()),
+                to_key.as_slice(),
+                transaction
+            )
+        )
+        .is_some()
+        {
+            return Err(Error::OverrideNotAllowed(
+                "a subtree can not be moved onto an existing element",
+            ))
+            .wrap_with_cost(cost);
+        }
+
+        let mut subtree_paths = vec![qualified_path.clone()];
+        let mut index = 0;
+        while index < subtree_paths.len() {
+            let children = cost_return_on_error!(
+                &mut cost,
+                self.get_subtree_children(subtree_paths[index].clone(), transaction)
+            );
+            for (child_path, child_key, child) in children {
+                if child.is_tree() {
+                    let mut child_qualified_path = child_path;
+                    child_qualified_path.push(child_key);
+                    subtree_paths.push(child_qualified_path);
+                    continue;
+                }
+
+                // Descendants keep their keys under the new path
+                let mut new_path = to_qualified_path.clone();
+                new_path.extend_from_slice(&child_path[qualified_path.len()..]);
+                let new_child = cost_return_on_error_no_add!(
+                    &cost,
+                    rewrite_moved_element(
+                        &child,
+                        &child_path,
+                        &child_key,
+                        &new_path,
+                        &child_key,
+                        &qualified_path,
+                        &to_qualified_path
+                    )
+                );
+                if new_child != child {
+                    ops.push(GroveDbOp::replace_op(new_path, child_key, new_child));
+                }
+            }
+            index += 1;
+        }
+
+        let is_sum_tree = matches!(element, Element::SumTree(..));
+        ops.push(GroveDbOp::move_op(
+            path.clone(),
+            key.clone(),
+            to_path,
+            to_key,
+        ));
+        ops.push(GroveDbOp::delete_tree_op(path, key, is_sum_tree));
+
+        Ok(()).wrap_with_cost(cost)
+    }
+
+    /// Relinks the subtree moved by a move op kept by `push_move_ops` at its
+    /// destination, copying the nodes of the subtree and its nested subtrees
+    /// under their new prefixes like a fork does and committing them to the
+    /// transaction. The previous values of the records it writes are added to
+    /// `previous_records` if given.
+    pub(crate) fn relink_moved_subtree(
+        &self,
+        op: &GroveDbOp,
+        transaction: &Transaction<S>,
+        previous_records: Option<&mut PreviousRecords>,
+    ) -> CostResult<(), Error> {
+        let mut cost = OperationCost::default();
+
+        let Op::Move { to_path, to_key } = &op.op else {
+            return Err(Error::CorruptedCodeExecution(
+                "only move ops relink subtrees",
+            ))
+            .wrap_with_cost(cost);
+        };
+        let mut qualified_path = op.path.to_path();
+        qualified_path.push(op.key.get_key_clone());
+        let mut to_qualified_path = to_path.clone();
+        to_qualified_path.push(to_key.clone());
+
+        let batch = StorageBatch::new();
+        cost_return_on_error!(
+            &mut cost,
+            self.fork_subtree_on_transaction(
+                SubtreePath::from(qualified_path.as_slice()),
+                SubtreePath::from(to_qualified_path.as_slice()),
+                transaction,
+                &batch
+            )
+        );
+
+        let transaction = self.db.narrow_transaction(transaction);
+        if let Some(previous_records) = previous_records {
+            let records = cost_return_on_error!(
+                &mut cost,
+                self.db
+                    .previous_records(&batch, Some(transaction))
+                    .map_err(Into::into)
+            );
+            previous_records.append(records);
+        }
+
+        self.db
+            .commit_multi_context_batch(batch, Some(transaction))
+            .map_err(Into::into)
+            .add_cost(cost)
+    }
+
+    /// Every element directly under the subtree at a path, together with its
+    /// path and key
+    pub(crate) fn get_subtree_children(
+        &self,
+        path: Vec<Vec<u8>>,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<SubtreeChildren, Error> {
+        let mut cost = OperationCost::default();
+        let mut children = Vec::new();
+
+        let subtree_path: SubtreePath<Vec<u8>> = path.as_slice().into();
+        storage_context_optional_tx!(self.db, subtree_path, None, transaction, storage, {
+            let storage = storage.unwrap_add_cost(&mut cost);
+            let mut raw_iter = Element::iterator(storage.raw_iter()).unwrap_add_cost(&mut cost);
+            while let Some((key, element)) =
+                cost_return_on_error!(&mut cost, raw_iter.next_element())
+            {
+                children.push((path.clone(), key, element));
+            }
+        });
+
+        Ok(children).wrap_with_cost(cost)
+    }
+}
+
+/// The element to write at the new location of a moved element, only
+/// references being rewritten
+fn rewrite_moved_element(
+    element: &Element,
+    path: &[Vec<u8>],
+    key: &[u8],
+    new_path: &[Vec<u8>],
+    new_key: &[u8],
+    moved_qualified_path: &[Vec<u8>],
+    to_qualified_path: &[Vec<u8>],
+) -> Result<Element, Error> {
+    let Element::Reference(reference_path_type, max_hop, flags) = element else {
+        return Ok(element.clone());
+    };
+    let reference_path_type = rewrite_moved_reference(
+        reference_path_type,
+        path,
+        key,
+        new_path,
+        new_key,
+        moved_qualified_path,
+        to_qualified_path,
+    )?;
+    Ok(Element::Reference(
+        reference_path_type,
+        *max_hop,
+        flags.clone(),
+    ))
+}
+
+/// Keeps a reference of a moved element pointing at the same element, which
+/// itself may have been moved. Relative references that would resolve
+/// elsewhere from the new location become absolute references.
+fn rewrite_moved_reference(
+    reference_path_type: &ReferencePathType,
+    path: &[Vec<u8>],
+    key: &[u8],
+    new_path: &[Vec<u8>],
+    new_key: &[u8],
+    moved_qualified_path: &[Vec<u8>],
+    to_qualified_path: &[Vec<u8>],
+) -> Result<ReferencePathType, Error> {
+    let mut reference_qualified_path = path.to_vec();
+    reference_qualified_path.push(key.to_vec());
+    let mut target = path_from_reference_qualified_path_type(
+        reference_path_type.clone(),
+        &reference_qualified_path,
+    )?;
+    if target.starts_with(moved_qualified_path) {
+        let rest = target.split_off(moved_qualified_path.len());
+        target = to_qualified_path.to_vec();
+        target.extend(rest);
+    }
+
+    if let ReferencePathType::AbsolutePathReference(_) = reference_path_type {
+        return Ok(ReferencePathType::AbsolutePathReference(target));
+    }
+
+    let mut new_reference_qualified_path = new_path.to_vec();
+    new_reference_qualified_path.push(new_key.to_vec());
+    match path_from_reference_qualified_path_type(
+        reference_path_type.clone(),
+        &new_reference_qualified_path,
+    ) {
+        Ok(new_target) if new_target == target => Ok(reference_path_type.clone()),
+        _ => Ok(ReferencePathType::AbsolutePathReference(target)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        batch::GroveDbOp,
+        reference_path::ReferencePathType,
+        tests::{make_test_grovedb, ANOTHER_TEST_LEAF, TEST_LEAF},
+        Element, Error, GroveDb,
+    };
+
+    #[test]
+    fn test_move_item() {
+        let db = make_test_grovedb();
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            b"key",
+            Element::new_item(b"value".to_vec()),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+
+        db.move_element(
+            [TEST_LEAF].as_ref(),
+            b"key",
+            [ANOTHER_TEST_LEAF].as_ref(),
+            b"new_key",
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("expected to move item");
+
+        assert!(matches!(
+            db.get([TEST_LEAF].as_ref(), b"key", None).unwrap(),
+            Err(Error::PathKeyNotFound(_))
+        ));
+        assert_eq!(
+            db.get([ANOTHER_TEST_LEAF].as_ref(), b"new_key", None)
+                .unwrap()
+                .expect("expected moved item"),
+            Element::new_item(b"value".to_vec())
+        );
+        assert!(db.verify_grovedb().is_empty());
+    }
+
+    #[test]
+    fn test_move_subtree_with_references() {
+        let db = make_test_grovedb();
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            b"tree",
+            Element::empty_tree(),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+        db.insert(
+            [TEST_LEAF, b"tree"].as_ref(),
+            b"sums",
+            Element::empty_sum_tree(),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+        db.insert(
+            [TEST_LEAF, b"tree", b"sums"].as_ref(),
+            b"a",
+            Element::new_sum_item(3),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+        db.insert(
+            [TEST_LEAF, b"tree"].as_ref(),
+            b"item",
+            Element::new_item(b"value".to_vec()),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+        db.insert(
+            [TEST_LEAF, b"tree"].as_ref(),
+            b"sibling_ref",
+            Element::new_reference(ReferencePathType::SiblingReference(b"item".to_vec())),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+        db.insert(
+            [TEST_LEAF, b"tree"].as_ref(),
+            b"absolute_ref",
+            Element::new_reference(ReferencePathType::AbsolutePathReference(vec![
+                TEST_LEAF.to_vec(),
+                b"tree".to_vec(),
+                b"item".to_vec(),
+            ])),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+
+        let op = GroveDbOp::move_op(
+            vec![TEST_LEAF.to_vec()],
+            b"tree".to_vec(),
+            vec![ANOTHER_TEST_LEAF.to_vec()],
+            b"moved".to_vec(),
+        );
+        db.apply_batch(vec![op], None, None)
+            .unwrap()
+            .expect("expected to move subtree");
+
+        assert!(matches!(
+            db.get([TEST_LEAF].as_ref(), b"tree", None).unwrap(),
+            Err(Error::PathKeyNotFound(_))
+        ));
+        assert_eq!(
+            db.get([ANOTHER_TEST_LEAF, b"moved"].as_ref(), b"sibling_ref", None)
+                .unwrap()
+                .expect("expected reference to resolve"),
+            Element::new_item(b"value".to_vec())
+        );
+        assert_eq!(
+            db.get_raw(
+                [ANOTHER_TEST_LEAF, b"moved"].as_ref().into(),
+                b"absolute_ref",
+                None
+            )
+            .unwrap()
+            .expect("expected reference"),
+            Element::new_reference(ReferencePathType::AbsolutePathReference(vec![
+                ANOTHER_TEST_LEAF.to_vec(),
+                b"moved".to_vec(),
+                b"item".to_vec(),
+            ]))
+        );
+        assert_eq!(
+            db.get([ANOTHER_TEST_LEAF, b"moved"].as_ref(), b"sums", None)
+                .unwrap()
+                .expect("expected sum tree")
+                .sum_value_or_default(),
+            3
+        );
+        assert!(db.verify_grovedb().is_empty());
+
+        assert!(matches!(
+            db.move_element(
+                [ANOTHER_TEST_LEAF].as_ref(),
+                b"moved",
+                [ANOTHER_TEST_LEAF, b"moved"].as_ref(),
+                b"inner",
+                None,
+                None,
+            )
+            .unwrap(),
+            Err(Error::InvalidBatchOperation(_))
+        ));
+    }
+
+    fn insert_subtree_with_nested_subtree(db: &GroveDb) {
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            b"tree",
+            Element::empty_tree(),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+        db.insert(
+            [TEST_LEAF, b"tree"].as_ref(),
+            b"inner",
+            Element::empty_tree(),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful insert");
+        for key in [b"a", b"b", b"c"] {
+            db.insert(
+                [TEST_LEAF, b"tree", b"inner"].as_ref(),
+                key,
+                Element::new_item(key.to_vec()),
+                None,
+                None,
+            )
+            .unwrap()
+            .expect("successful insert");
+        }
+    }
+
+    #[test]
+    fn test_move_subtree_relinks_it_and_removes_source_records() {
+        let db = make_test_grovedb();
+        insert_subtree_with_nested_subtree(&db);
+        let element = db
+            .get_raw([TEST_LEAF].as_ref().into(), b"tree", None)
+            .unwrap()
+            .expect("expected subtree");
+        let inner_element = db
+            .get_raw([TEST_LEAF, b"tree"].as_ref().into(), b"inner", None)
+            .unwrap()
+            .expect("expected nested subtree");
+
+        db.move_element(
+            [TEST_LEAF].as_ref(),
+            b"tree",
+            [ANOTHER_TEST_LEAF].as_ref(),
+            b"moved",
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("expected to move subtree");
+
+        // The tree elements keep the root keys of their Merk trees
+        assert_eq!(
+            db.get_raw([ANOTHER_TEST_LEAF].as_ref().into(), b"moved", None)
+                .unwrap()
+                .expect("expected moved subtree"),
+            element
+        );
+        assert_eq!(
+            db.get_raw(
+                [ANOTHER_TEST_LEAF, b"moved"].as_ref().into(),
+                b"inner",
+                None
+            )
+            .unwrap()
+            .expect("expected moved nested subtree"),
+            inner_element
+        );
+        assert_eq!(
+            db.get([ANOTHER_TEST_LEAF, b"moved", b"inner"].as_ref(), b"b", None)
+                .unwrap()
+                .expect("expected moved item"),
+            Element::new_item(b"b".to_vec())
+        );
+        assert!(db.verify_grovedb().is_empty());
+
+        for path in [
+            vec![TEST_LEAF.to_vec(), b"tree".to_vec()],
+            vec![TEST_LEAF.to_vec(), b"tree".to_vec(), b"inner".to_vec()],
+        ] {
+            assert!(db
+                .get_subtree_children(path, None)
+                .unwrap()
+                .expect("expected to read the old prefix")
+                .is_empty());
+        }
+    }
+
+    #[test]
+    fn test_move_subtree_undo() {
+        let db = make_test_grovedb();
+        insert_subtree_with_nested_subtree(&db);
+        let root_hash = db.root_hash(None).unwrap().expect("expected root hash");
+
+        let undo_batch = db
+            .apply_batch_with_undo(
+                vec![GroveDbOp::move_op(
+                    vec![TEST_LEAF.to_vec()],
+                    b"tree".to_vec(),
+                    vec![ANOTHER_TEST_LEAF.to_vec()],
+                    b"moved".to_vec(),
+                )],
+                None,
+                None,
+            )
+            .unwrap()
+            .expect("expected to move subtree");
+        db.apply_undo_batch(undo_batch, None)
+            .unwrap()
+            .expect("expected to undo the move");
+
+        assert_eq!(
+            db.root_hash(None).unwrap().expect("expected root hash"),
+            root_hash
+        );
+        assert!(matches!(
+            db.get_raw([ANOTHER_TEST_LEAF].as_ref().into(), b"moved", None)
+                .unwrap(),
+            Err(Error::PathKeyNotFound(_))
+        ));
+        assert!(db
+            .get_subtree_children(
+                vec![
+                    ANOTHER_TEST_LEAF.to_vec(),
+                    b"moved".to_vec(),
+                    b"inner".to_vec()
+                ],
+                None
+            )
+            .unwrap()
+            .expect("expected to read the new prefix")
+            .is_empty());
+        assert!(db.verify_grovedb().is_empty());
+    }
+
+    #[test]
+    fn test_failed_subtree_move_is_rolled_back() {
+        let db = make_test_grovedb();
+        insert_subtree_with_nested_subtree(&db);
+        let tx = db.start_transaction();
+        let root_hash = db
+            .root_hash(Some(&tx))
+            .unwrap()
+            .expect("expected root hash");
+
+        let ops = vec![
+            GroveDbOp::move_op(
+                vec![TEST_LEAF.to_vec()],
+                b"tree".to_vec(),
+                vec![ANOTHER_TEST_LEAF.to_vec()],
+                b"moved".to_vec(),
+            ),
+            GroveDbOp::insert_op(
+                vec![b"missing".to_vec()],
+                b"key".to_vec(),
+                Element::new_item(b"value".to_vec()),
+            ),
+        ];
+        assert!(db.apply_batch(ops, None, Some(&tx)).unwrap().is_err());
+
+        assert_eq!(
+            db.root_hash(Some(&tx))
+                .unwrap()
+                .expect("expected root hash"),
+            root_hash
+        );
+        assert!(db
+            .get_subtree_children(
+                vec![
+                    ANOTHER_TEST_LEAF.to_vec(),
+                    b"moved".to_vec(),
+                    b"inner".to_vec()
+                ],
+                Some(&tx)
+            )
+            .unwrap()
+            .expect("expected to read the new prefix")
+            .is_empty());
+
+        db.insert(
+            [ANOTHER_TEST_LEAF].as_ref(),
+            b"moved",
+            Element::new_item(b"value".to_vec()),
+            None,
+            Some(&tx),
+        )
+        .unwrap()
+        .expect("successful insert");
+        assert!(matches!(
+            db.move_element(
+                [TEST_LEAF].as_ref(),
+                b"tree",
+                [ANOTHER_TEST_LEAF].as_ref(),
+                b"moved",
+                None,
+                Some(&tx),
+            )
+            .unwrap(),
+            Err(Error::OverrideNotAllowed(_))
+        ));
+    }
+}
@@ -32,6 +32,9 @@
 //  that supports multiple implementations for verbose and non-verbose
 // generation
 
+#[cfg(feature = "full")]
+use std::{collections::VecDeque, io::Write};
+
 use grovedb_costs::cost_return_on_error_default;
 #[cfg(feature = "full")]
 use grovedb_costs::{
@@ -39,23 +42,27 @@ use grovedb_costs::{
 };
 #[cfg(feature = "full")]
 use grovedb_merk::{
-    proofs::{encode_into, Node, Op},
+    proofs::{encode_into, query::SubqueryBranch, Node, Op},
     tree::value_hash,
     KVIterator, Merk, ProofWithoutEncodingResult,
 };
 use grovedb_path::SubtreePath;
+use grovedb_storage::CovariantStorage;
 #[cfg(feature = "full")]
 use grovedb_storage::StorageContext;
+#[cfg(feature = "full")]
+use indexmap::IndexMap;
 
 #[cfg(feature = "full")]
 use crate::element::helpers::raw_decode;
 #[cfg(feature = "full")]
 use crate::{
     operations::proof::util::{
-        reduce_limit_and_offset_by, write_to_vec, ProofTokenType, EMPTY_TREE_HASH,
+        reduce_limit_and_offset_by, write_subtree_chunks, write_to_vec, ProofContinuation,
+        ProofTokenType, ProofWriteResult, EMPTY_TREE_HASH,
     },
     reference_path::path_from_reference_path_type,
-    Element, Error, GroveDb, PathQuery, Query,
+    Element, Error, GroveDb, PathQuery, Query, QueryItem, SizedQuery, Transaction, TransactionArg,
 };
 use crate::{
     operations::proof::util::{write_slice_of_slice_to_slice, write_slice_to_vec},
@@ -66,7 +73,196 @@ use crate::{
 type LimitOffset = (Option<u16>, Option<u16>);
 
 #[cfg(feature = "full")]
-impl GroveDb {
+/// Destination of a proof that is streamed layer by layer under a byte budget
+struct ProofStream<'w> {
+    writer: &'w mut dyn Write,
+    /// Bytes left for query layers, the path proof is already accounted for
+    remaining_bytes: usize,
+    bytes_written: usize,
+    /// Layers that don't prove a result yet. A verifier with a limit stops
+    /// reading after its last result, so these are only written once a
+    /// following layer proves one.
+    pending: Vec<u8>,
+    /// Limit and offset as of the last layer that was written
+    boundary_limit_offset: LimitOffset,
+    /// Keys of the subtrees proof generation descended into from the queried
+    /// subtree
+    descent: Vec<Vec<u8>>,
+    /// Keys leading to the last result that was written: the descent as of the
+    /// last layer that was written, followed by the last key proven in that
+    /// layer if it proves only part of the results of its subtree
+    boundary_descent: Vec<Vec<u8>>,
+    /// Set once a layer didn't fit into the remaining budget
+    exhausted: bool,
+}
+
+#[cfg(feature = "full")]
+impl<'w> ProofStream<'w> {
+    /// Bytes left for the next layer
+    fn available_bytes(&self) -> usize {
+        self.remaining_bytes - self.pending.len()
+    }
+
+    /// Checks if a layer of the given size fits into the remaining budget
+    fn fits(&self, layer_len: usize) -> bool {
+        !self.exhausted && layer_len <= self.available_bytes()
+    }
+
+    /// Stages the layer in `layer` if it fits into the remaining budget,
+    /// otherwise discards it together with the staged layers and marks the
+    /// stream as exhausted. Staged layers are written as soon as one of them
+    /// proves a result. Returns whether the layer was staged.
+    fn flush_layer(
+        &mut self,
+        layer: &mut Vec<u8>,
+        limit_offset: LimitOffset,
+    ) -> Result<bool, Error> {
+        if !self.fits(layer.len()) {
+            layer.clear();
+            self.pending.clear();
+            self.exhausted = true;
+            return Ok(false);
+        }
+        self.pending.append(layer);
+        if limit_offset.0 < self.boundary_limit_offset.0 {
+            self.write_pending()?;
+            self.boundary_limit_offset = limit_offset;
+            self.boundary_descent = self.descent.clone();
+        }
+        Ok(true)
+    }
+
+    /// Writes the staged layers
+    fn write_pending(&mut self) -> Result<(), Error> {
+        write_to_vec(&mut self.writer, &self.pending)?;
+        self.remaining_bytes -= self.pending.len();
+        self.bytes_written += self.pending.len();
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "full")]
+/// Parameters of the merk proof of a single layer
+struct MerkProofLayer<'k> {
+    limit_offset: LimitOffset,
+    proof_token_type: ProofTokenType,
+    /// Key of the subtree, written ahead of the proof if it is verbose
+    key: &'k [u8],
+    is_verbose: bool,
+}
+
+#[cfg(feature = "full")]
+/// State of a query proof carried through the subquery traversal
+struct ProofGeneration<'p, 'w> {
+    /// Layers generated so far, or the layer being staged if the proof is
+    /// being streamed
+    proofs: &'p mut Vec<u8>,
+    limit: Option<u16>,
+    offset: Option<u16>,
+    is_verbose: bool,
+    stream: Option<ProofStream<'w>>,
+}
+
+#[cfg(feature = "full")]
+impl<'p, 'w> ProofGeneration<'p, 'w> {
+    /// Flushes the layer staged in `proofs` if the proof is being streamed.
+    /// Returns whether proof generation can go on.
+    fn flush_layer(&mut self, limit_offset: LimitOffset) -> Result<bool, Error> {
+        match &mut self.stream {
+            Some(stream) => stream.flush_layer(self.proofs, limit_offset),
+            None => Ok(true),
+        }
+    }
+
+    /// Checks if a streamed proof ran out of budget
+    fn is_exhausted(&self) -> bool {
+        matches!(&self.stream, Some(stream) if stream.exhausted)
+    }
+
+    /// Parameters of the merk proof of the subtree at `path`
+    fn layer<'k>(
+        &self,
+        path: &[&'k [u8]],
+        limit_offset: LimitOffset,
+        proof_token_type: ProofTokenType,
+    ) -> MerkProofLayer<'k> {
+        MerkProofLayer {
+            limit_offset,
+            proof_token_type,
+            key: path.last().copied().unwrap_or_default(),
+            is_verbose: self.is_verbose,
+        }
+    }
+}
+
+#[cfg(feature = "full")]
+/// Narrows `query` to what is left to prove after a streamed proof stopped
+/// at `descent`, the keys leading from the queried subtree to the last proven
+/// result. Returns `None` if nothing is left.
+fn resume_query(query: &Query, descent: &[Vec<u8>]) -> Option<Query> {
+    let Some((key, rest)) = descent.split_first() else {
+        return Some(query.clone());
+    };
+
+    let (resume_at_key, resumed_branch) = if rest.is_empty() {
+        (false, None)
+    } else {
+        let (subquery_path, subquery) = Element::subquery_paths_and_value_for_sized_query(
+            &SizedQuery::new(query.clone(), None, None),
+            key,
+        );
+        match subquery {
+            Some(subquery) => match resume_query(&subquery, rest) {
+                Some(resumed_subquery) => (
+                    true,
+                    Some(SubqueryBranch {
+                        subquery_path,
+                        subquery: Some(Box::new(resumed_subquery)),
+                    }),
+                ),
+                None => (false, None),
+            },
+            // without a subquery the subtree below the subquery path is queried for
+            // a single key, which is proven by now
+            None => (false, None),
+        }
+    };
+
+    let resume_item = match (query.left_to_right, resume_at_key) {
+        (true, true) => QueryItem::RangeFrom(key.clone()..),
+        (true, false) => QueryItem::RangeAfter(key.clone()..),
+        (false, true) => QueryItem::RangeToInclusive(..=key.clone()),
+        (false, false) => QueryItem::RangeTo(..key.clone()),
+    };
+
+    let mut resumed_query = query.clone();
+    resumed_query.items = query
+        .items
+        .iter()
+        .filter_map(|item| item.intersect(&resume_item).in_both)
+        .collect();
+    if resumed_query.items.is_empty() {
+        return None;
+    }
+
+    if let Some(resumed_branch) = resumed_branch {
+        let mut conditional_subquery_branches = IndexMap::new();
+        conditional_subquery_branches.insert(QueryItem::Key(key.clone()), resumed_branch);
+        // the narrowed branch comes first so that it takes precedence
+        for (item, branch) in query.conditional_subquery_branches.iter().flatten() {
+            conditional_subquery_branches
+                .entry(item.clone())
+                .or_insert_with(|| branch.clone());
+        }
+        resumed_query.conditional_subquery_branches = Some(conditional_subquery_branches);
+    }
+
+    Some(resumed_query)
+}
+
+#[cfg(feature = "full")]
+impl<S: CovariantStorage> GroveDb<S> {
     /// Prove one or more path queries.
     /// If we more than one path query, we merge into a single path query before
     /// proving.
@@ -96,7 +292,18 @@ impl GroveDb {
     /// Proofs generated with this can only be verified by the path query used
     /// to generate them.
     pub fn prove_query(&self, query: &PathQuery) -> CostResult<Vec<u8>, Error> {
-        self.prove_internal(query, false)
+        self.prove_internal(query, false, None)
+    }
+
+    /// Generate a minimalistic proof for a given path query, reading the
+    /// state seen by the transaction or snapshot. Without one the proof is
+    /// generated from a snapshot taken at the start.
+    pub fn prove_query_with_transaction(
+        &self,
+        query: &PathQuery,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<Vec<u8>, Error> {
+        self.prove_internal(query, false, transaction)
     }
 
     /// Generate a verbose proof for a given path query
@@ -107,23 +314,324 @@ impl GroveDb {
         //      when using a path query that has a limit and offset value,
         //      to get the expected behaviour, you need to know exactly
         //      how the proving internals work and how your state looks.
-        self.prove_internal(query, true)
+        self.prove_internal(query, true, None)
+    }
+
+    /// Generate a proof of the status of the subtree at the given path: whether
+    /// there is no tree, an empty tree or a tree with elements at the path.
+    /// Verify it with `GroveDb::verify_subtree_status`.
+    pub fn prove_subtree_status(&self, path: &[Vec<u8>]) -> CostResult<Vec<u8>, Error> {
+        // a query without items proves nothing but the root hash of the subtree,
+        // or that it is empty or absent
+        self.prove_query(&PathQuery::new_unsized(path.to_vec(), Query::new()))
+    }
+
+    /// Generate a proof of the whole subtree at the given path, including all
+    /// of its nested subtrees, that can be verified offline against the root
+    /// hash with `GroveDb::verify_subtree_audit`.
+    ///
+    /// Every subtree is proven by the chunks replication would send for it,
+    /// in breadth-first order, together with the values its references point
+    /// to. The proof of the path up to the root comes last.
+    pub fn prove_subtree_audit(&self, path: &[Vec<u8>]) -> CostResult<Vec<u8>, Error> {
+        let mut cost = OperationCost::default();
+        let snapshot = self.db.start_snapshot_transaction();
+        let transaction = &snapshot;
+
+        let mut proof_result =
+            cost_return_on_error_default!(prepend_version_to_bytes(vec![], PROOF_VERSION));
+
+        let path_slices = path.iter().map(|x| x.as_slice()).collect::<Vec<_>>();
+
+        cost_return_on_error!(
+            &mut cost,
+            self.check_subtree_exists_path_not_found(
+                path_slices.as_slice().into(),
+                Some(transaction)
+            )
+        );
+
+        let mut chunk_producer =
+            self.chunks_with_transaction(self.db.narrow_transaction(transaction));
+        let mut subtree_paths = VecDeque::from([path.to_vec()]);
+        while let Some(subtree_path) = subtree_paths.pop_front() {
+            let mut chunks = vec![];
+            let mut referenced_values = vec![];
+
+            loop {
+                let ops = cost_return_on_error_no_add!(
+                    &cost,
+                    chunk_producer
+                        .get_chunk(subtree_path.iter().map(|k| k.as_slice()), chunks.len())
+                );
+                if ops.is_empty() {
+                    // only the audited subtree can be empty, nested empty trees are not
+                    // visited
+                    break;
+                }
+
+                for op in ops.iter() {
+                    if let Op::Push(Node::KVValueHashFeatureType(key, value, ..)) = op {
+                        match cost_return_on_error_no_add!(&cost, Element::deserialize(value)) {
+                            Element::Tree(Some(_), _) | Element::SumTree(Some(_), ..) => {
+                                let mut child_path = subtree_path.clone();
+                                child_path.push(key.clone());
+                                subtree_paths.push_back(child_path);
+                            }
+                            Element::Reference(reference_path, ..) => {
+                                let absolute_path = cost_return_on_error_no_add!(
+                                    &cost,
+                                    path_from_reference_path_type(
+                                        reference_path,
+                                        &subtree_path,
+                                        Some(key.as_slice())
+                                    )
+                                );
+                                let referenced_elem = cost_return_on_error!(
+                                    &mut cost,
+                                    self.follow_reference(
+                                        absolute_path.as_slice().into(),
+                                        true,
+                                        Some(transaction)
+                                    )
+                                );
+                                referenced_values.push((
+                                    key.clone(),
+                                    cost_return_on_error_no_add!(
+                                        &cost,
+                                        referenced_elem.serialize()
+                                    ),
+                                ));
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+
+                let mut chunk_bytes = Vec::with_capacity(128);
+                encode_into(ops.iter(), &mut chunk_bytes);
+                chunks.push(chunk_bytes);
+
+                if chunks.len() == chunk_producer.chunks_in_current_producer() {
+                    break;
+                }
+            }
+
+            cost_return_on_error_no_add!(
+                &cost,
+                write_subtree_chunks(&mut proof_result, &chunks, &referenced_values)
+            );
+        }
+
+        cost_return_on_error!(
+            &mut cost,
+            self.prove_path(&mut proof_result, path_slices, false, transaction)
+        );
+
+        Ok(proof_result).wrap_with_cost(cost)
+    }
+
+    /// Generate a minimalistic proof for a given path query and stream it to
+    /// a writer layer by layer, writing at most `max_bytes`.
+    ///
+    /// If the budget is reached, generation stops after the last layer that
+    /// fits and the proof is closed with the path to the root. What was
+    /// written then verifies against `ProofContinuation::proven_query` and the
+    /// rest of the results can be proven with
+    /// `ProofContinuation::remaining_query`.
+    /// Queries without a limit are proven as if limited to `u16::MAX`
+    /// results.
+    pub fn prove_query_to_writer<W: Write>(
+        &self,
+        query: &PathQuery,
+        writer: &mut W,
+        max_bytes: usize,
+    ) -> CostResult<ProofWriteResult, Error> {
+        let mut cost = OperationCost::default();
+        let snapshot = self.db.start_snapshot_transaction();
+        let transaction = &snapshot;
+
+        let version_bytes =
+            cost_return_on_error_default!(prepend_version_to_bytes(vec![], PROOF_VERSION));
+
+        let path_slices = query.path.iter().map(|x| x.as_slice()).collect::<Vec<_>>();
+
+        let subtree_exists = self
+            .check_subtree_exists_path_not_found(path_slices.as_slice().into(), Some(transaction))
+            .unwrap_add_cost(&mut cost);
+
+        if subtree_exists.is_err() {
+            let mut proof_result = version_bytes;
+            cost_return_on_error!(
+                &mut cost,
+                self.generate_and_store_absent_path_proof(
+                    &path_slices,
+                    &mut proof_result,
+                    false,
+                    transaction
+                )
+            );
+            if proof_result.len() > max_bytes {
+                return Err(Error::InvalidInput(
+                    "max_bytes is too small to hold the absent path proof",
+                ))
+                .wrap_with_cost(cost);
+            }
+            cost_return_on_error_no_add!(&cost, write_to_vec(writer, &proof_result));
+            return Ok(ProofWriteResult {
+                bytes_written: proof_result.len(),
+                continuation: None,
+            })
+            .wrap_with_cost(cost);
+        }
+
+        // the path proof closes the proof wherever generation stops, so it is
+        // generated first to reserve room for it
+        let mut path_proof = vec![];
+        cost_return_on_error!(
+            &mut cost,
+            self.prove_path(&mut path_proof, path_slices.clone(), false, transaction)
+        );
+
+        let reserved_bytes = version_bytes.len() + path_proof.len();
+        if reserved_bytes > max_bytes {
+            return Err(Error::InvalidInput(
+                "max_bytes is too small to hold the path proof",
+            ))
+            .wrap_with_cost(cost);
+        }
+
+        cost_return_on_error_no_add!(&cost, write_to_vec(writer, &version_bytes));
+
+        let effective_limit = query.query.limit.unwrap_or(u16::MAX);
+        let limit = Some(effective_limit);
+        let offset = query.query.offset;
+
+        let mut generation = ProofGeneration {
+            proofs: &mut vec![],
+            limit,
+            offset,
+            is_verbose: false,
+            stream: Some(ProofStream {
+                writer,
+                remaining_bytes: max_bytes - reserved_bytes,
+                bytes_written: version_bytes.len(),
+                pending: vec![],
+                boundary_limit_offset: (limit, offset),
+                descent: vec![],
+                boundary_descent: vec![],
+                exhausted: false,
+            }),
+        };
+
+        cost_return_on_error!(
+            &mut cost,
+            self.prove_subqueries(&mut generation, path_slices, query, true, transaction)
+        );
+
+        let limit = generation.limit;
+        let mut stream = generation.stream.expect("stream is set above");
+        let exhausted = stream.exhausted;
+        let remaining_limit = if exhausted {
+            stream.boundary_limit_offset.0
+        } else {
+            cost_return_on_error_no_add!(&cost, stream.write_pending());
+            limit
+        };
+        let bytes_written = stream.bytes_written + path_proof.len();
+        let mut writer = &mut *stream.writer;
+        cost_return_on_error_no_add!(&cost, write_to_vec(&mut writer, &path_proof));
+
+        let results_proven = effective_limit - remaining_limit.unwrap_or_default();
+        let limit_reached_without_query_limit =
+            query.query.limit.is_none() && remaining_limit == Some(0);
+
+        let continuation = if exhausted || limit_reached_without_query_limit {
+            if results_proven == 0 {
+                return Err(Error::InvalidInput(
+                    "max_bytes is too small to prove a single result",
+                ))
+                .wrap_with_cost(cost);
+            }
+            let mut proven_query = query.clone();
+            proven_query.query.limit = Some(results_proven);
+
+            let mut remaining_query = query.clone();
+            remaining_query.query.limit = query.query.limit.map(|limit| limit - results_proven);
+
+            let resume_offset = if exhausted && !stream.boundary_descent.is_empty() {
+                // resume right after the last proven result rather than skipping
+                // everything proven so far with the offset, which would have to be
+                // proven again
+                remaining_query.query.query =
+                    resume_query(&query.query.query, &stream.boundary_descent).unwrap_or_else(
+                        || {
+                            let mut nothing_left = query.query.query.clone();
+                            nothing_left.items.clear();
+                            nothing_left
+                        },
+                    );
+                Some(stream.boundary_limit_offset.1.unwrap_or_default())
+            } else {
+                query
+                    .query
+                    .offset
+                    .unwrap_or_default()
+                    .checked_add(results_proven)
+            };
+            remaining_query.query.offset = Some(cost_return_on_error_no_add!(
+                &cost,
+                resume_offset.ok_or(Error::InvalidInput(
+                    "continuation offset does not fit into u16"
+                ))
+            ));
+
+            Some(ProofContinuation {
+                results_proven,
+                proven_query,
+                remaining_query,
+            })
+        } else {
+            None
+        };
+
+        Ok(ProofWriteResult {
+            bytes_written,
+            continuation,
+        })
+        .wrap_with_cost(cost)
     }
 
     /// Generates a verbose or non verbose proof based on a bool
-    fn prove_internal(&self, query: &PathQuery, is_verbose: bool) -> CostResult<Vec<u8>, Error> {
+    fn prove_internal(
+        &self,
+        query: &PathQuery,
+        is_verbose: bool,
+        transaction: TransactionArg<S>,
+    ) -> CostResult<Vec<u8>, Error> {
         let mut cost = OperationCost::default();
 
+        // every layer must be proven from the same state for the proof to
+        // verify against one root hash
+        let snapshot;
+        let transaction = match transaction {
+            Some(transaction) => self.db.narrow_transaction(transaction),
+            None => {
+                snapshot = self.db.start_snapshot_transaction();
+                &snapshot
+            }
+        };
+
         let mut proof_result =
             cost_return_on_error_default!(prepend_version_to_bytes(vec![], PROOF_VERSION));
 
-        let mut limit: Option<u16> = query.query.limit;
-        let mut offset: Option<u16> = query.query.offset;
+        let limit: Option<u16> = query.query.limit;
+        let offset: Option<u16> = query.query.offset;
 
         let path_slices = query.path.iter().map(|x| x.as_slice()).collect::<Vec<_>>();
 
         let subtree_exists = self
-            .check_subtree_exists_path_not_found(path_slices.as_slice().into(), None)
+            .check_subtree_exists_path_not_found(path_slices.as_slice().into(), Some(transaction))
             .unwrap_add_cost(&mut cost);
 
         // if the subtree at the given path doesn't exists, prove that this path
@@ -139,7 +647,8 @@ impl GroveDb {
                     self.generate_and_store_absent_path_proof(
                         &path_slices,
                         &mut proof_result,
-                        is_verbose
+                        is_verbose,
+                        transaction
                     )
                 );
                 // return the absence proof no need to continue proof generation
@@ -152,53 +661,61 @@ impl GroveDb {
         if is_verbose {
             cost_return_on_error!(
                 &mut cost,
-                Self::generate_and_store_path_proof(path_slices.clone(), &mut proof_result)
+                GroveDb::generate_and_store_path_proof(path_slices.clone(), &mut proof_result)
             );
         }
 
         cost_return_on_error!(
             &mut cost,
             self.prove_subqueries(
-                &mut proof_result,
+                &mut ProofGeneration {
+                    proofs: &mut proof_result,
+                    limit,
+                    offset,
+                    is_verbose,
+                    stream: None,
+                },
                 path_slices.clone(),
                 query,
-                &mut limit,
-                &mut offset,
                 true,
-                is_verbose
+                transaction
             )
         );
         cost_return_on_error!(
             &mut cost,
-            self.prove_path(&mut proof_result, path_slices, is_verbose)
+            self.prove_path(&mut proof_result, path_slices, is_verbose, transaction)
         );
 
         Ok(proof_result).wrap_with_cost(cost)
     }
 
     /// Perform a pre-order traversal of the tree based on the provided
-    /// subqueries
+    /// subqueries.
+    /// When a stream is given, every layer is handed to it as soon as it is
+    /// generated and the traversal stops at the first layer that doesn't fit.
     fn prove_subqueries(
         &self,
-        proofs: &mut Vec<u8>,
+        generation: &mut ProofGeneration,
         path: Vec<&[u8]>,
         query: &PathQuery,
-        current_limit: &mut Option<u16>,
-        current_offset: &mut Option<u16>,
         is_first_call: bool,
-        is_verbose: bool,
+        transaction: &Transaction<S>,
     ) -> CostResult<(), Error> {
         let mut cost = OperationCost::default();
         let mut to_add_to_result_set: u16 = 0;
 
         let subtree = cost_return_on_error!(
             &mut cost,
-            self.open_non_transactional_merk_at_path(path.as_slice().into(), None)
+            self.open_transactional_merk_at_path(path.as_slice().into(), transaction, None)
         );
         if subtree.root_hash().unwrap_add_cost(&mut cost) == EMPTY_TREE_HASH {
             cost_return_on_error_no_add!(
                 &cost,
-                write_to_vec(proofs, &[ProofTokenType::EmptyTree.into()])
+                write_to_vec(generation.proofs, &[ProofTokenType::EmptyTree.into()])
+            );
+            cost_return_on_error_no_add!(
+                &cost,
+                generation.flush_layer((generation.limit, generation.offset))
             );
             return Ok(()).wrap_with_cost(cost);
         }
@@ -212,13 +729,19 @@ impl GroveDb {
                         &path.as_slice().into(),
                         &subtree,
                         &query.query.query,
-                        (*current_limit, *current_offset),
-                        ProofTokenType::SizedMerk,
-                        proofs,
-                        is_verbose,
-                        path.iter().last().unwrap_or(&(&[][..]))
+                        generation.layer(
+                            &path,
+                            (generation.limit, generation.offset),
+                            ProofTokenType::SizedMerk
+                        ),
+                        generation.proofs,
+                        transaction
                     )
                 );
+                cost_return_on_error_no_add!(
+                    &cost,
+                    generation.flush_layer((generation.limit, generation.offset))
+                );
             }
             return Ok(()).wrap_with_cost(cost);
         }
@@ -240,7 +763,11 @@ impl GroveDb {
                     if subquery_value.is_none() && subquery_path.is_none() {
                         // this element should be added to the result set
                         // hence we have to update the limit and offset value
-                        reduce_limit_and_offset_by(current_limit, current_offset, 1);
+                        reduce_limit_and_offset_by(
+                            &mut generation.limit,
+                            &mut generation.offset,
+                            1,
+                        );
                         continue;
                     }
 
@@ -257,13 +784,17 @@ impl GroveDb {
                                 &path.as_slice().into(),
                                 &subtree,
                                 &query.query.query,
-                                (None, None),
-                                ProofTokenType::Merk,
-                                proofs,
-                                is_verbose,
-                                path.iter().last().unwrap_or(&Default::default())
+                                generation.layer(&path, (None, None), ProofTokenType::Merk),
+                                generation.proofs,
+                                transaction
                             )
                         );
+                        if !cost_return_on_error_no_add!(
+                            &cost,
+                            generation.flush_layer((generation.limit, generation.offset))
+                        ) {
+                            return Ok(()).wrap_with_cost(cost);
+                        }
                     }
 
                     let mut new_path = path.clone();
@@ -276,9 +807,10 @@ impl GroveDb {
                             for subkey in subquery_path.iter() {
                                 let inner_subtree = cost_return_on_error!(
                                     &mut cost,
-                                    self.open_non_transactional_merk_at_path(
+                                    self.open_transactional_merk_at_path(
                                         new_path.as_slice().into(),
-                                        None,
+                                        transaction,
+                                        None
                                     )
                                 );
 
@@ -291,20 +823,28 @@ impl GroveDb {
                                         &new_path.as_slice().into(),
                                         &inner_subtree,
                                         &key_as_query,
-                                        (None, None),
-                                        ProofTokenType::Merk,
-                                        proofs,
-                                        is_verbose,
-                                        new_path.iter().last().unwrap_or(&Default::default())
+                                        generation.layer(
+                                            &new_path,
+                                            (None, None),
+                                            ProofTokenType::Merk
+                                        ),
+                                        generation.proofs,
+                                        transaction
                                     )
                                 );
+                                if !cost_return_on_error_no_add!(
+                                    &cost,
+                                    generation.flush_layer((generation.limit, generation.offset))
+                                ) {
+                                    return Ok(()).wrap_with_cost(cost);
+                                }
 
                                 new_path.push(subkey);
 
                                 if self
                                     .check_subtree_exists_path_not_found(
                                         new_path.as_slice().into(),
-                                        None,
+                                        Some(transaction),
                                     )
                                     .unwrap_add_cost(&mut cost)
                                     .is_err()
@@ -330,8 +870,9 @@ impl GroveDb {
                         for subkey in subquery_path.iter() {
                             let inner_subtree = cost_return_on_error!(
                                 &mut cost,
-                                self.open_non_transactional_merk_at_path(
+                                self.open_transactional_merk_at_path(
                                     new_path.as_slice().into(),
+                                    transaction,
                                     None
                                 )
                             );
@@ -345,13 +886,17 @@ impl GroveDb {
                                     &new_path.as_slice().into(),
                                     &inner_subtree,
                                     &key_as_query,
-                                    (None, None),
-                                    ProofTokenType::Merk,
-                                    proofs,
-                                    is_verbose,
-                                    new_path.iter().last().unwrap_or(&Default::default())
+                                    generation.layer(&new_path, (None, None), ProofTokenType::Merk),
+                                    generation.proofs,
+                                    transaction
                                 )
                             );
+                            if !cost_return_on_error_no_add!(
+                                &cost,
+                                generation.flush_layer((generation.limit, generation.offset))
+                            ) {
+                                return Ok(()).wrap_with_cost(cost);
+                            }
 
                             new_path.push(subkey);
 
@@ -360,7 +905,7 @@ impl GroveDb {
                             if self
                                 .check_subtree_exists_path_not_found(
                                     new_path.as_slice().into(),
-                                    None,
+                                    Some(transaction),
                                 )
                                 .unwrap_add_cost(&mut cost)
                                 .is_err()
@@ -387,27 +932,38 @@ impl GroveDb {
                     let new_path_query = PathQuery::new_unsized(new_path_owned, query.unwrap());
 
                     if self
-                        .check_subtree_exists_path_not_found(new_path.as_slice().into(), None)
+                        .check_subtree_exists_path_not_found(
+                            new_path.as_slice().into(),
+                            Some(transaction),
+                        )
                         .unwrap_add_cost(&mut cost)
                         .is_err()
                     {
                         continue;
                     }
 
+                    if let Some(stream) = &mut generation.stream {
+                        stream.descent.push(key.clone());
+                    }
                     cost_return_on_error!(
                         &mut cost,
                         self.prove_subqueries(
-                            proofs,
+                            generation,
                             new_path,
                             &new_path_query,
-                            current_limit,
-                            current_offset,
                             false,
-                            is_verbose,
+                            transaction
                         )
                     );
+                    if let Some(stream) = &mut generation.stream {
+                        stream.descent.pop();
+                    }
 
-                    if *current_limit == Some(0) {
+                    if generation.is_exhausted() {
+                        return Ok(()).wrap_with_cost(cost);
+                    }
+
+                    if generation.limit == Some(0) {
                         break;
                     }
                 }
@@ -420,25 +976,86 @@ impl GroveDb {
         if is_leaf_tree {
             // if no useful subtree, then we care about the result set of this subtree.
             // apply the sized query
-            let limit_offset = cost_return_on_error!(
+            let mut limit_offset = cost_return_on_error!(
                 &mut cost,
                 self.generate_and_store_merk_proof(
                     &path.as_slice().into(),
                     &subtree,
                     &query.query.query,
-                    (*current_limit, *current_offset),
-                    ProofTokenType::SizedMerk,
-                    proofs,
-                    is_verbose,
-                    path.iter().last().unwrap_or(&Default::default())
+                    generation.layer(
+                        &path,
+                        (generation.limit, generation.offset),
+                        ProofTokenType::SizedMerk
+                    ),
+                    generation.proofs,
+                    transaction
                 )
             );
 
+            let mut is_partial_leaf = false;
+            if let (Some(stream), Some(_)) = (&generation.stream, generation.limit) {
+                if !stream.fits(generation.proofs.len()) {
+                    // the leaf doesn't fit as a whole, so prove as many of its results as
+                    // the budget allows and stop right after them
+                    let fitting_layer = cost_return_on_error!(
+                        &mut cost,
+                        self.generate_sized_merk_proof_within_budget(
+                            &path.as_slice().into(),
+                            &subtree,
+                            &query.query.query,
+                            generation.layer(
+                                &path,
+                                (generation.limit, generation.offset),
+                                ProofTokenType::SizedMerk
+                            ),
+                            stream.available_bytes(),
+                            transaction
+                        )
+                    );
+                    if let Some((layer, fitting_limit_offset)) = fitting_layer {
+                        *generation.proofs = layer;
+                        limit_offset = fitting_limit_offset;
+                        is_partial_leaf = true;
+                    }
+                }
+            }
+
+            if !cost_return_on_error_no_add!(&cost, generation.flush_layer(limit_offset)) {
+                return Ok(()).wrap_with_cost(cost);
+            }
+            if is_partial_leaf {
+                if let Some(stream) = &mut generation.stream {
+                    stream.exhausted = true;
+                    // the layer is only written if it proves a result
+                    if stream.pending.is_empty() {
+                        let skipped = generation.offset.unwrap_or_default()
+                            - limit_offset.1.unwrap_or_default();
+                        let proven = generation.limit.unwrap_or_default()
+                            - limit_offset.0.unwrap_or_default();
+                        let mut kv_iterator =
+                            KVIterator::new(subtree.storage.raw_iter(), &query.query.query)
+                                .unwrap_add_cost(&mut cost);
+                        let mut last_proven_key = None;
+                        for _ in 0..skipped + proven {
+                            last_proven_key = kv_iterator
+                                .next_kv()
+                                .unwrap_add_cost(&mut cost)
+                                .map(|(key, _)| key);
+                        }
+                        stream.boundary_descent.extend(last_proven_key);
+                    }
+                }
+            }
+
             // update limit and offset values
-            *current_limit = limit_offset.0;
-            *current_offset = limit_offset.1;
+            generation.limit = limit_offset.0;
+            generation.offset = limit_offset.1;
         } else {
-            reduce_limit_and_offset_by(current_limit, current_offset, to_add_to_result_set);
+            reduce_limit_and_offset_by(
+                &mut generation.limit,
+                &mut generation.offset,
+                to_add_to_result_set,
+            );
         }
 
         Ok(()).wrap_with_cost(cost)
@@ -451,6 +1068,7 @@ impl GroveDb {
         proof_result: &mut Vec<u8>,
         path_slices: Vec<&[u8]>,
         is_verbose: bool,
+        transaction: &Transaction<S>,
     ) -> CostResult<(), Error> {
         let mut cost = OperationCost::default();
 
@@ -459,7 +1077,7 @@ impl GroveDb {
         while let Some((key, path_slice)) = split_path {
             let subtree = cost_return_on_error!(
                 &mut cost,
-                self.open_non_transactional_merk_at_path(path_slice.into(), None)
+                self.open_transactional_merk_at_path(path_slice.into(), transaction, None)
             );
             let mut query = Query::new();
             query.insert_key(key.to_vec());
@@ -470,11 +1088,14 @@ impl GroveDb {
                     &path_slice.into(),
                     &subtree,
                     &query,
-                    (None, None),
-                    ProofTokenType::Merk,
+                    MerkProofLayer {
+                        limit_offset: (None, None),
+                        proof_token_type: ProofTokenType::Merk,
+                        key: path_slice.iter().last().unwrap_or(&Default::default()),
+                        is_verbose
+                    },
                     proof_result,
-                    is_verbose,
-                    path_slice.iter().last().unwrap_or(&Default::default())
+                    transaction
                 )
             );
             split_path = path_slice.split_last();
@@ -484,21 +1105,25 @@ impl GroveDb {
 
     /// Generates query proof given a subtree and appends the result to a proof
     /// list
-    fn generate_and_store_merk_proof<'a, S, B>(
+    fn generate_and_store_merk_proof<'a, 'db, C, B>(
         &self,
         path: &SubtreePath<B>,
-        subtree: &'a Merk<S>,
+        subtree: &'a Merk<C>,
         query: &Query,
-        limit_offset: LimitOffset,
-        proof_token_type: ProofTokenType,
+        layer: MerkProofLayer,
         proofs: &mut Vec<u8>,
-        is_verbose: bool,
-        key: &[u8],
+        transaction: &Transaction<S>,
     ) -> CostResult<(Option<u16>, Option<u16>), Error>
     where
-        S: StorageContext<'a> + 'a,
+        C: StorageContext<'db> + 'a,
         B: AsRef<[u8]>,
     {
+        let MerkProofLayer {
+            limit_offset,
+            proof_token_type,
+            key,
+            is_verbose,
+        } = layer;
         if proof_token_type != ProofTokenType::Merk && proof_token_type != ProofTokenType::SizedMerk
         {
             return Err(Error::InvalidInput(
@@ -514,7 +1139,10 @@ impl GroveDb {
             .unwrap()
             .expect("should generate proof");
 
-        cost_return_on_error!(&mut cost, self.post_process_proof(path, &mut proof_result));
+        cost_return_on_error!(
+            &mut cost,
+            self.post_process_proof(path, &mut proof_result, transaction)
+        );
 
         let mut proof_bytes = Vec::with_capacity(128);
         encode_into(proof_result.proof.iter(), &mut proof_bytes);
@@ -532,21 +1160,65 @@ impl GroveDb {
         Ok((proof_result.limit, proof_result.offset)).wrap_with_cost(cost)
     }
 
-    /// Serializes a path and add it to the proof vector
-    fn generate_and_store_path_proof(
-        path: Vec<&[u8]>,
-        proofs: &mut Vec<u8>,
-    ) -> CostResult<(), Error> {
-        let cost = OperationCost::default();
-
-        cost_return_on_error_no_add!(
-            &cost,
-            write_to_vec(proofs, &[ProofTokenType::PathInfo.into()])
-        );
-
-        cost_return_on_error_no_add!(&cost, write_slice_of_slice_to_slice(proofs, &path));
+    /// Generates the sized proof of a leaf subtree for the largest limit below
+    /// the layer's limit that fits into `max_len` bytes. The returned limit is
+    /// what would remain of the layer's limit after the proven results.
+    /// Returns `None` if not even the proof of a single result fits.
+    fn generate_sized_merk_proof_within_budget<'a, 'db, C, B>(
+        &self,
+        path: &SubtreePath<B>,
+        subtree: &'a Merk<C>,
+        query: &Query,
+        layer: MerkProofLayer,
+        max_len: usize,
+        transaction: &Transaction<S>,
+    ) -> CostResult<Option<(Vec<u8>, LimitOffset)>, Error>
+    where
+        C: StorageContext<'db> + 'a,
+        B: AsRef<[u8]>,
+    {
+        let mut cost = OperationCost::default();
+        let (Some(limit), offset) = layer.limit_offset else {
+            return Err(Error::CorruptedCodeExecution(
+                "a proof within a budget needs a limit",
+            ))
+            .wrap_with_cost(cost);
+        };
+
+        let mut fitting = None;
+        // the proof for `fitting_limit` is known to fit and the one for
+        // `too_large_limit` is known not to
+        let mut fitting_limit = 0;
+        let mut too_large_limit = limit;
+        while too_large_limit - fitting_limit > 1 {
+            let candidate_limit = fitting_limit + (too_large_limit - fitting_limit) / 2;
+            let mut candidate = vec![];
+            let (remaining_limit, remaining_offset) = cost_return_on_error!(
+                &mut cost,
+                self.generate_and_store_merk_proof(
+                    path,
+                    subtree,
+                    query,
+                    MerkProofLayer {
+                        limit_offset: (Some(candidate_limit), offset),
+                        proof_token_type: ProofTokenType::SizedMerk,
+                        key: layer.key,
+                        is_verbose: layer.is_verbose,
+                    },
+                    &mut candidate,
+                    transaction
+                )
+            );
+            if candidate.len() <= max_len {
+                let consumed = candidate_limit - remaining_limit.unwrap_or_default();
+                fitting = Some((candidate, (Some(limit - consumed), remaining_offset)));
+                fitting_limit = candidate_limit;
+            } else {
+                too_large_limit = candidate_limit;
+            }
+        }
 
-        Ok(()).wrap_with_cost(cost)
+        Ok(fitting).wrap_with_cost(cost)
     }
 
     fn generate_and_store_absent_path_proof(
@@ -554,6 +1226,7 @@ impl GroveDb {
         path_slices: &[&[u8]],
         proof_result: &mut Vec<u8>,
         is_verbose: bool,
+        transaction: &Transaction<S>,
     ) -> CostResult<(), Error> {
         let mut cost = OperationCost::default();
 
@@ -566,7 +1239,7 @@ impl GroveDb {
         let mut split_path = path_slices.split_first();
         while let Some((key, path_slice)) = split_path {
             let subtree = self
-                .open_non_transactional_merk_at_path(current_path.as_slice().into(), None)
+                .open_transactional_merk_at_path(current_path.as_slice().into(), transaction, None)
                 .unwrap_add_cost(&mut cost);
 
             if subtree.is_err() {
@@ -588,11 +1261,14 @@ impl GroveDb {
                     &current_path.as_slice().into(),
                     &subtree.expect("confirmed not error above"),
                     &next_key_query,
-                    (None, None),
-                    ProofTokenType::Merk,
+                    MerkProofLayer {
+                        limit_offset: (None, None),
+                        proof_token_type: ProofTokenType::Merk,
+                        key: current_path.iter().last().unwrap_or(&(&[][..])),
+                        is_verbose
+                    },
                     proof_result,
-                    is_verbose,
-                    current_path.iter().last().unwrap_or(&(&[][..]))
+                    transaction
                 )
             );
 
@@ -616,6 +1292,7 @@ impl GroveDb {
         &self,
         path: &SubtreePath<B>,
         proof_result: &mut ProofWithoutEncodingResult,
+        transaction: &Transaction<S>,
     ) -> CostResult<(), Error> {
         let mut cost = OperationCost::default();
 
@@ -641,7 +1318,7 @@ impl GroveDb {
                                     self.follow_reference(
                                         absolute_path.as_slice().into(),
                                         true,
-                                        None
+                                        Some(transaction)
                                     )
                                 );
 
@@ -674,11 +1351,32 @@ impl GroveDb {
     }
 }
 
+#[cfg(feature = "full")]
+impl GroveDb {
+    /// Serializes a path and add it to the proof vector
+    fn generate_and_store_path_proof(
+        path: Vec<&[u8]>,
+        proofs: &mut Vec<u8>,
+    ) -> CostResult<(), Error> {
+        let cost = OperationCost::default();
+
+        cost_return_on_error_no_add!(
+            &cost,
+            write_to_vec(proofs, &[ProofTokenType::PathInfo.into()])
+        );
+
+        cost_return_on_error_no_add!(&cost, write_slice_of_slice_to_slice(proofs, &path));
+
+        Ok(()).wrap_with_cost(cost)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use grovedb_merk::{execute_proof, proofs::Query};
     use grovedb_storage::StorageBatch;
 
+    use super::MerkProofLayer;
     use crate::{
         operations::proof::util::{ProofReader, ProofTokenType},
         tests::{common::EMPTY_PATH, make_deep_tree, TEST_LEAF},
@@ -708,6 +1406,7 @@ mod tests {
         query.insert_all();
 
         let batch = StorageBatch::new();
+        let transaction = db.start_transaction();
 
         let merk = db
             .open_non_transactional_merk_at_path(
@@ -723,11 +1422,14 @@ mod tests {
             &path.as_slice().into(),
             &merk,
             &query,
-            (None, None),
-            ProofTokenType::Merk,
+            MerkProofLayer {
+                limit_offset: (None, None),
+                proof_token_type: ProofTokenType::Merk,
+                key: b"innertree",
+                is_verbose: true,
+            },
             &mut proof,
-            true,
-            b"innertree",
+            &transaction,
         )
         .unwrap()
         .unwrap();
@@ -757,11 +1459,14 @@ mod tests {
             &EMPTY_PATH,
             &merk,
             &query,
-            (None, None),
-            ProofTokenType::Merk,
+            MerkProofLayer {
+                limit_offset: (None, None),
+                proof_token_type: ProofTokenType::Merk,
+                key: &[],
+                is_verbose: true,
+            },
             &mut proof,
-            true,
-            &[],
+            &transaction,
         )
         .unwrap()
         .unwrap();
@@ -793,6 +1498,7 @@ mod tests {
         let path = vec![TEST_LEAF, b"innertree"];
 
         let batch = StorageBatch::new();
+        let transaction = db.start_transaction();
 
         let merk = db
             .open_non_transactional_merk_at_path(path.as_slice().into(), Some(&batch))
@@ -803,11 +1509,14 @@ mod tests {
             &path.as_slice().into(),
             &merk,
             &query,
-            (None, None),
-            ProofTokenType::Merk,
+            MerkProofLayer {
+                limit_offset: (None, None),
+                proof_token_type: ProofTokenType::Merk,
+                key: path.iter().last().unwrap_or(&(&[][..])),
+                is_verbose: true,
+            },
             &mut proofs,
-            true,
-            path.iter().last().unwrap_or(&(&[][..])),
+            &transaction,
         )
         .unwrap()
         .unwrap();
@@ -823,11 +1532,14 @@ mod tests {
             &path.as_slice().into(),
             &merk,
             &query,
-            (None, None),
-            ProofTokenType::Merk,
+            MerkProofLayer {
+                limit_offset: (None, None),
+                proof_token_type: ProofTokenType::Merk,
+                key: path.iter().last().unwrap_or(&(&[][..])),
+                is_verbose: true,
+            },
             &mut proofs,
-            true,
-            path.iter().last().unwrap_or(&(&[][..])),
+            &transaction,
         )
         .unwrap()
         .unwrap();
@@ -843,11 +1555,14 @@ mod tests {
             &path.as_slice().into(),
             &merk,
             &query,
-            (None, None),
-            ProofTokenType::Merk,
+            MerkProofLayer {
+                limit_offset: (None, None),
+                proof_token_type: ProofTokenType::Merk,
+                key: path.iter().last().unwrap_or(&(&[][..])),
+                is_verbose: true,
+            },
             &mut proofs,
-            true,
-            path.iter().last().unwrap_or(&(&[][..])),
+            &transaction,
         )
         .unwrap()
         .unwrap();
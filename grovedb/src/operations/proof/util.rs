@@ -39,14 +39,21 @@ use grovedb_merk::{
 use integer_encoding::{VarInt, VarIntReader};
 
 use crate::operations::proof::verify::ProvedKeyValues;
+#[cfg(feature = "full")]
+use crate::PathQuery;
 #[cfg(any(feature = "full", feature = "verify"))]
-use crate::Error;
+use crate::{element::SumValue, Error};
 
 #[cfg(any(feature = "full", feature = "verify"))]
 pub const EMPTY_TREE_HASH: [u8; 32] = [0; 32];
 
 pub type ProofTokenInfo = (ProofTokenType, Vec<u8>, Option<Vec<u8>>);
 
+#[cfg(any(feature = "full", feature = "verify"))]
+/// Encoded chunks of a subtree and the keys of its references with the values
+/// they point to
+pub type SubtreeChunks = (Vec<Vec<u8>>, Vec<(Vec<u8>, Vec<u8>)>);
+
 #[cfg(any(feature = "full", feature = "verify"))]
 #[derive(Debug, PartialEq, Eq)]
 /// Proof type
@@ -57,6 +64,7 @@ pub enum ProofTokenType {
     EmptyTree,
     AbsentPath,
     PathInfo,
+    SubtreeChunks,
     Invalid,
 }
 
@@ -69,6 +77,7 @@ impl From<ProofTokenType> for u8 {
             ProofTokenType::EmptyTree => 0x04,
             ProofTokenType::AbsentPath => 0x05,
             ProofTokenType::PathInfo => 0x06,
+            ProofTokenType::SubtreeChunks => 0x07,
             ProofTokenType::Invalid => 0x10,
         }
     }
@@ -83,6 +92,7 @@ impl From<u8> for ProofTokenType {
             0x04 => ProofTokenType::EmptyTree,
             0x05 => ProofTokenType::AbsentPath,
             0x06 => ProofTokenType::PathInfo,
+            0x07 => ProofTokenType::SubtreeChunks,
             _ => ProofTokenType::Invalid,
         }
     }
@@ -269,6 +279,44 @@ impl<'a> ProofReader<'a> {
 
         Ok(path)
     }
+
+    /// Reads the chunks of a subtree and the values its references point to
+    pub fn read_subtree_chunks(&mut self) -> Result<SubtreeChunks, Error> {
+        let mut data_type = [0; 1];
+        self.read_into_slice(&mut data_type)?;
+
+        if data_type != [ProofTokenType::SubtreeChunks.into()] {
+            return Err(Error::InvalidProof(
+                "wrong data_type, expected subtree chunks",
+            ));
+        }
+
+        let chunk_count = self.read_length_data()?;
+        let mut chunks = Vec::new();
+        for _ in 0..chunk_count {
+            chunks.push(self.read_length_prefixed_data()?);
+        }
+
+        let referenced_value_count = self.read_length_data()?;
+        let mut referenced_values = Vec::new();
+        for _ in 0..referenced_value_count {
+            let key = self.read_length_prefixed_data()?;
+            let referenced_value = self.read_length_prefixed_data()?;
+            referenced_values.push((key, referenced_value));
+        }
+
+        Ok((chunks, referenced_values))
+    }
+
+    /// Read varint encoded length information followed by that many bytes
+    fn read_length_prefixed_data(&mut self) -> Result<Vec<u8>, Error> {
+        let length = self.read_length_data()?;
+        let mut data = vec![0; length];
+        if self.read_into_slice(&mut data)? != length {
+            return Err(Error::InvalidProof("unexpected end of proof data"));
+        }
+        Ok(data)
+    }
 }
 
 #[cfg(feature = "full")]
@@ -287,6 +335,27 @@ pub fn write_slice_to_vec<W: Write>(dest: &mut W, value: &[u8]) -> Result<(), Er
     Ok(())
 }
 
+#[cfg(feature = "full")]
+/// Write the chunks of a subtree followed by the keys of its references and
+/// the values they point to
+pub fn write_subtree_chunks<W: Write>(
+    dest: &mut W,
+    chunks: &[Vec<u8>],
+    referenced_values: &[(Vec<u8>, Vec<u8>)],
+) -> Result<(), Error> {
+    write_to_vec(dest, &[ProofTokenType::SubtreeChunks.into()])?;
+    write_to_vec(dest, chunks.len().encode_var_vec().as_slice())?;
+    for chunk in chunks {
+        write_slice_to_vec(dest, chunk)?;
+    }
+    write_to_vec(dest, referenced_values.len().encode_var_vec().as_slice())?;
+    for (key, referenced_value) in referenced_values {
+        write_slice_to_vec(dest, key)?;
+        write_slice_to_vec(dest, referenced_value)?;
+    }
+    Ok(())
+}
+
 #[cfg(feature = "full")]
 /// Write a slice of a slice to a flat vector:w
 pub fn write_slice_of_slice_to_slice<W: Write>(dest: &mut W, value: &[&[u8]]) -> Result<(), Error> {
@@ -333,6 +402,48 @@ pub fn reduce_limit_and_offset_by(
     skip_limit
 }
 
+#[cfg(feature = "full")]
+/// Outcome of streaming a proof into a writer
+#[derive(Debug)]
+pub struct ProofWriteResult {
+    /// Number of bytes written, including the proof version
+    pub bytes_written: usize,
+    /// Set if the byte budget or the result limit stopped proof generation
+    /// before the whole query was proven
+    pub continuation: Option<ProofContinuation>,
+}
+
+#[cfg(feature = "full")]
+/// Point at which a streamed proof stopped
+#[derive(Debug, Clone)]
+pub struct ProofContinuation {
+    /// Number of results contained in the written proof
+    pub results_proven: u16,
+    /// Query the written proof verifies against, this is the original query
+    /// with its limit set to `results_proven`
+    pub proven_query: PathQuery,
+    /// Query for the results that were not proven yet
+    pub remaining_query: PathQuery,
+}
+
+#[cfg(any(feature = "full", feature = "verify"))]
+/// Status of the subtree at a path, as proven by
+/// `GroveDb::prove_subtree_status`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubtreeStatus {
+    /// The path doesn't point to a tree
+    Absent,
+    /// The path points to an empty tree
+    Empty,
+    /// The path points to a tree that has elements
+    NonEmpty {
+        /// Root hash of the tree
+        root_hash: CryptoHash,
+        /// Sum of the tree if it is a sum tree
+        sum: Option<SumValue>,
+    },
+}
+
 /// Proved path-key-values
 pub type ProvedPathKeyValues = Vec<ProvedPathKeyValue>;
 
@@ -386,6 +497,7 @@ mod tests {
         assert_eq!(0x04_u8, ProofTokenType::EmptyTree.into());
         assert_eq!(0x05_u8, ProofTokenType::AbsentPath.into());
         assert_eq!(0x06_u8, ProofTokenType::PathInfo.into());
+        assert_eq!(0x07_u8, ProofTokenType::SubtreeChunks.into());
         assert_eq!(0x10_u8, ProofTokenType::Invalid.into());
     }
 
@@ -396,6 +508,7 @@ mod tests {
         assert_eq!(ProofTokenType::EmptyTree, 0x04_u8.into());
         assert_eq!(ProofTokenType::AbsentPath, 0x05_u8.into());
         assert_eq!(ProofTokenType::PathInfo, 0x06_u8.into());
+        assert_eq!(ProofTokenType::SubtreeChunks, 0x07_u8.into());
         assert_eq!(ProofTokenType::Invalid, 0x10_u8.into());
     }
 
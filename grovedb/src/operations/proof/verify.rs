@@ -28,11 +28,23 @@
 
 //! Verify proof operations
 
-use std::{borrow::Cow, collections::BTreeMap};
+#[cfg(feature = "full")]
+use std::collections::VecDeque;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    mem,
+};
 
+#[cfg(any(feature = "full", feature = "verify"))]
+use grovedb_costs::{
+    cost_return_on_error, cost_return_on_error_no_add, CostResult, CostsExt, OperationCost,
+};
 use grovedb_merk::proofs::query::PathKey;
 #[cfg(any(feature = "full", feature = "verify"))]
 pub use grovedb_merk::proofs::query::{Path, ProvedKeyValue};
+#[cfg(feature = "full")]
+use grovedb_merk::proofs::{chunk::verify_chunks, Decoder, Node, Op};
 #[cfg(any(feature = "full", feature = "verify"))]
 use grovedb_merk::{
     proofs::Query,
@@ -40,6 +52,8 @@ use grovedb_merk::{
     CryptoHash,
 };
 
+#[cfg(feature = "full")]
+use crate::query_result_type::PathKeyElementTrio;
 use crate::{
     operations::proof::util::{
         reduce_limit_and_offset_by, ProvedPathKeyValue, ProvedPathKeyValues,
@@ -51,7 +65,7 @@ use crate::{
 #[cfg(any(feature = "full", feature = "verify"))]
 use crate::{
     operations::proof::util::{
-        ProofReader, ProofTokenType, ProofTokenType::AbsentPath, EMPTY_TREE_HASH,
+        ProofReader, ProofTokenType, ProofTokenType::AbsentPath, SubtreeStatus, EMPTY_TREE_HASH,
     },
     Element, Error, GroveDb, PathQuery,
 };
@@ -62,6 +76,10 @@ pub type ProvedKeyValues = Vec<ProvedKeyValue>;
 #[cfg(any(feature = "full", feature = "verify"))]
 type EncounteredAbsence = bool;
 
+#[cfg(feature = "full")]
+/// Serialized tree element and its value hash
+type TreeElementValue = (Vec<u8>, CryptoHash);
+
 #[cfg(any(feature = "full", feature = "verify"))]
 impl GroveDb {
     /// Verify proof given a path query
@@ -83,7 +101,7 @@ impl GroveDb {
         proof: &[u8],
         query: &PathQuery,
     ) -> Result<([u8; 32], ProvedPathKeyValues), Error> {
-        let mut verifier = ProofVerifier::new(query);
+        let mut verifier = ProofExecutor::new(query);
         let hash = verifier.execute_proof(proof, query, false)?;
 
         Ok((hash, verifier.result_set))
@@ -124,7 +142,7 @@ impl GroveDb {
         proof: &[u8],
         query: &PathQuery,
     ) -> Result<([u8; 32], ProvedPathKeyValues), Error> {
-        let mut verifier = ProofVerifier::new(query);
+        let mut verifier = ProofExecutor::new(query);
         let hash = verifier.execute_proof(proof, query, true)?;
         Ok((hash, verifier.result_set))
     }
@@ -232,24 +250,274 @@ impl GroveDb {
 
         Ok((last_root_hash, results))
     }
+
+    /// Verify a proof generated by `prove_subtree_status` for the given path.
+    /// Returns the root hash and the status of the subtree at the path.
+    pub fn verify_subtree_status(
+        proof: &[u8],
+        path: &[Vec<u8>],
+    ) -> Result<([u8; 32], SubtreeStatus), Error> {
+        let query = PathQuery::new_unsized(path.to_vec(), Query::new());
+        let mut verifier = ProofExecutor::new(&query);
+        verifier.execute_subtree_status_proof(proof, &query)
+    }
+
+    /// Verify a proof generated by `prove_subtree_audit` for the given path.
+    /// Returns the root hash and every element of the subtree and of its
+    /// nested subtrees, with the path of the subtree it belongs to.
+    #[cfg(feature = "full")]
+    pub fn verify_subtree_audit(
+        proof: &[u8],
+        path: &[Vec<u8>],
+    ) -> Result<([u8; 32], Vec<PathKeyElementTrio>), Error> {
+        let query = PathQuery::new_unsized(path.to_vec(), Query::new());
+        let mut verifier = ProofExecutor::new(&query);
+        verifier.execute_subtree_audit_proof(proof, &query)
+    }
+}
+
+#[cfg(any(feature = "full", feature = "verify"))]
+/// Verifies proofs against a set of trusted root hashes.
+///
+/// Merk proof layers that already verified, such as the upper layers shared by
+/// proofs of the same state, are remembered and not hashed again, which makes
+/// verifying many proofs together cheaper than verifying each of them with
+/// `GroveDb::verify_query`. The least recently used layers are forgotten once
+/// the cache holds its capacity.
+#[derive(Debug, Default)]
+pub struct ProofVerifier {
+    trusted_root_hashes: BTreeSet<CryptoHash>,
+    layer_cache: LayerCache,
+}
+
+#[cfg(any(feature = "full", feature = "verify"))]
+impl ProofVerifier {
+    /// New verifier accepting proofs for any of the given root hashes
+    pub fn new<I: IntoIterator<Item = CryptoHash>>(trusted_root_hashes: I) -> Self {
+        Self::with_cache_capacity(trusted_root_hashes, DEFAULT_LAYER_CACHE_CAPACITY)
+    }
+
+    /// New verifier accepting proofs for any of the given root hashes and
+    /// remembering at most `capacity` verified layers
+    pub fn with_cache_capacity<I: IntoIterator<Item = CryptoHash>>(
+        trusted_root_hashes: I,
+        capacity: usize,
+    ) -> Self {
+        ProofVerifier {
+            trusted_root_hashes: trusted_root_hashes.into_iter().collect(),
+            layer_cache: LayerCache::new(capacity),
+        }
+    }
+
+    /// Accept proofs for another root hash
+    pub fn trust_root_hash(&mut self, root_hash: CryptoHash) {
+        self.trusted_root_hashes.insert(root_hash);
+    }
+
+    /// Stop accepting proofs for a root hash, returns whether it was trusted
+    pub fn distrust_root_hash(&mut self, root_hash: &CryptoHash) -> bool {
+        self.trusted_root_hashes.remove(root_hash)
+    }
+
+    /// Forget the verified layers
+    pub fn clear_cache(&mut self) {
+        self.layer_cache = LayerCache::new(self.layer_cache.capacity);
+    }
+
+    /// Verify a proof for a path query against the trusted root hashes.
+    /// Returns the root hash of the proof and the deserialized elements.
+    pub fn verify_query(
+        &mut self,
+        proof: &[u8],
+        query: &PathQuery,
+    ) -> CostResult<(CryptoHash, Vec<PathKeyOptionalElementTrio>), Error> {
+        let mut executor = ProofExecutor::new(query);
+        executor.layer_cache = Some(mem::take(&mut self.layer_cache));
+        let result = executor.execute_proof(proof, query, false);
+        let ProofExecutor {
+            result_set,
+            cost,
+            layer_cache,
+            ..
+        } = executor;
+        self.layer_cache = layer_cache.unwrap_or_default();
+
+        let root_hash = cost_return_on_error_no_add!(&cost, result);
+        if !self.trusted_root_hashes.contains(&root_hash) {
+            return Err(Error::InvalidProof("proof is not for a trusted root hash"))
+                .wrap_with_cost(cost);
+        }
+
+        let path_key_optional_elements = cost_return_on_error_no_add!(
+            &cost,
+            result_set
+                .into_iter()
+                .map(|pkv| pkv.try_into())
+                .collect::<Result<Vec<PathKeyOptionalElementTrio>, Error>>()
+        );
+        Ok((root_hash, path_key_optional_elements)).wrap_with_cost(cost)
+    }
+
+    /// Verify a batch of proofs, each with its path query, against the trusted
+    /// root hashes. Fails with the index of the first proof that doesn't
+    /// verify, the cost is the combined cost of verifying the proofs.
+    pub fn verify_queries(
+        &mut self,
+        proofs: &[(&[u8], &PathQuery)],
+    ) -> CostResult<Vec<(CryptoHash, Vec<PathKeyOptionalElementTrio>)>, Error> {
+        let mut cost = OperationCost::default();
+
+        let mut results = Vec::with_capacity(proofs.len());
+        for (index, (proof, query)) in proofs.iter().enumerate() {
+            results.push(cost_return_on_error!(
+                &mut cost,
+                self.verify_query(proof, query)
+                    .map_err(|e| Error::InvalidBatchProof(index, Box::new(e)))
+            ));
+        }
+
+        Ok(results).wrap_with_cost(cost)
+    }
 }
 
 #[cfg(any(feature = "full", feature = "verify"))]
-/// Proof verifier
-struct ProofVerifier {
+/// Number of verified layers a `ProofVerifier` remembers by default
+pub const DEFAULT_LAYER_CACHE_CAPACITY: usize = 1024;
+
+#[cfg(any(feature = "full", feature = "verify"))]
+/// Merk proof layers that verified, by the hash of their encoded proof,
+/// holding at most `capacity` of them
+#[derive(Debug)]
+struct LayerCache {
+    capacity: usize,
+    layers: HashMap<CryptoHash, CachedLayer>,
+    /// Layer hashes by when they were last used
+    recently_used: BTreeMap<u64, CryptoHash>,
+    uses: u64,
+}
+
+#[cfg(any(feature = "full", feature = "verify"))]
+/// Verifications of a layer and when it was last used
+#[derive(Debug)]
+struct CachedLayer {
+    last_used: u64,
+    verifications: Vec<VerifiedLayer>,
+}
+
+#[cfg(any(feature = "full", feature = "verify"))]
+/// Query a merk proof layer verified with and what it hashed to
+#[derive(Debug)]
+struct VerifiedLayer {
+    query: Query,
+    left_to_right: bool,
+    root_hash: CryptoHash,
+    result_set: ProvedKeyValues,
+}
+
+#[cfg(any(feature = "full", feature = "verify"))]
+impl Default for LayerCache {
+    fn default() -> Self {
+        LayerCache::new(DEFAULT_LAYER_CACHE_CAPACITY)
+    }
+}
+
+#[cfg(any(feature = "full", feature = "verify"))]
+impl LayerCache {
+    fn new(capacity: usize) -> Self {
+        LayerCache {
+            capacity,
+            layers: HashMap::new(),
+            recently_used: BTreeMap::new(),
+            uses: 0,
+        }
+    }
+
+    /// Marks a cached layer as the most recently used one
+    fn touch(&mut self, layer_hash: &CryptoHash) -> Option<&mut CachedLayer> {
+        let layer = self.layers.get_mut(layer_hash)?;
+        self.recently_used.remove(&layer.last_used);
+        self.uses += 1;
+        layer.last_used = self.uses;
+        self.recently_used.insert(self.uses, *layer_hash);
+        Some(layer)
+    }
+
+    /// Root hash and results of a layer already verified with the same query
+    fn get(
+        &mut self,
+        layer_hash: &CryptoHash,
+        query: &Query,
+        left_to_right: bool,
+    ) -> Option<(CryptoHash, ProvedKeyValues)> {
+        self.touch(layer_hash)?
+            .verifications
+            .iter()
+            .find(|layer| layer.left_to_right == left_to_right && &layer.query == query)
+            .map(|layer| (layer.root_hash, layer.result_set.clone()))
+    }
+
+    /// Remembers a verified layer, forgetting the least recently used one if
+    /// the cache is full
+    fn insert(
+        &mut self,
+        layer_hash: CryptoHash,
+        query: &Query,
+        left_to_right: bool,
+        root_hash: CryptoHash,
+        result_set: ProvedKeyValues,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.touch(&layer_hash).is_none() {
+            if self.layers.len() >= self.capacity {
+                if let Some((_, oldest)) = self.recently_used.pop_first() {
+                    self.layers.remove(&oldest);
+                }
+            }
+            self.uses += 1;
+            self.recently_used.insert(self.uses, layer_hash);
+            self.layers.insert(
+                layer_hash,
+                CachedLayer {
+                    last_used: self.uses,
+                    verifications: Vec::new(),
+                },
+            );
+        }
+        if let Some(layer) = self.layers.get_mut(&layer_hash) {
+            layer.verifications.push(VerifiedLayer {
+                query: query.clone(),
+                left_to_right,
+                root_hash,
+                result_set,
+            });
+        }
+    }
+}
+
+#[cfg(any(feature = "full", feature = "verify"))]
+/// Executes a single proof
+struct ProofExecutor {
     limit: Option<u16>,
     offset: Option<u16>,
     result_set: ProvedPathKeyValues,
+    /// Cost of hashing the merk proofs
+    cost: OperationCost,
+    /// Verified layers to reuse, if any
+    layer_cache: Option<LayerCache>,
 }
 
 #[cfg(any(feature = "full", feature = "verify"))]
-impl ProofVerifier {
+impl ProofExecutor {
     /// New query
     pub fn new(query: &PathQuery) -> Self {
-        ProofVerifier {
+        ProofExecutor {
             limit: query.query.limit,
             offset: query.query.offset,
             result_set: vec![],
+            cost: OperationCost::default(),
+            layer_cache: None,
         }
     }
 
@@ -331,11 +599,182 @@ impl ProofVerifier {
                 &mut proof_reader,
                 &mut last_subtree_root_hash,
             )?
+            .0
         };
 
         Ok(root_hash)
     }
 
+    /// Execute a proof of the status of the subtree at the path of the given
+    /// path query, the query itself must be empty
+    fn execute_subtree_status_proof(
+        &mut self,
+        proof: &[u8],
+        query: &PathQuery,
+    ) -> Result<([u8; 32], SubtreeStatus), Error> {
+        let (_, proof) = read_and_consume_proof_version(proof)?;
+        let mut proof_reader = ProofReader::new(proof);
+
+        let path_slices = query.path.iter().map(|x| x.as_slice()).collect::<Vec<_>>();
+
+        let (proof_token_type, proof, _) = proof_reader.read_proof()?;
+        let subtree_root_hash = match proof_token_type {
+            AbsentPath => {
+                let root_hash = self.verify_absent_path(&mut proof_reader, path_slices)?;
+                return Ok((root_hash, SubtreeStatus::Absent));
+            }
+            ProofTokenType::EmptyTree => EMPTY_TREE_HASH,
+            ProofTokenType::SizedMerk => {
+                let (root_hash, _) = self.execute_merk_proof(
+                    ProofTokenType::SizedMerk,
+                    &proof,
+                    &query.query.query,
+                    query.query.query.left_to_right,
+                    query.path.clone(),
+                )?;
+                if !self.result_set.is_empty() {
+                    return Err(Error::InvalidProof(
+                        "subtree status proofs must not return any elements",
+                    ));
+                }
+                root_hash
+            }
+            _ => {
+                return Err(Error::InvalidProof(
+                    "wrong data_type expected a subtree status proof",
+                ))
+            }
+        };
+        let mut last_root_hash = subtree_root_hash;
+        let (root_hash, subtree_element) =
+            self.verify_path_to_root(query, path_slices, &mut proof_reader, &mut last_root_hash)?;
+
+        let status = if subtree_root_hash == EMPTY_TREE_HASH {
+            SubtreeStatus::Empty
+        } else {
+            SubtreeStatus::NonEmpty {
+                root_hash: subtree_root_hash,
+                // the root tree has no element and is never a sum tree
+                sum: match subtree_element {
+                    Some(Element::SumTree(_, sum, _)) => Some(sum),
+                    _ => None,
+                },
+            }
+        };
+
+        Ok((root_hash, status))
+    }
+
+    /// Execute a proof of the whole subtree at the path of the given path
+    /// query, every subtree hash and element value is checked against the
+    /// value hash committed to in its parent
+    #[cfg(feature = "full")]
+    fn execute_subtree_audit_proof(
+        &mut self,
+        proof: &[u8],
+        query: &PathQuery,
+    ) -> Result<([u8; 32], Vec<PathKeyElementTrio>), Error> {
+        let (_, proof) = read_and_consume_proof_version(proof)?;
+        let mut proof_reader = ProofReader::new(proof);
+
+        let mut elements = vec![];
+        let mut audited_root_hash = None;
+
+        // subtrees left to verify, with the tree element in their parent
+        let mut subtrees: VecDeque<(Path, Option<TreeElementValue>)> =
+            VecDeque::from([(query.path.clone(), None)]);
+        while let Some((path, tree_element)) = subtrees.pop_front() {
+            let (chunks, referenced_values) = proof_reader.read_subtree_chunks()?;
+
+            let (subtree_root_hash, nodes) = if chunks.is_empty() {
+                (EMPTY_TREE_HASH, vec![])
+            } else {
+                let chunks = chunks
+                    .iter()
+                    .map(|chunk| Decoder::new(chunk).collect::<Result<Vec<Op>, _>>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| Error::InvalidProof("failed to decode subtree chunk"))?;
+                verify_chunks(chunks)
+                    .unwrap()
+                    .map_err(|_| Error::InvalidProof("subtree chunks are not a valid merk"))?
+            };
+
+            match tree_element {
+                Some((value, value_hash)) => {
+                    let combined_hash =
+                        combine_hash(value_hash_fn(&value).value(), &subtree_root_hash).unwrap();
+                    if combined_hash != value_hash {
+                        return Err(Error::InvalidProof(
+                            "subtree root hash doesn't match its tree element",
+                        ));
+                    }
+                }
+                None => audited_root_hash = Some(subtree_root_hash),
+            }
+
+            let mut referenced_values: BTreeMap<Vec<u8>, Vec<u8>> =
+                referenced_values.into_iter().collect();
+
+            for node in nodes {
+                let (key, value, value_hash) = match node {
+                    Node::KVValueHash(key, value, value_hash)
+                    | Node::KVValueHashFeatureType(key, value, value_hash, _) => {
+                        (key, value, value_hash)
+                    }
+                    _ => {
+                        return Err(Error::InvalidProof(
+                            "subtree chunks must contain value hashes",
+                        ))
+                    }
+                };
+                let element = Element::deserialize(&value)?;
+
+                let expected_value_hash = match &element {
+                    Element::Tree(Some(_), _) | Element::SumTree(Some(_), ..) => {
+                        // checked once the subtree itself is verified
+                        let mut child_path = path.clone();
+                        child_path.push(key.clone());
+                        subtrees.push_back((child_path, Some((value, value_hash))));
+                        value_hash
+                    }
+                    Element::Tree(None, _) | Element::SumTree(None, ..) => {
+                        combine_hash(value_hash_fn(&value).value(), &EMPTY_TREE_HASH).unwrap()
+                    }
+                    Element::Reference(..) => {
+                        let referenced_value = referenced_values.remove(&key).ok_or(
+                            Error::InvalidProof("missing the value pointed to by a reference"),
+                        )?;
+                        combine_hash(
+                            value_hash_fn(&value).value(),
+                            value_hash_fn(&referenced_value).value(),
+                        )
+                        .unwrap()
+                    }
+                    Element::Item(..) | Element::SumItem(..) => value_hash_fn(&value).unwrap(),
+                };
+                if expected_value_hash != value_hash {
+                    return Err(Error::InvalidProof("value hash doesn't match the element"));
+                }
+
+                elements.push((path.clone(), key, element));
+            }
+
+            if !referenced_values.is_empty() {
+                return Err(Error::InvalidProof(
+                    "referenced values must belong to references",
+                ));
+            }
+        }
+
+        let mut last_root_hash =
+            audited_root_hash.expect("the subtree at the path is always verified first");
+        let path_slices = query.path.iter().map(|x| x.as_slice()).collect::<Vec<_>>();
+        let (root_hash, _) =
+            self.verify_path_to_root(query, path_slices, &mut proof_reader, &mut last_root_hash)?;
+
+        Ok((root_hash, elements))
+    }
+
     fn execute_subquery_proof(
         &mut self,
         proof_token_type: ProofTokenType,
@@ -704,6 +1143,7 @@ impl ProofVerifier {
         let mut root_key_hash = None;
         let mut expected_child_hash = None;
         let mut last_result_set: ProvedPathKeyValues = vec![];
+        let mut reached_absence = false;
 
         for key in path_slices {
             let (proof_token_type, merk_proof, _) = proof_reader.read_proof()?;
@@ -743,20 +1183,25 @@ impl ProofVerifier {
                 .expect("MERK_PROOF always returns a result set");
             if last_result_set.is_empty() {
                 // if result set is empty then we have reached the absence point, break
+                reached_absence = true;
                 break;
             }
 
             let elem = Element::deserialize(last_result_set[0].value.as_slice())?;
-            let child_hash = match elem {
-                Element::Tree(..) | Element::SumTree(..) => Ok(Some(last_result_set[0].proof)),
-                _ => Err(Error::InvalidProof(
-                    "intermediate proofs should be for trees",
-                )),
-            }?;
-            expected_child_hash = child_hash;
+            match elem {
+                Element::Tree(..) | Element::SumTree(..) => {
+                    expected_child_hash = Some(last_result_set[0].proof);
+                }
+                _ => {
+                    // the path continues through an element that is not a tree, which
+                    // is an absence point as well
+                    reached_absence = true;
+                    break;
+                }
+            }
         }
 
-        if last_result_set.is_empty() {
+        if reached_absence {
             if let Some(hash) = root_key_hash {
                 Ok(hash)
             } else {
@@ -768,14 +1213,16 @@ impl ProofVerifier {
     }
 
     /// Verifies that the correct proof was provided to confirm the path in
-    /// query
+    /// query. Returns the root hash and the element of the subtree at the path
+    /// unless it is the root tree.
     fn verify_path_to_root(
         &mut self,
         query: &PathQuery,
         path_slices: Vec<&[u8]>,
         proof_reader: &mut ProofReader,
         expected_root_hash: &mut [u8; 32],
-    ) -> Result<[u8; 32], Error> {
+    ) -> Result<([u8; 32], Option<Element>), Error> {
+        let mut subtree_element = None;
         let mut split_path = path_slices.split_last();
         while let Some((key, path_slice)) = split_path {
             // for every subtree, there should be a corresponding proof for the parent
@@ -812,6 +1259,9 @@ impl ProofVerifier {
                     "intermediate proofs should be for trees",
                 )),
             }?;
+            if subtree_element.is_none() {
+                subtree_element = Some(elem);
+            }
 
             let combined_root_hash = combine_hash(
                 value_hash_fn(&result_set[0].value).value(),
@@ -830,7 +1280,7 @@ impl ProofVerifier {
             split_path = path_slice.split_last();
         }
 
-        Ok(*expected_root_hash)
+        Ok((*expected_root_hash, subtree_element))
     }
 
     /// Execute a merk proof, update the state when a sized proof is
@@ -852,21 +1302,50 @@ impl ProofVerifier {
             offset = self.offset;
         }
 
-        let (hash, result) =
-            grovedb_merk::execute_proof(proof, query, limit, offset, left_to_right)
-                .unwrap()
-                .map_err(|e| {
-                    eprintln!("{e}");
-                    Error::InvalidProof("invalid proof verification parameters")
-                })?;
+        // a layer without limit and offset only depends on its proof and query
+        let layer_hash = if is_sized_proof || self.layer_cache.is_none() {
+            None
+        } else {
+            Some(value_hash_fn(proof).unwrap_add_cost(&mut self.cost))
+        };
+        let cached_layer = layer_hash.as_ref().and_then(|layer_hash| {
+            self.layer_cache
+                .as_mut()
+                .and_then(|cache| cache.get(layer_hash, query, left_to_right))
+        });
+
+        let (hash, result_set) = match cached_layer {
+            Some(cached_layer) => cached_layer,
+            None => {
+                let (hash, result) =
+                    grovedb_merk::execute_proof(proof, query, limit, offset, left_to_right)
+                        .unwrap_add_cost(&mut self.cost)
+                        .map_err(|e| {
+                            eprintln!("{e}");
+                            Error::InvalidProof("invalid proof verification parameters")
+                        })?;
+                if is_sized_proof {
+                    self.limit = result.limit;
+                    self.offset = result.offset;
+                } else if let (Some(cache), Some(layer_hash)) =
+                    (self.layer_cache.as_mut(), layer_hash)
+                {
+                    cache.insert(
+                        layer_hash,
+                        query,
+                        left_to_right,
+                        hash,
+                        result.result_set.clone(),
+                    );
+                }
+                (hash, result.result_set)
+            }
+        };
 
         // convert the result set to proved_path_key_values
-        let proved_path_key_values =
-            ProvedPathKeyValue::from_proved_key_values(path, result.result_set);
+        let proved_path_key_values = ProvedPathKeyValue::from_proved_key_values(path, result_set);
 
         if is_sized_proof {
-            self.limit = result.limit;
-            self.offset = result.offset;
             self.result_set.extend(proved_path_key_values);
             Ok((hash, None))
         } else {
@@ -874,3 +1353,84 @@ impl ProofVerifier {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use grovedb_merk::proofs::Query;
+
+    use super::{LayerCache, ProofVerifier, ProvedKeyValue};
+    use crate::{tests::make_deep_tree, PathQuery};
+
+    #[test]
+    fn test_repeated_layer_is_served_from_cache() {
+        let db = make_deep_tree();
+        let root_hash = db.root_hash(None).unwrap().unwrap();
+        let mut query = Query::new();
+        query.insert_all();
+        let path_query = PathQuery::new_unsized(
+            vec![
+                b"deep_leaf".to_vec(),
+                b"deep_node_1".to_vec(),
+                b"deeper_1".to_vec(),
+            ],
+            query,
+        );
+        let proof = db.prove_query(&path_query).unwrap().unwrap();
+
+        let mut verifier = ProofVerifier::new([root_hash]);
+        let first = verifier.verify_query(&proof, &path_query);
+        let first_cost = first.cost().clone();
+        let first = first.unwrap().expect("should verify proof");
+        let cached_layers = verifier.layer_cache.layers.len();
+        assert!(cached_layers > 0);
+        let uses_after_first = verifier.layer_cache.uses;
+
+        let again = verifier.verify_query(&proof, &path_query);
+        let again_cost = again.cost().clone();
+        assert_eq!(again.unwrap().expect("should verify proof"), first);
+        // every layer was looked up again and none was added
+        assert_eq!(verifier.layer_cache.layers.len(), cached_layers);
+        assert!(verifier
+            .layer_cache
+            .layers
+            .values()
+            .all(|layer| layer.last_used > uses_after_first && layer.verifications.len() == 1));
+        assert!(again_cost.hash_node_calls < first_cost.hash_node_calls);
+
+        // without a cache the proof is hashed in full every time
+        let mut uncached_verifier = ProofVerifier::with_cache_capacity([root_hash], 0);
+        for _ in 0..2 {
+            assert_eq!(
+                uncached_verifier.verify_query(&proof, &path_query).cost(),
+                &first_cost
+            );
+        }
+        assert!(uncached_verifier.layer_cache.layers.is_empty());
+    }
+
+    #[test]
+    fn test_layer_cache_forgets_least_recently_used_layer() {
+        let query = Query::new();
+        let result_set = |value: u8| {
+            vec![ProvedKeyValue {
+                key: vec![value],
+                value: vec![value],
+                proof: [value; 32],
+            }]
+        };
+        let mut cache = LayerCache::new(2);
+        cache.insert([1; 32], &query, true, [1; 32], result_set(1));
+        cache.insert([2; 32], &query, true, [2; 32], result_set(2));
+        assert_eq!(
+            cache.get(&[1; 32], &query, true),
+            Some(([1; 32], result_set(1)))
+        );
+        assert_eq!(cache.get(&[1; 32], &query, false), None);
+
+        cache.insert([3; 32], &query, true, [3; 32], result_set(3));
+        assert_eq!(cache.layers.len(), 2);
+        assert_eq!(cache.get(&[2; 32], &query, true), None);
+        assert!(cache.get(&[1; 32], &query, true).is_some());
+        assert!(cache.get(&[3; 32], &query, true).is_some());
+    }
+}
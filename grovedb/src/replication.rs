@@ -38,42 +38,69 @@ use grovedb_merk::{
     Merk, TreeFeatureType,
 };
 use grovedb_path::SubtreePath;
-use grovedb_storage::{
-    rocksdb_storage::{PrefixedRocksDbImmediateStorageContext, PrefixedRocksDbStorageContext},
-    Storage, StorageContext,
-};
+use grovedb_storage::{rocksdb_storage::RocksDbStorage, CovariantStorage, Storage, StorageContext};
 
 use crate::{Element, Error, GroveDb, Hash, Transaction};
 
 const OPS_PER_CHUNK: usize = 128;
 
-impl GroveDb {
+impl<S: CovariantStorage> GroveDb<S> {
     /// Creates a chunk producer to replicate GroveDb.
-    pub fn chunks(&self) -> SubtreeChunkProducer {
-        SubtreeChunkProducer::new(self)
+    pub fn chunks(&self) -> SubtreeChunkProducer<'_, S> {
+        SubtreeChunkProducer::new(self, None)
+    }
+
+    /// Creates a chunk producer to replicate GroveDb as seen by a transaction.
+    pub fn chunks_with_transaction<'db>(
+        &'db self,
+        transaction: &'db Transaction<'db, S>,
+    ) -> SubtreeChunkProducer<'db, S> {
+        SubtreeChunkProducer::new(self, Some(transaction))
     }
 }
 
 /// Subtree chunks producer.
-pub struct SubtreeChunkProducer<'db> {
-    grove_db: &'db GroveDb,
-    cache: Option<SubtreeChunkProducerCache<'db>>,
+pub struct SubtreeChunkProducer<'db, S: CovariantStorage = RocksDbStorage> {
+    grove_db: &'db GroveDb<S>,
+    transaction: Option<&'db Transaction<'db, S>>,
+    cache: Option<SubtreeChunkProducerCache<'db, S>>,
 }
 
-struct SubtreeChunkProducerCache<'db> {
+struct SubtreeChunkProducerCache<'db, S: CovariantStorage> {
     current_merk_path: Vec<Vec<u8>>,
-    current_merk: Merk<PrefixedRocksDbStorageContext<'db>>,
-    // This needed to be an `Option` because it requires a reference on Merk but it's within the
-    // same struct and during struct init a referenced Merk would be moved inside a struct,
-    // using `Option` this init happens in two steps.
-    current_chunk_producer:
-        Option<grovedb_merk::ChunkProducer<'db, PrefixedRocksDbStorageContext<'db>>>,
+    current_chunk_producer: MerkChunkProducer<'db, S>,
+}
+
+/// Merk chunk producer over a transactional or a non-transactional storage
+/// context.
+enum MerkChunkProducer<'db, S: CovariantStorage> {
+    NonTransactional(grovedb_merk::ChunkProducer<'db, <S as Storage<'db>>::BatchStorageContext>),
+    Transactional(
+        grovedb_merk::ChunkProducer<'db, <S as Storage<'db>>::BatchTransactionalStorageContext>,
+    ),
 }
 
-impl<'db> SubtreeChunkProducer<'db> {
-    fn new(storage: &'db GroveDb) -> Self {
+impl<'db, S: CovariantStorage> MerkChunkProducer<'db, S> {
+    fn len(&self) -> usize {
+        match self {
+            MerkChunkProducer::NonTransactional(producer) => producer.len(),
+            MerkChunkProducer::Transactional(producer) => producer.len(),
+        }
+    }
+
+    fn chunk(&mut self, index: usize) -> Result<Vec<Op>, grovedb_merk::Error> {
+        match self {
+            MerkChunkProducer::NonTransactional(producer) => producer.chunk(index),
+            MerkChunkProducer::Transactional(producer) => producer.chunk(index),
+        }
+    }
+}
+
+impl<'db, S: CovariantStorage> SubtreeChunkProducer<'db, S> {
+    fn new(storage: &'db GroveDb<S>, transaction: Option<&'db Transaction<'db, S>>) -> Self {
         SubtreeChunkProducer {
             grove_db: storage,
+            transaction,
             cache: None,
         }
     }
@@ -82,7 +109,7 @@ impl<'db> SubtreeChunkProducer<'db> {
     pub fn chunks_in_current_producer(&self) -> usize {
         self.cache
             .as_ref()
-            .and_then(|c| c.current_chunk_producer.as_ref().map(|p| p.len()))
+            .map(|c| c.current_chunk_producer.len())
             .unwrap_or(0)
     }
 
@@ -104,54 +131,64 @@ impl<'db> SubtreeChunkProducer<'db> {
         }
 
         if self.cache.is_none() {
-            let current_merk = self
-                .grove_db
-                .open_non_transactional_merk_at_path(
-                    path_iter.clone().collect::<Vec<_>>().as_slice().into(),
-                    None,
+            let merk_path = path_iter.clone().collect::<Vec<_>>();
+            let current_chunk_producer = if let Some(transaction) = self.transaction {
+                let current_merk = self
+                    .grove_db
+                    .open_transactional_merk_at_path(merk_path.as_slice().into(), transaction, None)
+                    .unwrap()?;
+
+                if current_merk.root_key().is_none() {
+                    return Ok(Vec::new());
+                }
+
+                MerkChunkProducer::Transactional(
+                    grovedb_merk::ChunkProducer::new(&current_merk)
+                        .map_err(|e| Error::CorruptedData(e.to_string()))?,
                 )
-                .unwrap()?;
+            } else {
+                let current_merk = self
+                    .grove_db
+                    .open_non_transactional_merk_at_path(merk_path.as_slice().into(), None)
+                    .unwrap()?;
 
-            if current_merk.root_key().is_none() {
-                return Ok(Vec::new());
-            }
+                if current_merk.root_key().is_none() {
+                    return Ok(Vec::new());
+                }
+
+                MerkChunkProducer::NonTransactional(
+                    grovedb_merk::ChunkProducer::new(&current_merk)
+                        .map_err(|e| Error::CorruptedData(e.to_string()))?,
+                )
+            };
 
             self.cache = Some(SubtreeChunkProducerCache {
                 current_merk_path: path_iter.map(|p| p.to_vec()).collect(),
-                current_merk,
-                current_chunk_producer: None,
+                current_chunk_producer,
             });
-            let cache = self.cache.as_mut().expect("exists at this point");
-            cache.current_chunk_producer = Some(
-                grovedb_merk::ChunkProducer::new(&cache.current_merk)
-                    .map_err(|e| Error::CorruptedData(e.to_string()))?,
-            );
         }
 
         self.cache
             .as_mut()
             .expect("must exist at this point")
             .current_chunk_producer
-            .as_mut()
-            .expect("must exist at this point")
             .chunk(index)
             .map_err(|e| Error::CorruptedData(e.to_string()))
     }
 }
 
-// TODO: make generic over storage_cost context
-type MerkRestorer<'db> = grovedb_merk::Restorer<PrefixedRocksDbImmediateStorageContext<'db>>;
+type MerkRestorer<'db, S> = grovedb_merk::Restorer<<S as Storage<'db>>::ImmediateStorageContext>;
 
 type Path = Vec<Vec<u8>>;
 
 /// Structure to drive GroveDb restore process.
-pub struct Restorer<'db> {
-    current_merk_restorer: Option<MerkRestorer<'db>>,
+pub struct Restorer<'db, S: CovariantStorage = RocksDbStorage> {
+    current_merk_restorer: Option<MerkRestorer<'db, S>>,
     current_merk_chunk_index: usize,
     current_merk_path: Path,
     queue: VecDeque<(Path, Vec<u8>, Hash, TreeFeatureType)>,
-    grove_db: &'db GroveDb,
-    tx: &'db Transaction<'db>,
+    grove_db: &'db GroveDb<S>,
+    tx: &'db Transaction<'db, S>,
 }
 
 /// Indicates what next piece of information `Restorer` expects or wraps a
@@ -165,16 +202,16 @@ pub enum RestorerResponse {
 #[derive(Debug)]
 pub struct RestorerError(String);
 
-impl<'db> Restorer<'db> {
+impl<'db, S: CovariantStorage> Restorer<'db, S> {
     /// Create a GroveDb restorer using a backing storage_cost and root hash.
     pub fn new(
-        grove_db: &'db GroveDb,
+        grove_db: &'db GroveDb<S>,
         root_hash: Hash,
-        tx: &'db Transaction<'db>,
+        tx: &'db Transaction<'db, S>,
     ) -> Result<Self, RestorerError> {
         Ok(Restorer {
             tx,
-            current_merk_restorer: Some(MerkRestorer::new(
+            current_merk_restorer: Some(MerkRestorer::<S>::new(
                 Merk::open_base(
                     grove_db
                         .db
@@ -269,7 +306,7 @@ impl<'db> Restorer<'db> {
                     .grove_db
                     .open_merk_for_replication(next_path.as_slice().into(), self.tx)
                     .map_err(|e| RestorerError(e.to_string()))?;
-                self.current_merk_restorer = Some(MerkRestorer::new(
+                self.current_merk_restorer = Some(MerkRestorer::<S>::new(
                     merk,
                     Some(combining_value),
                     expected_hash,
@@ -300,8 +337,8 @@ impl<'db> Restorer<'db> {
 /// Because `Restorer` builds GroveDb replica breadth-first way from top to
 /// bottom it makes sense to send a subtree's siblings next instead of its own
 /// subtrees.
-pub struct SiblingsChunkProducer<'db> {
-    chunk_producer: SubtreeChunkProducer<'db>,
+pub struct SiblingsChunkProducer<'db, S: CovariantStorage = RocksDbStorage> {
+    chunk_producer: SubtreeChunkProducer<'db, S>,
 }
 
 #[derive(Debug)]
@@ -309,9 +346,9 @@ pub struct GroveChunk {
     subtree_chunks: Vec<(usize, Vec<Op>)>,
 }
 
-impl<'db> SiblingsChunkProducer<'db> {
+impl<'db, S: CovariantStorage> SiblingsChunkProducer<'db, S> {
     /// New
-    pub fn new(chunk_producer: SubtreeChunkProducer<'db>) -> Self {
+    pub fn new(chunk_producer: SubtreeChunkProducer<'db, S>) -> Self {
         SiblingsChunkProducer { chunk_producer }
     }
 
@@ -423,13 +460,13 @@ impl<'db> SiblingsChunkProducer<'db> {
 /// `Restorer` wrapper that applies multiple chunks at once and eventually
 /// returns less requests. It is named by analogy with IO types that do less
 /// syscalls.
-pub struct BufferedRestorer<'db> {
-    restorer: Restorer<'db>,
+pub struct BufferedRestorer<'db, S: CovariantStorage = RocksDbStorage> {
+    restorer: Restorer<'db, S>,
 }
 
-impl<'db> BufferedRestorer<'db> {
+impl<'db, S: CovariantStorage> BufferedRestorer<'db, S> {
     /// New
-    pub fn new(restorer: Restorer<'db>) -> Self {
+    pub fn new(restorer: Restorer<'db, S>) -> Self {
         BufferedRestorer { restorer }
     }
 
@@ -577,6 +614,37 @@ mod test {
         test_replication_internal(original_db, to_compare, replicate_bigger_messages);
     }
 
+    #[test]
+    fn chunks_with_transaction_see_uncommitted_writes() {
+        let db = make_test_grovedb();
+        let tx = db.start_transaction();
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            b"key1",
+            Element::new_item(b"ayy".to_vec()),
+            None,
+            Some(&tx),
+        )
+        .unwrap()
+        .expect("cannot insert an element");
+
+        let path = [TEST_LEAF];
+        assert!(db
+            .chunks()
+            .get_chunk(path.iter().copied(), 0)
+            .expect("cannot get chunk")
+            .is_empty());
+
+        let ops = db
+            .chunks_with_transaction(&tx)
+            .get_chunk(path.iter().copied(), 0)
+            .expect("cannot get chunk");
+        assert!(ops.iter().any(|op| matches!(
+            op,
+            Op::Push(Node::KVValueHashFeatureType(key, ..)) if key == b"key1"
+        )));
+    }
+
     #[test]
     fn replicate_wrong_root_hash() {
         let db = make_test_grovedb();
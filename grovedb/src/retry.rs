@@ -0,0 +1,236 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Retries of transactions failing because of conflicting commits
+
+use std::{thread, time::Duration};
+
+use grovedb_costs::{CostResult, CostsExt, OperationCost};
+use grovedb_storage::CovariantStorage;
+
+use crate::{Error, GroveDb, Transaction};
+
+/// Bounds the attempts `GroveDb::run_in_transaction` makes when a transaction
+/// fails with `Error::TransactionConflict`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Number of attempts, the conflict is returned once they are all made
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled before each next one
+    pub initial_backoff: Duration,
+    /// Upper bound of the delay between two attempts
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(100),
+        }
+    }
+}
+
+impl<S: CovariantStorage> GroveDb<S> {
+    /// Runs `f` in a new transaction and commits it, starting over in a fresh
+    /// transaction when `f` or the commit fail with
+    /// `Error::TransactionConflict`, as bounded by `retry_policy`. Any other
+    /// error drops the transaction and is returned. Costs of all attempts are
+    /// added up.
+    pub fn run_in_transaction<T>(
+        &self,
+        retry_policy: Option<RetryPolicy>,
+        mut f: impl FnMut(&Transaction<S>) -> CostResult<T, Error>,
+    ) -> CostResult<T, Error> {
+        let retry_policy = retry_policy.unwrap_or_default();
+        let mut cost = OperationCost::default();
+        let mut backoff = retry_policy.initial_backoff.min(retry_policy.max_backoff);
+        let mut attempt = 1;
+
+        loop {
+            let transaction = self.start_transaction();
+            let result = f(&transaction)
+                .flat_map_ok(|value| self.commit_transaction(transaction).map_ok(|_| value))
+                .unwrap_add_cost(&mut cost);
+
+            match result {
+                Err(Error::TransactionConflict) if attempt < retry_policy.max_attempts => {
+                    thread::sleep(backoff);
+                    backoff = backoff.saturating_mul(2).min(retry_policy.max_backoff);
+                    attempt += 1;
+                }
+                result => return result.wrap_with_cost(cost),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use grovedb_storage::memory_storage::MemoryStorage;
+
+    use super::*;
+    use crate::{
+        tests::{common::EMPTY_PATH, make_test_grovedb, TEST_LEAF},
+        Element, TransactionArg,
+    };
+
+    const NO_BACKOFF: RetryPolicy = RetryPolicy {
+        max_attempts: 3,
+        initial_backoff: Duration::ZERO,
+        max_backoff: Duration::ZERO,
+    };
+
+    fn insert_item<S: CovariantStorage>(
+        db: &GroveDb<S>,
+        key: &[u8],
+        value: &[u8],
+        transaction: TransactionArg<S>,
+    ) -> CostResult<(), Error> {
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            key,
+            Element::new_item(value.to_vec()),
+            None,
+            transaction,
+        )
+    }
+
+    fn check_conflicting_commit<S: CovariantStorage>(db: &GroveDb<S>) {
+        let transaction = db.start_transaction();
+        insert_item(db, b"key", b"tx", Some(&transaction))
+            .unwrap()
+            .expect("cannot insert item");
+        insert_item(db, b"key", b"other", None)
+            .unwrap()
+            .expect("cannot insert item");
+
+        assert!(matches!(
+            db.commit_transaction(transaction).unwrap(),
+            Err(Error::TransactionConflict)
+        ));
+        assert_eq!(
+            db.get([TEST_LEAF].as_ref(), b"key", None).unwrap().unwrap(),
+            Element::new_item(b"other".to_vec())
+        );
+    }
+
+    fn check_retries<S: CovariantStorage>(db: &GroveDb<S>) {
+        let attempts = Cell::new(0);
+        db.run_in_transaction(Some(NO_BACKOFF), |transaction| {
+            attempts.set(attempts.get() + 1);
+            insert_item(db, b"retried", b"tx", Some(transaction)).flat_map_ok(|_| {
+                if attempts.get() == 1 {
+                    insert_item(db, b"retried", b"other", None)
+                } else {
+                    Ok(()).wrap_with_cost(Default::default())
+                }
+            })
+        })
+        .unwrap()
+        .expect("transaction should succeed once retried");
+        assert_eq!(attempts.get(), 2);
+        assert_eq!(
+            db.get([TEST_LEAF].as_ref(), b"retried", None)
+                .unwrap()
+                .unwrap(),
+            Element::new_item(b"tx".to_vec())
+        );
+
+        let attempts = Cell::new(0);
+        let result = db
+            .run_in_transaction(Some(NO_BACKOFF), |transaction| {
+                attempts.set(attempts.get() + 1);
+                insert_item(db, b"contended", b"tx", Some(transaction))
+                    .flat_map_ok(|_| insert_item(db, b"contended", b"other", None))
+            })
+            .unwrap();
+        assert!(matches!(result, Err(Error::TransactionConflict)));
+        assert_eq!(attempts.get(), NO_BACKOFF.max_attempts);
+
+        let attempts = Cell::new(0);
+        let result = db
+            .run_in_transaction(Some(NO_BACKOFF), |_| {
+                attempts.set(attempts.get() + 1);
+                Err::<(), _>(Error::InternalError("step failed")).wrap_with_cost(Default::default())
+            })
+            .unwrap();
+        assert!(matches!(result, Err(Error::InternalError(_))));
+        assert_eq!(attempts.get(), 1);
+    }
+
+    fn make_memory_grovedb() -> GroveDb<MemoryStorage> {
+        let db = GroveDb::with_storage(MemoryStorage::new());
+        db.insert(EMPTY_PATH, TEST_LEAF, Element::empty_tree(), None, None)
+            .unwrap()
+            .expect("cannot insert leaf");
+        db
+    }
+
+    #[test]
+    fn test_conflicting_commit() {
+        check_conflicting_commit(&*make_test_grovedb());
+        check_conflicting_commit(&make_memory_grovedb());
+    }
+
+    #[test]
+    fn test_run_in_transaction_retries() {
+        check_retries(&*make_test_grovedb());
+        check_retries(&make_memory_grovedb());
+    }
+
+    fn check_validation<S: CovariantStorage>(db: &GroveDb<S>) {
+        let transaction = db.start_transaction();
+        insert_item(db, b"key", b"tx", Some(&transaction))
+            .unwrap()
+            .expect("cannot insert item");
+        db.validate_transaction(&transaction)
+            .expect("transaction should be valid");
+
+        insert_item(db, b"key", b"other", None)
+            .unwrap()
+            .expect("cannot insert item");
+        assert!(matches!(
+            db.validate_transaction(&transaction),
+            Err(Error::TransactionConflict)
+        ));
+        assert!(matches!(
+            db.commit_transaction(transaction).unwrap(),
+            Err(Error::TransactionConflict)
+        ));
+    }
+
+    #[test]
+    fn test_validate_transaction() {
+        check_validation(&*make_test_grovedb());
+        check_validation(&make_memory_grovedb());
+    }
+}
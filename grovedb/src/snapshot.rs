@@ -0,0 +1,161 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Read snapshots of GroveDb pinned to the data committed when they are taken.
+
+use std::ops::Deref;
+
+use grovedb_storage::{rocksdb_storage::RocksDbStorage, CovariantStorage};
+
+use crate::{GroveDb, Transaction, TransactionArg};
+
+/// Read only view of GroveDb pinned to the data committed when it was taken,
+/// so reads and proofs made through it see one consistent root no matter
+/// what is committed meanwhile. It is passed wherever a `TransactionArg` is
+/// accepted using `Snapshot::transaction_arg`. A snapshot can't be committed,
+/// writes made through it fail with `Error::NotSupported`.
+pub struct Snapshot<'db, S: CovariantStorage = RocksDbStorage> {
+    transaction: Transaction<'db, S>,
+}
+
+impl<'db, S: CovariantStorage> Snapshot<'db, S> {
+    /// Returns the snapshot as a transaction argument
+    pub fn transaction_arg(&self) -> TransactionArg<'db, '_, S> {
+        Some(&self.transaction)
+    }
+}
+
+impl<'db, S: CovariantStorage> Deref for Snapshot<'db, S> {
+    type Target = Transaction<'db, S>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.transaction
+    }
+}
+
+impl<S: CovariantStorage> GroveDb<S> {
+    /// Takes a read snapshot of the data committed so far, see `Snapshot`
+    pub fn snapshot(&self) -> Snapshot<'_, S> {
+        Snapshot {
+            transaction: self.db.start_snapshot_transaction(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use grovedb_storage::memory_storage::MemoryStorage;
+
+    use super::*;
+    use crate::{
+        tests::{common::EMPTY_PATH, make_test_grovedb, TEST_LEAF},
+        Element, Error, PathQuery, Query,
+    };
+
+    fn insert_item<S: CovariantStorage>(db: &GroveDb<S>, key: &[u8], value: &[u8]) {
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            key,
+            Element::new_item(value.to_vec()),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("cannot insert item");
+    }
+
+    /// Checks that reads through a snapshot ignore what was committed after it
+    fn check_snapshot_isolation<S: CovariantStorage>(db: &GroveDb<S>) {
+        insert_item(db, b"key1", b"old");
+        let root_hash = db.root_hash(None).unwrap().unwrap();
+
+        let snapshot = db.snapshot();
+        insert_item(db, b"key1", b"new");
+        insert_item(db, b"key2", b"new");
+        assert_ne!(db.root_hash(None).unwrap().unwrap(), root_hash);
+        assert!(matches!(
+            db.insert(
+                [TEST_LEAF].as_ref(),
+                b"key3",
+                Element::new_item(b"new".to_vec()),
+                None,
+                snapshot.transaction_arg(),
+            )
+            .unwrap(),
+            Err(Error::NotSupported(_))
+        ));
+
+        assert_eq!(
+            db.root_hash(snapshot.transaction_arg()).unwrap().unwrap(),
+            root_hash
+        );
+        assert_eq!(
+            db.get([TEST_LEAF].as_ref(), b"key1", snapshot.transaction_arg())
+                .unwrap()
+                .unwrap(),
+            Element::new_item(b"old".to_vec())
+        );
+        assert!(matches!(
+            db.get([TEST_LEAF].as_ref(), b"key2", snapshot.transaction_arg())
+                .unwrap(),
+            Err(Error::PathKeyNotFound(_))
+        ));
+
+        let mut query = Query::new();
+        query.insert_all();
+        let path_query = PathQuery::new_unsized(vec![TEST_LEAF.to_vec()], query);
+        let (values, _) = db
+            .query_item_value(&path_query, true, snapshot.transaction_arg())
+            .unwrap()
+            .unwrap();
+        assert_eq!(values, vec![b"old".to_vec()]);
+
+        let proof = db
+            .prove_query_with_transaction(&path_query, snapshot.transaction_arg())
+            .unwrap()
+            .unwrap();
+        let (proven_root_hash, result_set) = GroveDb::verify_query(&proof, &path_query).unwrap();
+        assert_eq!(proven_root_hash, root_hash);
+        assert_eq!(result_set.len(), 1);
+    }
+
+    #[test]
+    fn test_snapshot_isolation() {
+        let db = make_test_grovedb();
+        check_snapshot_isolation(&db);
+    }
+
+    #[test]
+    fn test_snapshot_isolation_on_memory_storage() {
+        let db = GroveDb::with_storage(MemoryStorage::new());
+        db.insert(EMPTY_PATH, TEST_LEAF, Element::empty_tree(), None, None)
+            .unwrap()
+            .expect("cannot insert leaf");
+        check_snapshot_isolation(&db);
+    }
+}
@@ -0,0 +1,345 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Sub transactions rolled back independently of the transaction they are
+//! part of.
+
+use std::ops::Deref;
+
+use grovedb_storage::{rocksdb_storage::RocksDbStorage, CovariantStorage};
+
+use crate::{Error, GroveDb, Transaction, TransactionArg};
+
+/// Part of a transaction that can be rolled back on its own, built on
+/// transaction savepoints. Writes made through `transaction_arg` are kept in
+/// the transaction by `commit` and discarded by `rollback`, dropping a sub
+/// transaction that wasn't committed rolls it back. Sub transactions nest, a
+/// rollback also discards the writes of the committed sub transactions
+/// started from it. Only the most recently started sub transaction that is
+/// still open can be committed or rolled back.
+pub struct SubTransaction<'db, 'a, S: CovariantStorage = RocksDbStorage> {
+    db: &'a GroveDb<S>,
+    transaction: &'a Transaction<'db, S>,
+    /// Savepoint depth of the transaction with the savepoint of this sub
+    /// transaction on top
+    depth: usize,
+    finished: bool,
+}
+
+impl<S: CovariantStorage> GroveDb<S> {
+    /// Starts a sub transaction of `transaction`, see `SubTransaction`
+    pub fn start_sub_transaction<'db, 'a>(
+        &'a self,
+        transaction: &'a Transaction<'db, S>,
+    ) -> SubTransaction<'db, 'a, S> {
+        SubTransaction::new(self, transaction)
+    }
+}
+
+impl<'db, 'a, S: CovariantStorage> SubTransaction<'db, 'a, S> {
+    fn new(db: &'a GroveDb<S>, transaction: &'a Transaction<'db, S>) -> Self {
+        db.db.set_savepoint(transaction);
+        SubTransaction {
+            db,
+            transaction,
+            depth: db.db.savepoint_depth(transaction),
+            finished: false,
+        }
+    }
+
+    /// Whether the savepoint of this sub transaction is the last one of the
+    /// transaction
+    fn is_on_top(&self) -> bool {
+        self.db.db.savepoint_depth(self.transaction) == self.depth
+    }
+
+    /// Returns the transaction argument to write through
+    pub fn transaction_arg(&self) -> TransactionArg<'db, '_, S> {
+        Some(self.transaction)
+    }
+
+    /// Starts a sub transaction of this one
+    pub fn start_sub_transaction(&self) -> SubTransaction<'db, '_, S> {
+        SubTransaction::new(self.db, self.transaction)
+    }
+
+    /// Keeps the writes of the sub transaction in the transaction it is part
+    /// of, fails leaving the transaction as it is if a sub transaction started
+    /// later is still open
+    pub fn commit(mut self) -> Result<(), Error> {
+        self.finished = true;
+        if !self.is_on_top() {
+            return Err(Error::InvalidCodeExecution(
+                "sub transaction committed while a later one is open",
+            ));
+        }
+        Ok(self.db.db.release_savepoint(self.transaction)?)
+    }
+
+    /// Discards the writes of the sub transaction, fails leaving the
+    /// transaction as it is if a sub transaction started later is still open
+    pub fn rollback(mut self) -> Result<(), Error> {
+        self.finished = true;
+        if !self.is_on_top() {
+            return Err(Error::InvalidCodeExecution(
+                "sub transaction rolled back while a later one is open",
+            ));
+        }
+        Ok(self.db.db.rollback_to_savepoint(self.transaction)?)
+    }
+}
+
+impl<'db, 'a, S: CovariantStorage> Deref for SubTransaction<'db, 'a, S> {
+    type Target = Transaction<'db, S>;
+
+    fn deref(&self) -> &Self::Target {
+        self.transaction
+    }
+}
+
+impl<'db, 'a, S: CovariantStorage> Drop for SubTransaction<'db, 'a, S> {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        if !self.is_on_top() {
+            // Rolling back would discard the writes of the later sub
+            // transaction instead
+            debug_assert!(
+                std::thread::panicking(),
+                "sub transaction dropped while a later one is open"
+            );
+            return;
+        }
+        // Errors can't be reported from a drop, `rollback` reports them
+        let _ = self.db.db.rollback_to_savepoint(self.transaction);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use grovedb_storage::memory_storage::MemoryStorage;
+
+    use super::*;
+    use crate::{
+        tests::{common::EMPTY_PATH, make_test_grovedb, TEST_LEAF},
+        Element,
+    };
+
+    fn insert_item<S: CovariantStorage>(
+        db: &GroveDb<S>,
+        key: &[u8],
+        transaction: TransactionArg<S>,
+    ) {
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            key,
+            Element::new_item(key.to_vec()),
+            None,
+            transaction,
+        )
+        .unwrap()
+        .expect("cannot insert item");
+    }
+
+    fn insert_tree_with_item<S: CovariantStorage>(
+        db: &GroveDb<S>,
+        key: &[u8],
+        transaction: TransactionArg<S>,
+    ) {
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            key,
+            Element::empty_tree(),
+            None,
+            transaction,
+        )
+        .unwrap()
+        .expect("cannot insert tree");
+        db.insert(
+            [TEST_LEAF, key].as_ref(),
+            b"item",
+            Element::new_item(b"value".to_vec()),
+            None,
+            transaction,
+        )
+        .unwrap()
+        .expect("cannot insert item");
+    }
+
+    fn has_item<S: CovariantStorage>(
+        db: &GroveDb<S>,
+        key: &[u8],
+        transaction: TransactionArg<S>,
+    ) -> bool {
+        db.get_raw_optional([TEST_LEAF].as_ref().into(), key, transaction)
+            .unwrap()
+            .expect("cannot get item")
+            .is_some()
+    }
+
+    /// Checks that rolled back sub steps leave the transaction with the root
+    /// hash and data it had before them
+    fn check_partial_rollbacks<S: CovariantStorage>(db: &GroveDb<S>) {
+        insert_item(db, b"base", None);
+        let root_hash = db.root_hash(None).unwrap().unwrap();
+        let tx = db.start_transaction();
+
+        db.set_savepoint(&tx);
+        insert_tree_with_item(db, b"tree", Some(&tx));
+        db.rollback_to_savepoint(&tx)
+            .expect("cannot rollback to savepoint");
+        assert_eq!(db.root_hash(Some(&tx)).unwrap().unwrap(), root_hash);
+        assert!(!has_item(db, b"tree", Some(&tx)));
+
+        let outer = db.start_sub_transaction(&tx);
+        insert_item(db, b"a", outer.transaction_arg());
+        let outer_root_hash = db.root_hash(Some(&tx)).unwrap().unwrap();
+
+        let inner = outer.start_sub_transaction();
+        insert_item(db, b"b", inner.transaction_arg());
+        inner.rollback().expect("cannot rollback sub transaction");
+        assert_eq!(db.root_hash(Some(&tx)).unwrap().unwrap(), outer_root_hash);
+
+        let inner = outer.start_sub_transaction();
+        insert_tree_with_item(db, b"c", inner.transaction_arg());
+        inner.commit().expect("cannot commit sub transaction");
+        {
+            let inner = outer.start_sub_transaction();
+            insert_item(db, b"d", inner.transaction_arg());
+        }
+        assert!(has_item(db, b"c", Some(&tx)));
+        assert!(!has_item(db, b"d", Some(&tx)));
+
+        outer.rollback().expect("cannot rollback sub transaction");
+        assert_eq!(db.root_hash(Some(&tx)).unwrap().unwrap(), root_hash);
+        assert!(!has_item(db, b"a", Some(&tx)));
+        assert!(!has_item(db, b"c", Some(&tx)));
+
+        let kept = db.start_sub_transaction(&tx);
+        insert_tree_with_item(db, b"e", kept.transaction_arg());
+        kept.commit().expect("cannot commit sub transaction");
+
+        // Committed sub transactions don't leave savepoints behind
+        db.set_savepoint(&tx);
+        let released = db.start_sub_transaction(&tx);
+        insert_item(db, b"f", released.transaction_arg());
+        released.commit().expect("cannot commit sub transaction");
+        db.rollback_to_savepoint(&tx)
+            .expect("cannot rollback to savepoint");
+        assert!(has_item(db, b"e", Some(&tx)));
+        assert!(!has_item(db, b"f", Some(&tx)));
+        assert!(db.rollback_to_savepoint(&tx).is_err());
+
+        db.commit_transaction(tx)
+            .unwrap()
+            .expect("cannot commit transaction");
+
+        assert!(has_item(db, b"e", None));
+        assert!(!has_item(db, b"a", None));
+        assert!(db.verify_grovedb().is_empty());
+    }
+
+    /// Checks that sibling sub transactions can only be finished in the
+    /// reverse order they were started in
+    fn check_interleaved_sub_transactions<S: CovariantStorage>(db: &GroveDb<S>) {
+        let tx = db.start_transaction();
+        db.set_savepoint(&tx);
+
+        let first = db.start_sub_transaction(&tx);
+        insert_item(db, b"a", first.transaction_arg());
+        let second = db.start_sub_transaction(&tx);
+        insert_item(db, b"b", second.transaction_arg());
+        assert!(matches!(
+            first.commit(),
+            Err(Error::InvalidCodeExecution(_))
+        ));
+        assert!(has_item(db, b"a", Some(&tx)));
+        assert!(has_item(db, b"b", Some(&tx)));
+
+        let third = db.start_sub_transaction(&tx);
+        insert_item(db, b"c", third.transaction_arg());
+        assert!(matches!(
+            second.rollback(),
+            Err(Error::InvalidCodeExecution(_))
+        ));
+        third.rollback().expect("cannot rollback sub transaction");
+        assert!(has_item(db, b"b", Some(&tx)));
+        assert!(!has_item(db, b"c", Some(&tx)));
+
+        // The savepoints of the sub transactions that failed to finish are
+        // left to the transaction
+        db.rollback_to_savepoint(&tx)
+            .expect("cannot rollback to savepoint");
+        assert!(has_item(db, b"a", Some(&tx)));
+        assert!(!has_item(db, b"b", Some(&tx)));
+        db.rollback_to_savepoint(&tx)
+            .expect("cannot rollback to savepoint");
+        assert!(!has_item(db, b"a", Some(&tx)));
+        db.rollback_to_savepoint(&tx)
+            .expect("cannot rollback to savepoint");
+        assert!(db.rollback_to_savepoint(&tx).is_err());
+    }
+
+    #[test]
+    fn test_interleaved_sub_transactions() {
+        let db = make_test_grovedb();
+        check_interleaved_sub_transactions(&db);
+        let db = GroveDb::with_storage(MemoryStorage::new());
+        db.insert(EMPTY_PATH, TEST_LEAF, Element::empty_tree(), None, None)
+            .unwrap()
+            .expect("cannot insert leaf");
+        check_interleaved_sub_transactions(&db);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "sub transaction dropped while a later one is open")]
+    fn test_dropping_sub_transaction_below_another() {
+        let db = make_test_grovedb();
+        let tx = db.start_transaction();
+        let first = db.start_sub_transaction(&tx);
+        let _second = db.start_sub_transaction(&tx);
+        drop(first);
+    }
+
+    #[test]
+    fn test_partial_rollbacks() {
+        let db = make_test_grovedb();
+        check_partial_rollbacks(&db);
+    }
+
+    #[test]
+    fn test_partial_rollbacks_on_memory_storage() {
+        let db = GroveDb::with_storage(MemoryStorage::new());
+        db.insert(EMPTY_PATH, TEST_LEAF, Element::empty_tree(), None, None)
+            .unwrap()
+            .expect("cannot insert leaf");
+        check_partial_rollbacks(&db);
+    }
+}
@@ -30,6 +30,8 @@
 
 pub mod common;
 
+mod no_std_verify_tests;
+
 mod query_tests;
 
 mod sum_tree_tests;
@@ -47,8 +49,10 @@ use tempfile::TempDir;
 use self::common::EMPTY_PATH;
 use super::*;
 use crate::{
+    operations::proof::{util::SubtreeStatus, verify::ProofVerifier},
     query_result_type::QueryResultType::QueryKeyElementPairResultType,
-    reference_path::ReferencePathType, tests::common::compare_result_tuples,
+    reference_path::ReferencePathType,
+    tests::common::compare_result_tuples,
 };
 
 pub const TEST_LEAF: &[u8] = b"test_leaf";
@@ -1124,6 +1128,381 @@ fn test_proof_for_non_existent_data() {
     assert_eq!(result_set.len(), 0);
 }
 
+#[test]
+fn test_subtree_status_proofs() {
+    let db = make_deep_tree();
+    db.insert(
+        [TEST_LEAF].as_ref(),
+        b"empty_tree",
+        Element::empty_tree(),
+        None,
+        None,
+    )
+    .unwrap()
+    .expect("successful subtree insert");
+    db.insert(
+        [TEST_LEAF].as_ref(),
+        b"sum_tree",
+        Element::empty_sum_tree(),
+        None,
+        None,
+    )
+    .unwrap()
+    .expect("successful sum tree insert");
+    db.insert(
+        [TEST_LEAF, b"sum_tree"].as_ref(),
+        b"item1",
+        Element::new_sum_item(5),
+        None,
+        None,
+    )
+    .unwrap()
+    .expect("successful sum item insert");
+    db.insert(
+        [TEST_LEAF, b"sum_tree"].as_ref(),
+        b"item2",
+        Element::new_sum_item(-2),
+        None,
+        None,
+    )
+    .unwrap()
+    .expect("successful sum item insert");
+    let root_hash = db.root_hash(None).unwrap().unwrap();
+
+    let prove_and_verify = |path: &[&[u8]]| {
+        let path = path.iter().map(|key| key.to_vec()).collect::<Vec<_>>();
+        let proof = db.prove_subtree_status(&path).unwrap().unwrap();
+        let (hash, status) =
+            GroveDb::verify_subtree_status(&proof, &path).expect("should execute proof");
+        assert_eq!(hash, root_hash);
+        status
+    };
+    let subtree_root_hash = |path: &[&[u8]]| {
+        db.open_non_transactional_merk_at_path(path.into(), None)
+            .unwrap()
+            .expect("should open subtree")
+            .root_hash()
+            .unwrap()
+    };
+
+    assert_eq!(
+        prove_and_verify(&[DEEP_LEAF, b"deep_node_1"]),
+        SubtreeStatus::NonEmpty {
+            root_hash: subtree_root_hash(&[DEEP_LEAF, b"deep_node_1"]),
+            sum: None,
+        }
+    );
+    assert_eq!(
+        prove_and_verify(&[TEST_LEAF, b"sum_tree"]),
+        SubtreeStatus::NonEmpty {
+            root_hash: subtree_root_hash(&[TEST_LEAF, b"sum_tree"]),
+            sum: Some(3),
+        }
+    );
+    assert_eq!(
+        prove_and_verify(&[]),
+        SubtreeStatus::NonEmpty {
+            root_hash,
+            sum: None,
+        }
+    );
+    assert_eq!(
+        prove_and_verify(&[TEST_LEAF, b"empty_tree"]),
+        SubtreeStatus::Empty
+    );
+    assert_eq!(
+        prove_and_verify(&[DEEP_LEAF, b"invalid_key"]),
+        SubtreeStatus::Absent
+    );
+    assert_eq!(
+        prove_and_verify(&[b"invalid_key", b"deep_node_1"]),
+        SubtreeStatus::Absent
+    );
+    // paths through elements that are not trees don't lead to a subtree either
+    assert_eq!(
+        prove_and_verify(&[TEST_LEAF, b"innertree", b"key1"]),
+        SubtreeStatus::Absent
+    );
+    assert_eq!(
+        prove_and_verify(&[TEST_LEAF, b"innertree", b"key1", b"deeper"]),
+        SubtreeStatus::Absent
+    );
+
+    // a proof for one path doesn't verify for another one
+    let proof = db
+        .prove_subtree_status(&[TEST_LEAF.to_vec(), b"empty_tree".to_vec()])
+        .unwrap()
+        .unwrap();
+    assert!(
+        GroveDb::verify_subtree_status(&proof, &[TEST_LEAF.to_vec(), b"sum_tree".to_vec()])
+            .is_err()
+    );
+}
+
+#[test]
+fn test_subtree_audit_proofs() {
+    let db = make_deep_tree();
+    db.insert(
+        [DEEP_LEAF, b"deep_node_1"].as_ref(),
+        b"empty_tree",
+        Element::empty_tree(),
+        None,
+        None,
+    )
+    .unwrap()
+    .expect("successful subtree insert");
+    db.insert(
+        [DEEP_LEAF, b"deep_node_2"].as_ref(),
+        b"reference",
+        Element::new_reference(ReferencePathType::AbsolutePathReference(vec![
+            DEEP_LEAF.to_vec(),
+            b"deep_node_1".to_vec(),
+            b"deeper_1".to_vec(),
+            b"key2".to_vec(),
+        ])),
+        None,
+        None,
+    )
+    .unwrap()
+    .expect("successful reference insert");
+    // big enough to be split into a trunk and leaf chunks
+    db.insert(
+        [DEEP_LEAF].as_ref(),
+        b"big_node",
+        Element::empty_tree(),
+        None,
+        None,
+    )
+    .unwrap()
+    .expect("successful subtree insert");
+    for i in 0u32..1200 {
+        db.insert(
+            [DEEP_LEAF, b"big_node"].as_ref(),
+            &i.to_be_bytes(),
+            Element::new_item(i.to_le_bytes().to_vec()),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful item insert");
+    }
+    let root_hash = db.root_hash(None).unwrap().unwrap();
+
+    let proof = db
+        .prove_subtree_audit(&[DEEP_LEAF.to_vec()])
+        .unwrap()
+        .expect("should generate audit proof");
+    let (hash, elements) = GroveDb::verify_subtree_audit(&proof, &[DEEP_LEAF.to_vec()])
+        .expect("should verify audit proof");
+    assert_eq!(hash, root_hash);
+    // 3 trees at the first level, 5 trees and a reference below the deep nodes,
+    // 11 items and the items of the big tree
+    assert_eq!(elements.len(), 1220);
+    assert!(elements.contains(&(
+        vec![
+            DEEP_LEAF.to_vec(),
+            b"deep_node_1".to_vec(),
+            b"deeper_1".to_vec()
+        ],
+        b"key2".to_vec(),
+        Element::new_item(b"value2".to_vec())
+    )));
+    assert!(elements.contains(&(
+        vec![DEEP_LEAF.to_vec(), b"deep_node_1".to_vec()],
+        b"empty_tree".to_vec(),
+        Element::empty_tree()
+    )));
+    assert!(elements.contains(&(
+        vec![DEEP_LEAF.to_vec(), b"big_node".to_vec()],
+        1199u32.to_be_bytes().to_vec(),
+        Element::new_item(1199u32.to_le_bytes().to_vec())
+    )));
+    assert!(elements.iter().any(|(path, key, element)| path
+        == &vec![DEEP_LEAF.to_vec(), b"deep_node_2".to_vec()]
+        && key == b"reference"
+        && matches!(element, Element::Reference(..))));
+
+    // the whole database
+    let proof = db.prove_subtree_audit(&[]).unwrap().unwrap();
+    let (hash, elements) =
+        GroveDb::verify_subtree_audit(&proof, &[]).expect("should verify audit proof");
+    assert_eq!(hash, root_hash);
+    assert!(elements
+        .iter()
+        .any(|(path, key, _)| path.is_empty() && key == DEEP_LEAF));
+    assert!(elements.contains(&(
+        vec![
+            DEEP_LEAF.to_vec(),
+            b"deep_node_1".to_vec(),
+            b"deeper_1".to_vec()
+        ],
+        b"key2".to_vec(),
+        Element::new_item(b"value2".to_vec())
+    )));
+
+    // an empty subtree
+    let empty_path = vec![
+        DEEP_LEAF.to_vec(),
+        b"deep_node_1".to_vec(),
+        b"empty_tree".to_vec(),
+    ];
+    let proof = db.prove_subtree_audit(&empty_path).unwrap().unwrap();
+    let (hash, elements) =
+        GroveDb::verify_subtree_audit(&proof, &empty_path).expect("should verify audit proof");
+    assert_eq!(hash, root_hash);
+    assert!(elements.is_empty());
+
+    // a path that is not a subtree can't be audited
+    assert!(db
+        .prove_subtree_audit(&[DEEP_LEAF.to_vec(), b"invalid_key".to_vec()])
+        .unwrap()
+        .is_err());
+
+    // a proof for one path doesn't verify for another one
+    let proof = db
+        .prove_subtree_audit(&[DEEP_LEAF.to_vec(), b"deep_node_1".to_vec()])
+        .unwrap()
+        .unwrap();
+    assert!(
+        GroveDb::verify_subtree_audit(&proof, &[DEEP_LEAF.to_vec(), b"deep_node_2".to_vec()])
+            .map_or(true, |(hash, _)| hash != root_hash)
+    );
+
+    // tampering with an element is detected
+    let value_position = proof
+        .windows(6)
+        .position(|window| window == b"value4")
+        .expect("proof contains the item value");
+    let mut tampered_proof = proof.clone();
+    tampered_proof[value_position + 5] = b'X';
+    assert!(GroveDb::verify_subtree_audit(
+        &tampered_proof,
+        &[DEEP_LEAF.to_vec(), b"deep_node_1".to_vec()]
+    )
+    .is_err());
+}
+
+#[test]
+fn test_batch_proof_verification() {
+    let db = make_deep_tree();
+    let root_hash = db.root_hash(None).unwrap().unwrap();
+
+    let mut query = Query::new();
+    query.insert_all();
+    let deeper_1_query = PathQuery::new_unsized(
+        vec![
+            DEEP_LEAF.to_vec(),
+            b"deep_node_1".to_vec(),
+            b"deeper_1".to_vec(),
+        ],
+        query.clone(),
+    );
+    let deeper_2_query = PathQuery::new_unsized(
+        vec![
+            DEEP_LEAF.to_vec(),
+            b"deep_node_1".to_vec(),
+            b"deeper_2".to_vec(),
+        ],
+        query,
+    );
+    let mut query = Query::new();
+    query.insert_key(b"key7".to_vec());
+    let deeper_3_query = PathQuery::new_unsized(
+        vec![
+            DEEP_LEAF.to_vec(),
+            b"deep_node_2".to_vec(),
+            b"deeper_3".to_vec(),
+        ],
+        query,
+    );
+    let queries = [&deeper_1_query, &deeper_2_query, &deeper_3_query];
+    let proofs = queries
+        .iter()
+        .map(|query| db.prove_query(query).unwrap().unwrap())
+        .collect::<Vec<_>>();
+    let batch = proofs
+        .iter()
+        .zip(queries)
+        .map(|(proof, query)| (proof.as_slice(), query))
+        .collect::<Vec<_>>();
+
+    // every proof verified on its own
+    let mut separate_cost = OperationCost::default();
+    for (proof, query) in batch.iter() {
+        let (hash, elements) = ProofVerifier::new([root_hash])
+            .verify_query(proof, query)
+            .unwrap_add_cost(&mut separate_cost)
+            .expect("should verify proof");
+        assert_eq!(
+            (hash, elements),
+            GroveDb::verify_query(proof, query).unwrap()
+        );
+    }
+
+    // the batch shares the upper layers of the proofs
+    let mut verifier = ProofVerifier::new([root_hash]);
+    let results = verifier.verify_queries(&batch);
+    let batch_cost = results.cost().clone();
+    let results = results.unwrap().expect("should verify batch");
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].1.len(), 3);
+    assert_eq!(results[1].1.len(), 3);
+    assert_eq!(
+        results[2].1,
+        vec![(
+            deeper_3_query.path.clone(),
+            b"key7".to_vec(),
+            Some(Element::new_item(b"value7".to_vec()))
+        )]
+    );
+    assert!(results.iter().all(|(hash, _)| hash == &root_hash));
+    assert!(batch_cost.hash_node_calls < separate_cost.hash_node_calls);
+
+    // verifying again only hashes the layers with the results
+    let again_cost = verifier.verify_queries(&batch).cost().clone();
+    assert!(again_cost.hash_node_calls < batch_cost.hash_node_calls);
+
+    // a tampered proof is reported with its index
+    let mut tampered_proof = proofs[1].clone();
+    let value_position = tampered_proof
+        .windows(6)
+        .position(|window| window == b"value5")
+        .expect("proof contains the item value");
+    tampered_proof[value_position + 5] = b'X';
+    let mut tampered_batch = batch.clone();
+    tampered_batch[1].0 = tampered_proof.as_slice();
+    assert!(matches!(
+        verifier.verify_queries(&tampered_batch).unwrap(),
+        Err(Error::InvalidBatchProof(1, _))
+    ));
+
+    // proofs of a new state are only accepted once its root hash is trusted
+    db.insert(
+        [DEEP_LEAF, b"deep_node_1", b"deeper_1"].as_ref(),
+        b"key4",
+        Element::new_item(b"value4".to_vec()),
+        None,
+        None,
+    )
+    .unwrap()
+    .expect("successful item insert");
+    let new_root_hash = db.root_hash(None).unwrap().unwrap();
+    let new_proof = db.prove_query(&deeper_1_query).unwrap().unwrap();
+    let new_batch = [(new_proof.as_slice(), &deeper_1_query)];
+    assert!(matches!(
+        verifier.verify_queries(&new_batch).unwrap(),
+        Err(Error::InvalidBatchProof(0, _))
+    ));
+    verifier.trust_root_hash(new_root_hash);
+    let results = verifier
+        .verify_queries(&new_batch)
+        .unwrap()
+        .expect("should verify proof of the new state");
+    assert_eq!(results[0].1.len(), 4);
+    assert!(verifier.distrust_root_hash(&root_hash));
+    assert!(verifier.verify_queries(&batch).unwrap().is_err());
+}
+
 #[test]
 fn test_path_query_proofs_without_subquery_with_reference() {
     // Tree Structure
@@ -2907,3 +3286,194 @@ fn test_tree_value_exists_method_tx() {
         .unwrap());
     assert!(db.has_raw(EMPTY_PATH, b"leaf", None).unwrap().unwrap());
 }
+
+#[test]
+fn test_grovedb_on_memory_storage() {
+    let rocksdb = make_test_grovedb();
+    let db = GroveDb::with_storage(grovedb_storage::memory_storage::MemoryStorage::new());
+    db.insert(EMPTY_PATH, TEST_LEAF, Element::empty_tree(), None, None)
+        .unwrap()
+        .expect("successful root tree leaf insert");
+    db.insert(
+        EMPTY_PATH,
+        ANOTHER_TEST_LEAF,
+        Element::empty_tree(),
+        None,
+        None,
+    )
+    .unwrap()
+    .expect("successful root tree leaf 2 insert");
+    assert_eq!(
+        db.root_hash(None).unwrap().unwrap(),
+        rocksdb.root_hash(None).unwrap().unwrap()
+    );
+
+    let tx = db.start_transaction();
+    db.insert(
+        [TEST_LEAF].as_ref(),
+        b"key",
+        Element::new_item(b"ayy".to_vec()),
+        None,
+        Some(&tx),
+    )
+    .unwrap()
+    .expect("cannot insert item");
+    assert!(db
+        .has_raw([TEST_LEAF].as_ref(), b"key", Some(&tx))
+        .unwrap()
+        .unwrap());
+    assert!(!db
+        .has_raw([TEST_LEAF].as_ref(), b"key", None)
+        .unwrap()
+        .unwrap());
+    db.commit_transaction(tx)
+        .unwrap()
+        .expect("cannot commit transaction");
+
+    rocksdb
+        .insert(
+            [TEST_LEAF].as_ref(),
+            b"key",
+            Element::new_item(b"ayy".to_vec()),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("cannot insert item");
+    assert_eq!(
+        db.get([TEST_LEAF].as_ref(), b"key", None)
+            .unwrap()
+            .expect("successful get"),
+        Element::new_item(b"ayy".to_vec())
+    );
+    assert_eq!(
+        db.root_hash(None).unwrap().unwrap(),
+        rocksdb.root_hash(None).unwrap().unwrap()
+    );
+
+    let mut query = Query::new();
+    query.insert_all();
+    let path_query = PathQuery::new_unsized(vec![TEST_LEAF.to_vec()], query);
+    let proof = db.prove_query(&path_query).unwrap().unwrap();
+    let (hash, result_set) = GroveDb::verify_query(&proof, &path_query).unwrap();
+    assert_eq!(hash, db.root_hash(None).unwrap().unwrap());
+    assert_eq!(result_set.len(), 1);
+}
+
+#[test]
+fn test_open_with_options() {
+    let tmp_dir = TempDir::new().unwrap();
+    let config = GroveDbConfig {
+        parallelism: Some(2),
+        block_cache_size: Some(8 << 20),
+        default_cf: ColumnFamilyConfig {
+            compression: Some(DBCompressionType::None),
+            ..ColumnFamilyConfig::subtree_prefixed()
+        },
+        ..Default::default()
+    };
+    {
+        let mut db = GroveDb::open_with_options(tmp_dir.path(), &config).unwrap();
+        add_test_leaves(&mut db);
+        db.insert(
+            [TEST_LEAF].as_ref(),
+            b"key",
+            Element::new_item(b"ayy".to_vec()),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("cannot insert item");
+    }
+
+    let db = GroveDb::open_with_options(tmp_dir.path(), &config).unwrap();
+    assert_eq!(
+        db.get([TEST_LEAF].as_ref(), b"key", None)
+            .unwrap()
+            .expect("successful get"),
+        Element::new_item(b"ayy".to_vec())
+    );
+    let mut query = Query::new();
+    query.insert_all();
+    let path_query = PathQuery::new_unsized(vec![TEST_LEAF.to_vec()], query);
+    let (elements, _) = db
+        .query_item_value(&path_query, true, None)
+        .unwrap()
+        .expect("successful query");
+    assert_eq!(elements, vec![b"ayy".to_vec()]);
+}
+
+#[test]
+fn test_read_only_and_secondary_modes() {
+    let db = make_test_grovedb();
+    db.insert(
+        [TEST_LEAF].as_ref(),
+        b"key1",
+        Element::new_item(b"ayy".to_vec()),
+        None,
+        None,
+    )
+    .unwrap()
+    .expect("cannot insert item");
+    db.flush().expect("cannot flush");
+
+    let read_only = GroveDb::open_read_only(db._tmp_dir.path()).unwrap();
+    assert_eq!(
+        read_only
+            .get([TEST_LEAF].as_ref(), b"key1", None)
+            .unwrap()
+            .expect("successful get"),
+        Element::new_item(b"ayy".to_vec())
+    );
+    assert!(matches!(
+        read_only
+            .insert(
+                [TEST_LEAF].as_ref(),
+                b"key2",
+                Element::new_item(b"ayy".to_vec()),
+                None,
+                None,
+            )
+            .unwrap(),
+        Err(Error::NotSupported(_))
+    ));
+    let tx = read_only.start_transaction();
+    assert!(matches!(
+        read_only
+            .put_aux(b"key", b"value", None, Some(&tx))
+            .unwrap(),
+        Err(Error::NotSupported(_))
+    ));
+
+    let secondary_dir = TempDir::new().unwrap();
+    let secondary = GroveDb::open_as_secondary(db._tmp_dir.path(), secondary_dir.path()).unwrap();
+    db.insert(
+        [TEST_LEAF].as_ref(),
+        b"key2",
+        Element::new_item(b"ayy2".to_vec()),
+        None,
+        None,
+    )
+    .unwrap()
+    .expect("cannot insert item");
+    secondary
+        .try_catch_up_with_primary()
+        .expect("cannot catch up with primary");
+    assert_eq!(
+        secondary
+            .get([TEST_LEAF].as_ref(), b"key2", None)
+            .unwrap()
+            .expect("successful get"),
+        Element::new_item(b"ayy2".to_vec())
+    );
+    assert_eq!(
+        secondary.root_hash(None).unwrap().unwrap(),
+        db.root_hash(None).unwrap().unwrap()
+    );
+    assert!(matches!(
+        secondary
+            .delete([TEST_LEAF].as_ref(), b"key1", None, None)
+            .unwrap(),
+        Err(Error::NotSupported(_))
+    ));
+}
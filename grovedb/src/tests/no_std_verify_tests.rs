@@ -0,0 +1,354 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Tests checking the no_std verifier against the proofs of GroveDB
+
+use grovedb_merk::proofs::{query::QueryItem, Query};
+
+use crate::{
+    operations::proof::util::ProvedPathKeyValue,
+    reference_path::ReferencePathType,
+    tests::{make_deep_tree, ANOTHER_TEST_LEAF, DEEP_LEAF, TEST_LEAF},
+    Element, GroveDb, PathQuery, SizedQuery,
+};
+
+fn to_verify_query_item(item: &QueryItem) -> grovedb_verify::QueryItem {
+    match item.clone() {
+        QueryItem::Key(key) => grovedb_verify::QueryItem::Key(key),
+        QueryItem::Range(range) => grovedb_verify::QueryItem::Range(range),
+        QueryItem::RangeInclusive(range) => grovedb_verify::QueryItem::RangeInclusive(range),
+        QueryItem::RangeFull(range) => grovedb_verify::QueryItem::RangeFull(range),
+        QueryItem::RangeFrom(range) => grovedb_verify::QueryItem::RangeFrom(range),
+        QueryItem::RangeTo(range) => grovedb_verify::QueryItem::RangeTo(range),
+        QueryItem::RangeToInclusive(range) => grovedb_verify::QueryItem::RangeToInclusive(range),
+        QueryItem::RangeAfter(range) => grovedb_verify::QueryItem::RangeAfter(range),
+        QueryItem::RangeAfterTo(range) => grovedb_verify::QueryItem::RangeAfterTo(range),
+        QueryItem::RangeAfterToInclusive(range) => {
+            grovedb_verify::QueryItem::RangeAfterToInclusive(range)
+        }
+    }
+}
+
+/// Rebuilds the query through the insert methods of the no_std verifier, so
+/// that its item ordering and merging is exercised as well
+fn to_verify_query(query: &Query) -> grovedb_verify::Query {
+    let mut verify_query = grovedb_verify::Query::new_with_direction(query.left_to_right);
+    for item in query.items.iter().rev() {
+        verify_query.insert_item(to_verify_query_item(item));
+    }
+    if let Some(path) = &query.default_subquery_branch.subquery_path {
+        verify_query.set_subquery_path(path.clone());
+    }
+    if let Some(subquery) = &query.default_subquery_branch.subquery {
+        verify_query.set_subquery(to_verify_query(subquery));
+    }
+    for (item, branch) in query.conditional_subquery_branches.iter().flatten() {
+        verify_query.add_conditional_subquery(
+            to_verify_query_item(item),
+            branch.subquery_path.clone(),
+            branch
+                .subquery
+                .as_ref()
+                .map(|subquery| to_verify_query(subquery)),
+        );
+    }
+    verify_query
+}
+
+fn to_verify_path_query(path_query: &PathQuery) -> grovedb_verify::PathQuery {
+    grovedb_verify::PathQuery::new(
+        path_query.path.clone(),
+        grovedb_verify::SizedQuery::new(
+            to_verify_query(&path_query.query.query),
+            path_query.query.limit,
+            path_query.query.offset,
+        ),
+    )
+}
+
+/// Path, key and serialized value of a proved element
+type RawResult = (Vec<Vec<u8>>, Vec<u8>, Vec<u8>);
+
+fn raw_results(results: Vec<ProvedPathKeyValue>) -> Vec<RawResult> {
+    results
+        .into_iter()
+        .map(|pkv| (pkv.path, pkv.key, pkv.value))
+        .collect()
+}
+
+fn no_std_raw_results(results: Vec<grovedb_verify::ProvedPathKeyValue>) -> Vec<RawResult> {
+    results
+        .into_iter()
+        .map(|pkv| (pkv.path, pkv.key, pkv.value))
+        .collect()
+}
+
+/// Proves the query and checks both verifiers agree on the root hash and the
+/// result set
+fn assert_verifiers_agree(db: &GroveDb, path_query: &PathQuery) {
+    let proof = db.prove_query(path_query).unwrap().unwrap();
+    let (hash, result_set) = GroveDb::verify_query_raw(&proof, path_query).unwrap();
+
+    let verify_path_query = to_verify_path_query(path_query);
+    let (no_std_hash, no_std_result_set) =
+        grovedb_verify::verify_query_raw(&proof, &verify_path_query).unwrap();
+
+    assert_eq!(hash, db.root_hash(None).unwrap().unwrap());
+    assert_eq!(no_std_hash, hash);
+    assert_eq!(
+        no_std_raw_results(no_std_result_set),
+        raw_results(result_set)
+    );
+
+    // elements decode to the same values
+    let (_, elements) = GroveDb::verify_query(&proof, path_query).unwrap();
+    let (_, no_std_elements) = grovedb_verify::verify_query(&proof, &verify_path_query).unwrap();
+    assert_eq!(elements.len(), no_std_elements.len());
+    for ((path, key, element), (no_std_path, no_std_key, no_std_element)) in
+        elements.into_iter().zip(no_std_elements)
+    {
+        assert_eq!(path, no_std_path);
+        assert_eq!(key, no_std_key);
+        let value = element.map(|element| element.serialize().unwrap());
+        let no_std_value = no_std_element.map(|element| match element {
+            grovedb_verify::Element::Item(value, flags) => Element::Item(value, flags),
+            grovedb_verify::Element::SumItem(value, flags) => Element::SumItem(value, flags),
+            grovedb_verify::Element::Tree(root_key, flags) => Element::Tree(root_key, flags),
+            grovedb_verify::Element::SumTree(root_key, sum, flags) => {
+                Element::SumTree(root_key, sum, flags)
+            }
+            grovedb_verify::Element::Reference(..) => {
+                panic!("proofs contain referenced values, not references")
+            }
+        });
+        let no_std_value = no_std_value.map(|element| element.serialize().unwrap());
+        assert_eq!(value, no_std_value);
+    }
+}
+
+#[test]
+fn test_no_std_verifier_matches_on_subqueries() {
+    let db = make_deep_tree();
+
+    let mut query = Query::new();
+    query.insert_all();
+    assert_verifiers_agree(
+        &db,
+        &PathQuery::new_unsized(vec![TEST_LEAF.to_vec()], query),
+    );
+
+    let mut query = Query::new();
+    query.insert_range(b"innertree".to_vec()..b"innertree5".to_vec());
+    let mut subquery = Query::new();
+    subquery.insert_key(b"key2".to_vec());
+    subquery.insert_range_after(b"key3".to_vec()..);
+    query.set_subquery(subquery);
+    assert_verifiers_agree(
+        &db,
+        &PathQuery::new_unsized(vec![TEST_LEAF.to_vec()], query),
+    );
+
+    let mut query = Query::new();
+    query.insert_all();
+    let mut subquery = Query::new();
+    subquery.insert_all();
+    query.set_subquery(subquery);
+    let mut outer_query = Query::new();
+    outer_query.insert_all();
+    outer_query.set_subquery(query);
+    outer_query.add_conditional_subquery(
+        QueryItem::Key(b"deep_node_1".to_vec()),
+        Some(vec![b"deeper_1".to_vec()]),
+        None,
+    );
+    assert_verifiers_agree(
+        &db,
+        &PathQuery::new_unsized(vec![DEEP_LEAF.to_vec()], outer_query),
+    );
+
+    let mut query = Query::new();
+    query.insert_all();
+    query.set_subquery_path(vec![b"deeper_3".to_vec()]);
+    let mut subquery = Query::new();
+    subquery.insert_all();
+    query.set_subquery(subquery);
+    assert_verifiers_agree(
+        &db,
+        &PathQuery::new_unsized(vec![DEEP_LEAF.to_vec()], query),
+    );
+}
+
+#[test]
+fn test_no_std_verifier_matches_with_limit_offset_and_direction() {
+    let db = make_deep_tree();
+
+    let mut query = Query::new_with_direction(false);
+    query.insert_all();
+    let mut subquery = Query::new_with_direction(false);
+    subquery.insert_all();
+    query.set_subquery(subquery);
+    let mut outer_query = Query::new_with_direction(false);
+    outer_query.insert_all();
+    outer_query.set_subquery(query);
+
+    for (limit, offset) in [
+        (None, None),
+        (Some(4), None),
+        (Some(5), Some(2)),
+        (None, Some(3)),
+    ] {
+        let path_query = PathQuery::new(
+            vec![DEEP_LEAF.to_vec()],
+            SizedQuery::new(outer_query.clone(), limit, offset),
+        );
+        assert_verifiers_agree(&db, &path_query);
+    }
+}
+
+#[test]
+fn test_no_std_verifier_matches_on_references_and_absent_paths() {
+    let db = make_deep_tree();
+    db.insert(
+        [ANOTHER_TEST_LEAF, b"innertree2"].as_ref(),
+        b"key5",
+        Element::new_reference(ReferencePathType::AbsolutePathReference(vec![
+            TEST_LEAF.to_vec(),
+            b"innertree".to_vec(),
+            b"key1".to_vec(),
+        ])),
+        None,
+        None,
+    )
+    .unwrap()
+    .expect("successful reference insert");
+
+    let mut query = Query::new();
+    query.insert_all();
+    assert_verifiers_agree(
+        &db,
+        &PathQuery::new_unsized(
+            vec![ANOTHER_TEST_LEAF.to_vec(), b"innertree2".to_vec()],
+            query.clone(),
+        ),
+    );
+
+    assert_verifiers_agree(
+        &db,
+        &PathQuery::new_unsized(
+            vec![
+                TEST_LEAF.to_vec(),
+                b"not_a_tree".to_vec(),
+                b"deeper".to_vec(),
+            ],
+            query,
+        ),
+    );
+}
+
+#[test]
+fn test_no_std_verifier_subset_and_absence_proofs() {
+    let db = make_deep_tree();
+
+    let mut query = Query::new();
+    query.insert_all();
+    let mut subquery = Query::new();
+    subquery.insert_all();
+    query.set_subquery(subquery);
+    let path_query =
+        PathQuery::new_unsized(vec![DEEP_LEAF.to_vec(), b"deep_node_1".to_vec()], query);
+    let proof = db.prove_verbose(&path_query).unwrap().unwrap();
+
+    let mut subset_query = Query::new();
+    subset_query.insert_key(b"key2".to_vec());
+    let subset_path_query = PathQuery::new_unsized(
+        vec![
+            DEEP_LEAF.to_vec(),
+            b"deep_node_1".to_vec(),
+            b"deeper_1".to_vec(),
+        ],
+        subset_query,
+    );
+    let (hash, result_set) = GroveDb::verify_subset_query_raw(&proof, &subset_path_query).unwrap();
+    let (no_std_hash, no_std_result_set) =
+        grovedb_verify::verify_subset_query_raw(&proof, &to_verify_path_query(&subset_path_query))
+            .unwrap();
+    assert_eq!(no_std_hash, hash);
+    assert_eq!(no_std_result_set.len(), 1);
+    assert_eq!(
+        no_std_raw_results(no_std_result_set),
+        raw_results(result_set)
+    );
+
+    let mut query = Query::new();
+    query.insert_keys(vec![b"key1".to_vec(), b"key4".to_vec(), b"key9".to_vec()]);
+    let path_query = PathQuery::new(
+        vec![TEST_LEAF.to_vec(), b"innertree4".to_vec()],
+        SizedQuery::new(query, Some(3), None),
+    );
+    let proof = db.prove_query(&path_query).unwrap().unwrap();
+    let (hash, results) = GroveDb::verify_query_with_absence_proof(&proof, &path_query).unwrap();
+    let (no_std_hash, no_std_results) =
+        grovedb_verify::verify_query_with_absence_proof(&proof, &to_verify_path_query(&path_query))
+            .unwrap();
+    assert_eq!(no_std_hash, hash);
+    assert_eq!(no_std_results.len(), 3);
+    for ((path, key, element), (no_std_path, no_std_key, no_std_element)) in
+        results.into_iter().zip(no_std_results)
+    {
+        assert_eq!(path, no_std_path);
+        assert_eq!(key, no_std_key);
+        assert_eq!(element.is_some(), no_std_element.is_some());
+    }
+}
+
+#[test]
+fn test_no_std_verifier_rejects_tampered_proof() {
+    let db = make_deep_tree();
+
+    let mut query = Query::new();
+    query.insert_all();
+    let path_query = PathQuery::new_unsized(vec![TEST_LEAF.to_vec(), b"innertree".to_vec()], query);
+    let mut proof = db.prove_query(&path_query).unwrap().unwrap();
+    let verify_path_query = to_verify_path_query(&path_query);
+    let (hash, _) = grovedb_verify::verify_query_raw(&proof, &verify_path_query).unwrap();
+
+    // flip a byte of the first value, the proof either stops decoding or
+    // results in a different root hash
+    let position = proof
+        .windows(b"value1".len())
+        .position(|window| window == b"value1")
+        .expect("value is in the proof");
+    proof[position] ^= 1;
+    if let Ok((tampered_hash, _)) = grovedb_verify::verify_query_raw(&proof, &verify_path_query) {
+        assert_ne!(tampered_hash, hash);
+    }
+
+    assert!(
+        grovedb_verify::verify_query_raw(&proof[..proof.len() - 1], &verify_path_query).is_err()
+    );
+}
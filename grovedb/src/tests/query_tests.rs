@@ -2658,3 +2658,116 @@ fn test_query_b_depends_on_query_a() {
     assert_eq!(age_result[0].2, Some(Element::new_item(vec![12])));
     assert_eq!(age_result[1].2, Some(Element::new_item(vec![46])));
 }
+
+#[test]
+fn test_prove_query_to_writer_with_byte_budget() {
+    let db = make_deep_tree();
+
+    // proves the query in pages of at most `max_bytes`, checking that every page
+    // verifies on its own, and returns the number of pages and the results
+    let prove_in_pages = |path_query: &PathQuery, max_bytes: usize| {
+        let root_hash = db.root_hash(None).unwrap().unwrap();
+        let mut current_query = path_query.clone();
+        let mut paged_result_set = vec![];
+        let mut pages = 0;
+        loop {
+            pages += 1;
+            let mut page = vec![];
+            let write_result = db
+                .prove_query_to_writer(&current_query, &mut page, max_bytes)
+                .unwrap()
+                .unwrap();
+            assert!(page.len() <= max_bytes);
+            assert_eq!(write_result.bytes_written, page.len());
+
+            match write_result.continuation {
+                Some(continuation) => {
+                    let (hash, result_set) =
+                        GroveDb::verify_query_raw(&page, &continuation.proven_query).unwrap();
+                    assert_eq!(hash, root_hash);
+                    assert_eq!(result_set.len(), continuation.results_proven as usize);
+                    paged_result_set.extend(result_set);
+                    current_query = continuation.remaining_query;
+                }
+                None => {
+                    let (hash, result_set) =
+                        GroveDb::verify_query_raw(&page, &current_query).unwrap();
+                    assert_eq!(hash, root_hash);
+                    paged_result_set.extend(result_set);
+                    return (pages, paged_result_set);
+                }
+            }
+        }
+    };
+
+    for left_to_right in [true, false] {
+        let mut query = Query::new_with_direction(left_to_right);
+        query.insert_all();
+        let mut subq = Query::new_with_direction(left_to_right);
+        subq.insert_all();
+        let mut sub_subquery = Query::new_with_direction(left_to_right);
+        sub_subquery.insert_all();
+        subq.set_subquery(sub_subquery);
+        query.set_subquery(subq);
+        let path_query = PathQuery::new_unsized(vec![b"deep_leaf".to_vec()], query);
+
+        let proof = db.prove_query(&path_query).unwrap().unwrap();
+        let (_, full_result_set) = GroveDb::verify_query_raw(&proof, &path_query).unwrap();
+        assert_eq!(full_result_set.len(), 11);
+
+        // with enough room the streamed proof is the regular proof
+        let mut streamed_proof = vec![];
+        let write_result = db
+            .prove_query_to_writer(&path_query, &mut streamed_proof, proof.len())
+            .unwrap()
+            .unwrap();
+        assert_eq!(streamed_proof, proof);
+        assert_eq!(write_result.bytes_written, proof.len());
+        assert!(write_result.continuation.is_none());
+
+        // with a smaller budget the proof is split into pages that together return
+        // the whole result set
+        let (pages, paged_result_set) = prove_in_pages(&path_query, proof.len() * 3 / 4);
+        assert!(pages > 1);
+        assert_eq!(paged_result_set, full_result_set);
+    }
+
+    // a leaf that doesn't fit is split within its results
+    db.insert(
+        [b"deep_leaf".as_slice()].as_ref(),
+        b"wide_leaf",
+        Element::empty_tree(),
+        None,
+        None,
+    )
+    .unwrap()
+    .expect("successful subtree insert");
+    for i in 0u8..64 {
+        db.insert(
+            [b"deep_leaf".as_slice(), b"wide_leaf".as_slice()].as_ref(),
+            &[i],
+            Element::new_item(vec![i; 8]),
+            None,
+            None,
+        )
+        .unwrap()
+        .expect("successful item insert");
+    }
+    let mut query = Query::new();
+    query.insert_all();
+    let path_query =
+        PathQuery::new_unsized(vec![b"deep_leaf".to_vec(), b"wide_leaf".to_vec()], query);
+    let proof = db.prove_query(&path_query).unwrap().unwrap();
+    let (_, full_result_set) = GroveDb::verify_query_raw(&proof, &path_query).unwrap();
+    assert_eq!(full_result_set.len(), 64);
+    let (pages, paged_result_set) = prove_in_pages(&path_query, proof.len() / 2);
+    assert!(pages > 2);
+    assert_eq!(paged_result_set, full_result_set);
+
+    // a budget that can't hold the path to the root is rejected
+    let mut page = vec![];
+    assert!(db
+        .prove_query_to_writer(&path_query, &mut page, 2)
+        .unwrap()
+        .is_err());
+}
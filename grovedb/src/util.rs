@@ -31,8 +31,8 @@
 macro_rules! storage_context_optional_tx {
     ($db:expr, $path:expr, $batch:expr, $transaction:ident, $storage:ident, { $($body:tt)* }) => {
         {
-            use ::grovedb_storage::Storage;
             if let Some(tx) = $transaction {
+                let tx = $db.narrow_transaction(tx);
                 let $storage = $db
                     .get_transactional_storage_context($path, $batch, tx);
                 $($body)*
@@ -60,8 +60,8 @@ macro_rules! storage_context_with_parent_optional_tx {
 	{ $($body:tt)* }
     ) => {
         {
-            use ::grovedb_storage::Storage;
             if let Some(tx) = $transaction {
+                let tx = $db.narrow_transaction(tx);
                 let $storage = $db
                     .get_transactional_storage_context($path.clone(), $batch, tx)
 		            .unwrap_add_cost(&mut $cost);
@@ -154,8 +154,8 @@ macro_rules! storage_context_with_parent_optional_tx {
 macro_rules! meta_storage_context_optional_tx {
     ($db:expr, $batch:expr, $transaction:ident, $storage:ident, { $($body:tt)* }) => {
         {
-            use ::grovedb_storage::Storage;
             if let Some(tx) = $transaction {
+                let tx = $db.narrow_transaction(tx);
                 let $storage = $db
                     .get_transactional_storage_context(
                         ::grovedb_path::SubtreePath::empty(),
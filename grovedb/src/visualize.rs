@@ -33,7 +33,7 @@ use std::io::{Result, Write};
 use bincode::Options;
 use grovedb_merk::{Merk, VisualizeableMerk};
 use grovedb_path::SubtreePathBuilder;
-use grovedb_storage::StorageContext;
+use grovedb_storage::{CovariantStorage, StorageContext};
 use grovedb_visualize::{visualize_stdout, Drawer, Visualize};
 
 use crate::{
@@ -138,12 +138,12 @@ impl Visualize for ReferencePathType {
     }
 }
 
-impl GroveDb {
+impl<S: CovariantStorage> GroveDb<S> {
     fn draw_subtree<W: Write, B: AsRef<[u8]>>(
         &self,
         mut drawer: Drawer<W>,
         path: SubtreePathBuilder<'_, B>,
-        transaction: TransactionArg,
+        transaction: TransactionArg<S>,
     ) -> Result<Drawer<W>> {
         drawer.down();
 
@@ -183,7 +183,7 @@ impl GroveDb {
     fn draw_root_tree<W: Write>(
         &self,
         mut drawer: Drawer<W>,
-        transaction: TransactionArg,
+        transaction: TransactionArg<S>,
     ) -> Result<Drawer<W>> {
         drawer.down();
 
@@ -196,7 +196,7 @@ impl GroveDb {
     fn visualize_start<W: Write>(
         &self,
         mut drawer: Drawer<W>,
-        transaction: TransactionArg,
+        transaction: TransactionArg<S>,
     ) -> Result<Drawer<W>> {
         drawer.write(b"root")?;
         drawer = self.draw_root_tree(drawer, transaction)?;
@@ -212,7 +212,7 @@ mod tests {
 
     use super::*;
     use crate::{
-        proofs::chunk::{verify_leaf, verify_trunk},
+        proofs::chunk::{verify_chunks, verify_leaf, verify_trunk},
         test_utils::*,
     };
 
@@ -263,6 +263,43 @@ mod tests {
         }
     }
 
+    #[test]
+    fn verify_all_chunks() {
+        let mut merk = TempMerk::new();
+        let batch = make_batch_seq(1..10_000);
+        merk.apply::<_, Vec<_>>(&batch, &[], None).unwrap().unwrap();
+        merk.commit();
+
+        let chunks = merk
+            .chunks()
+            .unwrap()
+            .into_iter()
+            .map(|x| x.unwrap())
+            .collect::<Vec<_>>();
+
+        let (root_hash, nodes) = verify_chunks(chunks.clone()).unwrap().unwrap();
+        assert_eq!(root_hash, merk.root_hash().unwrap());
+        assert_eq!(nodes.len(), 9_999);
+        let keys = nodes
+            .iter()
+            .map(|node| match node {
+                Node::KVValueHashFeatureType(key, ..) => key.clone(),
+                _ => panic!("expected key-value nodes"),
+            })
+            .collect::<Vec<_>>();
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+
+        // leaf chunks out of order
+        let mut swapped = chunks.clone();
+        swapped.swap(1, 2);
+        assert!(verify_chunks(swapped).unwrap().is_err());
+
+        // missing leaf chunk
+        let mut missing = chunks;
+        missing.pop();
+        assert!(verify_chunks(missing).unwrap().is_err());
+    }
+
     #[test]
     fn chunks_from_reopen() {
         let tmp_dir = TempDir::new().expect("cannot create tempdir");
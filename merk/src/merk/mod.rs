@@ -115,11 +115,14 @@ impl ProofWithoutEncodingResult {
     }
 }
 
+/// Deleted keys along with the storage their removal frees
+pub type DeletedKeys = LinkedList<(Vec<u8>, Option<KeyValueStorageCost>)>;
+
 /// Key update types
 pub struct KeyUpdates {
     pub new_keys: BTreeSet<Vec<u8>>,
     pub updated_keys: BTreeSet<Vec<u8>>,
-    pub deleted_keys: LinkedList<(Vec<u8>, Option<KeyValueStorageCost>)>,
+    pub deleted_keys: DeletedKeys,
     pub updated_root_key_from: Option<Vec<u8>>,
 }
 
@@ -128,7 +131,7 @@ impl KeyUpdates {
     pub fn new(
         new_keys: BTreeSet<Vec<u8>>,
         updated_keys: BTreeSet<Vec<u8>>,
-        deleted_keys: LinkedList<(Vec<u8>, Option<KeyValueStorageCost>)>,
+        deleted_keys: DeletedKeys,
         updated_root_key_from: Option<Vec<u8>>,
     ) -> Self {
         Self {
@@ -357,25 +360,11 @@ where
         merk.load_root().map_ok(|_| merk)
     }
 
-    /// Deletes tree data
+    /// Deletes tree data, batched storage deletes the whole subtree prefix
     pub fn clear(&mut self) -> CostResult<(), Error> {
         let mut cost = OperationCost::default();
-
-        let mut iter = self.storage.raw_iter();
-        iter.seek_to_first().unwrap_add_cost(&mut cost);
-
-        let mut to_delete = self.storage.new_batch();
-        while iter.valid().unwrap_add_cost(&mut cost) {
-            if let Some(key) = iter.key().unwrap_add_cost(&mut cost) {
-                // todo: deal with cost reimbursement
-                to_delete.delete(key, None);
-            }
-            iter.next().unwrap_add_cost(&mut cost);
-        }
-        cost_return_on_error!(
-            &mut cost,
-            self.storage.commit_batch(to_delete).map_err(StorageError)
-        );
+        // todo: deal with cost reimbursement
+        cost_return_on_error!(&mut cost, self.storage.clear().map_err(StorageError));
         self.tree.set(None);
         Ok(()).wrap_with_cost(cost)
     }
@@ -887,11 +876,71 @@ where
         ) -> Result<(bool, Option<ValueDefinedCostType>), Error>,
         R: FnMut(&Vec<u8>, u32, u32) -> Result<(StorageRemovedBytes, StorageRemovedBytes), Error>,
     {
-        let maybe_walker = self
+        self.apply_with_range_deletes_unchecked(
+            &[],
+            batch,
+            aux,
+            options,
+            old_specialized_cost,
+            update_tree_value_based_on_costs,
+            section_removal_bytes,
+        )
+    }
+
+    /// Deletes every key within the given ranges, then applies the batch,
+    /// committing both at once. Whole subtrees between the bounds of a range
+    /// are removed without being rebalanced node by node, and the storage
+    /// freed by every deleted key is reported as for a delete op, with its old
+    /// cost given by `old_specialized_cost`.
+    ///
+    /// This is unsafe for the same reasons as `apply_unchecked`. The ranges
+    /// are deleted before the batch is applied, so a batch key within a range
+    /// applies to the tree the range deletes left.
+    #[allow(clippy::too_many_arguments)]
+    pub fn apply_with_range_deletes_unchecked<KB, KA, C, U, R>(
+        &mut self,
+        ranges: &[QueryItem],
+        batch: &MerkBatch<KB>,
+        aux: &AuxMerkBatch<KA>,
+        options: Option<MerkOptions>,
+        old_specialized_cost: &C,
+        update_tree_value_based_on_costs: &mut U,
+        section_removal_bytes: &mut R,
+    ) -> CostResult<(), Error>
+    where
+        KB: AsRef<[u8]>,
+        KA: AsRef<[u8]>,
+        C: Fn(&Vec<u8>, &Vec<u8>) -> Result<u32, Error>,
+        U: FnMut(
+            &StorageCost,
+            &Vec<u8>,
+            &mut Vec<u8>,
+        ) -> Result<(bool, Option<ValueDefinedCostType>), Error>,
+        R: FnMut(&Vec<u8>, u32, u32) -> Result<(StorageRemovedBytes, StorageRemovedBytes), Error>,
+    {
+        let mut cost = OperationCost::default();
+
+        let mut maybe_walker = self
             .tree
             .take()
             .take()
             .map(|tree| Walker::new(tree, self.source()));
+        let old_root_key = maybe_walker
+            .as_ref()
+            .map(|walker| walker.tree().key().to_vec());
+
+        let mut range_deleted_keys = DeletedKeys::new();
+        for range in ranges {
+            let Some(walker) = maybe_walker.take() else {
+                break;
+            };
+            let (remaining, mut deleted_keys) = cost_return_on_error!(
+                &mut cost,
+                walker.delete_range(range, old_specialized_cost, section_removal_bytes)
+            );
+            maybe_walker = remaining;
+            range_deleted_keys.append(&mut deleted_keys);
+        }
 
         Walker::apply_to(
             maybe_walker,
@@ -900,7 +949,15 @@ where
             old_specialized_cost,
             section_removal_bytes,
         )
-        .flat_map_ok(|(maybe_tree, key_updates)| {
+        .flat_map_ok(|(maybe_tree, mut key_updates)| {
+            if !ranges.is_empty() {
+                key_updates.deleted_keys.append(&mut range_deleted_keys);
+                // the root key may have changed in the range deletes as well
+                let new_root_key = maybe_tree.as_ref().map(|tree| tree.key());
+                key_updates.updated_root_key_from = old_root_key
+                    .clone()
+                    .filter(|old_root_key| Some(old_root_key.as_slice()) != new_root_key);
+            }
             // we set the new root node of the merk tree
             self.tree.set(maybe_tree);
             // commit changes to db
@@ -913,6 +970,7 @@ where
                 section_removal_bytes,
             )
         })
+        .add_cost(cost)
     }
 
     /// Creates a Merkle proof for the list of queried keys. For each key in the
@@ -1433,7 +1491,9 @@ impl Commit for MerkCommitter {
 
 #[cfg(test)]
 mod test {
-    use grovedb_costs::OperationCost;
+    use grovedb_costs::{
+        storage_cost::removal::StorageRemovedBytes::BasicStorageRemoval, OperationCost,
+    };
     use grovedb_path::SubtreePath;
     use grovedb_storage::{
         rocksdb_storage::{test_utils::TempStorage, PrefixedRocksDbStorageContext, RocksDbStorage},
@@ -1442,7 +1502,9 @@ mod test {
     use tempfile::TempDir;
 
     use super::{Merk, MerkSource, RefWalker};
-    use crate::{test_utils::*, Op, TreeFeatureType::BasicMerk};
+    use crate::{
+        proofs::query::query_item::QueryItem, test_utils::*, Op, TreeFeatureType::BasicMerk,
+    };
 
     // TODO: Close and then reopen test
 
@@ -1651,6 +1713,46 @@ mod test {
         assert!(value.is_none());
     }
 
+    #[test]
+    fn apply_with_range_deletes() {
+        let mut merk = TempMerk::new();
+        merk.apply::<_, Vec<_>>(&make_batch_seq(0..100), &[], None)
+            .unwrap()
+            .expect("apply failed");
+        merk.commit();
+
+        let range = QueryItem::Range(seq_key(20).to_vec()..seq_key(80).to_vec());
+        merk.apply_with_range_deletes_unchecked::<_, Vec<u8>, _, _, _>(
+            &[range],
+            &[put_entry(150)],
+            &[],
+            None,
+            &|_, value| Ok(value.len() as u32),
+            &mut |_, _, _| Ok((false, None)),
+            &mut |_, key_bytes_to_remove, value_bytes_to_remove| {
+                Ok((
+                    BasicStorageRemoval(key_bytes_to_remove),
+                    BasicStorageRemoval(value_bytes_to_remove),
+                ))
+            },
+        )
+        .unwrap()
+        .expect("apply failed");
+        assert_invariants(&merk);
+        merk.commit();
+        assert_invariants(&merk);
+
+        for n in 0..100 {
+            let value = merk.get(&seq_key(n), true).unwrap().expect("get failed");
+            assert_eq!(value.is_some(), !(20..80).contains(&n), "key {n}");
+        }
+        assert!(merk
+            .get(&seq_key(150), true)
+            .unwrap()
+            .expect("get failed")
+            .is_some());
+    }
+
     #[test]
     fn aux_data() {
         let mut merk = TempMerk::new();
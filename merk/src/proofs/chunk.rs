@@ -257,7 +257,6 @@ pub(crate) fn get_next_chunk(
 /// were no abridged nodes (Hash or KVHash) and the proof hashes to
 /// `expected_hash`.
 #[cfg(feature = "full")]
-#[allow(dead_code)] // TODO: remove when proofs will be enabled
 pub(crate) fn verify_leaf<I: Iterator<Item = Result<Op, Error>>>(
     ops: I,
     expected_hash: CryptoHash,
@@ -271,12 +270,11 @@ pub(crate) fn verify_leaf<I: Iterator<Item = Result<Op, Error>>>(
     .flat_map_ok(|tree| {
         tree.hash().map(|hash| {
             if hash != expected_hash {
-                Error::ChunkRestoringError(format!(
+                return Err(Error::ChunkRestoringError(format!(
                     "Leaf chunk proof did not match expected hash\n\tExpected: {:?}\n\tActual: \
                      {:?}",
-                    expected_hash,
-                    tree.hash()
-                ));
+                    expected_hash, hash
+                )));
             }
             Ok(tree)
         })
@@ -375,6 +373,94 @@ pub(crate) fn verify_trunk<I: Iterator<Item = Result<Op, Error>>>(
     Ok((tree, height)).wrap_with_cost(cost)
 }
 
+/// Verifies all the chunks of a Merk, as produced by a `ChunkProducer`,
+/// without restoring them: the trunk first, then the leaf chunks in order, each
+/// checked against the hash at the bottom of the trunk that it replaces.
+/// Returns the root hash of the Merk and its key-value nodes in key order.
+#[cfg(feature = "full")]
+pub fn verify_chunks<I: IntoIterator<Item = Vec<Op>>>(
+    chunks: I,
+) -> CostResult<(CryptoHash, Vec<Node>), Error> {
+    let mut cost = OperationCost::default();
+
+    let mut chunks = chunks.into_iter();
+    let trunk_ops = match chunks.next() {
+        Some(trunk_ops) => trunk_ops,
+        None => {
+            return Err(Error::ChunkRestoringError(
+                "Expected a trunk chunk".to_string(),
+            ))
+            .wrap_with_cost(cost)
+        }
+    };
+    let (trunk, height) =
+        cost_return_on_error!(&mut cost, verify_trunk(trunk_ops.into_iter().map(Ok)));
+    let root_hash = trunk.hash().unwrap_add_cost(&mut cost);
+
+    let trunk_height = height / 2;
+    let leaf_hashes = if trunk_height >= MIN_TRUNK_HEIGHT {
+        trunk
+            .layer(trunk_height)
+            .map(|node| node.hash().unwrap_add_cost(&mut cost))
+            .collect::<Vec<CryptoHash>>()
+    } else {
+        vec![]
+    };
+
+    // the trunk also holds hash nodes standing in for the leaf chunks and the
+    // height proof, only its key-value nodes are kept
+    let mut nodes = Vec::new();
+    trunk.visit_nodes(&mut |node| {
+        if matches!(
+            node,
+            Node::KV(..) | Node::KVValueHash(..) | Node::KVValueHashFeatureType(..)
+        ) {
+            nodes.push(node);
+        }
+    });
+
+    let mut leaf_hashes = leaf_hashes.into_iter();
+    for leaf_ops in chunks {
+        let leaf_hash = match leaf_hashes.next() {
+            Some(leaf_hash) => leaf_hash,
+            None => {
+                return Err(Error::ChunkRestoringError(
+                    "Received more chunks than expected".to_string(),
+                ))
+                .wrap_with_cost(cost)
+            }
+        };
+        let leaf = cost_return_on_error!(
+            &mut cost,
+            verify_leaf(leaf_ops.into_iter().map(Ok), leaf_hash)
+        );
+        leaf.visit_nodes(&mut |node| nodes.push(node));
+    }
+    if leaf_hashes.next().is_some() {
+        return Err(Error::ChunkRestoringError(
+            "Expected more leaf chunks".to_string(),
+        ))
+        .wrap_with_cost(cost);
+    }
+
+    nodes.sort_by(|a, b| node_key(a).cmp(node_key(b)));
+
+    Ok((root_hash, nodes)).wrap_with_cost(cost)
+}
+
+/// Key of a key-value node
+#[cfg(feature = "full")]
+fn node_key(node: &Node) -> &[u8] {
+    match node {
+        Node::KV(key, _)
+        | Node::KVValueHash(key, ..)
+        | Node::KVValueHashFeatureType(key, ..)
+        | Node::KVDigest(key, _)
+        | Node::KVRefValueHash(key, ..) => key.as_slice(),
+        Node::Hash(_) | Node::KVHash(_) => &[],
+    }
+}
+
 #[cfg(feature = "full")]
 #[cfg(test)]
 mod tests {
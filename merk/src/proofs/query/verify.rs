@@ -300,7 +300,7 @@ pub fn execute_proof(
 }
 
 #[cfg(any(feature = "full", feature = "verify"))]
-#[derive(PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug)]
 /// Proved key-value
 pub struct ProvedKeyValue {
     /// Key
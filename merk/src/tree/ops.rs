@@ -52,8 +52,14 @@ use Op::*;
 #[cfg(feature = "full")]
 use super::{Fetch, Link, Tree, Walker};
 #[cfg(feature = "full")]
-use crate::{error::Error, tree::tree_feature_type::TreeFeatureType, CryptoHash, HASH_LENGTH_U32};
-use crate::{merk::KeyUpdates, tree::kv::ValueDefinedCostType::SpecializedValueDefinedCost};
+use crate::{
+    error::Error, proofs::query::query_item::QueryItem, tree::tree_feature_type::TreeFeatureType,
+    CryptoHash, HASH_LENGTH_U32,
+};
+use crate::{
+    merk::{DeletedKeys, KeyUpdates},
+    tree::kv::ValueDefinedCostType::SpecializedValueDefinedCost,
+};
 
 #[cfg(feature = "full")]
 /// An operation to be applied to a key in the store.
@@ -152,6 +158,24 @@ impl Fetch for PanicSource {
     }
 }
 
+#[cfg(feature = "full")]
+/// Whether the key sorts before every key of the range.
+fn is_below_range(range: &QueryItem, key: &[u8]) -> bool {
+    match range.lower_bound() {
+        (Some(lower), after) => key < lower || (after && key == lower),
+        (None, _) => false,
+    }
+}
+
+#[cfg(feature = "full")]
+/// Whether the key sorts after every key of the range.
+fn is_above_range(range: &QueryItem, key: &[u8]) -> bool {
+    match range.upper_bound() {
+        (Some(upper), inclusive) => key > upper || (!inclusive && key == upper),
+        (None, _) => false,
+    }
+}
+
 #[cfg(feature = "full")]
 impl<S> Walker<S>
 where
@@ -420,10 +444,6 @@ where
                         maybe_tree.map(|tree| Self::new(tree, source.clone()))
                     };
                     let key = self.tree().key().to_vec();
-                    let key_len = key.len() as u32;
-
-                    let prefixed_key_len = HASH_LENGTH_U32 + key_len;
-                    let total_key_len = prefixed_key_len + prefixed_key_len.required_space() as u32;
                     let value = self.tree().value_ref();
 
                     let old_cost = match &batch[index].1 {
@@ -437,24 +457,10 @@ where
                         _ => 0, // can't get here anyways
                     };
 
-                    let (r_key_cost, r_value_cost) = cost_return_on_error_no_add!(
+                    let deletion_cost = Some(cost_return_on_error_no_add!(
                         &cost,
-                        section_removal_bytes(value, total_key_len, old_cost)
-                    );
-                    let deletion_cost = Some(KeyValueStorageCost {
-                        key_storage_cost: StorageCost {
-                            added_bytes: 0,
-                            replaced_bytes: 0,
-                            removed_bytes: r_key_cost,
-                        },
-                        value_storage_cost: StorageCost {
-                            added_bytes: 0,
-                            replaced_bytes: 0,
-                            removed_bytes: r_value_cost,
-                        },
-                        new_node: false,
-                        needs_value_verification: false,
-                    });
+                        self.deletion_cost(old_cost, section_removal_bytes)
+                    ));
 
                     let maybe_tree = cost_return_on_error!(&mut cost, self.remove());
 
@@ -734,6 +740,244 @@ where
             self.detach(!left)
         }
     }
+
+    /// Computes the storage removed by deleting this node, given the cost of
+    /// its value.
+    fn deletion_cost<R>(
+        &self,
+        old_cost: u32,
+        section_removal_bytes: &mut R,
+    ) -> Result<KeyValueStorageCost, Error>
+    where
+        R: FnMut(&Vec<u8>, u32, u32) -> Result<(StorageRemovedBytes, StorageRemovedBytes), Error>,
+    {
+        let key_len = self.tree().key().len() as u32;
+        let prefixed_key_len = HASH_LENGTH_U32 + key_len;
+        let total_key_len = prefixed_key_len + prefixed_key_len.required_space() as u32;
+
+        let (r_key_cost, r_value_cost) =
+            section_removal_bytes(self.tree().value_ref(), total_key_len, old_cost)?;
+        Ok(KeyValueStorageCost {
+            key_storage_cost: StorageCost {
+                added_bytes: 0,
+                replaced_bytes: 0,
+                removed_bytes: r_key_cost,
+            },
+            value_storage_cost: StorageCost {
+                added_bytes: 0,
+                replaced_bytes: 0,
+                removed_bytes: r_value_cost,
+            },
+            new_node: false,
+            needs_value_verification: false,
+        })
+    }
+
+    /// Deletes every key of the tree within `range`. Subtrees lying entirely
+    /// within the range are removed whole, and the remaining parts are joined
+    /// back together, so the tree is only rebalanced along the range bounds.
+    /// Returns the remaining tree (if any) along with the deleted keys and the
+    /// storage they free. The old value cost of every deleted node is given by
+    /// `old_specialized_cost`.
+    pub fn delete_range<C, R>(
+        self,
+        range: &QueryItem,
+        old_specialized_cost: &C,
+        section_removal_bytes: &mut R,
+    ) -> CostResult<(Option<Self>, DeletedKeys), Error>
+    where
+        C: Fn(&Vec<u8>, &Vec<u8>) -> Result<u32, Error>,
+        R: FnMut(&Vec<u8>, u32, u32) -> Result<(StorageRemovedBytes, StorageRemovedBytes), Error>,
+    {
+        let mut deleted_keys = LinkedList::new();
+        self.delete_range_within(
+            range,
+            (false, false),
+            old_specialized_cost,
+            section_removal_bytes,
+            &mut deleted_keys,
+        )
+        .map_ok(|maybe_tree| (maybe_tree, deleted_keys))
+    }
+
+    /// Range delete of a subtree, `within_bounds` tells whether all keys of
+    /// the subtree are already known to satisfy the lower and upper bounds of
+    /// the range.
+    fn delete_range_within<C, R>(
+        self,
+        range: &QueryItem,
+        within_bounds: (bool, bool),
+        old_specialized_cost: &C,
+        section_removal_bytes: &mut R,
+        deleted_keys: &mut DeletedKeys,
+    ) -> CostResult<Option<Self>, Error>
+    where
+        C: Fn(&Vec<u8>, &Vec<u8>) -> Result<u32, Error>,
+        R: FnMut(&Vec<u8>, u32, u32) -> Result<(StorageRemovedBytes, StorageRemovedBytes), Error>,
+    {
+        let mut cost = OperationCost::default();
+
+        let (within_lower, within_upper) = within_bounds;
+        if within_lower && within_upper {
+            cost_return_on_error!(
+                &mut cost,
+                self.delete_all(old_specialized_cost, section_removal_bytes, deleted_keys)
+            );
+            return Ok(None).wrap_with_cost(cost);
+        }
+
+        let key = self.tree().key();
+        let below = !within_lower && is_below_range(range, key);
+        let above = !within_upper && is_above_range(range, key);
+        if below || above {
+            // this node stays, only the side facing the range is affected
+            let left = above;
+            let (tree, maybe_child) = cost_return_on_error!(&mut cost, self.detach(left));
+            let maybe_child = match maybe_child {
+                Some(child) => cost_return_on_error!(
+                    &mut cost,
+                    child.delete_range_within(
+                        range,
+                        within_bounds,
+                        old_specialized_cost,
+                        section_removal_bytes,
+                        deleted_keys,
+                    )
+                ),
+                None => None,
+            };
+            return tree.join(left, maybe_child).map_ok(Some).add_cost(cost);
+        }
+
+        let (tree, maybe_left) = cost_return_on_error!(&mut cost, self.detach(true));
+        let (tree, maybe_right) = cost_return_on_error!(&mut cost, tree.detach(false));
+        let old_cost = cost_return_on_error_no_add!(
+            &cost,
+            old_specialized_cost(&tree.tree().key().to_vec(), tree.tree().value_ref())
+        );
+        let deletion_cost = cost_return_on_error_no_add!(
+            &cost,
+            tree.deletion_cost(old_cost, section_removal_bytes)
+        );
+        deleted_keys.push_back((tree.tree().key().to_vec(), Some(deletion_cost)));
+
+        let maybe_left = match maybe_left {
+            Some(left) => cost_return_on_error!(
+                &mut cost,
+                left.delete_range_within(
+                    range,
+                    (within_lower, true),
+                    old_specialized_cost,
+                    section_removal_bytes,
+                    deleted_keys,
+                )
+            ),
+            None => None,
+        };
+        let maybe_right = match maybe_right {
+            Some(right) => cost_return_on_error!(
+                &mut cost,
+                right.delete_range_within(
+                    range,
+                    (true, within_upper),
+                    old_specialized_cost,
+                    section_removal_bytes,
+                    deleted_keys,
+                )
+            ),
+            None => None,
+        };
+
+        match (maybe_left, maybe_right) {
+            (Some(left), Some(right)) => {
+                // the leftmost node of the right side becomes the new root
+                let (edge, maybe_right) = cost_return_on_error!(&mut cost, right.remove_edge(true));
+                edge.attach(false, maybe_right)
+                    .join(true, Some(left))
+                    .map_ok(Some)
+                    .add_cost(cost)
+            }
+            (maybe_left, None) => Ok(maybe_left).wrap_with_cost(cost),
+            (None, maybe_right) => Ok(maybe_right).wrap_with_cost(cost),
+        }
+    }
+
+    /// Deletes every node of the tree.
+    fn delete_all<C, R>(
+        self,
+        old_specialized_cost: &C,
+        section_removal_bytes: &mut R,
+        deleted_keys: &mut DeletedKeys,
+    ) -> CostResult<(), Error>
+    where
+        C: Fn(&Vec<u8>, &Vec<u8>) -> Result<u32, Error>,
+        R: FnMut(&Vec<u8>, u32, u32) -> Result<(StorageRemovedBytes, StorageRemovedBytes), Error>,
+    {
+        let mut cost = OperationCost::default();
+
+        let (tree, maybe_left) = cost_return_on_error!(&mut cost, self.detach(true));
+        let (tree, maybe_right) = cost_return_on_error!(&mut cost, tree.detach(false));
+        for child in maybe_left.into_iter().chain(maybe_right) {
+            cost_return_on_error!(
+                &mut cost,
+                child.delete_all(old_specialized_cost, section_removal_bytes, deleted_keys)
+            );
+        }
+
+        let key = tree.tree().key().to_vec();
+        let old_cost = cost_return_on_error_no_add!(
+            &cost,
+            old_specialized_cost(&key, tree.tree().value_ref())
+        );
+        let deletion_cost = cost_return_on_error_no_add!(
+            &cost,
+            tree.deletion_cost(old_cost, section_removal_bytes)
+        );
+        deleted_keys.push_back((key, Some(deletion_cost)));
+
+        Ok(()).wrap_with_cost(cost)
+    }
+
+    /// Attaches `maybe_child` on the given side, which must be empty, with the
+    /// tree's other side possibly being of any height. All keys of the child
+    /// must be on that side of the root key. The tree is rebalanced down the
+    /// spine of the taller side, as in an AVL join.
+    fn join(self, left: bool, maybe_child: Option<Self>) -> CostResult<Self, Error> {
+        let mut cost = OperationCost::default();
+
+        let child_height = maybe_child
+            .as_ref()
+            .map_or(0, |child| child.tree().height());
+        let other_height = self.tree().child_height(!left);
+
+        if other_height > child_height + 1 {
+            // the other side is too tall, join within its inner spine
+            let (tree, other) = cost_return_on_error!(&mut cost, self.detach_expect(!left));
+            let (other, maybe_inner) = cost_return_on_error!(&mut cost, other.detach(left));
+            let joined = cost_return_on_error!(
+                &mut cost,
+                tree.attach(!left, maybe_inner).join(left, maybe_child)
+            );
+            other
+                .attach(left, Some(joined))
+                .maybe_balance()
+                .add_cost(cost)
+        } else {
+            match maybe_child {
+                Some(child) if child_height > other_height + 1 => {
+                    // the child is too tall, join within its inner spine
+                    let (child, maybe_inner) =
+                        cost_return_on_error!(&mut cost, child.detach(!left));
+                    let joined = cost_return_on_error!(&mut cost, self.join(left, maybe_inner));
+                    child
+                        .attach(!left, Some(joined))
+                        .maybe_balance()
+                        .add_cost(cost)
+                }
+                maybe_child => Ok(self.attach(left, maybe_child)).wrap_with_cost(cost),
+            }
+        }
+    }
 }
 
 #[cfg(feature = "full")]
@@ -1088,4 +1332,125 @@ mod test {
         assert_eq!(tree.child(true).expect("expected child").key(), &[31]);
         assert_eq!(tree.child(false).expect("expected child").key(), &[79]);
     }
+
+    fn delete_range_memonly(tree: Tree, range: &QueryItem) -> (Option<Tree>, DeletedKeys) {
+        let (maybe_walker, deleted_keys) = Walker::new(tree, PanicSource {})
+            .delete_range(
+                range,
+                &|_, value| Ok(value.len() as u32),
+                &mut |_, key_bytes_to_remove, value_bytes_to_remove| {
+                    Ok((
+                        BasicStorageRemoval(key_bytes_to_remove),
+                        BasicStorageRemoval(value_bytes_to_remove),
+                    ))
+                },
+            )
+            .unwrap()
+            .expect("range delete errored");
+        let maybe_tree = maybe_walker.map(|walker| {
+            let mut tree = walker.into_inner();
+            tree.commit(
+                &mut NoopCommit {},
+                &|_, _| Ok(0),
+                &mut |_, _, _| Ok((false, None)),
+                &mut |_, key_bytes_to_remove, value_bytes_to_remove| {
+                    Ok((
+                        BasicStorageRemoval(key_bytes_to_remove),
+                        BasicStorageRemoval(value_bytes_to_remove),
+                    ))
+                },
+            )
+            .unwrap()
+            .expect("commit failed");
+            assert_tree_invariants(&tree);
+            tree
+        });
+        (maybe_tree, deleted_keys)
+    }
+
+    fn collect_keys(maybe_tree: Option<&Tree>, keys: &mut Vec<Vec<u8>>) {
+        if let Some(tree) = maybe_tree {
+            collect_keys(tree.child(true), keys);
+            keys.push(tree.key().to_vec());
+            collect_keys(tree.child(false), keys);
+        }
+    }
+
+    #[test]
+    fn delete_range_middle() {
+        let tree = make_tree_seq(100);
+        let mut keys = vec![];
+        collect_keys(Some(&tree), &mut keys);
+
+        let range = QueryItem::Range(seq_key(20).to_vec()..seq_key(71).to_vec());
+        let (maybe_tree, deleted_keys) = delete_range_memonly(tree, &range);
+
+        let mut remaining_keys = vec![];
+        collect_keys(maybe_tree.as_ref(), &mut remaining_keys);
+        keys.retain(|key| !range.contains(key));
+        assert_eq!(remaining_keys, keys);
+
+        let mut deleted: Vec<Vec<u8>> = deleted_keys.iter().map(|(key, _)| key.clone()).collect();
+        deleted.sort();
+        assert_eq!(
+            deleted,
+            (20..71).map(|n| seq_key(n).to_vec()).collect::<Vec<_>>()
+        );
+        for (_, cost) in deleted_keys {
+            let cost = cost.expect("expected a deletion cost");
+            assert_eq!(
+                cost.key_storage_cost.removed_bytes,
+                BasicStorageRemoval(HASH_LENGTH_U32 + 8 + 1)
+            );
+            assert_eq!(
+                cost.value_storage_cost.removed_bytes,
+                BasicStorageRemoval(60)
+            );
+        }
+    }
+
+    #[test]
+    fn delete_range_bounds() {
+        let tree = make_tree_seq(100);
+        let mut keys = vec![];
+        collect_keys(Some(&tree), &mut keys);
+
+        let ranges = [
+            QueryItem::Key(seq_key(40).to_vec()),
+            QueryItem::Key(b"missing".to_vec()),
+            QueryItem::RangeInclusive(seq_key(3).to_vec()..=seq_key(97).to_vec()),
+            QueryItem::RangeFrom(seq_key(63).to_vec()..),
+            QueryItem::RangeAfter(seq_key(0).to_vec()..),
+            QueryItem::RangeTo(..seq_key(50).to_vec()),
+            QueryItem::RangeToInclusive(..=seq_key(1).to_vec()),
+            QueryItem::RangeAfterTo(seq_key(10).to_vec()..seq_key(12).to_vec()),
+            QueryItem::RangeAfterToInclusive(seq_key(31).to_vec()..=seq_key(95).to_vec()),
+            QueryItem::Range(seq_key(200).to_vec()..seq_key(300).to_vec()),
+        ];
+        for range in ranges {
+            let (maybe_tree, deleted_keys) = delete_range_memonly(tree.clone(), &range);
+
+            let mut remaining_keys = vec![];
+            collect_keys(maybe_tree.as_ref(), &mut remaining_keys);
+            let expected_keys: Vec<Vec<u8>> = keys
+                .iter()
+                .filter(|key| !range.contains(key))
+                .cloned()
+                .collect();
+            assert_eq!(remaining_keys, expected_keys, "{range:?}");
+            assert_eq!(
+                deleted_keys.len(),
+                keys.len() - expected_keys.len(),
+                "{range:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn delete_range_full() {
+        let tree = make_tree_seq(100);
+        let (maybe_tree, deleted_keys) = delete_range_memonly(tree, &QueryItem::RangeFull(..));
+        assert!(maybe_tree.is_none());
+        assert_eq!(deleted_keys.len(), 101);
+    }
 }
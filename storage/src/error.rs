@@ -34,6 +34,12 @@ pub enum Error {
     /// Storage Error
     #[error("storage error: {0}")]
     StorageError(String),
+    /// Operation not supported by the storage
+    #[error("not supported: {0}")]
+    NotSupported(&'static str),
+    /// Transaction conflicts with writes committed since it made its own
+    #[error("transaction conflict")]
+    TransactionConflict,
     /// Cost Error
     #[error("cost error: {0}")]
     CostError(grovedb_costs::error::Error),
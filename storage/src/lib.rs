@@ -31,12 +31,21 @@
 #![deny(missing_docs)]
 
 pub mod error;
+#[cfg(feature = "memory_storage")]
+pub mod memory_storage;
+#[cfg(any(feature = "rocksdb_storage", feature = "memory_storage"))]
+mod prefix;
 #[cfg(feature = "rocksdb_storage")]
 pub mod rocksdb_storage;
 mod storage;
 pub mod worst_case_costs;
 
+#[cfg(any(feature = "rocksdb_storage", feature = "memory_storage"))]
+pub use crate::prefix::{build_prefix, SubtreePrefix};
 pub use crate::{
     error::Error,
-    storage::{Batch, ChildrenSizes, RawIterator, Storage, StorageBatch, StorageContext},
+    storage::{
+        Batch, ChildrenSizes, CovariantStorage, PreviousRecords, RawIterator, Storage,
+        StorageBatch, StorageContext,
+    },
 };
@@ -0,0 +1,159 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Prefixed storage batch implementation for in-memory backend.
+
+use grovedb_costs::{
+    storage_cost::key_value_cost::KeyValueStorageCost, ChildrenSizesWithIsSumTree, OperationCost,
+};
+use integer_encoding::VarInt;
+
+use super::storage::Write;
+use crate::{
+    prefix::{make_prefixed_key, SubtreePrefix},
+    storage::Column,
+    Batch,
+};
+
+/// Batch of writes applied at once to a transaction, accumulating costs the
+/// same way a RocksDB batch does.
+pub struct MemoryBatch {
+    pub(crate) prefix: SubtreePrefix,
+    pub(crate) writes: Vec<Write>,
+    pub(crate) cost_acc: OperationCost,
+}
+
+impl Batch for MemoryBatch {
+    fn put<K: AsRef<[u8]>>(
+        &mut self,
+        key: K,
+        value: &[u8],
+        children_sizes: ChildrenSizesWithIsSumTree,
+        cost_info: Option<KeyValueStorageCost>,
+    ) -> Result<(), grovedb_costs::error::Error> {
+        let prefixed_key = make_prefixed_key(&self.prefix, key);
+
+        // Update the key_storage_cost based on the prefixed key
+        let updated_cost_info = cost_info.map(|mut key_value_storage_cost| {
+            if key_value_storage_cost.new_node {
+                // key is new, storage_cost needs to be created for it
+                key_value_storage_cost.key_storage_cost.added_bytes +=
+                    (prefixed_key.len() + prefixed_key.len().required_space()) as u32;
+            }
+            key_value_storage_cost
+        });
+
+        self.cost_acc.seek_count += 1;
+        self.cost_acc.add_key_value_storage_costs(
+            prefixed_key.len() as u32,
+            value.len() as u32,
+            children_sizes,
+            updated_cost_info,
+        )?;
+
+        self.writes
+            .push((Column::Data, prefixed_key, Some(value.to_vec())));
+        Ok(())
+    }
+
+    fn put_aux<K: AsRef<[u8]>>(
+        &mut self,
+        key: K,
+        value: &[u8],
+        cost_info: Option<KeyValueStorageCost>,
+    ) -> Result<(), grovedb_costs::error::Error> {
+        let prefixed_key = make_prefixed_key(&self.prefix, key);
+
+        self.cost_acc.seek_count += 1;
+        self.cost_acc.add_key_value_storage_costs(
+            prefixed_key.len() as u32,
+            value.len() as u32,
+            None,
+            cost_info,
+        )?;
+
+        self.writes
+            .push((Column::Aux, prefixed_key, Some(value.to_vec())));
+        Ok(())
+    }
+
+    fn put_root<K: AsRef<[u8]>>(
+        &mut self,
+        key: K,
+        value: &[u8],
+        cost_info: Option<KeyValueStorageCost>,
+    ) -> Result<(), grovedb_costs::error::Error> {
+        let prefixed_key = make_prefixed_key(&self.prefix, key);
+
+        self.cost_acc.seek_count += 1;
+        // put root only pays if cost info is set
+        if cost_info.is_some() {
+            self.cost_acc.add_key_value_storage_costs(
+                prefixed_key.len() as u32,
+                value.len() as u32,
+                None,
+                cost_info,
+            )?;
+        }
+
+        self.writes
+            .push((Column::Roots, prefixed_key, Some(value.to_vec())));
+        Ok(())
+    }
+
+    fn delete<K: AsRef<[u8]>>(&mut self, key: K, cost_info: Option<KeyValueStorageCost>) {
+        self.delete_from(Column::Data, key, cost_info)
+    }
+
+    fn delete_aux<K: AsRef<[u8]>>(&mut self, key: K, cost_info: Option<KeyValueStorageCost>) {
+        self.delete_from(Column::Aux, key, cost_info)
+    }
+
+    fn delete_root<K: AsRef<[u8]>>(&mut self, key: K, cost_info: Option<KeyValueStorageCost>) {
+        self.delete_from(Column::Roots, key, cost_info)
+    }
+}
+
+impl MemoryBatch {
+    fn delete_from<K: AsRef<[u8]>>(
+        &mut self,
+        column: Column,
+        key: K,
+        cost_info: Option<KeyValueStorageCost>,
+    ) {
+        let prefixed_key = make_prefixed_key(&self.prefix, key);
+
+        self.cost_acc.seek_count += 1;
+
+        if let Some(removed_bytes) = cost_info {
+            self.cost_acc.storage_cost.removed_bytes += removed_bytes.combined_removed_bytes();
+        }
+
+        self.writes.push((column, prefixed_key, None));
+    }
+}
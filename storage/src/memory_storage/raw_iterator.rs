@@ -0,0 +1,156 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Prefixed storage_cost raw iterator implementation for in-memory backend.
+
+use grovedb_costs::{CostContext, CostsExt, OperationCost};
+
+use crate::{
+    prefix::{make_prefixed_key, SubtreePrefix, MAX_PREFIXED_KEY_LENGTH},
+    RawIterator,
+};
+
+/// Raw iterator over the data records of a subtree, as they were when the
+/// iterator was created.
+pub struct MemoryRawIterator {
+    prefix: SubtreePrefix,
+    /// Records with prefixed keys, ordered by key
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    position: Option<usize>,
+}
+
+impl MemoryRawIterator {
+    pub(crate) fn new(prefix: SubtreePrefix, entries: Vec<(Vec<u8>, Vec<u8>)>) -> Self {
+        MemoryRawIterator {
+            prefix,
+            entries,
+            position: None,
+        }
+    }
+
+    fn entry(&self) -> Option<&(Vec<u8>, Vec<u8>)> {
+        self.position
+            .and_then(|position| self.entries.get(position))
+    }
+
+    fn position_if_exists(&self, position: usize) -> Option<usize> {
+        (position < self.entries.len()).then_some(position)
+    }
+}
+
+impl RawIterator for MemoryRawIterator {
+    fn seek_to_first(&mut self) -> CostContext<()> {
+        self.position = self.position_if_exists(0);
+        ().wrap_with_cost(OperationCost::with_seek_count(1))
+    }
+
+    fn seek_to_last(&mut self) -> CostContext<()> {
+        self.position = self.entries.len().checked_sub(1);
+        ().wrap_with_cost(OperationCost::with_seek_count(1))
+    }
+
+    fn seek<K: AsRef<[u8]>>(&mut self, key: K) -> CostContext<()> {
+        let prefixed_key = make_prefixed_key(&self.prefix, key);
+        let position = self
+            .entries
+            .partition_point(|(k, _)| k.as_slice() < prefixed_key.as_slice());
+        self.position = self.position_if_exists(position);
+        ().wrap_with_cost(OperationCost::with_seek_count(1))
+    }
+
+    fn seek_for_prev<K: AsRef<[u8]>>(&mut self, key: K) -> CostContext<()> {
+        let prefixed_key = make_prefixed_key(&self.prefix, key);
+        let position = self
+            .entries
+            .partition_point(|(k, _)| k.as_slice() <= prefixed_key.as_slice());
+        self.position = position.checked_sub(1);
+        ().wrap_with_cost(OperationCost::with_seek_count(1))
+    }
+
+    fn next(&mut self) -> CostContext<()> {
+        self.position = self
+            .position
+            .and_then(|position| self.position_if_exists(position + 1));
+        ().wrap_with_cost(OperationCost::with_seek_count(1))
+    }
+
+    fn prev(&mut self) -> CostContext<()> {
+        self.position = self.position.and_then(|position| position.checked_sub(1));
+        ().wrap_with_cost(OperationCost::with_seek_count(1))
+    }
+
+    fn value(&self) -> CostContext<Option<&[u8]>> {
+        let mut cost = OperationCost::default();
+
+        let value = if self.valid().unwrap_add_cost(&mut cost) {
+            self.entry().map(|(_, v)| {
+                cost.storage_loaded_bytes += v.len() as u32;
+                v.as_slice()
+            })
+        } else {
+            None
+        };
+
+        value.wrap_with_cost(cost)
+    }
+
+    fn key(&self) -> CostContext<Option<&[u8]>> {
+        let mut cost = OperationCost::default();
+
+        // Positions out of the subtree are charged a fixed size, as RocksDB does
+        // for the boundary checks of a prefix
+        let value = match self.entry() {
+            Some((k, _)) => {
+                cost.storage_loaded_bytes += k.len() as u32;
+                Some(k.split_at(self.prefix.len()).1)
+            }
+            None => {
+                cost.storage_loaded_bytes += MAX_PREFIXED_KEY_LENGTH;
+                None
+            }
+        };
+
+        value.wrap_with_cost(cost)
+    }
+
+    fn valid(&self) -> CostContext<bool> {
+        let mut cost = OperationCost::default();
+
+        match self.entry() {
+            Some((k, _)) => {
+                cost.storage_loaded_bytes += k.len() as u32;
+                true
+            }
+            None => {
+                cost.storage_loaded_bytes += MAX_PREFIXED_KEY_LENGTH;
+                false
+            }
+        }
+        .wrap_with_cost(cost)
+    }
+}
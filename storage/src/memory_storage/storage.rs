@@ -0,0 +1,1231 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Implementation for a storage abstraction in memory.
+
+use std::{
+    collections::BTreeMap,
+    ops::Bound,
+    path::Path,
+    sync::{Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use grovedb_costs::{
+    cost_return_on_error, cost_return_on_error_no_add,
+    storage_cost::{
+        key_value_cost::KeyValueStorageCost, removal::StorageRemovedBytes::BasicStorageRemoval,
+    },
+    CostContext, CostResult, CostsExt, OperationCost,
+};
+use grovedb_path::SubtreePath;
+use integer_encoding::VarInt;
+
+use super::{storage_context::get_cost, MemoryImmediateStorageContext, MemoryStorageContext};
+use crate::{
+    error::Error::{self, CostError},
+    prefix::{self, make_prefixed_key, RecordCursor, SubtreePrefix},
+    storage::{AbstractBatchOperation, Column},
+    worst_case_costs::WorstKeyLength,
+    CovariantStorage, PreviousRecords, Storage, StorageBatch,
+};
+
+/// Records of every column
+#[derive(Debug, Clone)]
+pub(crate) struct Columns<V> {
+    data: BTreeMap<Vec<u8>, V>,
+    aux: BTreeMap<Vec<u8>, V>,
+    roots: BTreeMap<Vec<u8>, V>,
+    meta: BTreeMap<Vec<u8>, V>,
+}
+
+impl<V> Default for Columns<V> {
+    fn default() -> Self {
+        Columns {
+            data: BTreeMap::new(),
+            aux: BTreeMap::new(),
+            roots: BTreeMap::new(),
+            meta: BTreeMap::new(),
+        }
+    }
+}
+
+impl<V> Columns<V> {
+    fn column(&self, column: Column) -> &BTreeMap<Vec<u8>, V> {
+        match column {
+            Column::Data => &self.data,
+            Column::Aux => &self.aux,
+            Column::Roots => &self.roots,
+            Column::Meta => &self.meta,
+        }
+    }
+
+    fn column_mut(&mut self, column: Column) -> &mut BTreeMap<Vec<u8>, V> {
+        match column {
+            Column::Data => &mut self.data,
+            Column::Aux => &mut self.aux,
+            Column::Roots => &mut self.roots,
+            Column::Meta => &mut self.meta,
+        }
+    }
+}
+
+/// Write of a record into a column, `None` deletes the record
+pub(crate) type Write = (Column, Vec<u8>, Option<Vec<u8>>);
+
+/// Records of a column whose keys start with a prefix
+fn prefixed_range<'a, V>(
+    map: &'a BTreeMap<Vec<u8>, V>,
+    prefix: &'a [u8],
+) -> impl Iterator<Item = (&'a Vec<u8>, &'a V)> {
+    map.range(prefix.to_vec()..)
+        .take_while(move |(key, _)| key.starts_with(prefix))
+}
+
+/// Cursor over records already in memory
+struct EntriesCursor<'a> {
+    entries: &'a BTreeMap<Vec<u8>, Vec<u8>>,
+    current: Option<(&'a Vec<u8>, &'a Vec<u8>)>,
+}
+
+impl RecordCursor for EntriesCursor<'_> {
+    fn seek_to(&mut self, key: &[u8]) {
+        self.current = self
+            .entries
+            .range::<[u8], _>((Bound::Included(key), Bound::Unbounded))
+            .next();
+    }
+
+    fn record(&self) -> Option<(&[u8], usize)> {
+        self.current
+            .map(|(key, value)| (key.as_slice(), value.len()))
+    }
+
+    fn next_record(&mut self) {
+        if let Some((key, _)) = self.current {
+            self.current = self
+                .entries
+                .range::<[u8], _>((Bound::Excluded(key.as_slice()), Bound::Unbounded))
+                .next();
+        }
+    }
+}
+
+/// Storage which keeps all the data in memory, to be used for tests, fuzzing
+/// and ephemeral nodes. Keys are prefixed and costs are reported the same way
+/// `RocksDbStorage` does it.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    db: RwLock<Columns<Vec<u8>>>,
+    sequences: Mutex<Sequences>,
+}
+
+/// Sequence numbers of commits, to detect transaction conflicts the way
+/// RocksDB optimistic transactions do
+#[derive(Debug, Default)]
+struct Sequences {
+    /// Sequence number of the last commit
+    last: u64,
+    /// Sequence number of the last commit of each record
+    records: Columns<u64>,
+}
+
+impl MemoryStorage {
+    /// Create empty in-memory storage.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A helper method to build a prefix to storage keys or identify a subtree
+    /// in `subtrees` map by tree path;
+    pub fn build_prefix<B>(path: SubtreePath<B>) -> CostContext<SubtreePrefix>
+    where
+        B: AsRef<[u8]>,
+    {
+        prefix::build_prefix(path)
+    }
+
+    /// Committed records, a poisoned lock is fine to use as writes are applied
+    /// one record at a time
+    fn db(&self) -> RwLockReadGuard<'_, Columns<Vec<u8>>> {
+        self.db.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn db_mut(&self) -> RwLockWriteGuard<'_, Columns<Vec<u8>>> {
+        self.db.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Commit sequence numbers, locked before the committed records so
+    /// conflicts are checked and writes applied as one step
+    fn sequences(&self) -> MutexGuard<'_, Sequences> {
+        self.sequences
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Get a committed record
+    pub(crate) fn get(&self, column: Column, key: &[u8]) -> Option<Vec<u8>> {
+        self.db().column(column).get(key).cloned()
+    }
+
+    /// Apply writes to the committed records
+    pub(crate) fn write(&self, writes: impl IntoIterator<Item = Write>) {
+        self.write_with_sequences(&mut self.sequences(), writes)
+    }
+
+    /// Apply writes to the committed records as the next commit
+    fn write_with_sequences(
+        &self,
+        sequences: &mut Sequences,
+        writes: impl IntoIterator<Item = Write>,
+    ) {
+        sequences.last += 1;
+        let mut db = self.db_mut();
+        for (column, key, value) in writes {
+            sequences
+                .records
+                .column_mut(column)
+                .insert(key.clone(), sequences.last);
+            match value {
+                Some(value) => db.column_mut(column).insert(key, value),
+                None => db.column_mut(column).remove(&key),
+            };
+        }
+    }
+
+    /// Committed records of a column whose keys start with a prefix
+    fn prefixed_entries(&self, column: Column, prefix: &[u8]) -> BTreeMap<Vec<u8>, Vec<u8>> {
+        prefixed_range(self.db().column(column), prefix)
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+
+    /// Returns the writes of a storage batch, with costs and pending costs.
+    /// Pending costs are costs that should only be applied after successful
+    /// write of the batch.
+    fn build_writes(
+        &self,
+        storage_batch: StorageBatch,
+        transaction: Option<&MemoryTransaction>,
+    ) -> CostResult<(Vec<Write>, OperationCost), Error> {
+        let mut cost = OperationCost::default();
+        // Until batch is committed these costs are pending (should not be added in case
+        // of early termination).
+        let mut pending_costs = OperationCost::default();
+        let mut writes = Vec::new();
+
+        for op in storage_batch.into_iter() {
+            match op {
+                AbstractBatchOperation::Put {
+                    key,
+                    value,
+                    children_sizes,
+                    cost_info,
+                } => {
+                    cost.seek_count += 1;
+                    cost_return_on_error_no_add!(
+                        &cost,
+                        pending_costs
+                            .add_key_value_storage_costs(
+                                key.len() as u32,
+                                value.len() as u32,
+                                children_sizes,
+                                cost_info
+                            )
+                            .map_err(CostError)
+                    );
+                    writes.push((Column::Data, key, Some(value)));
+                }
+                AbstractBatchOperation::PutAux {
+                    key,
+                    value,
+                    cost_info,
+                } => {
+                    cost.seek_count += 1;
+                    cost_return_on_error_no_add!(
+                        &cost,
+                        pending_costs
+                            .add_key_value_storage_costs(
+                                key.len() as u32,
+                                value.len() as u32,
+                                None,
+                                cost_info
+                            )
+                            .map_err(CostError)
+                    );
+                    writes.push((Column::Aux, key, Some(value)));
+                }
+                AbstractBatchOperation::PutRoot {
+                    key,
+                    value,
+                    cost_info,
+                } => {
+                    cost.seek_count += 1;
+                    // We only add costs for put root if they are set, otherwise it is free
+                    if cost_info.is_some() {
+                        cost_return_on_error_no_add!(
+                            &cost,
+                            pending_costs
+                                .add_key_value_storage_costs(
+                                    key.len() as u32,
+                                    value.len() as u32,
+                                    None,
+                                    cost_info
+                                )
+                                .map_err(CostError)
+                        );
+                    }
+                    writes.push((Column::Roots, key, Some(value)));
+                }
+                AbstractBatchOperation::PutMeta {
+                    key,
+                    value,
+                    cost_info,
+                } => {
+                    cost.seek_count += 1;
+                    cost_return_on_error_no_add!(
+                        &cost,
+                        pending_costs
+                            .add_key_value_storage_costs(
+                                key.len() as u32,
+                                value.len() as u32,
+                                None,
+                                cost_info
+                            )
+                            .map_err(CostError)
+                    );
+                    writes.push((Column::Meta, key, Some(value)));
+                }
+                AbstractBatchOperation::Delete { key, cost_info } => {
+                    self.add_delete_costs(
+                        Column::Data,
+                        &key,
+                        cost_info,
+                        &mut cost,
+                        &mut pending_costs,
+                    );
+                    writes.push((Column::Data, key, None));
+                }
+                AbstractBatchOperation::DeleteAux { key, cost_info } => {
+                    self.add_delete_costs(
+                        Column::Aux,
+                        &key,
+                        cost_info,
+                        &mut cost,
+                        &mut pending_costs,
+                    );
+                    writes.push((Column::Aux, key, None));
+                }
+                AbstractBatchOperation::DeleteRoot { key, cost_info } => {
+                    self.add_delete_costs(
+                        Column::Roots,
+                        &key,
+                        cost_info,
+                        &mut cost,
+                        &mut pending_costs,
+                    );
+                    writes.push((Column::Roots, key, None));
+                }
+                AbstractBatchOperation::DeleteMeta { key, cost_info } => {
+                    self.add_delete_costs(
+                        Column::Meta,
+                        &key,
+                        cost_info,
+                        &mut cost,
+                        &mut pending_costs,
+                    );
+                    writes.push((Column::Meta, key, None));
+                }
+                AbstractBatchOperation::DeletePrefix { prefix } => {
+                    for column in [Column::Data, Column::Aux, Column::Roots, Column::Meta] {
+                        let entries = match transaction {
+                            Some(transaction) => transaction.prefixed_entries(column, &prefix),
+                            None => self.prefixed_entries(column, &prefix),
+                        };
+                        cost.seek_count += 1;
+                        for (key, value) in entries {
+                            cost.seek_count += 1;
+                            add_removed_record_costs(
+                                &key,
+                                value.len() as u32,
+                                &mut cost,
+                                &mut pending_costs,
+                            );
+                            writes.push((column, key, None));
+                        }
+                    }
+                }
+            }
+        }
+        Ok((writes, pending_costs)).wrap_with_cost(cost)
+    }
+
+    /// Costs of a deletion, when no removed bytes are provided the removed
+    /// record is loaded from committed data, as RocksDB does it
+    fn add_delete_costs(
+        &self,
+        column: Column,
+        key: &[u8],
+        cost_info: Option<KeyValueStorageCost>,
+        cost: &mut OperationCost,
+        pending_costs: &mut OperationCost,
+    ) {
+        if let Some(key_value_removed_bytes) = cost_info {
+            cost.seek_count += 1;
+            pending_costs.storage_cost.removed_bytes +=
+                key_value_removed_bytes.combined_removed_bytes();
+        } else {
+            cost.seek_count += 2;
+            let value_len = self.get(column, key).map(|x| x.len() as u32).unwrap_or(0);
+            add_removed_record_costs(key, value_len, cost, pending_costs);
+        }
+    }
+}
+
+/// Loaded and removed bytes of a deletion of a record loaded from storage
+fn add_removed_record_costs(
+    key: &[u8],
+    value_len: u32,
+    cost: &mut OperationCost,
+    pending_costs: &mut OperationCost,
+) {
+    cost.storage_loaded_bytes += value_len;
+    let key_len = key.len() as u32;
+    pending_costs.storage_cost.removed_bytes += BasicStorageRemoval(
+        key_len + value_len + key_len.required_space() as u32 + value_len.required_space() as u32,
+    );
+}
+
+/// Transaction over in-memory storage. Writes are kept aside until commit,
+/// reads see them on top of the committed data. Like RocksDB optimistic
+/// transactions, a commit fails if a record the transaction wrote was
+/// committed after it first wrote it. Snapshot transactions are read only.
+#[derive(Debug)]
+pub struct MemoryTransaction<'db> {
+    storage: &'db MemoryStorage,
+    writes: Mutex<Columns<Option<Vec<u8>>>>,
+    /// Sequence number each written record is checked for conflicts against
+    tracked: Mutex<Columns<u64>>,
+    savepoints: Mutex<Vec<Savepoint>>,
+    /// Copy of the data committed at the start of a snapshot transaction
+    snapshot: Option<Columns<Vec<u8>>>,
+}
+
+/// Copies of the writes and tracked records a transaction had when a
+/// savepoint was set
+#[derive(Debug)]
+struct Savepoint {
+    writes: Columns<Option<Vec<u8>>>,
+    tracked: Columns<u64>,
+}
+
+impl<'db> MemoryTransaction<'db> {
+    fn writes(&self) -> MutexGuard<'_, Columns<Option<Vec<u8>>>> {
+        self.writes.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Get a record as seen by the transaction
+    pub(crate) fn get(&self, column: Column, key: &[u8]) -> Option<Vec<u8>> {
+        match self.writes().column(column).get(key) {
+            Some(value) => value.clone(),
+            None => match &self.snapshot {
+                Some(snapshot) => snapshot.column(column).get(key).cloned(),
+                None => self.storage.get(column, key),
+            },
+        }
+    }
+
+    fn tracked(&self) -> MutexGuard<'_, Columns<u64>> {
+        self.tracked.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Get a record as seen by the transaction, tracking it for conflicts as
+    /// if the transaction wrote it. Snapshot transactions don't track it.
+    fn get_for_update(&self, column: Column, key: &[u8]) -> Option<Vec<u8>> {
+        if self.snapshot.is_none() {
+            let sequence = self.storage.sequences().last;
+            self.tracked()
+                .column_mut(column)
+                .entry(key.to_vec())
+                .or_insert(sequence);
+        }
+        self.get(column, key)
+    }
+
+    /// Fails if the transaction is read only, as snapshot transactions are
+    pub(crate) fn check_writable(&self) -> Result<(), Error> {
+        if self.snapshot.is_some() {
+            Err(Error::NotSupported("writes through a snapshot transaction"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Apply writes to the transaction
+    pub(crate) fn write(&self, writes: impl IntoIterator<Item = Write>) -> Result<(), Error> {
+        self.check_writable()?;
+        let sequence = self.storage.sequences().last;
+        let mut tx_writes = self.writes();
+        let mut tracked = self.tracked();
+        for (column, key, value) in writes {
+            tracked
+                .column_mut(column)
+                .entry(key.clone())
+                .or_insert(sequence);
+            tx_writes.column_mut(column).insert(key, value);
+        }
+        Ok(())
+    }
+
+    /// Fails if a record the transaction wrote was committed after the
+    /// sequence number it is tracked with
+    fn validate(&self, sequences: &Sequences) -> Result<(), Error> {
+        let tracked = self.tracked();
+        for column in [Column::Data, Column::Aux, Column::Roots, Column::Meta] {
+            let committed = sequences.records.column(column);
+            for (key, sequence) in tracked.column(column) {
+                if matches!(committed.get(key), Some(last) if last > sequence) {
+                    return Err(Error::TransactionConflict);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Records of a column whose keys start with a prefix as seen by the
+    /// transaction
+    fn prefixed_entries(&self, column: Column, prefix: &[u8]) -> BTreeMap<Vec<u8>, Vec<u8>> {
+        let tx_writes = self.writes();
+        let mut entries = match &self.snapshot {
+            Some(snapshot) => prefixed_range(snapshot.column(column), prefix)
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            None => self.storage.prefixed_entries(column, prefix),
+        };
+        for (key, value) in prefixed_range(tx_writes.column(column), prefix) {
+            match value {
+                Some(value) => entries.insert(key.clone(), value.clone()),
+                None => entries.remove(key),
+            };
+        }
+        entries
+    }
+
+    fn savepoints(&self) -> MutexGuard<'_, Vec<Savepoint>> {
+        self.savepoints
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Takes the writes made so far, leaving the transaction empty
+    fn take_writes(&self) -> Vec<Write> {
+        self.savepoints().clear();
+        std::mem::take(&mut *self.tracked());
+        let tx_writes = std::mem::take(&mut *self.writes());
+        [
+            (Column::Data, tx_writes.data),
+            (Column::Aux, tx_writes.aux),
+            (Column::Roots, tx_writes.roots),
+            (Column::Meta, tx_writes.meta),
+        ]
+        .into_iter()
+        .flat_map(|(column, writes)| {
+            writes
+                .into_iter()
+                .map(move |(key, value)| (column, key, value))
+        })
+        .collect()
+    }
+}
+
+/// Data a storage context reads
+#[derive(Clone, Copy)]
+pub(crate) enum View<'db> {
+    /// Committed data
+    Committed(&'db MemoryStorage),
+    /// Data as seen by a transaction
+    Transaction(&'db MemoryTransaction<'db>),
+}
+
+impl<'db> View<'db> {
+    /// Fails if writes must not be made on top of the view
+    pub(crate) fn check_writable(&self) -> Result<(), Error> {
+        match self {
+            View::Committed(_) => Ok(()),
+            View::Transaction(transaction) => transaction.check_writable(),
+        }
+    }
+
+    pub(crate) fn get(&self, column: Column, key: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            View::Committed(storage) => storage.get(column, key),
+            View::Transaction(transaction) => transaction.get(column, key),
+        }
+    }
+
+    pub(crate) fn prefixed_entries(&self, prefix: &SubtreePrefix) -> Vec<(Vec<u8>, Vec<u8>)> {
+        match self {
+            View::Committed(storage) => storage.prefixed_entries(Column::Data, prefix),
+            View::Transaction(transaction) => transaction.prefixed_entries(Column::Data, prefix),
+        }
+        .into_iter()
+        .collect()
+    }
+}
+
+impl<'db> Storage<'db> for MemoryStorage {
+    type BatchStorageContext = MemoryStorageContext<'db>;
+    type BatchTransactionalStorageContext = MemoryStorageContext<'db>;
+    type ImmediateStorageContext = MemoryImmediateStorageContext<'db>;
+    type Transaction = MemoryTransaction<'db>;
+
+    fn start_transaction(&'db self) -> Self::Transaction {
+        MemoryTransaction {
+            storage: self,
+            writes: Mutex::new(Columns::default()),
+            tracked: Mutex::new(Columns::default()),
+            savepoints: Mutex::new(Vec::new()),
+            snapshot: None,
+        }
+    }
+
+    fn start_snapshot_transaction(&'db self) -> Self::Transaction {
+        MemoryTransaction {
+            storage: self,
+            writes: Mutex::new(Columns::default()),
+            tracked: Mutex::new(Columns::default()),
+            savepoints: Mutex::new(Vec::new()),
+            snapshot: Some(self.db().clone()),
+        }
+    }
+
+    fn commit_transaction(&self, transaction: Self::Transaction) -> CostResult<(), Error> {
+        // All transaction costs were provided on method calls
+        let mut sequences = self.sequences();
+        transaction
+            .validate(&sequences)
+            .map(|_| self.write_with_sequences(&mut sequences, transaction.take_writes()))
+            .wrap_with_cost(Default::default())
+    }
+
+    fn rollback_transaction(&self, transaction: &Self::Transaction) -> Result<(), Error> {
+        transaction.take_writes();
+        Ok(())
+    }
+
+    fn set_savepoint(&self, transaction: &Self::Transaction) {
+        let savepoint = Savepoint {
+            writes: transaction.writes().clone(),
+            tracked: transaction.tracked().clone(),
+        };
+        transaction.savepoints().push(savepoint);
+    }
+
+    fn rollback_to_savepoint(&self, transaction: &Self::Transaction) -> Result<(), Error> {
+        let savepoint = transaction
+            .savepoints()
+            .pop()
+            .ok_or_else(|| Error::StorageError("transaction has no savepoint".to_owned()))?;
+        *transaction.writes() = savepoint.writes;
+        *transaction.tracked() = savepoint.tracked;
+        Ok(())
+    }
+
+    fn release_savepoint(&self, transaction: &Self::Transaction) -> Result<(), Error> {
+        transaction
+            .savepoints()
+            .pop()
+            .map(|_| ())
+            .ok_or_else(|| Error::StorageError("transaction has no savepoint".to_owned()))
+    }
+
+    fn savepoint_depth(&self, transaction: &Self::Transaction) -> usize {
+        transaction.savepoints().len()
+    }
+
+    fn validate_transaction(&self, transaction: &Self::Transaction) -> Result<(), Error> {
+        transaction.validate(&self.sequences())
+    }
+
+    fn get_for_update(
+        &self,
+        transaction: &Self::Transaction,
+        prefix: &SubtreePrefix,
+        key: &[u8],
+    ) -> CostResult<Option<Vec<u8>>, Error> {
+        let value = transaction.get_for_update(Column::Data, &make_prefixed_key(prefix, key));
+        let cost = get_cost(&value);
+        Ok(value).wrap_with_cost(cost)
+    }
+
+    fn previous_records(
+        &'db self,
+        batch: &StorageBatch,
+        transaction: Option<&'db Self::Transaction>,
+    ) -> CostResult<PreviousRecords, Error> {
+        batch.previous_records(
+            |column, key| {
+                Ok(match transaction {
+                    Some(transaction) => transaction.get(column, key),
+                    None => self.get(column, key),
+                })
+            },
+            |column, prefix| {
+                match transaction {
+                    Some(transaction) => transaction.prefixed_entries(column, prefix),
+                    None => self.prefixed_entries(column, prefix),
+                }
+                .into_iter()
+                .collect()
+            },
+        )
+    }
+
+    fn stored_prefixes(
+        &'db self,
+        from: &[u8],
+        limit: usize,
+        is_skipped: &dyn Fn(&[u8]) -> bool,
+        transaction: Option<&'db Self::Transaction>,
+    ) -> CostResult<BTreeMap<Vec<u8>, u64>, Error> {
+        let mut cost = OperationCost::default();
+        let mut sizes = BTreeMap::new();
+        for column in [Column::Data, Column::Aux, Column::Roots, Column::Meta] {
+            let entries = match transaction {
+                Some(transaction) => transaction.prefixed_entries(column, &[]),
+                None => self.prefixed_entries(column, &[]),
+            };
+            let mut cursor = EntriesCursor {
+                entries: &entries,
+                current: None,
+            };
+            cost += prefix::add_stored_prefixes(&mut sizes, &mut cursor, from, limit, is_skipped);
+        }
+        prefix::truncate_stored_prefixes(&mut sizes, limit);
+        Ok(sizes).wrap_with_cost(cost)
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn get_storage_context<'b, B>(
+        &'db self,
+        path: SubtreePath<'b, B>,
+        batch: Option<&'db StorageBatch>,
+    ) -> CostContext<Self::BatchStorageContext>
+    where
+        B: AsRef<[u8]> + 'b,
+    {
+        Self::build_prefix(path)
+            .map(|prefix| MemoryStorageContext::new(View::Committed(self), prefix, batch))
+    }
+
+    fn get_transactional_storage_context<'b, B>(
+        &'db self,
+        path: SubtreePath<'b, B>,
+        batch: Option<&'db StorageBatch>,
+        transaction: &'db Self::Transaction,
+    ) -> CostContext<Self::BatchTransactionalStorageContext>
+    where
+        B: AsRef<[u8]> + 'b,
+    {
+        Self::build_prefix(path)
+            .map(|prefix| MemoryStorageContext::new(View::Transaction(transaction), prefix, batch))
+    }
+
+    fn get_immediate_storage_context<'b, B>(
+        &'db self,
+        path: SubtreePath<'b, B>,
+        transaction: &'db Self::Transaction,
+    ) -> CostContext<Self::ImmediateStorageContext>
+    where
+        B: AsRef<[u8]> + 'b,
+    {
+        Self::build_prefix(path)
+            .map(|prefix| MemoryImmediateStorageContext::new(transaction, prefix))
+    }
+
+    fn commit_multi_context_batch(
+        &self,
+        batch: StorageBatch,
+        transaction: Option<&'db Self::Transaction>,
+    ) -> CostResult<(), Error> {
+        let mut cost = OperationCost::default();
+        let (writes, pending_costs) =
+            cost_return_on_error!(&mut cost, self.build_writes(batch, transaction));
+
+        match transaction {
+            None => self.write(writes),
+            Some(_) if writes.is_empty() => {}
+            Some(transaction) => {
+                cost_return_on_error_no_add!(&cost, transaction.write(writes))
+            }
+        }
+        Ok(()).wrap_with_cost(pending_costs).add_cost(cost)
+    }
+
+    fn multi_context_batch_cost(
+        &self,
+        batch: StorageBatch,
+        transaction: Option<&'db Self::Transaction>,
+    ) -> CostResult<(), Error> {
+        self.build_writes(batch, transaction)
+            .flat_map_ok(|(_, pending_costs)| Ok(()).wrap_with_cost(pending_costs))
+    }
+
+    fn get_storage_context_cost<L: WorstKeyLength>(path: &[L]) -> OperationCost {
+        prefix::build_prefix_worst_case_cost(path)
+    }
+
+    fn create_checkpoint<P: AsRef<Path>>(&self, _path: P) -> Result<(), Error> {
+        Err(Error::StorageError(
+            "checkpoints are not supported by in-memory storage".to_owned(),
+        ))
+    }
+}
+
+impl CovariantStorage for MemoryStorage {
+    fn narrow_transaction<'a, 'db>(
+        &self,
+        transaction: &'a <Self as Storage<'db>>::Transaction,
+    ) -> &'a <Self as Storage<'a>>::Transaction {
+        transaction
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Batch, RawIterator, StorageContext};
+
+    #[test]
+    fn test_transaction_isolation() {
+        let storage = MemoryStorage::new();
+        let path = SubtreePath::from(&[b"tree" as &[u8]]);
+
+        let transaction = storage.start_transaction();
+        let batch = StorageBatch::new();
+        let context = storage
+            .get_transactional_storage_context(path.clone(), Some(&batch), &transaction)
+            .unwrap();
+        context.put(b"key", b"value", None, None).unwrap().unwrap();
+        context.put_aux(b"key", b"aux", None).unwrap().unwrap();
+        storage
+            .commit_multi_context_batch(batch, Some(&transaction))
+            .unwrap()
+            .expect("cannot commit batch");
+
+        let context = storage
+            .get_transactional_storage_context(path.clone(), None, &transaction)
+            .unwrap();
+        assert_eq!(
+            context.get(b"key").unwrap().unwrap(),
+            Some(b"value".to_vec())
+        );
+        assert_eq!(
+            context.get_aux(b"key").unwrap().unwrap(),
+            Some(b"aux".to_vec())
+        );
+        let context = storage.get_storage_context(path.clone(), None).unwrap();
+        assert_eq!(context.get(b"key").unwrap().unwrap(), None);
+
+        storage
+            .rollback_transaction(&transaction)
+            .expect("cannot rollback transaction");
+        let context = storage
+            .get_transactional_storage_context(path.clone(), None, &transaction)
+            .unwrap();
+        assert_eq!(context.get(b"key").unwrap().unwrap(), None);
+
+        let context = storage
+            .get_immediate_storage_context(path.clone(), &transaction)
+            .unwrap();
+        let mut db_batch = context.new_batch();
+        db_batch.put(b"key", b"value", None, None).unwrap();
+        db_batch.delete(b"missing", None);
+        context.commit_batch(db_batch).unwrap().unwrap();
+        storage
+            .commit_transaction(transaction)
+            .unwrap()
+            .expect("cannot commit transaction");
+
+        let context = storage.get_storage_context(path, None).unwrap();
+        assert_eq!(
+            context.get(b"key").unwrap().unwrap(),
+            Some(b"value".to_vec())
+        );
+        assert_eq!(context.get(b"missing").unwrap().unwrap(), None);
+    }
+
+    #[test]
+    fn test_savepoints() {
+        let storage = MemoryStorage::new();
+        let path = SubtreePath::from(&[b"tree" as &[u8]]);
+        let transaction = storage.start_transaction();
+        let context = storage
+            .get_immediate_storage_context(path, &transaction)
+            .unwrap();
+
+        context.put(b"a", b"1", None, None).unwrap().unwrap();
+        storage.set_savepoint(&transaction);
+        context.put(b"a", b"2", None, None).unwrap().unwrap();
+        storage.set_savepoint(&transaction);
+        context.put(b"b", b"3", None, None).unwrap().unwrap();
+        assert_eq!(storage.savepoint_depth(&transaction), 2);
+
+        storage
+            .rollback_to_savepoint(&transaction)
+            .expect("cannot rollback to savepoint");
+        assert_eq!(storage.savepoint_depth(&transaction), 1);
+        assert_eq!(context.get(b"a").unwrap().unwrap(), Some(b"2".to_vec()));
+        assert_eq!(context.get(b"b").unwrap().unwrap(), None);
+
+        storage
+            .rollback_to_savepoint(&transaction)
+            .expect("cannot rollback to savepoint");
+        assert_eq!(context.get(b"a").unwrap().unwrap(), Some(b"1".to_vec()));
+        assert!(storage.rollback_to_savepoint(&transaction).is_err());
+
+        storage.set_savepoint(&transaction);
+        context.put(b"a", b"4", None, None).unwrap().unwrap();
+        storage
+            .release_savepoint(&transaction)
+            .expect("cannot release savepoint");
+        assert_eq!(context.get(b"a").unwrap().unwrap(), Some(b"4".to_vec()));
+        assert!(storage.rollback_to_savepoint(&transaction).is_err());
+    }
+
+    #[test]
+    fn test_transaction_conflicts() {
+        let storage = MemoryStorage::new();
+        let path = SubtreePath::from(&[b"tree" as &[u8]]);
+        let put = |transaction: &MemoryTransaction, key: &[u8]| {
+            storage
+                .get_immediate_storage_context(path.clone(), transaction)
+                .unwrap()
+                .put(key, b"value", None, None)
+                .unwrap()
+                .unwrap();
+        };
+
+        let first = storage.start_transaction();
+        let second = storage.start_transaction();
+        put(&first, b"a");
+        put(&second, b"a");
+        put(&second, b"b");
+        storage.commit_transaction(first).unwrap().unwrap();
+
+        assert!(matches!(
+            storage.validate_transaction(&second),
+            Err(Error::TransactionConflict)
+        ));
+        assert!(matches!(
+            storage.commit_transaction(second).unwrap(),
+            Err(Error::TransactionConflict)
+        ));
+
+        // Rolling back to a savepoint stops tracking the rolled back records
+        let transaction = storage.start_transaction();
+        put(&transaction, b"c");
+        storage.set_savepoint(&transaction);
+        put(&transaction, b"a");
+        let other = storage.start_transaction();
+        put(&other, b"a");
+        storage.commit_transaction(other).unwrap().unwrap();
+        storage
+            .rollback_to_savepoint(&transaction)
+            .expect("cannot rollback to savepoint");
+        storage
+            .commit_transaction(transaction)
+            .unwrap()
+            .expect("cannot commit transaction");
+
+        // Records read for update are tracked like written ones
+        let prefix = MemoryStorage::build_prefix(path.clone()).unwrap();
+        let transaction = storage.start_transaction();
+        assert_eq!(
+            storage
+                .get_for_update(&transaction, &prefix, b"c")
+                .unwrap()
+                .expect("cannot read record"),
+            Some(b"value".to_vec())
+        );
+        let other = storage.start_transaction();
+        put(&other, b"c");
+        storage.commit_transaction(other).unwrap().unwrap();
+        assert!(matches!(
+            storage.commit_transaction(transaction).unwrap(),
+            Err(Error::TransactionConflict)
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_transactions_are_read_only() {
+        let storage = MemoryStorage::new();
+        let path = SubtreePath::from(&[b"tree" as &[u8]]);
+        let snapshot = storage.start_snapshot_transaction();
+
+        let context = storage
+            .get_immediate_storage_context(path.clone(), &snapshot)
+            .unwrap();
+        assert!(matches!(
+            context.put(b"a", b"value", None, None).unwrap(),
+            Err(Error::NotSupported(_))
+        ));
+
+        let batch = StorageBatch::new();
+        let context = storage
+            .get_transactional_storage_context(path, Some(&batch), &snapshot)
+            .unwrap();
+        assert!(matches!(
+            context.delete(b"a", None).unwrap(),
+            Err(Error::NotSupported(_))
+        ));
+        batch.put_aux(b"a".to_vec(), b"value".to_vec(), None);
+        assert!(matches!(
+            storage
+                .commit_multi_context_batch(batch, Some(&snapshot))
+                .unwrap(),
+            Err(Error::NotSupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_raw_iterator_stays_in_subtree() {
+        let storage = MemoryStorage::new();
+        let path_a = SubtreePath::from(&[b"a" as &[u8]]);
+        let path_b = SubtreePath::from(&[b"b" as &[u8]]);
+
+        let batch = StorageBatch::new();
+        let context_a = storage
+            .get_storage_context(path_a.clone(), Some(&batch))
+            .unwrap();
+        let context_b = storage.get_storage_context(path_b, Some(&batch)).unwrap();
+        for key in [b"1", b"3", b"5"] {
+            context_a.put(key, b"a", None, None).unwrap().unwrap();
+            context_b.put(key, b"b", None, None).unwrap().unwrap();
+        }
+        storage
+            .commit_multi_context_batch(batch, None)
+            .unwrap()
+            .expect("cannot commit batch");
+
+        let context = storage.get_storage_context(path_a, None).unwrap();
+        let mut iter = context.raw_iter();
+        let mut keys = Vec::new();
+        iter.seek_to_first().unwrap();
+        while iter.valid().unwrap() {
+            keys.push(iter.key().unwrap().unwrap().to_vec());
+            assert_eq!(iter.value().unwrap(), Some(b"a".as_ref()));
+            iter.next().unwrap();
+        }
+        assert_eq!(keys, vec![b"1".to_vec(), b"3".to_vec(), b"5".to_vec()]);
+
+        iter.seek(b"2").unwrap();
+        assert_eq!(iter.key().unwrap(), Some(b"3".as_ref()));
+        iter.seek_for_prev(b"2").unwrap();
+        assert_eq!(iter.key().unwrap(), Some(b"1".as_ref()));
+        iter.prev().unwrap();
+        assert!(!iter.valid().unwrap());
+        iter.seek_to_last().unwrap();
+        assert_eq!(iter.key().unwrap(), Some(b"5".as_ref()));
+
+        // Leaving the subtree costs the same whatever comes after it
+        iter.next().unwrap();
+        let cost = iter.key().cost;
+        assert_eq!(cost.storage_loaded_bytes, prefix::MAX_PREFIXED_KEY_LENGTH);
+    }
+
+    #[test]
+    fn test_batch_commit_costs() {
+        let storage = MemoryStorage::new();
+        let path = SubtreePath::from(&[b"tree" as &[u8]]);
+
+        let batch = StorageBatch::new();
+        let context = storage
+            .get_storage_context(path.clone(), Some(&batch))
+            .unwrap();
+        context.put_aux(b"key", b"value", None).unwrap().unwrap();
+        let cost = storage.commit_multi_context_batch(batch, None).cost;
+        assert_eq!(cost.seek_count, 1);
+        assert_eq!(cost.storage_cost.added_bytes, 32 + 3 + 5 + 1 + 1);
+
+        let batch = StorageBatch::new();
+        let context = storage
+            .get_storage_context(path.clone(), Some(&batch))
+            .unwrap();
+        context.delete_aux(b"key", None).unwrap().unwrap();
+        let cost = storage.commit_multi_context_batch(batch, None).cost;
+        assert_eq!(cost.seek_count, 2);
+        assert_eq!(cost.storage_loaded_bytes, 5);
+        assert_eq!(
+            cost.storage_cost.removed_bytes,
+            BasicStorageRemoval(32 + 3 + 5 + 1 + 1)
+        );
+        let context = storage.get_storage_context(path, None).unwrap();
+        assert_eq!(context.get_aux(b"key").unwrap().unwrap(), None);
+    }
+
+    #[test]
+    fn test_stored_prefixes() {
+        let storage = MemoryStorage::new();
+        let paths: Vec<[&[u8]; 1]> = vec![[b"a"], [b"b"], [b"c"]];
+        let batch = StorageBatch::new();
+        for path in paths.iter() {
+            let context = storage
+                .get_storage_context(path.as_ref().into(), Some(&batch))
+                .unwrap();
+            context.put(b"key", b"value", None, None).unwrap().unwrap();
+            context.put_meta(b"meta", b"value", None).unwrap().unwrap();
+        }
+        storage
+            .commit_multi_context_batch(batch, None)
+            .unwrap()
+            .expect("cannot commit batch");
+
+        let mut prefixes: Vec<Vec<u8>> = paths
+            .iter()
+            .map(|path| prefix::build_prefix(path.as_ref().into()).unwrap().to_vec())
+            .collect();
+        prefixes.sort();
+
+        // 32 bytes of prefix for both keys, 3 and 4 bytes keys, 5 bytes values
+        let size = 2 * 32 + 3 + 4 + 2 * 5;
+        let stored = storage
+            .stored_prefixes(&[], usize::MAX, &|_| false, None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            stored,
+            prefixes
+                .iter()
+                .map(|prefix| (prefix.clone(), size))
+                .collect()
+        );
+
+        let skipped = prefixes[0].clone();
+        let stored = storage
+            .stored_prefixes(&[], 1, &|prefix| prefix == skipped.as_slice(), None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored, BTreeMap::from([(prefixes[1].clone(), size)]));
+
+        let stored = storage
+            .stored_prefixes(&prefixes[2], usize::MAX, &|_| false, None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored, BTreeMap::from([(prefixes[2].clone(), size)]));
+    }
+
+    #[test]
+    fn test_restore_previous_records() {
+        let storage = MemoryStorage::new();
+        let path_a = SubtreePath::from(&[b"a" as &[u8]]);
+        let path_b = SubtreePath::from(&[b"b" as &[u8]]);
+
+        let batch = StorageBatch::new();
+        let context_a = storage
+            .get_storage_context(path_a.clone(), Some(&batch))
+            .unwrap();
+        let context_b = storage
+            .get_storage_context(path_b.clone(), Some(&batch))
+            .unwrap();
+        context_a.put(b"1", b"a1", None, None).unwrap().unwrap();
+        context_a.put_aux(b"2", b"a2", None).unwrap().unwrap();
+        context_b.put(b"1", b"b1", None, None).unwrap().unwrap();
+        storage
+            .commit_multi_context_batch(batch, None)
+            .unwrap()
+            .expect("cannot commit batch");
+
+        // Delete subtree `a` and write a new record to it, overwrite a record
+        // of `b` and add another one
+        let batch = StorageBatch::new();
+        batch.delete_prefix(
+            MemoryStorage::build_prefix(path_a.clone())
+                .unwrap()
+                .to_vec(),
+        );
+        let context_a = storage
+            .get_storage_context(path_a.clone(), Some(&batch))
+            .unwrap();
+        let context_b = storage
+            .get_storage_context(path_b.clone(), Some(&batch))
+            .unwrap();
+        context_a.put(b"3", b"a3", None, None).unwrap().unwrap();
+        context_b.put(b"1", b"new", None, None).unwrap().unwrap();
+        context_b.put_root(b"root", b"b", None).unwrap().unwrap();
+
+        let tx = storage.start_transaction();
+        let previous = storage
+            .previous_records(&batch, Some(&tx))
+            .unwrap()
+            .expect("cannot read previous records");
+        assert_eq!(previous.data.len(), 3);
+        assert_eq!(previous.aux.len(), 1);
+        assert_eq!(
+            previous.roots,
+            vec![(
+                [
+                    MemoryStorage::build_prefix(path_b.clone())
+                        .unwrap()
+                        .as_slice(),
+                    b"root"
+                ]
+                .concat(),
+                None
+            )]
+        );
+        storage
+            .commit_multi_context_batch(batch, Some(&tx))
+            .unwrap()
+            .expect("cannot commit batch");
+
+        let batch = StorageBatch::new();
+        batch.restore_previous_records(previous);
+        storage
+            .commit_multi_context_batch(batch, Some(&tx))
+            .unwrap()
+            .expect("cannot commit batch");
+        storage
+            .commit_transaction(tx)
+            .unwrap()
+            .expect("cannot commit transaction");
+
+        let context_a = storage.get_storage_context(path_a, None).unwrap();
+        let context_b = storage.get_storage_context(path_b, None).unwrap();
+        assert_eq!(context_a.get(b"1").unwrap().unwrap(), Some(b"a1".to_vec()));
+        assert_eq!(
+            context_a.get_aux(b"2").unwrap().unwrap(),
+            Some(b"a2".to_vec())
+        );
+        assert_eq!(context_a.get(b"3").unwrap().unwrap(), None);
+        assert_eq!(context_b.get(b"1").unwrap().unwrap(), Some(b"b1".to_vec()));
+        assert_eq!(context_b.get_root(b"root").unwrap().unwrap(), None);
+    }
+}
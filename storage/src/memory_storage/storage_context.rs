@@ -0,0 +1,404 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Implementation of prefixed storage contexts in memory.
+
+use grovedb_costs::{
+    cost_return_on_error_no_add, storage_cost::key_value_cost::KeyValueStorageCost,
+    ChildrenSizesWithIsSumTree, CostResult, CostsExt, OperationCost,
+};
+
+use super::{
+    storage::{MemoryTransaction, View},
+    MemoryBatch, MemoryRawIterator,
+};
+use crate::{
+    error::Error,
+    prefix::{make_prefixed_key, PrefixedMultiContextBatchPart, SubtreePrefix},
+    storage::Column,
+    StorageBatch, StorageContext,
+};
+
+/// Cost of a read, the same as a RocksDB get
+pub(super) fn get_cost(value: &Option<Vec<u8>>) -> OperationCost {
+    OperationCost {
+        seek_count: 1,
+        storage_loaded_bytes: value.as_ref().map(|x| x.len() as u32).unwrap_or(0),
+        ..Default::default()
+    }
+}
+
+/// Storage context with a prefix applied to be used in a subtree, keeping
+/// writes in a multi-context batch. Reads either committed data or data as seen
+/// by a transaction.
+pub struct MemoryStorageContext<'db> {
+    view: View<'db>,
+    prefix: SubtreePrefix,
+    batch: Option<&'db StorageBatch>,
+}
+
+impl<'db> MemoryStorageContext<'db> {
+    /// Create a new prefixed storage context instance
+    pub(crate) fn new(
+        view: View<'db>,
+        prefix: SubtreePrefix,
+        batch: Option<&'db StorageBatch>,
+    ) -> Self {
+        MemoryStorageContext {
+            view,
+            prefix,
+            batch,
+        }
+    }
+
+    /// Batch to write into, fails if the view is read only
+    fn writable_batch(&self) -> Result<Option<&'db StorageBatch>, Error> {
+        self.view.check_writable().map(|_| self.batch)
+    }
+
+    fn get_from(&self, column: Column, key: &[u8]) -> CostResult<Option<Vec<u8>>, Error> {
+        let value = self.view.get(column, &make_prefixed_key(&self.prefix, key));
+        let cost = get_cost(&value);
+        Ok(value).wrap_with_cost(cost)
+    }
+}
+
+impl<'db> StorageContext<'db> for MemoryStorageContext<'db> {
+    type Batch = PrefixedMultiContextBatchPart;
+    type RawIterator = MemoryRawIterator;
+
+    fn put<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+        value: &[u8],
+        children_sizes: ChildrenSizesWithIsSumTree,
+        cost_info: Option<KeyValueStorageCost>,
+    ) -> CostResult<(), Error> {
+        let cost = OperationCost::default();
+        if let Some(existing_batch) = cost_return_on_error_no_add!(&cost, self.writable_batch()) {
+            existing_batch.put(
+                make_prefixed_key(&self.prefix, key),
+                value.to_vec(),
+                children_sizes,
+                cost_info,
+            );
+        }
+        Ok(()).wrap_with_cost(cost)
+    }
+
+    fn put_aux<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+        value: &[u8],
+        cost_info: Option<KeyValueStorageCost>,
+    ) -> CostResult<(), Error> {
+        let cost = OperationCost::default();
+        if let Some(existing_batch) = cost_return_on_error_no_add!(&cost, self.writable_batch()) {
+            existing_batch.put_aux(
+                make_prefixed_key(&self.prefix, key),
+                value.to_vec(),
+                cost_info,
+            );
+        }
+        Ok(()).wrap_with_cost(cost)
+    }
+
+    fn put_root<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+        value: &[u8],
+        cost_info: Option<KeyValueStorageCost>,
+    ) -> CostResult<(), Error> {
+        let cost = OperationCost::default();
+        if let Some(existing_batch) = cost_return_on_error_no_add!(&cost, self.writable_batch()) {
+            existing_batch.put_root(
+                make_prefixed_key(&self.prefix, key),
+                value.to_vec(),
+                cost_info,
+            );
+        }
+        Ok(()).wrap_with_cost(cost)
+    }
+
+    fn put_meta<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+        value: &[u8],
+        cost_info: Option<KeyValueStorageCost>,
+    ) -> CostResult<(), Error> {
+        let cost = OperationCost::default();
+        if let Some(existing_batch) = cost_return_on_error_no_add!(&cost, self.writable_batch()) {
+            existing_batch.put_meta(
+                make_prefixed_key(&self.prefix, key),
+                value.to_vec(),
+                cost_info,
+            );
+        }
+        Ok(()).wrap_with_cost(cost)
+    }
+
+    fn delete<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+        cost_info: Option<KeyValueStorageCost>,
+    ) -> CostResult<(), Error> {
+        let cost = OperationCost::default();
+        if let Some(existing_batch) = cost_return_on_error_no_add!(&cost, self.writable_batch()) {
+            existing_batch.delete(make_prefixed_key(&self.prefix, key), cost_info);
+        }
+        Ok(()).wrap_with_cost(cost)
+    }
+
+    fn delete_aux<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+        cost_info: Option<KeyValueStorageCost>,
+    ) -> CostResult<(), Error> {
+        let cost = OperationCost::default();
+        if let Some(existing_batch) = cost_return_on_error_no_add!(&cost, self.writable_batch()) {
+            existing_batch.delete_aux(make_prefixed_key(&self.prefix, key), cost_info);
+        }
+        Ok(()).wrap_with_cost(cost)
+    }
+
+    fn delete_root<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+        cost_info: Option<KeyValueStorageCost>,
+    ) -> CostResult<(), Error> {
+        let cost = OperationCost::default();
+        if let Some(existing_batch) = cost_return_on_error_no_add!(&cost, self.writable_batch()) {
+            existing_batch.delete_root(make_prefixed_key(&self.prefix, key), cost_info);
+        }
+        Ok(()).wrap_with_cost(cost)
+    }
+
+    fn delete_meta<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+        cost_info: Option<KeyValueStorageCost>,
+    ) -> CostResult<(), Error> {
+        let cost = OperationCost::default();
+        if let Some(existing_batch) = cost_return_on_error_no_add!(&cost, self.writable_batch()) {
+            existing_batch.delete_meta(make_prefixed_key(&self.prefix, key), cost_info);
+        }
+        Ok(()).wrap_with_cost(cost)
+    }
+
+    fn get<K: AsRef<[u8]>>(&self, key: K) -> CostResult<Option<Vec<u8>>, Error> {
+        self.get_from(Column::Data, key.as_ref())
+    }
+
+    fn get_aux<K: AsRef<[u8]>>(&self, key: K) -> CostResult<Option<Vec<u8>>, Error> {
+        self.get_from(Column::Aux, key.as_ref())
+    }
+
+    fn get_root<K: AsRef<[u8]>>(&self, key: K) -> CostResult<Option<Vec<u8>>, Error> {
+        self.get_from(Column::Roots, key.as_ref())
+    }
+
+    fn get_meta<K: AsRef<[u8]>>(&self, key: K) -> CostResult<Option<Vec<u8>>, Error> {
+        self.get_from(Column::Meta, key.as_ref())
+    }
+
+    fn new_batch(&self) -> Self::Batch {
+        PrefixedMultiContextBatchPart {
+            prefix: self.prefix,
+            batch: StorageBatch::new(),
+        }
+    }
+
+    fn commit_batch(&self, batch: Self::Batch) -> CostResult<(), Error> {
+        if let Some(existing_batch) = self.batch {
+            existing_batch.merge(batch.batch);
+        }
+        Ok(()).wrap_with_cost(OperationCost::default())
+    }
+
+    fn raw_iter(&self) -> Self::RawIterator {
+        MemoryRawIterator::new(self.prefix, self.view.prefixed_entries(&self.prefix))
+    }
+
+    fn clear(&mut self) -> CostResult<(), Error> {
+        let cost = OperationCost::default();
+        if let Some(existing_batch) = cost_return_on_error_no_add!(&cost, self.writable_batch()) {
+            existing_batch.delete_prefix(self.prefix.to_vec());
+        }
+        Ok(()).wrap_with_cost(cost)
+    }
+}
+
+/// Storage context with a prefix applied to be used in a subtree, writing
+/// straight into a transaction.
+pub struct MemoryImmediateStorageContext<'db> {
+    transaction: &'db MemoryTransaction<'db>,
+    prefix: SubtreePrefix,
+}
+
+impl<'db> MemoryImmediateStorageContext<'db> {
+    /// Create a new prefixed immediate storage context instance
+    pub(crate) fn new(transaction: &'db MemoryTransaction<'db>, prefix: SubtreePrefix) -> Self {
+        MemoryImmediateStorageContext {
+            transaction,
+            prefix,
+        }
+    }
+
+    fn write<K: AsRef<[u8]>>(
+        &self,
+        column: Column,
+        key: K,
+        value: Option<&[u8]>,
+    ) -> CostResult<(), Error> {
+        self.transaction
+            .write([(
+                column,
+                make_prefixed_key(&self.prefix, key),
+                value.map(<[u8]>::to_vec),
+            )])
+            .wrap_with_cost(Default::default())
+    }
+
+    fn get_from(&self, column: Column, key: &[u8]) -> CostResult<Option<Vec<u8>>, Error> {
+        Ok(self
+            .transaction
+            .get(column, &make_prefixed_key(&self.prefix, key)))
+        .wrap_with_cost(Default::default())
+    }
+}
+
+impl<'db> StorageContext<'db> for MemoryImmediateStorageContext<'db> {
+    type Batch = MemoryBatch;
+    type RawIterator = MemoryRawIterator;
+
+    fn put<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+        value: &[u8],
+        _children_sizes: ChildrenSizesWithIsSumTree,
+        _cost_info: Option<KeyValueStorageCost>,
+    ) -> CostResult<(), Error> {
+        self.write(Column::Data, key, Some(value))
+    }
+
+    fn put_aux<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+        value: &[u8],
+        _cost_info: Option<KeyValueStorageCost>,
+    ) -> CostResult<(), Error> {
+        self.write(Column::Aux, key, Some(value))
+    }
+
+    fn put_root<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+        value: &[u8],
+        _cost_info: Option<KeyValueStorageCost>,
+    ) -> CostResult<(), Error> {
+        self.write(Column::Roots, key, Some(value))
+    }
+
+    fn put_meta<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+        value: &[u8],
+        _cost_info: Option<KeyValueStorageCost>,
+    ) -> CostResult<(), Error> {
+        self.write(Column::Meta, key, Some(value))
+    }
+
+    fn delete<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+        _cost_info: Option<KeyValueStorageCost>,
+    ) -> CostResult<(), Error> {
+        self.write(Column::Data, key, None)
+    }
+
+    fn delete_aux<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+        _cost_info: Option<KeyValueStorageCost>,
+    ) -> CostResult<(), Error> {
+        self.write(Column::Aux, key, None)
+    }
+
+    fn delete_root<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+        _cost_info: Option<KeyValueStorageCost>,
+    ) -> CostResult<(), Error> {
+        self.write(Column::Roots, key, None)
+    }
+
+    fn delete_meta<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+        _cost_info: Option<KeyValueStorageCost>,
+    ) -> CostResult<(), Error> {
+        self.write(Column::Meta, key, None)
+    }
+
+    fn get<K: AsRef<[u8]>>(&self, key: K) -> CostResult<Option<Vec<u8>>, Error> {
+        self.get_from(Column::Data, key.as_ref())
+    }
+
+    fn get_aux<K: AsRef<[u8]>>(&self, key: K) -> CostResult<Option<Vec<u8>>, Error> {
+        self.get_from(Column::Aux, key.as_ref())
+    }
+
+    fn get_root<K: AsRef<[u8]>>(&self, key: K) -> CostResult<Option<Vec<u8>>, Error> {
+        self.get_from(Column::Roots, key.as_ref())
+    }
+
+    fn get_meta<K: AsRef<[u8]>>(&self, key: K) -> CostResult<Option<Vec<u8>>, Error> {
+        self.get_from(Column::Meta, key.as_ref())
+    }
+
+    fn new_batch(&self) -> Self::Batch {
+        MemoryBatch {
+            prefix: self.prefix,
+            writes: Vec::new(),
+            cost_acc: Default::default(),
+        }
+    }
+
+    fn commit_batch(&self, batch: Self::Batch) -> CostResult<(), Error> {
+        self.transaction
+            .write(batch.writes)
+            .wrap_with_cost(Default::default())
+    }
+
+    fn raw_iter(&self) -> Self::RawIterator {
+        MemoryRawIterator::new(
+            self.prefix,
+            View::Transaction(self.transaction).prefixed_entries(&self.prefix),
+        )
+    }
+}
@@ -0,0 +1,275 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Subtree prefixes shared by storage backends.
+
+use std::collections::BTreeMap;
+
+use grovedb_costs::{
+    storage_cost::key_value_cost::KeyValueStorageCost, ChildrenSizesWithIsSumTree, CostContext,
+    CostsExt, OperationCost,
+};
+use grovedb_path::SubtreePath;
+use integer_encoding::VarInt;
+
+use crate::{worst_case_costs::WorstKeyLength, Batch, StorageBatch};
+
+const BLAKE_BLOCK_LEN: usize = 64;
+
+/// 256 bytes for the key and 32 bytes for the prefix
+pub(crate) const MAX_PREFIXED_KEY_LENGTH: u32 = 256 + 32;
+
+/// Prefix of the storage keys of a subtree
+pub type SubtreePrefix = [u8; blake3::OUT_LEN];
+
+fn blake_block_count(len: usize) -> usize {
+    if len == 0 {
+        1
+    } else {
+        1 + (len - 1) / BLAKE_BLOCK_LEN
+    }
+}
+
+fn build_prefix_body<B>(path: SubtreePath<B>) -> (Vec<u8>, usize)
+where
+    B: AsRef<[u8]>,
+{
+    let segments_iter = path.into_reverse_iter();
+    let mut segments_count: usize = 0;
+    let mut res = Vec::new();
+    let mut lengthes = Vec::new();
+
+    for s in segments_iter {
+        segments_count += 1;
+        res.extend_from_slice(s);
+        lengthes.push(s.len() as u8); // if the key len is under 255 bytes
+    }
+
+    res.extend(segments_count.to_ne_bytes());
+    res.extend(lengthes);
+    (res, segments_count)
+}
+
+/// Builds a prefix to storage keys of a subtree by its path
+pub fn build_prefix<B>(path: SubtreePath<B>) -> CostContext<SubtreePrefix>
+where
+    B: AsRef<[u8]>,
+{
+    let (body, segments_count) = build_prefix_body(path);
+    if segments_count == 0 {
+        SubtreePrefix::default().wrap_with_cost(OperationCost::default())
+    } else {
+        let blocks_count = blake_block_count(body.len());
+        SubtreePrefix::from(blake3::hash(&body))
+            .wrap_with_cost(OperationCost::with_hash_node_calls(blocks_count as u32))
+    }
+}
+
+/// Worst case cost of building a prefix for a path
+pub(crate) fn build_prefix_worst_case_cost<L: WorstKeyLength>(path: &[L]) -> OperationCost {
+    if path.is_empty() {
+        OperationCost::default()
+    } else {
+        let body_size = path.len() + path.iter().map(|a| a.max_length() as usize).sum::<usize>();
+        // the block size of blake3 is 64
+        let blocks_num = blake_block_count(body_size) as u32;
+        OperationCost::with_hash_node_calls(blocks_num)
+    }
+}
+
+/// Cursor over the records of a column in key order
+pub(crate) trait RecordCursor {
+    /// Moves to the first record with a key at or after the given one
+    fn seek_to(&mut self, key: &[u8]);
+
+    /// Key and value length of the current record, `None` past the last one
+    fn record(&self) -> Option<(&[u8], usize)>;
+
+    /// Moves to the next record
+    fn next_record(&mut self);
+}
+
+/// First prefix after the given one, `None` if it's the last possible one
+pub(crate) fn next_prefix(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut next = prefix.to_vec();
+    let index = next.iter().rposition(|byte| *byte != u8::MAX)?;
+    next[index] += 1;
+    next.truncate(index + 1);
+    Some(next)
+}
+
+/// Adds to `sizes` the number of key and value bytes stored under each of the
+/// first `limit` subtree prefixes a cursor sees from `from` on. Records are
+/// read one by one, those of prefixes `is_skipped` returns true for are passed
+/// over by seeking to the next prefix, and records with keys too short to
+/// have a prefix are ignored.
+pub(crate) fn add_stored_prefixes(
+    sizes: &mut BTreeMap<Vec<u8>, u64>,
+    cursor: &mut impl RecordCursor,
+    from: &[u8],
+    limit: usize,
+    is_skipped: &dyn Fn(&[u8]) -> bool,
+) -> OperationCost {
+    let mut cost = OperationCost::default();
+    let mut current: Option<(Vec<u8>, u64)> = None;
+    let mut found = 0;
+
+    cost.seek_count += 1;
+    cursor.seek_to(from);
+    while let Some((key, value_len)) = cursor.record() {
+        let Some(prefix) = key.get(..blake3::OUT_LEN) else {
+            cursor.next_record();
+            continue;
+        };
+        let record_len = (key.len() + value_len) as u64;
+        match current.as_mut() {
+            Some((current_prefix, size)) if current_prefix.as_slice() == prefix => {
+                *size += record_len;
+            }
+            _ if is_skipped(prefix) => {
+                let Some(next) = next_prefix(prefix) else {
+                    break;
+                };
+                cost.seek_count += 1;
+                cursor.seek_to(&next);
+                continue;
+            }
+            _ if found == limit => break,
+            _ => {
+                found += 1;
+                if let Some((previous, size)) = current.replace((prefix.to_vec(), record_len)) {
+                    *sizes.entry(previous).or_default() += size;
+                }
+            }
+        }
+        cost.storage_loaded_bytes += value_len as u32;
+        cursor.next_record();
+    }
+    if let Some((prefix, size)) = current {
+        *sizes.entry(prefix).or_default() += size;
+    }
+    cost
+}
+
+/// Keeps the first `limit` prefixes of sizes added from several columns. As
+/// every column added its own first `limit` prefixes, the sizes of the kept
+/// ones are complete.
+pub(crate) fn truncate_stored_prefixes(sizes: &mut BTreeMap<Vec<u8>, u64>, limit: usize) {
+    while sizes.len() > limit {
+        sizes.pop_last();
+    }
+}
+
+/// Make prefixed key
+pub fn make_prefixed_key<K: AsRef<[u8]>>(prefix: &SubtreePrefix, key: K) -> Vec<u8> {
+    let mut prefix_vec = prefix.to_vec();
+    prefix_vec.extend_from_slice(key.as_ref());
+    prefix_vec
+}
+
+/// Batch with no backing storage_cost (it's not a RocksDB batch, but our own
+/// way to represent a set of operations) that eventually will be merged into
+/// multi-context batch.
+pub struct PrefixedMultiContextBatchPart {
+    pub(crate) prefix: SubtreePrefix,
+    pub(crate) batch: StorageBatch,
+}
+
+/// Implementation of a batch for multi-context batch, shared by all backends.
+impl Batch for PrefixedMultiContextBatchPart {
+    fn put<K: AsRef<[u8]>>(
+        &mut self,
+        key: K,
+        value: &[u8],
+        children_sizes: ChildrenSizesWithIsSumTree,
+        cost_info: Option<KeyValueStorageCost>,
+    ) -> Result<(), grovedb_costs::error::Error> {
+        let prefixed_key = make_prefixed_key(&self.prefix, key);
+
+        // Update the key_storage_cost based on the prefixed key
+        let updated_cost_info = cost_info.map(|mut key_value_storage_cost| {
+            if key_value_storage_cost.new_node {
+                // key is new, storage_cost needs to be created for it
+                key_value_storage_cost.key_storage_cost.added_bytes +=
+                    (prefixed_key.len() + prefixed_key.len().required_space()) as u32;
+            }
+            key_value_storage_cost
+        });
+
+        self.batch.put(
+            prefixed_key,
+            value.to_vec(),
+            children_sizes,
+            updated_cost_info,
+        );
+        Ok(())
+    }
+
+    fn put_aux<K: AsRef<[u8]>>(
+        &mut self,
+        key: K,
+        value: &[u8],
+        cost_info: Option<KeyValueStorageCost>,
+    ) -> Result<(), grovedb_costs::error::Error> {
+        self.batch.put_aux(
+            make_prefixed_key(&self.prefix, key),
+            value.to_vec(),
+            cost_info,
+        );
+        Ok(())
+    }
+
+    fn put_root<K: AsRef<[u8]>>(
+        &mut self,
+        key: K,
+        value: &[u8],
+        cost_info: Option<KeyValueStorageCost>,
+    ) -> Result<(), grovedb_costs::error::Error> {
+        self.batch.put_root(
+            make_prefixed_key(&self.prefix, key),
+            value.to_vec(),
+            cost_info,
+        );
+        Ok(())
+    }
+
+    fn delete<K: AsRef<[u8]>>(&mut self, key: K, cost_info: Option<KeyValueStorageCost>) {
+        self.batch
+            .delete(make_prefixed_key(&self.prefix, key), cost_info);
+    }
+
+    fn delete_aux<K: AsRef<[u8]>>(&mut self, key: K, cost_info: Option<KeyValueStorageCost>) {
+        self.batch
+            .delete_aux(make_prefixed_key(&self.prefix, key), cost_info);
+    }
+
+    fn delete_root<K: AsRef<[u8]>>(&mut self, key: K, cost_info: Option<KeyValueStorageCost>) {
+        self.batch
+            .delete_root(make_prefixed_key(&self.prefix, key), cost_info);
+    }
+}
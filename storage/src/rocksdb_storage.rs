@@ -27,16 +27,24 @@
 // DEALINGS IN THE SOFTWARE.
 
 //! GroveDB storage layer implemented over RocksDB backend.
+mod config;
+mod read_only_storage;
 mod storage;
 mod storage_context;
 pub mod test_utils;
 #[cfg(test)]
 mod tests;
+mod write_batch;
 
-pub use rocksdb::{Error, WriteBatchWithTransaction};
+pub use config::{ColumnFamilyConfig, RocksDbConfig};
+pub use rocksdb::{DBCompressionType, Error, WriteBatchWithTransaction};
 pub use storage_context::{
-    PrefixedRocksDbBatch, PrefixedRocksDbImmediateStorageContext, PrefixedRocksDbRawIterator,
+    PrefixedReadOnlyRocksDbStorageContext, PrefixedRocksDbBatch,
+    PrefixedRocksDbImmediateStorageContext, PrefixedRocksDbRawIterator,
     PrefixedRocksDbStorageContext, PrefixedRocksDbTransactionContext,
 };
 
-pub use self::storage::RocksDbStorage;
+pub use self::{
+    read_only_storage::{ReadOnlyRocksDbStorage, ReadOnlyTransaction},
+    storage::{RocksDbStorage, RocksDbTransaction},
+};
@@ -0,0 +1,192 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Tunable RocksDB options.
+
+use std::path::PathBuf;
+
+use rocksdb::{BlockBasedOptions, Cache, DBCompressionType, Options, SliceTransform, WriteOptions};
+
+use crate::prefix::SubtreePrefix;
+
+/// Bits per key of bloom filters set by `ColumnFamilyConfig::subtree_prefixed`
+const DEFAULT_BLOOM_FILTER_BITS_PER_KEY: f64 = 10.0;
+
+/// Share of the memtable size used by its prefix bloom filter
+const MEMTABLE_PREFIX_BLOOM_RATIO: f64 = 0.1;
+
+/// Settings of a single column family
+#[derive(Debug, Clone, Default)]
+pub struct ColumnFamilyConfig {
+    /// Compression of the column family data, RocksDB default if `None`
+    pub compression: Option<DBCompressionType>,
+    /// Size of a single memtable in bytes, RocksDB default if `None`
+    pub write_buffer_size: Option<usize>,
+    /// Bits per key of the bloom filter, no bloom filter if `None`
+    pub bloom_filter_bits_per_key: Option<f64>,
+    /// Use the 32 bytes subtree prefix of keys as RocksDB prefix, so bloom
+    /// filters and memtables are checked per subtree
+    pub subtree_prefix_extractor: bool,
+}
+
+impl ColumnFamilyConfig {
+    /// Column family settings with a subtree prefix extractor and bloom
+    /// filters on it
+    pub fn subtree_prefixed() -> Self {
+        ColumnFamilyConfig {
+            bloom_filter_bits_per_key: Some(DEFAULT_BLOOM_FILTER_BITS_PER_KEY),
+            subtree_prefix_extractor: true,
+            ..Default::default()
+        }
+    }
+
+    /// Applies the column family settings on top of database options
+    fn apply(&self, opts: &mut Options, block_cache: Option<&Cache>) {
+        if let Some(compression) = self.compression {
+            opts.set_compression_type(compression);
+        }
+        if let Some(size) = self.write_buffer_size {
+            opts.set_write_buffer_size(size);
+        }
+        if self.subtree_prefix_extractor {
+            opts.set_prefix_extractor(SliceTransform::create_fixed_prefix(std::mem::size_of::<
+                SubtreePrefix,
+            >()));
+            if self.bloom_filter_bits_per_key.is_some() {
+                opts.set_memtable_prefix_bloom_ratio(MEMTABLE_PREFIX_BLOOM_RATIO);
+            }
+        }
+        if self.bloom_filter_bits_per_key.is_some() || block_cache.is_some() {
+            let mut table_opts = BlockBasedOptions::default();
+            if let Some(bits) = self.bloom_filter_bits_per_key {
+                table_opts.set_bloom_filter(bits, false);
+            }
+            if let Some(cache) = block_cache {
+                table_opts.set_block_cache(cache);
+            }
+            opts.set_block_based_table_factory(&table_opts);
+        }
+    }
+}
+
+/// Options to open RocksDB storage with. Defaults are the ones used by
+/// `RocksDbStorage::default_rocksdb_with_path`.
+#[derive(Debug, Clone)]
+pub struct RocksDbConfig {
+    /// Create the database if it doesn't exist
+    pub create_if_missing: bool,
+    /// Number of background threads, the number of CPUs if `None`
+    pub parallelism: Option<i32>,
+    /// Use mmap to read SST files
+    pub allow_mmap_reads: bool,
+    /// Use mmap to write SST files
+    pub allow_mmap_writes: bool,
+    /// Size in bytes of the LRU block cache shared by all column families,
+    /// RocksDB default cache of each column family if `None`
+    pub block_cache_size: Option<usize>,
+    /// Directory to keep the write ahead log in, the database directory if
+    /// `None`
+    pub wal_dir: Option<PathBuf>,
+    /// Size in bytes of write ahead log files that triggers flushes of
+    /// memtables, RocksDB default if `None`
+    pub max_total_wal_size: Option<u64>,
+    /// Skip the write ahead log on writes, unflushed data is lost on crash
+    pub disable_wal: bool,
+    /// Settings of the default column family, holding subtrees data
+    pub default_cf: ColumnFamilyConfig,
+    /// Settings of the auxiliary data column family
+    pub aux_cf: ColumnFamilyConfig,
+    /// Settings of the subtrees roots column family
+    pub roots_cf: ColumnFamilyConfig,
+    /// Settings of the metadata column family
+    pub meta_cf: ColumnFamilyConfig,
+}
+
+impl Default for RocksDbConfig {
+    fn default() -> Self {
+        RocksDbConfig {
+            create_if_missing: true,
+            parallelism: None,
+            allow_mmap_reads: true,
+            allow_mmap_writes: true,
+            block_cache_size: None,
+            wal_dir: None,
+            max_total_wal_size: None,
+            disable_wal: false,
+            default_cf: ColumnFamilyConfig::default(),
+            aux_cf: ColumnFamilyConfig::default(),
+            roots_cf: ColumnFamilyConfig::default(),
+            meta_cf: ColumnFamilyConfig::default(),
+        }
+    }
+}
+
+impl RocksDbConfig {
+    /// Database wide options, without column family settings
+    fn db_options(&self) -> Options {
+        let mut opts = Options::default();
+        opts.create_if_missing(self.create_if_missing);
+        opts.increase_parallelism(self.parallelism.unwrap_or(num_cpus::get() as i32));
+        opts.set_allow_mmap_writes(self.allow_mmap_writes);
+        opts.set_allow_mmap_reads(self.allow_mmap_reads);
+        opts.create_missing_column_families(true);
+        opts.set_atomic_flush(true);
+        if let Some(wal_dir) = &self.wal_dir {
+            opts.set_wal_dir(wal_dir);
+        }
+        if let Some(size) = self.max_total_wal_size {
+            opts.set_max_total_wal_size(size);
+        }
+        opts
+    }
+
+    /// Options of the default column family and of the aux, roots and meta
+    /// column families, in that order
+    pub(crate) fn column_family_options(&self) -> [Options; 4] {
+        let db_opts = self.db_options();
+        let block_cache = self.block_cache_size.map(Cache::new_lru_cache);
+        [
+            &self.default_cf,
+            &self.aux_cf,
+            &self.roots_cf,
+            &self.meta_cf,
+        ]
+        .map(|cf| {
+            let mut opts = db_opts.clone();
+            cf.apply(&mut opts, block_cache.as_ref());
+            opts
+        })
+    }
+
+    /// Options to write with
+    pub(crate) fn write_options(&self) -> WriteOptions {
+        let mut opts = WriteOptions::default();
+        opts.disable_wal(self.disable_wal);
+        opts
+    }
+}
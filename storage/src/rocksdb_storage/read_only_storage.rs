@@ -0,0 +1,365 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Read only and secondary instances of RocksDB storage.
+
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use grovedb_costs::{CostContext, CostResult, CostsExt, OperationCost};
+use grovedb_path::SubtreePath;
+use rocksdb::{
+    checkpoint::Checkpoint, ColumnFamilyDescriptor, DBWithThreadMode, Options, SingleThreaded,
+};
+
+use super::{
+    config::RocksDbConfig,
+    storage::{visit_prefixed_records, AUX_CF_NAME, META_CF_NAME, ROOTS_CF_NAME},
+    storage_context::raw_iterator_read_options,
+    PrefixedReadOnlyRocksDbStorageContext, RocksDbStorage,
+};
+use crate::{
+    error::{Error, Error::RocksDBError},
+    prefix::{self, make_prefixed_key, SubtreePrefix},
+    storage::Column,
+    worst_case_costs::WorstKeyLength,
+    CovariantStorage, PreviousRecords, Storage, StorageBatch,
+};
+
+/// Type alias for a read only database
+pub(crate) type ReadOnlyDb = DBWithThreadMode<SingleThreaded>;
+
+/// Transaction of read only storage. There are no writes to isolate, reads
+/// under it see the latest state of the database.
+#[derive(Debug, Default)]
+pub struct ReadOnlyTransaction {
+    /// Number of savepoints set, there is nothing to roll back to them
+    savepoints: Mutex<usize>,
+}
+
+impl ReadOnlyTransaction {
+    fn savepoints(&self) -> MutexGuard<'_, usize> {
+        self.savepoints
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn remove_savepoint(&self) -> Result<(), Error> {
+        let mut savepoints = self.savepoints();
+        *savepoints = savepoints
+            .checked_sub(1)
+            .ok_or_else(|| Error::StorageError("transaction has no savepoint".to_owned()))?;
+        Ok(())
+    }
+}
+
+/// Storage which uses RocksDB opened in read only or secondary mode as its
+/// backend. All writes are refused with `Error::NotSupported`.
+pub struct ReadOnlyRocksDbStorage {
+    db: ReadOnlyDb,
+}
+
+impl ReadOnlyRocksDbStorage {
+    /// Open RocksDb storage at `path` in read only mode. The view of the data
+    /// is fixed at opening.
+    pub fn read_only_with_path<P: AsRef<Path>>(
+        path: P,
+        config: &RocksDbConfig,
+    ) -> Result<Self, Error> {
+        let [default_opts, aux_opts, roots_opts, meta_opts] = config.column_family_options();
+        let db = ReadOnlyDb::open_cf_descriptors_read_only(
+            &default_opts,
+            path,
+            column_family_descriptors(aux_opts, roots_opts, meta_opts),
+            false,
+        )
+        .map_err(RocksDBError)?;
+
+        Ok(ReadOnlyRocksDbStorage { db })
+    }
+
+    /// Open RocksDb storage at `primary_path` as a secondary instance keeping
+    /// its own logs in `secondary_path`. The primary can keep writing
+    /// meanwhile, its writes become visible after `try_catch_up_with_primary`.
+    pub fn secondary_with_path<P: AsRef<Path>, Q: AsRef<Path>>(
+        primary_path: P,
+        secondary_path: Q,
+        config: &RocksDbConfig,
+    ) -> Result<Self, Error> {
+        let [mut default_opts, aux_opts, roots_opts, meta_opts] = config.column_family_options();
+        // Secondary instances must keep all files of the primary open
+        default_opts.set_max_open_files(-1);
+        let db = ReadOnlyDb::open_cf_descriptors_as_secondary(
+            &default_opts,
+            primary_path.as_ref(),
+            secondary_path.as_ref(),
+            column_family_descriptors(aux_opts, roots_opts, meta_opts),
+        )
+        .map_err(RocksDBError)?;
+
+        Ok(ReadOnlyRocksDbStorage { db })
+    }
+
+    /// Catch up with the writes of the primary instance. Fails unless the
+    /// storage was opened as a secondary.
+    pub fn try_catch_up_with_primary(&self) -> Result<(), Error> {
+        self.db.try_catch_up_with_primary().map_err(RocksDBError)
+    }
+}
+
+/// Descriptors of the aux, roots and meta column families
+fn column_family_descriptors(
+    aux_opts: Options,
+    roots_opts: Options,
+    meta_opts: Options,
+) -> [ColumnFamilyDescriptor; 3] {
+    [
+        ColumnFamilyDescriptor::new(AUX_CF_NAME, aux_opts),
+        ColumnFamilyDescriptor::new(ROOTS_CF_NAME, roots_opts),
+        ColumnFamilyDescriptor::new(META_CF_NAME, meta_opts),
+    ]
+}
+
+impl<'db> Storage<'db> for ReadOnlyRocksDbStorage {
+    type BatchStorageContext = PrefixedReadOnlyRocksDbStorageContext<'db>;
+    type BatchTransactionalStorageContext = PrefixedReadOnlyRocksDbStorageContext<'db>;
+    type ImmediateStorageContext = PrefixedReadOnlyRocksDbStorageContext<'db>;
+    type Transaction = ReadOnlyTransaction;
+
+    fn start_transaction(&'db self) -> Self::Transaction {
+        ReadOnlyTransaction::default()
+    }
+
+    fn start_snapshot_transaction(&'db self) -> Self::Transaction {
+        // Read only storage doesn't change under the reader, a secondary one
+        // only does on explicit catch up with the primary
+        ReadOnlyTransaction::default()
+    }
+
+    fn commit_transaction(&self, _transaction: Self::Transaction) -> CostResult<(), Error> {
+        // Nothing could be written under the transaction
+        Ok(()).wrap_with_cost(Default::default())
+    }
+
+    fn rollback_transaction(&self, transaction: &Self::Transaction) -> Result<(), Error> {
+        *transaction.savepoints() = 0;
+        Ok(())
+    }
+
+    fn set_savepoint(&self, transaction: &Self::Transaction) {
+        *transaction.savepoints() += 1;
+    }
+
+    fn rollback_to_savepoint(&self, transaction: &Self::Transaction) -> Result<(), Error> {
+        transaction.remove_savepoint()
+    }
+
+    fn release_savepoint(&self, transaction: &Self::Transaction) -> Result<(), Error> {
+        transaction.remove_savepoint()
+    }
+
+    fn savepoint_depth(&self, transaction: &Self::Transaction) -> usize {
+        *transaction.savepoints()
+    }
+
+    fn validate_transaction(&self, _transaction: &Self::Transaction) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn get_for_update(
+        &self,
+        _transaction: &Self::Transaction,
+        prefix: &SubtreePrefix,
+        key: &[u8],
+    ) -> CostResult<Option<Vec<u8>>, Error> {
+        // Nothing can be committed under the transaction meanwhile
+        self.db
+            .get(make_prefixed_key(prefix, key))
+            .map_err(RocksDBError)
+            .wrap_fn_cost(|value| OperationCost {
+                seek_count: 1,
+                storage_loaded_bytes: value
+                    .as_ref()
+                    .ok()
+                    .and_then(Option::as_ref)
+                    .map(|x| x.len() as u32)
+                    .unwrap_or(0),
+                ..Default::default()
+            })
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        Err(Error::NotSupported("flush of read only storage"))
+    }
+
+    fn previous_records(
+        &'db self,
+        batch: &StorageBatch,
+        _transaction: Option<&'db Self::Transaction>,
+    ) -> CostResult<PreviousRecords, Error> {
+        let cf = |column| match column {
+            Column::Data => None,
+            Column::Aux => self.db.cf_handle(AUX_CF_NAME),
+            Column::Roots => self.db.cf_handle(ROOTS_CF_NAME),
+            Column::Meta => self.db.cf_handle(META_CF_NAME),
+        };
+        batch.previous_records(
+            |column, key| {
+                match cf(column) {
+                    Some(cf) => self.db.get_cf(cf, key),
+                    None => self.db.get(key),
+                }
+                .map_err(RocksDBError)
+            },
+            |column, prefix| {
+                let mut records = Vec::new();
+                let visit = |key: &[u8], value: &[u8]| records.push((key.to_vec(), value.to_vec()));
+                match cf(column) {
+                    Some(cf) => visit_prefixed_records(
+                        self.db.raw_iterator_cf_opt(cf, raw_iterator_read_options()),
+                        prefix,
+                        visit,
+                    ),
+                    None => visit_prefixed_records(
+                        self.db.raw_iterator_opt(raw_iterator_read_options()),
+                        prefix,
+                        visit,
+                    ),
+                }
+                records
+            },
+        )
+    }
+
+    fn stored_prefixes(
+        &'db self,
+        from: &[u8],
+        limit: usize,
+        is_skipped: &dyn Fn(&[u8]) -> bool,
+        _transaction: Option<&'db Self::Transaction>,
+    ) -> CostResult<BTreeMap<Vec<u8>, u64>, Error> {
+        let mut sizes = BTreeMap::new();
+        let mut cost = prefix::add_stored_prefixes(
+            &mut sizes,
+            &mut self.db.raw_iterator_opt(raw_iterator_read_options()),
+            from,
+            limit,
+            is_skipped,
+        );
+        for cf_name in [AUX_CF_NAME, ROOTS_CF_NAME, META_CF_NAME] {
+            let cf = self
+                .db
+                .cf_handle(cf_name)
+                .expect("column family must exist");
+            cost += prefix::add_stored_prefixes(
+                &mut sizes,
+                &mut self.db.raw_iterator_cf_opt(cf, raw_iterator_read_options()),
+                from,
+                limit,
+                is_skipped,
+            );
+        }
+        prefix::truncate_stored_prefixes(&mut sizes, limit);
+        Ok(sizes).wrap_with_cost(cost)
+    }
+
+    fn get_storage_context<'b, B>(
+        &'db self,
+        path: SubtreePath<'b, B>,
+        batch: Option<&'db StorageBatch>,
+    ) -> CostContext<Self::BatchStorageContext>
+    where
+        B: AsRef<[u8]> + 'b,
+    {
+        RocksDbStorage::build_prefix(path)
+            .map(|prefix| PrefixedReadOnlyRocksDbStorageContext::new(&self.db, prefix, batch))
+    }
+
+    fn get_transactional_storage_context<'b, B>(
+        &'db self,
+        path: SubtreePath<'b, B>,
+        batch: Option<&'db StorageBatch>,
+        _transaction: &'db Self::Transaction,
+    ) -> CostContext<Self::BatchTransactionalStorageContext>
+    where
+        B: AsRef<[u8]> + 'b,
+    {
+        self.get_storage_context(path, batch)
+    }
+
+    fn get_immediate_storage_context<'b, B>(
+        &'db self,
+        path: SubtreePath<'b, B>,
+        _transaction: &'db Self::Transaction,
+    ) -> CostContext<Self::ImmediateStorageContext>
+    where
+        B: AsRef<[u8]> + 'b,
+    {
+        self.get_storage_context(path, None)
+    }
+
+    fn commit_multi_context_batch(
+        &self,
+        _batch: StorageBatch,
+        _transaction: Option<&'db Self::Transaction>,
+    ) -> CostResult<(), Error> {
+        Err(Error::NotSupported("writes to read only storage"))
+            .wrap_with_cost(OperationCost::default())
+    }
+
+    fn multi_context_batch_cost(
+        &self,
+        _batch: StorageBatch,
+        _transaction: Option<&'db Self::Transaction>,
+    ) -> CostResult<(), Error> {
+        Err(Error::NotSupported("writes to read only storage"))
+            .wrap_with_cost(OperationCost::default())
+    }
+
+    fn get_storage_context_cost<L: WorstKeyLength>(path: &[L]) -> OperationCost {
+        prefix::build_prefix_worst_case_cost(path)
+    }
+
+    fn create_checkpoint<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        Checkpoint::new(&self.db)
+            .and_then(|x| x.create_checkpoint(path))
+            .map_err(RocksDBError)
+    }
+}
+
+impl CovariantStorage for ReadOnlyRocksDbStorage {
+    fn narrow_transaction<'a, 'db>(
+        &self,
+        transaction: &'a <Self as Storage<'db>>::Transaction,
+    ) -> &'a <Self as Storage<'a>>::Transaction {
+        transaction
+    }
+}
@@ -28,7 +28,12 @@
 
 //! Implementation for a storage abstraction over RocksDB.
 
-use std::path::Path;
+use std::{
+    collections::BTreeMap,
+    ops::Deref,
+    path::Path,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
 
 use error::Error;
 use grovedb_costs::{
@@ -38,36 +43,31 @@ use grovedb_costs::{
 };
 use grovedb_path::SubtreePath;
 use integer_encoding::VarInt;
-use lazy_static::lazy_static;
 use rocksdb::{
-    checkpoint::Checkpoint, ColumnFamily, ColumnFamilyDescriptor, OptimisticTransactionDB,
-    Transaction, WriteBatchWithTransaction,
+    checkpoint::Checkpoint, ColumnFamily, ColumnFamilyDescriptor, DBAccess,
+    DBRawIteratorWithThreadMode, ErrorKind, OptimisticTransactionDB, OptimisticTransactionOptions,
+    SnapshotWithThreadMode, Transaction, WriteBatchWithTransaction, WriteOptions,
 };
 
 use super::{
-    PrefixedRocksDbImmediateStorageContext, PrefixedRocksDbStorageContext,
+    config::RocksDbConfig,
+    storage_context::{
+        raw_iterator_read_options, transaction_raw_iterator_read_options, transaction_read_options,
+    },
+    write_batch, PrefixedRocksDbImmediateStorageContext, PrefixedRocksDbStorageContext,
     PrefixedRocksDbTransactionContext,
 };
+pub(crate) use crate::prefix::SubtreePrefix;
 use crate::{
     error,
-    error::Error::{CostError, RocksDBError},
-    storage::AbstractBatchOperation,
+    error::Error::{CostError, RocksDBError, TransactionConflict},
+    prefix,
+    prefix::{make_prefixed_key, RecordCursor},
+    storage::{AbstractBatchOperation, Column},
     worst_case_costs::WorstKeyLength,
-    Storage, StorageBatch,
+    CovariantStorage, PreviousRecords, Storage, StorageBatch,
 };
 
-const BLAKE_BLOCK_LEN: usize = 64;
-
-pub(crate) type SubtreePrefix = [u8; blake3::OUT_LEN];
-
-fn blake_block_count(len: usize) -> usize {
-    if len == 0 {
-        1
-    } else {
-        1 + (len - 1) / BLAKE_BLOCK_LEN
-    }
-}
-
 /// Name of column family used to store auxiliary data
 pub(crate) const AUX_CF_NAME: &str = "aux";
 /// Name of column family used to store subtrees roots data
@@ -75,65 +75,91 @@ pub(crate) const ROOTS_CF_NAME: &str = "roots";
 /// Name of column family used to store metadata
 pub(crate) const META_CF_NAME: &str = "meta";
 
-lazy_static! {
-    static ref DEFAULT_OPTS: rocksdb::Options = {
-        let mut opts = rocksdb::Options::default();
-        opts.create_if_missing(true);
-        opts.increase_parallelism(num_cpus::get() as i32);
-        opts.set_allow_mmap_writes(true);
-        opts.set_allow_mmap_reads(true);
-        opts.create_missing_column_families(true);
-        opts.set_atomic_flush(true);
-        opts
-    };
-}
-
 /// Type alias for a database
 pub(crate) type Db = OptimisticTransactionDB;
 
 /// Type alias for a transaction
 pub(crate) type Tx<'db> = Transaction<'db, Db>;
 
+/// Transaction of RocksDB storage. Snapshot transactions are read only, writes
+/// through them fail with `Error::NotSupported`.
+pub struct RocksDbTransaction<'db> {
+    transaction: Tx<'db>,
+    /// Records as of the start of the transaction, used to validate it before
+    /// commit. `None` for read only transactions.
+    start_snapshot: Option<SnapshotWithThreadMode<'db, Db>>,
+    /// Whether each savepoint set in RocksDB was released. RocksDB can't
+    /// remove a savepoint without rolling back to it, so released ones stay
+    /// until a rollback to a savepoint below them.
+    savepoints: Mutex<Vec<bool>>,
+}
+
+impl<'db> RocksDbTransaction<'db> {
+    fn new(transaction: Tx<'db>, start_snapshot: Option<SnapshotWithThreadMode<'db, Db>>) -> Self {
+        RocksDbTransaction {
+            transaction,
+            start_snapshot,
+            savepoints: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn savepoints(&self) -> MutexGuard<'_, Vec<bool>> {
+        self.savepoints
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Fails if the transaction is read only
+    pub(crate) fn check_writable(&self) -> Result<(), Error> {
+        if self.start_snapshot.is_none() {
+            Err(Error::NotSupported("writes through a snapshot transaction"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<'db> Deref for RocksDbTransaction<'db> {
+    type Target = Tx<'db>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.transaction
+    }
+}
+
 /// Storage which uses RocksDB as its backend.
 pub struct RocksDbStorage {
     db: OptimisticTransactionDB,
+    write_opts: WriteOptions,
 }
 
 impl RocksDbStorage {
     /// Create RocksDb storage with default parameters using `path`.
     pub fn default_rocksdb_with_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::rocksdb_with_config(path, &RocksDbConfig::default())
+    }
+
+    /// Create RocksDb storage with options from `config` using `path`.
+    pub fn rocksdb_with_config<P: AsRef<Path>>(
+        path: P,
+        config: &RocksDbConfig,
+    ) -> Result<Self, Error> {
+        let [default_opts, aux_opts, roots_opts, meta_opts] = config.column_family_options();
         let db = Db::open_cf_descriptors(
-            &DEFAULT_OPTS,
+            &default_opts,
             &path,
             [
-                ColumnFamilyDescriptor::new(AUX_CF_NAME, DEFAULT_OPTS.clone()),
-                ColumnFamilyDescriptor::new(ROOTS_CF_NAME, DEFAULT_OPTS.clone()),
-                ColumnFamilyDescriptor::new(META_CF_NAME, DEFAULT_OPTS.clone()),
+                ColumnFamilyDescriptor::new(AUX_CF_NAME, aux_opts),
+                ColumnFamilyDescriptor::new(ROOTS_CF_NAME, roots_opts),
+                ColumnFamilyDescriptor::new(META_CF_NAME, meta_opts),
             ],
         )
         .map_err(RocksDBError)?;
 
-        Ok(RocksDbStorage { db })
-    }
-
-    fn build_prefix_body<B>(path: SubtreePath<B>) -> (Vec<u8>, usize)
-    where
-        B: AsRef<[u8]>,
-    {
-        let segments_iter = path.into_reverse_iter();
-        let mut segments_count: usize = 0;
-        let mut res = Vec::new();
-        let mut lengthes = Vec::new();
-
-        for s in segments_iter {
-            segments_count += 1;
-            res.extend_from_slice(s);
-            lengthes.push(s.len() as u8); // if the key len is under 255 bytes
-        }
-
-        res.extend(segments_count.to_ne_bytes());
-        res.extend(lengthes);
-        (res, segments_count)
+        Ok(RocksDbStorage {
+            db,
+            write_opts: config.write_options(),
+        })
     }
 
     /// A helper method to build a prefix to rocksdb keys or identify a subtree
@@ -142,29 +168,20 @@ impl RocksDbStorage {
     where
         B: AsRef<[u8]>,
     {
-        let (body, segments_count) = Self::build_prefix_body(path);
-        if segments_count == 0 {
-            SubtreePrefix::default().wrap_with_cost(OperationCost::default())
-        } else {
-            let blocks_count = blake_block_count(body.len());
-            SubtreePrefix::from(blake3::hash(&body))
-                .wrap_with_cost(OperationCost::with_hash_node_calls(blocks_count as u32))
-        }
-    }
-
-    fn worst_case_body_size<L: WorstKeyLength>(path: &[L]) -> usize {
-        path.len() + path.iter().map(|a| a.max_length() as usize).sum::<usize>()
+        prefix::build_prefix(path)
     }
 
     /// Returns the write batch, with costs and pending costs
     /// Pending costs are costs that should only be applied after successful
-    /// write of the write batch.
+    /// write of the write batch. Deleted subtree prefixes are expanded into
+    /// deletes of the records seen by the `transaction` it will be written to.
     pub fn build_write_batch(
         &self,
         storage_batch: StorageBatch,
+        transaction: Option<&<RocksDbStorage as Storage>::Transaction>,
     ) -> CostResult<(WriteBatchWithTransaction<true>, OperationCost), Error> {
         let mut db_batch = WriteBatchWithTransaction::<true>::default();
-        self.continue_write_batch(&mut db_batch, storage_batch)
+        self.continue_write_batch(&mut db_batch, storage_batch, transaction)
             .map_ok(|operation_cost| (db_batch, operation_cost))
     }
 
@@ -175,6 +192,7 @@ impl RocksDbStorage {
         &self,
         db_batch: &mut WriteBatchWithTransaction<true>,
         storage_batch: StorageBatch,
+        transaction: Option<&<RocksDbStorage as Storage>::Transaction>,
     ) -> CostResult<OperationCost, Error> {
         let mut cost = OperationCost::default();
         // Until batch is committed these costs are pending (should not be added in case
@@ -380,11 +398,145 @@ impl RocksDbStorage {
                         );
                     }
                 }
+                AbstractBatchOperation::DeletePrefix { prefix } => {
+                    // Optimistic transaction databases refuse write batches with range
+                    // deletions even without a transaction, so every record under the
+                    // prefix is deleted on its own
+                    for cf in [
+                        None,
+                        Some(cf_aux(&self.db)),
+                        Some(cf_roots(&self.db)),
+                        Some(cf_meta(&self.db)),
+                    ] {
+                        cost.seek_count += 1;
+                        self.visit_prefixed_records(cf, &prefix, transaction, |key, value| {
+                            match cf {
+                                Some(cf) => db_batch.delete_cf(cf, key),
+                                None => db_batch.delete(key),
+                            }
+
+                            let value_len = value.len() as u32;
+                            cost.seek_count += 1;
+                            cost.storage_loaded_bytes += value_len;
+                            let key_len = key.len() as u32;
+                            pending_costs.storage_cost.removed_bytes += BasicStorageRemoval(
+                                key_len
+                                    + value_len
+                                    + key_len.required_space() as u32
+                                    + value_len.required_space() as u32,
+                            );
+                        });
+                    }
+                }
             }
         }
         Ok(pending_costs).wrap_with_cost(cost)
     }
 
+    /// Calls `visit` with the key and value of every record of a column
+    /// family under a prefix, the default column family is used if none is
+    /// given
+    fn visit_prefixed_records(
+        &self,
+        cf: Option<&ColumnFamily>,
+        prefix: &[u8],
+        transaction: Option<&<RocksDbStorage as Storage>::Transaction>,
+        visit: impl FnMut(&[u8], &[u8]),
+    ) {
+        match (transaction, cf) {
+            (Some(tx), Some(cf)) => visit_prefixed_records(
+                tx.raw_iterator_cf_opt(cf, transaction_raw_iterator_read_options(tx)),
+                prefix,
+                visit,
+            ),
+            (Some(tx), None) => visit_prefixed_records(
+                tx.raw_iterator_opt(transaction_raw_iterator_read_options(tx)),
+                prefix,
+                visit,
+            ),
+            (None, Some(cf)) => visit_prefixed_records(
+                self.db.raw_iterator_cf_opt(cf, raw_iterator_read_options()),
+                prefix,
+                visit,
+            ),
+            (None, None) => visit_prefixed_records(
+                self.db.raw_iterator_opt(raw_iterator_read_options()),
+                prefix,
+                visit,
+            ),
+        }
+    }
+
+    /// Adds the sizes of the first `limit` prefixes from `from` on of a column
+    /// family, the default column family is used if none is given
+    fn add_stored_prefixes(
+        &self,
+        cf: Option<&ColumnFamily>,
+        sizes: &mut BTreeMap<Vec<u8>, u64>,
+        from: &[u8],
+        limit: usize,
+        is_skipped: &dyn Fn(&[u8]) -> bool,
+        transaction: Option<&<RocksDbStorage as Storage>::Transaction>,
+    ) -> OperationCost {
+        match (transaction, cf) {
+            (Some(tx), Some(cf)) => prefix::add_stored_prefixes(
+                sizes,
+                &mut tx.raw_iterator_cf_opt(cf, transaction_raw_iterator_read_options(tx)),
+                from,
+                limit,
+                is_skipped,
+            ),
+            (Some(tx), None) => prefix::add_stored_prefixes(
+                sizes,
+                &mut tx.raw_iterator_opt(transaction_raw_iterator_read_options(tx)),
+                from,
+                limit,
+                is_skipped,
+            ),
+            (None, Some(cf)) => prefix::add_stored_prefixes(
+                sizes,
+                &mut self.db.raw_iterator_cf_opt(cf, raw_iterator_read_options()),
+                from,
+                limit,
+                is_skipped,
+            ),
+            (None, None) => prefix::add_stored_prefixes(
+                sizes,
+                &mut self.db.raw_iterator_opt(raw_iterator_read_options()),
+                from,
+                limit,
+                is_skipped,
+            ),
+        }
+    }
+
+    /// Reads a record of a column family, the default column family is used
+    /// if none is given
+    fn get_record(
+        &self,
+        cf: Option<&ColumnFamily>,
+        key: &[u8],
+        transaction: Option<&<RocksDbStorage as Storage>::Transaction>,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        match (transaction, cf) {
+            (Some(tx), Some(cf)) => tx.get_cf_opt(cf, key, &transaction_read_options(tx)),
+            (Some(tx), None) => tx.get_opt(key, &transaction_read_options(tx)),
+            (None, Some(cf)) => self.db.get_cf(cf, key),
+            (None, None) => self.db.get(key),
+        }
+        .map_err(RocksDBError)
+    }
+
+    /// Column family of a column, `None` for the default one
+    fn column_family(&self, column: Column) -> Option<&ColumnFamily> {
+        match column {
+            Column::Data => None,
+            Column::Aux => Some(cf_aux(&self.db)),
+            Column::Roots => Some(cf_roots(&self.db)),
+            Column::Meta => Some(cf_meta(&self.db)),
+        }
+    }
+
     /// Commits a write batch
     pub fn commit_db_write_batch(
         &self,
@@ -392,8 +544,13 @@ impl RocksDbStorage {
         pending_costs: OperationCost,
         transaction: Option<&<RocksDbStorage as Storage>::Transaction>,
     ) -> CostResult<(), Error> {
+        if let Some(transaction) = transaction.filter(|_| !db_batch.is_empty()) {
+            if let Err(e) = transaction.check_writable() {
+                return Err(e).wrap_with_cost(OperationCost::default());
+            }
+        }
         let result = match transaction {
-            None => self.db.write(db_batch),
+            None => self.db.write_opt(db_batch, &self.write_opts),
             Some(transaction) => transaction.rebuild_from_writebatch(&db_batch),
         };
 
@@ -411,28 +568,187 @@ impl<'db> Storage<'db> for RocksDbStorage {
     type BatchStorageContext = PrefixedRocksDbStorageContext<'db>;
     type BatchTransactionalStorageContext = PrefixedRocksDbTransactionContext<'db>;
     type ImmediateStorageContext = PrefixedRocksDbImmediateStorageContext<'db>;
-    type Transaction = Tx<'db>;
+    type Transaction = RocksDbTransaction<'db>;
 
     fn start_transaction(&'db self) -> Self::Transaction {
-        self.db.transaction()
+        RocksDbTransaction::new(
+            self.db
+                .transaction_opt(&self.write_opts, &OptimisticTransactionOptions::default()),
+            Some(self.db.snapshot()),
+        )
+    }
+
+    fn start_snapshot_transaction(&'db self) -> Self::Transaction {
+        let mut transaction_opts = OptimisticTransactionOptions::default();
+        transaction_opts.set_snapshot(true);
+        RocksDbTransaction::new(
+            self.db.transaction_opt(&self.write_opts, &transaction_opts),
+            None,
+        )
     }
 
     fn commit_transaction(&self, transaction: Self::Transaction) -> CostResult<(), Error> {
         // All transaction costs were provided on method calls
         transaction
+            .transaction
             .commit()
-            .map_err(RocksDBError)
+            .map_err(|e| match e.kind() {
+                // Optimistic transactions fail to commit with `Busy` if a record
+                // they wrote was committed meanwhile, and with `TryAgain` if
+                // memtable history is too short to tell
+                ErrorKind::Busy | ErrorKind::TryAgain => TransactionConflict,
+                _ => RocksDBError(e),
+            })
             .wrap_with_cost(Default::default())
     }
 
     fn rollback_transaction(&self, transaction: &Self::Transaction) -> Result<(), Error> {
+        // A rollback removes all savepoints too
+        transaction.savepoints().clear();
         transaction.rollback().map_err(RocksDBError)
     }
 
+    fn set_savepoint(&self, transaction: &Self::Transaction) {
+        transaction.savepoints().push(false);
+        transaction.set_savepoint()
+    }
+
+    fn rollback_to_savepoint(&self, transaction: &Self::Transaction) -> Result<(), Error> {
+        let mut savepoints = transaction.savepoints();
+        if !savepoints.contains(&false) {
+            return Err(Error::StorageError(
+                "transaction has no savepoint".to_owned(),
+            ));
+        }
+        // Rolling back to a released savepoint on top discards only writes the
+        // rollback to the last savepoint discards anyway
+        while let Some(released) = savepoints.pop() {
+            transaction.rollback_to_savepoint().map_err(RocksDBError)?;
+            if !released {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn release_savepoint(&self, transaction: &Self::Transaction) -> Result<(), Error> {
+        let mut savepoints = transaction.savepoints();
+        let released = savepoints
+            .iter_mut()
+            .rev()
+            .find(|released| !**released)
+            .ok_or_else(|| Error::StorageError("transaction has no savepoint".to_owned()))?;
+        *released = true;
+        Ok(())
+    }
+
+    fn savepoint_depth(&self, transaction: &Self::Transaction) -> usize {
+        transaction
+            .savepoints()
+            .iter()
+            .filter(|released| !**released)
+            .count()
+    }
+
+    fn validate_transaction(&self, transaction: &Self::Transaction) -> Result<(), Error> {
+        // Read only transactions write nothing that could conflict
+        let Some(start_snapshot) = &transaction.start_snapshot else {
+            return Ok(());
+        };
+        let mut column_families = vec![(write_batch::DEFAULT_COLUMN_FAMILY_ID, None)];
+        for cf in [cf_aux(&self.db), cf_roots(&self.db), cf_meta(&self.db)] {
+            column_families.push((write_batch::column_family_id(cf)?, Some(cf)));
+        }
+
+        let written_keys = write_batch::written_keys(transaction.get_writebatch().data())?;
+        for (column_family_id, key) in written_keys {
+            let cf = column_families
+                .iter()
+                .find_map(|(id, cf)| (*id == column_family_id).then_some(*cf))
+                .ok_or_else(|| {
+                    Error::StorageError("write batch of an unknown column family".to_owned())
+                })?;
+            let (committed, at_start) = match cf {
+                Some(cf) => (self.db.get_cf(cf, &key), start_snapshot.get_cf(cf, &key)),
+                None => (self.db.get(&key), start_snapshot.get(&key)),
+            };
+            if committed.map_err(RocksDBError)? != at_start.map_err(RocksDBError)? {
+                return Err(TransactionConflict);
+            }
+        }
+        Ok(())
+    }
+
+    fn get_for_update(
+        &self,
+        transaction: &Self::Transaction,
+        prefix: &SubtreePrefix,
+        key: &[u8],
+    ) -> CostResult<Option<Vec<u8>>, Error> {
+        transaction
+            .get_for_update_opt(
+                make_prefixed_key(prefix, key),
+                true,
+                &transaction_read_options(transaction),
+            )
+            .map_err(RocksDBError)
+            .wrap_fn_cost(|value| OperationCost {
+                seek_count: 1,
+                storage_loaded_bytes: value
+                    .as_ref()
+                    .ok()
+                    .and_then(Option::as_ref)
+                    .map(|x| x.len() as u32)
+                    .unwrap_or(0),
+                ..Default::default()
+            })
+    }
+
     fn flush(&self) -> Result<(), Error> {
         self.db.flush().map_err(RocksDBError)
     }
 
+    fn previous_records(
+        &'db self,
+        batch: &StorageBatch,
+        transaction: Option<&'db Self::Transaction>,
+    ) -> CostResult<PreviousRecords, Error> {
+        batch.previous_records(
+            |column, key| self.get_record(self.column_family(column), key, transaction),
+            |column, prefix| {
+                let mut records = Vec::new();
+                self.visit_prefixed_records(
+                    self.column_family(column),
+                    prefix,
+                    transaction,
+                    |key, value| records.push((key.to_vec(), value.to_vec())),
+                );
+                records
+            },
+        )
+    }
+
+    fn stored_prefixes(
+        &'db self,
+        from: &[u8],
+        limit: usize,
+        is_skipped: &dyn Fn(&[u8]) -> bool,
+        transaction: Option<&'db Self::Transaction>,
+    ) -> CostResult<BTreeMap<Vec<u8>, u64>, Error> {
+        let mut cost = OperationCost::default();
+        let mut sizes = BTreeMap::new();
+        for cf in [
+            None,
+            Some(cf_aux(&self.db)),
+            Some(cf_roots(&self.db)),
+            Some(cf_meta(&self.db)),
+        ] {
+            cost += self.add_stored_prefixes(cf, &mut sizes, from, limit, is_skipped, transaction);
+        }
+        prefix::truncate_stored_prefixes(&mut sizes, limit);
+        Ok(sizes).wrap_with_cost(cost)
+    }
+
     fn get_storage_context<'b, B>(
         &'db self,
         path: SubtreePath<'b, B>,
@@ -479,21 +795,23 @@ impl<'db> Storage<'db> for RocksDbStorage {
     ) -> CostResult<(), Error> {
         let mut cost = OperationCost::default();
         let (db_batch, pending_costs) =
-            cost_return_on_error!(&mut cost, self.build_write_batch(batch));
+            cost_return_on_error!(&mut cost, self.build_write_batch(batch, transaction));
 
         self.commit_db_write_batch(db_batch, pending_costs, transaction)
             .add_cost(cost)
     }
 
+    fn multi_context_batch_cost(
+        &self,
+        batch: StorageBatch,
+        transaction: Option<&'db Self::Transaction>,
+    ) -> CostResult<(), Error> {
+        self.build_write_batch(batch, transaction)
+            .flat_map_ok(|(_, pending_costs)| Ok(()).wrap_with_cost(pending_costs))
+    }
+
     fn get_storage_context_cost<L: WorstKeyLength>(path: &[L]) -> OperationCost {
-        if path.is_empty() {
-            OperationCost::default()
-        } else {
-            let body_size = Self::worst_case_body_size(path);
-            // the block size of blake3 is 64
-            let blocks_num = blake_block_count(body_size) as u32;
-            OperationCost::with_hash_node_calls(blocks_num)
-        }
+        prefix::build_prefix_worst_case_cost(path)
     }
 
     fn create_checkpoint<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
@@ -503,6 +821,46 @@ impl<'db> Storage<'db> for RocksDbStorage {
     }
 }
 
+impl CovariantStorage for RocksDbStorage {
+    fn narrow_transaction<'a, 'db>(
+        &self,
+        transaction: &'a <Self as Storage<'db>>::Transaction,
+    ) -> &'a <Self as Storage<'a>>::Transaction {
+        transaction
+    }
+}
+
+/// Calls `visit` with the key and value of every record a raw iterator sees
+/// under a prefix
+pub(crate) fn visit_prefixed_records<D: DBAccess>(
+    mut iterator: DBRawIteratorWithThreadMode<D>,
+    prefix: &[u8],
+    mut visit: impl FnMut(&[u8], &[u8]),
+) {
+    iterator.seek(prefix);
+    while let Some((key, value)) = iterator.item() {
+        if !key.starts_with(prefix) {
+            break;
+        }
+        visit(key, value);
+        iterator.next();
+    }
+}
+
+impl<D: DBAccess> RecordCursor for DBRawIteratorWithThreadMode<'_, D> {
+    fn seek_to(&mut self, key: &[u8]) {
+        self.seek(key);
+    }
+
+    fn record(&self) -> Option<(&[u8], usize)> {
+        self.item().map(|(key, value)| (key, value.len()))
+    }
+
+    fn next_record(&mut self) {
+        self.next();
+    }
+}
+
 /// Get auxiliary data column family
 fn cf_aux(storage: &Db) -> &ColumnFamily {
     storage
@@ -31,20 +31,40 @@
 mod batch;
 mod context_immediate;
 mod context_no_tx;
+mod context_read_only;
 mod context_tx;
 mod raw_iterator;
 
 pub use batch::PrefixedRocksDbBatch;
 pub use context_immediate::PrefixedRocksDbImmediateStorageContext;
 pub use context_no_tx::PrefixedRocksDbStorageContext;
+pub use context_read_only::PrefixedReadOnlyRocksDbStorageContext;
 pub use context_tx::PrefixedRocksDbTransactionContext;
 pub use raw_iterator::PrefixedRocksDbRawIterator;
+use rocksdb::ReadOptions;
 
-use super::storage::SubtreePrefix;
+pub use crate::prefix::make_prefixed_key;
+use crate::rocksdb_storage::storage::Tx;
 
-/// Make prefixed key
-pub fn make_prefixed_key<K: AsRef<[u8]>>(prefix: &SubtreePrefix, key: K) -> Vec<u8> {
-    let mut prefix_vec = prefix.to_vec();
-    prefix_vec.extend_from_slice(key.as_ref());
-    prefix_vec
+/// Read options of raw iterators. Seeking to the last key of a subtree targets
+/// the next subtree prefix, so iterators must ignore a prefix extractor.
+pub(crate) fn raw_iterator_read_options() -> ReadOptions {
+    let mut opts = ReadOptions::default();
+    opts.set_total_order_seek(true);
+    opts
+}
+
+/// Read options pinned to the snapshot of a transaction if it was started with
+/// one, otherwise the latest data is read.
+pub(crate) fn transaction_read_options(transaction: &Tx) -> ReadOptions {
+    let mut opts = ReadOptions::default();
+    opts.set_snapshot(&transaction.snapshot());
+    opts
+}
+
+/// Read options of raw iterators over the data seen by a transaction.
+pub(crate) fn transaction_raw_iterator_read_options(transaction: &Tx) -> ReadOptions {
+    let mut opts = transaction_read_options(transaction);
+    opts.set_total_order_seek(true);
+    opts
 }
@@ -35,11 +35,16 @@ use grovedb_costs::{
 };
 use rocksdb::{ColumnFamily, DBRawIteratorWithThreadMode, WriteBatchWithTransaction};
 
-use super::{make_prefixed_key, PrefixedRocksDbBatch, PrefixedRocksDbRawIterator};
+use super::{
+    make_prefixed_key, transaction_raw_iterator_read_options, transaction_read_options,
+    PrefixedRocksDbBatch, PrefixedRocksDbRawIterator,
+};
 use crate::{
     error,
     error::Error::RocksDBError,
-    rocksdb_storage::storage::{Db, SubtreePrefix, Tx, AUX_CF_NAME, META_CF_NAME, ROOTS_CF_NAME},
+    rocksdb_storage::storage::{
+        Db, RocksDbTransaction, SubtreePrefix, Tx, AUX_CF_NAME, META_CF_NAME, ROOTS_CF_NAME,
+    },
     StorageContext,
 };
 
@@ -47,13 +52,17 @@ use crate::{
 /// transaction.
 pub struct PrefixedRocksDbImmediateStorageContext<'db> {
     storage: &'db Db,
-    transaction: &'db Tx<'db>,
+    transaction: &'db RocksDbTransaction<'db>,
     prefix: SubtreePrefix,
 }
 
 impl<'db> PrefixedRocksDbImmediateStorageContext<'db> {
     /// Create a new prefixed transaction context instance
-    pub fn new(storage: &'db Db, transaction: &'db Tx<'db>, prefix: SubtreePrefix) -> Self {
+    pub fn new(
+        storage: &'db Db,
+        transaction: &'db RocksDbTransaction<'db>,
+        prefix: SubtreePrefix,
+    ) -> Self {
         PrefixedRocksDbImmediateStorageContext {
             storage,
             transaction,
@@ -83,6 +92,17 @@ impl<'db> PrefixedRocksDbImmediateStorageContext<'db> {
             .cf_handle(META_CF_NAME)
             .expect("meta column family must exist")
     }
+
+    /// Writes into the transaction unless it is read only
+    fn write(
+        &self,
+        write: impl FnOnce(&Tx<'db>) -> Result<(), rocksdb::Error>,
+    ) -> CostResult<(), Error> {
+        self.transaction
+            .check_writable()
+            .and_then(|_| write(self.transaction).map_err(RocksDBError))
+            .wrap_with_cost(Default::default())
+    }
 }
 
 impl<'db> StorageContext<'db> for PrefixedRocksDbImmediateStorageContext<'db> {
@@ -96,10 +116,7 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbImmediateStorageContext<'db> {
         _children_sizes: ChildrenSizesWithIsSumTree,
         _cost_info: Option<KeyValueStorageCost>,
     ) -> CostResult<(), Error> {
-        self.transaction
-            .put(make_prefixed_key(&self.prefix, &key), value)
-            .map_err(RocksDBError)
-            .wrap_with_cost(Default::default())
+        self.write(|transaction| transaction.put(make_prefixed_key(&self.prefix, &key), value))
     }
 
     fn put_aux<K: AsRef<[u8]>>(
@@ -108,10 +125,9 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbImmediateStorageContext<'db> {
         value: &[u8],
         _cost_info: Option<KeyValueStorageCost>,
     ) -> CostResult<(), Error> {
-        self.transaction
-            .put_cf(self.cf_aux(), make_prefixed_key(&self.prefix, &key), value)
-            .map_err(RocksDBError)
-            .wrap_with_cost(Default::default())
+        self.write(|transaction| {
+            transaction.put_cf(self.cf_aux(), make_prefixed_key(&self.prefix, &key), value)
+        })
     }
 
     fn put_root<K: AsRef<[u8]>>(
@@ -120,14 +136,13 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbImmediateStorageContext<'db> {
         value: &[u8],
         _cost_info: Option<KeyValueStorageCost>,
     ) -> CostResult<(), Error> {
-        self.transaction
-            .put_cf(
+        self.write(|transaction| {
+            transaction.put_cf(
                 self.cf_roots(),
                 make_prefixed_key(&self.prefix, &key),
                 value,
             )
-            .map_err(RocksDBError)
-            .wrap_with_cost(Default::default())
+        })
     }
 
     fn put_meta<K: AsRef<[u8]>>(
@@ -136,10 +151,9 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbImmediateStorageContext<'db> {
         value: &[u8],
         _cost_info: Option<KeyValueStorageCost>,
     ) -> CostResult<(), Error> {
-        self.transaction
-            .put_cf(self.cf_meta(), make_prefixed_key(&self.prefix, &key), value)
-            .map_err(RocksDBError)
-            .wrap_with_cost(Default::default())
+        self.write(|transaction| {
+            transaction.put_cf(self.cf_meta(), make_prefixed_key(&self.prefix, &key), value)
+        })
     }
 
     fn delete<K: AsRef<[u8]>>(
@@ -147,10 +161,7 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbImmediateStorageContext<'db> {
         key: K,
         _cost_info: Option<KeyValueStorageCost>,
     ) -> CostResult<(), Error> {
-        self.transaction
-            .delete(make_prefixed_key(&self.prefix, key))
-            .map_err(RocksDBError)
-            .wrap_with_cost(Default::default())
+        self.write(|transaction| transaction.delete(make_prefixed_key(&self.prefix, key)))
     }
 
     fn delete_aux<K: AsRef<[u8]>>(
@@ -158,10 +169,9 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbImmediateStorageContext<'db> {
         key: K,
         _cost_info: Option<KeyValueStorageCost>,
     ) -> CostResult<(), Error> {
-        self.transaction
-            .delete_cf(self.cf_aux(), make_prefixed_key(&self.prefix, key))
-            .map_err(RocksDBError)
-            .wrap_with_cost(Default::default())
+        self.write(|transaction| {
+            transaction.delete_cf(self.cf_aux(), make_prefixed_key(&self.prefix, key))
+        })
     }
 
     fn delete_root<K: AsRef<[u8]>>(
@@ -169,10 +179,9 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbImmediateStorageContext<'db> {
         key: K,
         _cost_info: Option<KeyValueStorageCost>,
     ) -> CostResult<(), Error> {
-        self.transaction
-            .delete_cf(self.cf_roots(), make_prefixed_key(&self.prefix, key))
-            .map_err(RocksDBError)
-            .wrap_with_cost(Default::default())
+        self.write(|transaction| {
+            transaction.delete_cf(self.cf_roots(), make_prefixed_key(&self.prefix, key))
+        })
     }
 
     fn delete_meta<K: AsRef<[u8]>>(
@@ -180,36 +189,50 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbImmediateStorageContext<'db> {
         key: K,
         _cost_info: Option<KeyValueStorageCost>,
     ) -> CostResult<(), Error> {
-        self.transaction
-            .delete_cf(self.cf_meta(), make_prefixed_key(&self.prefix, key))
-            .map_err(RocksDBError)
-            .wrap_with_cost(Default::default())
+        self.write(|transaction| {
+            transaction.delete_cf(self.cf_meta(), make_prefixed_key(&self.prefix, key))
+        })
     }
 
     fn get<K: AsRef<[u8]>>(&self, key: K) -> CostResult<Option<Vec<u8>>, Error> {
         self.transaction
-            .get(make_prefixed_key(&self.prefix, key))
+            .get_opt(
+                make_prefixed_key(&self.prefix, key),
+                &transaction_read_options(self.transaction),
+            )
             .map_err(RocksDBError)
             .wrap_with_cost(Default::default())
     }
 
     fn get_aux<K: AsRef<[u8]>>(&self, key: K) -> CostResult<Option<Vec<u8>>, Error> {
         self.transaction
-            .get_cf(self.cf_aux(), make_prefixed_key(&self.prefix, key))
+            .get_cf_opt(
+                self.cf_aux(),
+                make_prefixed_key(&self.prefix, key),
+                &transaction_read_options(self.transaction),
+            )
             .map_err(RocksDBError)
             .wrap_with_cost(Default::default())
     }
 
     fn get_root<K: AsRef<[u8]>>(&self, key: K) -> CostResult<Option<Vec<u8>>, Error> {
         self.transaction
-            .get_cf(self.cf_roots(), make_prefixed_key(&self.prefix, key))
+            .get_cf_opt(
+                self.cf_roots(),
+                make_prefixed_key(&self.prefix, key),
+                &transaction_read_options(self.transaction),
+            )
             .map_err(RocksDBError)
             .wrap_with_cost(Default::default())
     }
 
     fn get_meta<K: AsRef<[u8]>>(&self, key: K) -> CostResult<Option<Vec<u8>>, Error> {
         self.transaction
-            .get_cf(self.cf_meta(), make_prefixed_key(&self.prefix, key))
+            .get_cf_opt(
+                self.cf_meta(),
+                make_prefixed_key(&self.prefix, key),
+                &transaction_read_options(self.transaction),
+            )
             .map_err(RocksDBError)
             .wrap_with_cost(Default::default())
     }
@@ -225,16 +248,15 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbImmediateStorageContext<'db> {
     }
 
     fn commit_batch(&self, batch: Self::Batch) -> CostResult<(), Error> {
-        self.transaction
-            .rebuild_from_writebatch(&batch.batch)
-            .map_err(RocksDBError)
-            .wrap_with_cost(Default::default())
+        self.write(|transaction| transaction.rebuild_from_writebatch(&batch.batch))
     }
 
     fn raw_iter(&self) -> Self::RawIterator {
         PrefixedRocksDbRawIterator {
             prefix: self.prefix,
-            raw_iterator: self.transaction.raw_iterator(),
+            raw_iterator: self
+                .transaction
+                .raw_iterator_opt(transaction_raw_iterator_read_options(self.transaction)),
         }
     }
 }
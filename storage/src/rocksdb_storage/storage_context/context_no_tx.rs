@@ -35,7 +35,10 @@ use grovedb_costs::{
 };
 use rocksdb::{ColumnFamily, DBRawIteratorWithThreadMode};
 
-use super::{batch::PrefixedMultiContextBatchPart, make_prefixed_key, PrefixedRocksDbRawIterator};
+use super::{
+    batch::PrefixedMultiContextBatchPart, make_prefixed_key, raw_iterator_read_options,
+    PrefixedRocksDbRawIterator,
+};
 use crate::{
     error,
     error::Error::RocksDBError,
@@ -265,7 +268,7 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbStorageContext<'db> {
 
     fn new_batch(&self) -> Self::Batch {
         PrefixedMultiContextBatchPart {
-            prefix: self.prefix.clone(),
+            prefix: self.prefix,
             batch: StorageBatch::new(),
         }
     }
@@ -279,8 +282,15 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbStorageContext<'db> {
 
     fn raw_iter(&self) -> Self::RawIterator {
         PrefixedRocksDbRawIterator {
-            prefix: self.prefix.clone(),
-            raw_iterator: self.storage.raw_iterator(),
+            prefix: self.prefix,
+            raw_iterator: self.storage.raw_iterator_opt(raw_iterator_read_options()),
+        }
+    }
+
+    fn clear(&mut self) -> CostResult<(), Error> {
+        if let Some(existing_batch) = self.batch {
+            existing_batch.delete_prefix(self.prefix.to_vec());
         }
+        Ok(()).wrap_with_cost(OperationCost::default())
     }
 }
@@ -30,24 +30,29 @@
 
 use error::Error;
 use grovedb_costs::{
-    cost_return_on_error, storage_cost::key_value_cost::KeyValueStorageCost,
+    cost_return_on_error_no_add, storage_cost::key_value_cost::KeyValueStorageCost,
     ChildrenSizesWithIsSumTree, CostResult, CostsExt, OperationCost,
 };
 use rocksdb::{ColumnFamily, DBRawIteratorWithThreadMode};
 
-use super::{batch::PrefixedMultiContextBatchPart, make_prefixed_key, PrefixedRocksDbRawIterator};
+use super::{
+    batch::PrefixedMultiContextBatchPart, make_prefixed_key, transaction_raw_iterator_read_options,
+    transaction_read_options, PrefixedRocksDbRawIterator,
+};
 use crate::{
     error,
     error::Error::RocksDBError,
-    rocksdb_storage::storage::{Db, SubtreePrefix, Tx, AUX_CF_NAME, META_CF_NAME, ROOTS_CF_NAME},
-    RawIterator, StorageBatch, StorageContext,
+    rocksdb_storage::storage::{
+        Db, RocksDbTransaction, SubtreePrefix, Tx, AUX_CF_NAME, META_CF_NAME, ROOTS_CF_NAME,
+    },
+    StorageBatch, StorageContext,
 };
 
 /// Storage context with a prefix applied to be used in a subtree to be used in
 /// transaction.
 pub struct PrefixedRocksDbTransactionContext<'db> {
     storage: &'db Db,
-    transaction: &'db Tx<'db>,
+    transaction: &'db RocksDbTransaction<'db>,
     prefix: SubtreePrefix,
     batch: Option<&'db StorageBatch>,
 }
@@ -56,7 +61,7 @@ impl<'db> PrefixedRocksDbTransactionContext<'db> {
     /// Create a new prefixed transaction context instance
     pub fn new(
         storage: &'db Db,
-        transaction: &'db Tx<'db>,
+        transaction: &'db RocksDbTransaction<'db>,
         prefix: SubtreePrefix,
         batch: Option<&'db StorageBatch>,
     ) -> Self {
@@ -67,26 +72,6 @@ impl<'db> PrefixedRocksDbTransactionContext<'db> {
             batch,
         }
     }
-
-    /// Clears all the data in the tree at the storage level
-    pub fn clear(&mut self) -> CostResult<(), Error> {
-        let mut cost = OperationCost::default();
-
-        let mut iter = self.raw_iter();
-        iter.seek_to_first().unwrap_add_cost(&mut cost);
-
-        while iter.valid().unwrap_add_cost(&mut cost) {
-            if let Some(key) = iter.key().unwrap_add_cost(&mut cost) {
-                cost_return_on_error!(
-                    &mut cost,
-                    // todo: calculate cost
-                    self.delete(key, None)
-                );
-            }
-            iter.next().unwrap_add_cost(&mut cost);
-        }
-        Ok(()).wrap_with_cost(cost)
-    }
 }
 
 impl<'db> PrefixedRocksDbTransactionContext<'db> {
@@ -110,6 +95,11 @@ impl<'db> PrefixedRocksDbTransactionContext<'db> {
             .cf_handle(META_CF_NAME)
             .expect("meta column family must exist")
     }
+
+    /// Batch to write into, fails if the transaction is read only
+    fn writable_batch(&self) -> Result<Option<&'db StorageBatch>, Error> {
+        self.transaction.check_writable().map(|_| self.batch)
+    }
 }
 
 impl<'db> StorageContext<'db> for PrefixedRocksDbTransactionContext<'db> {
@@ -123,7 +113,8 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbTransactionContext<'db> {
         children_sizes: ChildrenSizesWithIsSumTree,
         cost_info: Option<KeyValueStorageCost>,
     ) -> CostResult<(), Error> {
-        if let Some(existing_batch) = self.batch {
+        let cost = OperationCost::default();
+        if let Some(existing_batch) = cost_return_on_error_no_add!(&cost, self.writable_batch()) {
             existing_batch.put(
                 make_prefixed_key(&self.prefix, key),
                 value.to_vec(),
@@ -131,7 +122,7 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbTransactionContext<'db> {
                 cost_info,
             );
         }
-        Ok(()).wrap_with_cost(OperationCost::default())
+        Ok(()).wrap_with_cost(cost)
     }
 
     fn put_aux<K: AsRef<[u8]>>(
@@ -140,14 +131,15 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbTransactionContext<'db> {
         value: &[u8],
         cost_info: Option<KeyValueStorageCost>,
     ) -> CostResult<(), Error> {
-        if let Some(existing_batch) = self.batch {
+        let cost = OperationCost::default();
+        if let Some(existing_batch) = cost_return_on_error_no_add!(&cost, self.writable_batch()) {
             existing_batch.put_aux(
                 make_prefixed_key(&self.prefix, key),
                 value.to_vec(),
                 cost_info,
             );
         }
-        Ok(()).wrap_with_cost(OperationCost::default())
+        Ok(()).wrap_with_cost(cost)
     }
 
     fn put_root<K: AsRef<[u8]>>(
@@ -156,14 +148,15 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbTransactionContext<'db> {
         value: &[u8],
         cost_info: Option<KeyValueStorageCost>,
     ) -> CostResult<(), Error> {
-        if let Some(existing_batch) = self.batch {
+        let cost = OperationCost::default();
+        if let Some(existing_batch) = cost_return_on_error_no_add!(&cost, self.writable_batch()) {
             existing_batch.put_root(
                 make_prefixed_key(&self.prefix, key),
                 value.to_vec(),
                 cost_info,
             );
         }
-        Ok(()).wrap_with_cost(OperationCost::default())
+        Ok(()).wrap_with_cost(cost)
     }
 
     fn put_meta<K: AsRef<[u8]>>(
@@ -172,14 +165,15 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbTransactionContext<'db> {
         value: &[u8],
         cost_info: Option<KeyValueStorageCost>,
     ) -> CostResult<(), Error> {
-        if let Some(existing_batch) = self.batch {
+        let cost = OperationCost::default();
+        if let Some(existing_batch) = cost_return_on_error_no_add!(&cost, self.writable_batch()) {
             existing_batch.put_meta(
                 make_prefixed_key(&self.prefix, key),
                 value.to_vec(),
                 cost_info,
             );
         }
-        Ok(()).wrap_with_cost(OperationCost::default())
+        Ok(()).wrap_with_cost(cost)
     }
 
     fn delete<K: AsRef<[u8]>>(
@@ -187,11 +181,12 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbTransactionContext<'db> {
         key: K,
         cost_info: Option<KeyValueStorageCost>,
     ) -> CostResult<(), Error> {
-        if let Some(existing_batch) = self.batch {
+        let cost = OperationCost::default();
+        if let Some(existing_batch) = cost_return_on_error_no_add!(&cost, self.writable_batch()) {
             existing_batch.delete(make_prefixed_key(&self.prefix, key), cost_info);
         }
 
-        Ok(()).wrap_with_cost(OperationCost::default())
+        Ok(()).wrap_with_cost(cost)
     }
 
     fn delete_aux<K: AsRef<[u8]>>(
@@ -199,11 +194,12 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbTransactionContext<'db> {
         key: K,
         cost_info: Option<KeyValueStorageCost>,
     ) -> CostResult<(), Error> {
-        if let Some(existing_batch) = self.batch {
+        let cost = OperationCost::default();
+        if let Some(existing_batch) = cost_return_on_error_no_add!(&cost, self.writable_batch()) {
             existing_batch.delete_aux(make_prefixed_key(&self.prefix, key), cost_info);
         }
 
-        Ok(()).wrap_with_cost(OperationCost::default())
+        Ok(()).wrap_with_cost(cost)
     }
 
     fn delete_root<K: AsRef<[u8]>>(
@@ -211,11 +207,12 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbTransactionContext<'db> {
         key: K,
         cost_info: Option<KeyValueStorageCost>,
     ) -> CostResult<(), Error> {
-        if let Some(existing_batch) = self.batch {
+        let cost = OperationCost::default();
+        if let Some(existing_batch) = cost_return_on_error_no_add!(&cost, self.writable_batch()) {
             existing_batch.delete_root(make_prefixed_key(&self.prefix, key), cost_info);
         }
 
-        Ok(()).wrap_with_cost(OperationCost::default())
+        Ok(()).wrap_with_cost(cost)
     }
 
     fn delete_meta<K: AsRef<[u8]>>(
@@ -223,16 +220,20 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbTransactionContext<'db> {
         key: K,
         cost_info: Option<KeyValueStorageCost>,
     ) -> CostResult<(), Error> {
-        if let Some(existing_batch) = self.batch {
+        let cost = OperationCost::default();
+        if let Some(existing_batch) = cost_return_on_error_no_add!(&cost, self.writable_batch()) {
             existing_batch.delete_meta(make_prefixed_key(&self.prefix, key), cost_info);
         }
 
-        Ok(()).wrap_with_cost(OperationCost::default())
+        Ok(()).wrap_with_cost(cost)
     }
 
     fn get<K: AsRef<[u8]>>(&self, key: K) -> CostResult<Option<Vec<u8>>, Error> {
         self.transaction
-            .get(make_prefixed_key(&self.prefix, key))
+            .get_opt(
+                make_prefixed_key(&self.prefix, key),
+                &transaction_read_options(self.transaction),
+            )
             .map_err(RocksDBError)
             .wrap_fn_cost(|value| OperationCost {
                 seek_count: 1,
@@ -248,7 +249,11 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbTransactionContext<'db> {
 
     fn get_aux<K: AsRef<[u8]>>(&self, key: K) -> CostResult<Option<Vec<u8>>, Error> {
         self.transaction
-            .get_cf(self.cf_aux(), make_prefixed_key(&self.prefix, key))
+            .get_cf_opt(
+                self.cf_aux(),
+                make_prefixed_key(&self.prefix, key),
+                &transaction_read_options(self.transaction),
+            )
             .map_err(RocksDBError)
             .wrap_fn_cost(|value| OperationCost {
                 seek_count: 1,
@@ -264,7 +269,11 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbTransactionContext<'db> {
 
     fn get_root<K: AsRef<[u8]>>(&self, key: K) -> CostResult<Option<Vec<u8>>, Error> {
         self.transaction
-            .get_cf(self.cf_roots(), make_prefixed_key(&self.prefix, key))
+            .get_cf_opt(
+                self.cf_roots(),
+                make_prefixed_key(&self.prefix, key),
+                &transaction_read_options(self.transaction),
+            )
             .map_err(RocksDBError)
             .wrap_fn_cost(|value| OperationCost {
                 seek_count: 1,
@@ -280,7 +289,11 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbTransactionContext<'db> {
 
     fn get_meta<K: AsRef<[u8]>>(&self, key: K) -> CostResult<Option<Vec<u8>>, Error> {
         self.transaction
-            .get_cf(self.cf_meta(), make_prefixed_key(&self.prefix, key))
+            .get_cf_opt(
+                self.cf_meta(),
+                make_prefixed_key(&self.prefix, key),
+                &transaction_read_options(self.transaction),
+            )
             .map_err(RocksDBError)
             .wrap_fn_cost(|value| OperationCost {
                 seek_count: 1,
@@ -296,12 +309,13 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbTransactionContext<'db> {
 
     fn new_batch(&self) -> Self::Batch {
         PrefixedMultiContextBatchPart {
-            prefix: self.prefix.clone(),
+            prefix: self.prefix,
             batch: StorageBatch::new(),
         }
     }
 
     fn commit_batch(&self, batch: Self::Batch) -> CostResult<(), Error> {
+        // Merged writes are refused when the multi-context batch is committed
         if let Some(existing_batch) = self.batch {
             existing_batch.merge(batch.batch);
         }
@@ -311,8 +325,18 @@ impl<'db> StorageContext<'db> for PrefixedRocksDbTransactionContext<'db> {
 
     fn raw_iter(&self) -> Self::RawIterator {
         PrefixedRocksDbRawIterator {
-            prefix: self.prefix.clone(),
-            raw_iterator: self.transaction.raw_iterator(),
+            prefix: self.prefix,
+            raw_iterator: self
+                .transaction
+                .raw_iterator_opt(transaction_raw_iterator_read_options(self.transaction)),
         }
     }
+
+    fn clear(&mut self) -> CostResult<(), Error> {
+        let cost = OperationCost::default();
+        if let Some(existing_batch) = cost_return_on_error_no_add!(&cost, self.writable_batch()) {
+            existing_batch.delete_prefix(self.prefix.to_vec());
+        }
+        Ok(()).wrap_with_cost(cost)
+    }
 }
@@ -43,8 +43,13 @@ pub struct TempStorage {
 impl TempStorage {
     /// Create new `TempStorage`
     pub fn new() -> Self {
+        Self::with_config(&RocksDbConfig::default())
+    }
+
+    /// Create new `TempStorage` with RocksDB tuned by `config`
+    pub fn with_config(config: &RocksDbConfig) -> Self {
         let dir = TempDir::new().expect("cannot create tempir");
-        let storage = RocksDbStorage::default_rocksdb_with_path(dir.path())
+        let storage = RocksDbStorage::rocksdb_with_config(dir.path(), config)
             .expect("cannot open rocksdb storage");
         TempStorage {
             dir: Cell::new(dir),
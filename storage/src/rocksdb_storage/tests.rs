@@ -773,6 +773,34 @@ mod batch_no_transaction {
             b"ayyavalue3"
         );
     }
+
+    #[test]
+    fn test_released_savepoints() {
+        let storage = TempStorage::new();
+        let tx = storage.start_transaction();
+        let context = storage
+            .get_immediate_storage_context([b"ayya"].as_ref().into(), &tx)
+            .unwrap();
+
+        context.put(b"a", b"1", None, None).unwrap().unwrap();
+        storage.set_savepoint(&tx);
+        context.put(b"a", b"2", None, None).unwrap().unwrap();
+        storage.set_savepoint(&tx);
+        context.put(b"b", b"3", None, None).unwrap().unwrap();
+        storage
+            .release_savepoint(&tx)
+            .expect("cannot release savepoint");
+        assert_eq!(context.get(b"b").unwrap().unwrap(), Some(b"3".to_vec()));
+
+        // The rollback skips the released savepoint and goes to the first one
+        storage
+            .rollback_to_savepoint(&tx)
+            .expect("cannot rollback to savepoint");
+        assert_eq!(context.get(b"a").unwrap().unwrap(), Some(b"1".to_vec()));
+        assert_eq!(context.get(b"b").unwrap().unwrap(), None);
+        assert!(storage.rollback_to_savepoint(&tx).is_err());
+        assert!(storage.release_savepoint(&tx).is_err());
+    }
 }
 
 mod batch_transaction {
@@ -1070,3 +1098,79 @@ mod batch_transaction {
         );
     }
 }
+
+mod config {
+    use super::*;
+    use crate::{
+        rocksdb_storage::{ColumnFamilyConfig, RocksDbConfig},
+        RawIterator, Storage, StorageBatch, StorageContext,
+    };
+
+    #[test]
+    fn test_subtree_prefixed_column_families() {
+        let storage = TempStorage::with_config(&RocksDbConfig {
+            block_cache_size: Some(1 << 20),
+            disable_wal: true,
+            default_cf: ColumnFamilyConfig::subtree_prefixed(),
+            aux_cf: ColumnFamilyConfig::subtree_prefixed(),
+            roots_cf: ColumnFamilyConfig::subtree_prefixed(),
+            meta_cf: ColumnFamilyConfig::subtree_prefixed(),
+            ..Default::default()
+        });
+        let batch = StorageBatch::new();
+        let context_ayya = storage.get_storage_context([b"ayya"].as_ref().into(), Some(&batch));
+        let context_ayyb = storage.get_storage_context([b"ayyb"].as_ref().into(), Some(&batch));
+        let context_ayya = context_ayya.unwrap();
+        let context_ayyb = context_ayyb.unwrap();
+
+        for key in [b"key1", b"key2", b"key3"] {
+            context_ayya
+                .put(key, b"ayyavalue", None, None)
+                .unwrap()
+                .expect("cannot insert into storage");
+            context_ayyb
+                .put(key, b"ayybvalue", None, None)
+                .unwrap()
+                .expect("cannot insert into storage");
+        }
+        context_ayya
+            .put_aux(b"key1", b"ayyavalue1", None)
+            .unwrap()
+            .expect("cannot insert into aux cf");
+
+        storage
+            .commit_multi_context_batch(batch, None)
+            .unwrap()
+            .expect("cannot commit batch");
+
+        let context_ayya = storage
+            .get_storage_context([b"ayya"].as_ref().into(), None)
+            .unwrap();
+        let context_ayyb = storage
+            .get_storage_context([b"ayyb"].as_ref().into(), None)
+            .unwrap();
+        assert_eq!(
+            context_ayya.get(b"key2").unwrap().unwrap(),
+            Some(b"ayyavalue".to_vec())
+        );
+        assert_eq!(
+            context_ayya.get_aux(b"key1").unwrap().unwrap(),
+            Some(b"ayyavalue1".to_vec())
+        );
+        assert_eq!(context_ayyb.get_aux(b"key1").unwrap().unwrap(), None);
+
+        // Seeking to the last key targets the next subtree prefix
+        let mut iter = context_ayya.raw_iter();
+        iter.seek_to_last().unwrap();
+        assert_eq!(iter.key().unwrap(), Some(b"key3".as_ref()));
+        iter.seek_to_first().unwrap();
+        assert_eq!(iter.key().unwrap(), Some(b"key1".as_ref()));
+
+        let mut count = 0;
+        while iter.valid().unwrap() {
+            count += 1;
+            iter.next().unwrap();
+        }
+        assert_eq!(count, 3);
+    }
+}
@@ -0,0 +1,137 @@
+// MIT LICENSE
+//
+// Copyright (c) 2021 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Decoding of the records of RocksDB write batches, which `rocksdb` only
+//! iterates for the default column family.
+
+use integer_encoding::VarInt;
+use rocksdb::{ColumnFamily, WriteBatchWithTransaction};
+
+use crate::error::Error;
+
+/// Length of the sequence number and count heading a write batch
+const HEADER_LENGTH: usize = 12;
+
+const TYPE_DELETION: u8 = 0x0;
+const TYPE_VALUE: u8 = 0x1;
+const TYPE_MERGE: u8 = 0x2;
+const TYPE_LOG_DATA: u8 = 0x3;
+const TYPE_COLUMN_FAMILY_DELETION: u8 = 0x4;
+const TYPE_COLUMN_FAMILY_VALUE: u8 = 0x5;
+const TYPE_COLUMN_FAMILY_MERGE: u8 = 0x6;
+const TYPE_SINGLE_DELETION: u8 = 0x7;
+const TYPE_COLUMN_FAMILY_SINGLE_DELETION: u8 = 0x8;
+const TYPE_NOOP: u8 = 0xD;
+
+/// Id of the default column family
+pub(crate) const DEFAULT_COLUMN_FAMILY_ID: u32 = 0;
+
+/// Id RocksDB gave to a column family, read back from a write batch as
+/// `rocksdb` doesn't expose it
+pub(crate) fn column_family_id(cf: &ColumnFamily) -> Result<u32, Error> {
+    let mut batch = WriteBatchWithTransaction::<true>::default();
+    batch.put_cf(cf, [], []);
+    written_keys(batch.data())?
+        .pop()
+        .map(|(column_family_id, _)| column_family_id)
+        .ok_or_else(|| Error::StorageError("write batch has no record".to_owned()))
+}
+
+/// Column family id and key of every record a write batch puts, merges or
+/// deletes, in the order of the batch
+pub(crate) fn written_keys(data: &[u8]) -> Result<Vec<(u32, Vec<u8>)>, Error> {
+    let mut reader = Reader {
+        data: data.get(HEADER_LENGTH..).unwrap_or_default(),
+    };
+    let mut keys = Vec::new();
+    while let Some(tag) = reader.byte() {
+        let column_family_id = match tag {
+            TYPE_COLUMN_FAMILY_VALUE
+            | TYPE_COLUMN_FAMILY_MERGE
+            | TYPE_COLUMN_FAMILY_DELETION
+            | TYPE_COLUMN_FAMILY_SINGLE_DELETION => reader.varint()?,
+            _ => DEFAULT_COLUMN_FAMILY_ID,
+        };
+        match tag {
+            TYPE_VALUE | TYPE_MERGE | TYPE_COLUMN_FAMILY_VALUE | TYPE_COLUMN_FAMILY_MERGE => {
+                keys.push((column_family_id, reader.slice()?.to_vec()));
+                reader.slice()?;
+            }
+            TYPE_DELETION
+            | TYPE_SINGLE_DELETION
+            | TYPE_COLUMN_FAMILY_DELETION
+            | TYPE_COLUMN_FAMILY_SINGLE_DELETION => {
+                keys.push((column_family_id, reader.slice()?.to_vec()));
+            }
+            TYPE_LOG_DATA => {
+                reader.slice()?;
+            }
+            TYPE_NOOP => {}
+            _ => {
+                return Err(Error::NotSupported(
+                    "write batch records other than puts, merges and deletes",
+                ))
+            }
+        }
+    }
+    Ok(keys)
+}
+
+/// Reads the fields of write batch records
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Option<u8> {
+        let (byte, rest) = self.data.split_first()?;
+        self.data = rest;
+        Some(*byte)
+    }
+
+    fn varint(&mut self) -> Result<u32, Error> {
+        let (value, length) = u32::decode_var(self.data).ok_or_else(corrupted)?;
+        self.data = &self.data[length..];
+        Ok(value)
+    }
+
+    /// Reads a length prefixed slice
+    fn slice(&mut self) -> Result<&'a [u8], Error> {
+        let length = self.varint()? as usize;
+        if self.data.len() < length {
+            return Err(corrupted());
+        }
+        let (slice, rest) = self.data.split_at(length);
+        self.data = rest;
+        Ok(slice)
+    }
+}
+
+fn corrupted() -> Error {
+    Error::StorageError("corrupted write batch".to_owned())
+}
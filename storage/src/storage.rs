@@ -29,19 +29,20 @@
 //! Storage for GroveDB
 
 use std::{
-    cell::RefCell,
-    collections::{btree_map::IntoValues, BTreeMap},
+    collections::{btree_map::IntoValues, btree_set, BTreeMap, BTreeSet},
     path::Path,
+    sync::{Mutex, MutexGuard, PoisonError},
 };
 
 use grovedb_costs::{
-    storage_cost::key_value_cost::KeyValueStorageCost, ChildrenSizesWithIsSumTree, CostContext,
-    CostResult, OperationCost,
+    cost_return_on_error_no_add,
+    storage_cost::{key_value_cost::KeyValueStorageCost, StorageCost},
+    ChildrenSizesWithIsSumTree, CostContext, CostResult, CostsExt, OperationCost,
 };
 use grovedb_path::SubtreePath;
 use grovedb_visualize::visualize_to_vec;
 
-use crate::{worst_case_costs::WorstKeyLength, Error};
+use crate::{worst_case_costs::WorstKeyLength, Error, SubtreePrefix};
 
 /// Top-level storage_cost abstraction.
 /// Should be able to hold storage_cost connection and to start transaction when
@@ -63,12 +64,48 @@ pub trait Storage<'db> {
     /// Starts a new transaction
     fn start_transaction(&'db self) -> Self::Transaction;
 
+    /// Starts a read only transaction whose reads are pinned to the data
+    /// committed at its start, later commits made outside of it are not seen.
+    /// Writes through it fail with `Error::NotSupported`.
+    fn start_snapshot_transaction(&'db self) -> Self::Transaction;
+
     /// Consumes and commits a transaction
     fn commit_transaction(&self, transaction: Self::Transaction) -> CostResult<(), Error>;
 
     /// Rollback a transaction
     fn rollback_transaction(&self, transaction: &Self::Transaction) -> Result<(), Error>;
 
+    /// Records the state of a transaction to roll back to later, savepoints
+    /// stack and are removed by a rollback or a release
+    fn set_savepoint(&self, transaction: &Self::Transaction);
+
+    /// Rolls a transaction back to its last savepoint and removes it, fails if
+    /// the transaction has no savepoint
+    fn rollback_to_savepoint(&self, transaction: &Self::Transaction) -> Result<(), Error>;
+
+    /// Removes the last savepoint of a transaction keeping the writes made
+    /// since, fails if the transaction has no savepoint
+    fn release_savepoint(&self, transaction: &Self::Transaction) -> Result<(), Error>;
+
+    /// Number of savepoints of a transaction not yet rolled back or released
+    fn savepoint_depth(&self, transaction: &Self::Transaction) -> usize;
+
+    /// Fails with `Error::TransactionConflict` if records the transaction
+    /// wrote were committed since it wrote them, or since it started for
+    /// storages only keeping the records as of its start.
+    fn validate_transaction(&self, transaction: &Self::Transaction) -> Result<(), Error>;
+
+    /// Reads a record of a subtree through a transaction and tracks it like
+    /// the records the transaction writes, so committing the transaction fails
+    /// with `Error::TransactionConflict` if the record is committed by another
+    /// one meanwhile
+    fn get_for_update(
+        &self,
+        transaction: &Self::Transaction,
+        prefix: &SubtreePrefix,
+        key: &[u8],
+    ) -> CostResult<Option<Vec<u8>>, Error>;
+
     /// Consumes and applies multi-context batch.
     fn commit_multi_context_batch(
         &self,
@@ -76,9 +113,39 @@ pub trait Storage<'db> {
         transaction: Option<&'db Self::Transaction>,
     ) -> CostResult<(), Error>;
 
+    /// Cost committing `batch` with `commit_multi_context_batch` would have,
+    /// without writing it
+    fn multi_context_batch_cost(
+        &self,
+        batch: StorageBatch,
+        transaction: Option<&'db Self::Transaction>,
+    ) -> CostResult<(), Error>;
+
     /// Forces data to be written
     fn flush(&self) -> Result<(), Error>;
 
+    /// Reads the values the records written by `batch` have before it is
+    /// committed, as seen by the transaction if provided. Every record under
+    /// a prefix the batch deletes is read.
+    fn previous_records(
+        &'db self,
+        batch: &StorageBatch,
+        transaction: Option<&'db Self::Transaction>,
+    ) -> CostResult<PreviousRecords, Error>;
+
+    /// Returns the first `limit` subtree prefixes from `from` on having
+    /// records in any column, with the number of key and value bytes stored
+    /// under each, as seen by the transaction if provided. Records are read
+    /// one by one and those of prefixes `is_skipped` returns true for are
+    /// passed over by seeking to the next prefix.
+    fn stored_prefixes(
+        &'db self,
+        from: &[u8],
+        limit: usize,
+        is_skipped: &dyn Fn(&[u8]) -> bool,
+        transaction: Option<&'db Self::Transaction>,
+    ) -> CostResult<BTreeMap<Vec<u8>, u64>, Error>;
+
     /// Make storage context for a subtree with path, keeping all write
     /// operations inside a `batch` if provided.
     fn get_storage_context<'b, B>(
@@ -117,6 +184,19 @@ pub trait Storage<'db> {
     fn get_storage_context_cost<L: WorstKeyLength>(path: &[L]) -> OperationCost;
 }
 
+/// Storage usable through a borrow of any lifetime, its transactions being
+/// covariant over the storage lifetime. Allows code to be generic over the
+/// storage. Batch storage contexts must be sendable so subtrees of a batch
+/// can be applied on several threads.
+pub trait CovariantStorage: for<'db> Storage<'db, BatchStorageContext: Send> {
+    /// Shortens the storage lifetime of a borrowed transaction to the lifetime
+    /// of the borrow
+    fn narrow_transaction<'a, 'db>(
+        &self,
+        transaction: &'a <Self as Storage<'db>>::Transaction,
+    ) -> &'a <Self as Storage<'a>>::Transaction;
+}
+
 pub use grovedb_costs::ChildrenSizes;
 
 /// Storage context.
@@ -211,6 +291,25 @@ pub trait StorageContext<'db> {
 
     /// Get raw iterator over storage_cost
     fn raw_iter(&self) -> Self::RawIterator;
+
+    /// Clears all the data in the tree at the storage level. Batched contexts
+    /// delete the whole subtree prefix when the batch is committed.
+    fn clear(&mut self) -> CostResult<(), Error> {
+        let mut cost = OperationCost::default();
+
+        let mut iter = self.raw_iter();
+        iter.seek_to_first().unwrap_add_cost(&mut cost);
+
+        let mut to_delete = self.new_batch();
+        while iter.valid().unwrap_add_cost(&mut cost) {
+            if let Some(key) = iter.key().unwrap_add_cost(&mut cost) {
+                // todo: calculate cost
+                to_delete.delete(key, None);
+            }
+            iter.next().unwrap_add_cost(&mut cost);
+        }
+        self.commit_batch(to_delete).add_cost(cost)
+    }
 }
 
 /// Database batch (not to be confused with multi-tree operations batch).
@@ -282,11 +381,54 @@ pub trait RawIterator {
     fn valid(&self) -> CostContext<bool>;
 }
 
+/// Column of the storage, the counterpart of a RocksDB column family
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Column {
+    Data,
+    Aux,
+    Roots,
+    Meta,
+}
+
+/// Values records had before a batch was committed, `None` for records that
+/// didn't exist. Keys include their subtree prefix.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PreviousRecords {
+    /// Records of the data column
+    pub data: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+    /// Records of the auxiliary column
+    pub aux: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+    /// Records of the subtree roots column
+    pub roots: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+    /// Records of the metadata column
+    pub meta: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+}
+
+impl PreviousRecords {
+    /// Adds the records of a batch committed after the one these records
+    /// come from, keeping the values read before it for records both write
+    pub fn append(&mut self, later: PreviousRecords) {
+        for (records, later_records) in [
+            (&mut self.data, later.data),
+            (&mut self.aux, later.aux),
+            (&mut self.roots, later.roots),
+            (&mut self.meta, later.meta),
+        ] {
+            let keys: BTreeSet<Vec<u8>> = records.iter().map(|(key, _)| key.clone()).collect();
+            records.extend(
+                later_records
+                    .into_iter()
+                    .filter(|(key, _)| !keys.contains(key)),
+            );
+        }
+    }
+}
+
 /// Structure to hold deferred database operations in "batched" storage_cost
-/// contexts.
+/// contexts. It can be shared between threads.
 #[derive(Debug)]
 pub struct StorageBatch {
-    operations: RefCell<Operations>,
+    operations: Mutex<Operations>,
 }
 
 #[derive(Default)]
@@ -295,6 +437,7 @@ struct Operations {
     roots: BTreeMap<Vec<u8>, AbstractBatchOperation>,
     aux: BTreeMap<Vec<u8>, AbstractBatchOperation>,
     meta: BTreeMap<Vec<u8>, AbstractBatchOperation>,
+    deleted_prefixes: BTreeSet<Vec<u8>>,
 }
 
 impl std::fmt::Debug for Operations {
@@ -305,6 +448,7 @@ impl std::fmt::Debug for Operations {
         fmt.field("aux", &self.aux.values());
         fmt.field("roots", &self.roots.values());
         fmt.field("meta", &self.meta.values());
+        fmt.field("deleted_prefixes", &self.deleted_prefixes);
 
         fmt.finish()
     }
@@ -314,17 +458,26 @@ impl StorageBatch {
     /// Create empty batch.
     pub fn new() -> Self {
         StorageBatch {
-            operations: RefCell::new(Operations::default()),
+            operations: Mutex::new(Operations::default()),
         }
     }
 
+    /// Operations of the batch, a poisoned lock is fine to use as every change
+    /// is a single map insertion
+    fn operations(&self) -> MutexGuard<'_, Operations> {
+        self.operations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
     #[cfg(test)]
     pub(crate) fn len(&self) -> usize {
-        let operations = self.operations.borrow();
+        let operations = self.operations();
         operations.data.len()
             + operations.roots.len()
             + operations.aux.len()
             + operations.meta.len()
+            + operations.deleted_prefixes.len()
     }
 
     /// Add deferred `put` operation
@@ -335,7 +488,7 @@ impl StorageBatch {
         children_sizes: ChildrenSizesWithIsSumTree,
         cost_info: Option<KeyValueStorageCost>,
     ) {
-        self.operations.borrow_mut().data.insert(
+        self.operations().data.insert(
             key.clone(),
             AbstractBatchOperation::Put {
                 key,
@@ -353,7 +506,7 @@ impl StorageBatch {
         value: Vec<u8>,
         cost_info: Option<KeyValueStorageCost>,
     ) {
-        self.operations.borrow_mut().aux.insert(
+        self.operations().aux.insert(
             key.clone(),
             AbstractBatchOperation::PutAux {
                 key,
@@ -370,7 +523,7 @@ impl StorageBatch {
         value: Vec<u8>,
         cost_info: Option<KeyValueStorageCost>,
     ) {
-        self.operations.borrow_mut().roots.insert(
+        self.operations().roots.insert(
             key.clone(),
             AbstractBatchOperation::PutRoot {
                 key,
@@ -387,7 +540,7 @@ impl StorageBatch {
         value: Vec<u8>,
         cost_info: Option<KeyValueStorageCost>,
     ) {
-        self.operations.borrow_mut().meta.insert(
+        self.operations().meta.insert(
             key.clone(),
             AbstractBatchOperation::PutMeta {
                 key,
@@ -399,7 +552,7 @@ impl StorageBatch {
 
     /// Add deferred `delete` operation
     pub(crate) fn delete(&self, key: Vec<u8>, cost_info: Option<KeyValueStorageCost>) {
-        let operations = &mut self.operations.borrow_mut().data;
+        let operations = &mut self.operations().data;
         if operations.get(&key).is_none() {
             operations.insert(
                 key.clone(),
@@ -410,7 +563,7 @@ impl StorageBatch {
 
     /// Add deferred `delete` operation for aux storage_cost
     pub(crate) fn delete_aux(&self, key: Vec<u8>, cost_info: Option<KeyValueStorageCost>) {
-        let operations = &mut self.operations.borrow_mut().aux;
+        let operations = &mut self.operations().aux;
         if operations.get(&key).is_none() {
             operations.insert(
                 key.clone(),
@@ -421,7 +574,7 @@ impl StorageBatch {
 
     /// Add deferred `delete` operation for subtree roots storage_cost
     pub(crate) fn delete_root(&self, key: Vec<u8>, cost_info: Option<KeyValueStorageCost>) {
-        let operations = &mut self.operations.borrow_mut().roots;
+        let operations = &mut self.operations().roots;
         if operations.get(&key).is_none() {
             operations.insert(
                 key.clone(),
@@ -432,7 +585,7 @@ impl StorageBatch {
 
     /// Add deferred `delete` operation for metadata storage_cost
     pub(crate) fn delete_meta(&self, key: Vec<u8>, cost_info: Option<KeyValueStorageCost>) {
-        let operations = &mut self.operations.borrow_mut().meta;
+        let operations = &mut self.operations().meta;
         if operations.get(&key).is_none() {
             operations.insert(
                 key.clone(),
@@ -441,6 +594,160 @@ impl StorageBatch {
         }
     }
 
+    /// Add deferred deletion of every record under a subtree prefix in all
+    /// columns. Operations added before are dropped, operations added after
+    /// are applied on top of the deletion.
+    ///
+    /// RocksDB optimistic transaction databases refuse range deletions in
+    /// every write, transactional or not, so on commit the deletion is written
+    /// as a delete of every record seen under the prefix, with or without a
+    /// transaction. Its cost and write batch size grow with the number of
+    /// records, and records written under the prefix by a concurrent
+    /// transaction committed later are not deleted.
+    pub fn delete_prefix(&self, prefix: Vec<u8>) {
+        let mut operations = self.operations();
+        let operations = &mut *operations;
+        for column in [
+            &mut operations.data,
+            &mut operations.aux,
+            &mut operations.roots,
+            &mut operations.meta,
+        ] {
+            column.retain(|key, _| !key.starts_with(&prefix));
+        }
+        operations.deleted_prefixes.insert(prefix);
+    }
+
+    /// Add deferred writes setting records back to their previous values
+    pub fn restore_previous_records(&self, records: PreviousRecords) {
+        for (key, value) in records.data {
+            match value {
+                Some(value) => self.put(key, value, None, None),
+                None => self.delete(key, None),
+            }
+        }
+        for (key, value) in records.aux {
+            match value {
+                Some(value) => self.put_aux(key, value, None),
+                None => self.delete_aux(key, None),
+            }
+        }
+        for (key, value) in records.roots {
+            match value {
+                Some(value) => self.put_root(key, value, None),
+                None => self.delete_root(key, None),
+            }
+        }
+        for (key, value) in records.meta {
+            match value {
+                Some(value) => self.put_meta(key, value, None),
+                None => self.delete_meta(key, None),
+            }
+        }
+    }
+
+    /// Reads the values the records written by the batch have before it is
+    /// committed, `get` reading a record of a column and `prefixed` every
+    /// record of a column under a prefix
+    pub(crate) fn previous_records(
+        &self,
+        mut get: impl FnMut(Column, &[u8]) -> Result<Option<Vec<u8>>, Error>,
+        mut prefixed: impl FnMut(Column, &[u8]) -> Vec<(Vec<u8>, Vec<u8>)>,
+    ) -> CostResult<PreviousRecords, Error> {
+        let mut cost = OperationCost::default();
+        let operations = self.operations();
+        let mut records = PreviousRecords::default();
+        for (column, written, previous) in [
+            (Column::Data, &operations.data, &mut records.data),
+            (Column::Aux, &operations.aux, &mut records.aux),
+            (Column::Roots, &operations.roots, &mut records.roots),
+            (Column::Meta, &operations.meta, &mut records.meta),
+        ] {
+            let mut values = BTreeMap::new();
+            for prefix in &operations.deleted_prefixes {
+                cost.seek_count += 1;
+                for (key, value) in prefixed(column, prefix) {
+                    cost.storage_loaded_bytes += value.len() as u32;
+                    values.insert(key, Some(value));
+                }
+            }
+            for key in written.keys() {
+                let deleted = operations
+                    .deleted_prefixes
+                    .iter()
+                    .any(|prefix| key.starts_with(prefix));
+                if deleted {
+                    // Records under a deleted prefix were all read above
+                    values.entry(key.clone()).or_insert(None);
+                    continue;
+                }
+                let value = cost_return_on_error_no_add!(&cost, get(column, key));
+                cost.seek_count += 1;
+                cost.storage_loaded_bytes += value.as_ref().map_or(0, |v| v.len() as u32);
+                values.insert(key.clone(), value);
+            }
+            previous.extend(values);
+        }
+        Ok(records).wrap_with_cost(cost)
+    }
+
+    /// Value a record of the data column has once the batch is committed,
+    /// `Some(None)` if the batch deletes it and `None` if the batch leaves it
+    /// as it is
+    pub fn data_value_after_commit(&self, key: &[u8]) -> Option<Option<Vec<u8>>> {
+        let operations = self.operations();
+        match operations.data.get(key) {
+            Some(AbstractBatchOperation::Put { value, .. }) => Some(Some(value.clone())),
+            Some(_) => Some(None),
+            None => operations
+                .deleted_prefixes
+                .iter()
+                .any(|prefix| key.starts_with(prefix))
+                .then_some(None),
+        }
+    }
+
+    /// Storage cost of every record the batch writes to the data column, by
+    /// prefixed key, as the cost info of its operation gives it. Deletions
+    /// without cost info are left out as their cost is only known on commit.
+    pub fn data_storage_costs(&self) -> Result<BTreeMap<Vec<u8>, StorageCost>, Error> {
+        let mut costs = BTreeMap::new();
+        for (key, operation) in self.operations().data.iter() {
+            match operation {
+                AbstractBatchOperation::Put {
+                    value,
+                    children_sizes,
+                    cost_info,
+                    ..
+                } => {
+                    let mut cost = OperationCost::default();
+                    cost.add_key_value_storage_costs(
+                        key.len() as u32,
+                        value.len() as u32,
+                        *children_sizes,
+                        cost_info.clone(),
+                    )
+                    .map_err(Error::CostError)?;
+                    costs.insert(key.clone(), cost.storage_cost);
+                }
+                AbstractBatchOperation::Delete {
+                    cost_info: Some(cost_info),
+                    ..
+                } => {
+                    costs.insert(
+                        key.clone(),
+                        StorageCost {
+                            removed_bytes: cost_info.clone().combined_removed_bytes(),
+                            ..Default::default()
+                        },
+                    );
+                }
+                _ => {}
+            }
+        }
+        Ok(costs)
+    }
+
     /// Merge batch into this one
     pub(crate) fn merge(&self, other: StorageBatch) {
         for op in other.into_iter() {
@@ -476,6 +783,7 @@ impl StorageBatch {
                 AbstractBatchOperation::DeleteMeta { key, cost_info } => {
                     self.delete_meta(key, cost_info)
                 }
+                AbstractBatchOperation::DeletePrefix { prefix } => self.delete_prefix(prefix),
             }
         }
     }
@@ -487,14 +795,18 @@ pub(crate) struct StorageBatchIter {
     aux: IntoValues<Vec<u8>, AbstractBatchOperation>,
     meta: IntoValues<Vec<u8>, AbstractBatchOperation>,
     roots: IntoValues<Vec<u8>, AbstractBatchOperation>,
+    deleted_prefixes: btree_set::IntoIter<Vec<u8>>,
 }
 
 impl Iterator for StorageBatchIter {
     type Item = AbstractBatchOperation;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.meta
+        // Prefix deletions go first for the rest of operations to be applied on top
+        self.deleted_prefixes
             .next()
+            .map(|prefix| AbstractBatchOperation::DeletePrefix { prefix })
+            .or_else(|| self.meta.next())
             .or_else(|| self.aux.next())
             .or_else(|| self.roots.next())
             .or_else(|| self.data.next())
@@ -505,13 +817,17 @@ impl Iterator for StorageBatchIter {
 // to leak multi context batch internals in any way
 impl StorageBatch {
     pub(crate) fn into_iter(self) -> StorageBatchIter {
-        let operations = self.operations.into_inner();
+        let operations = self
+            .operations
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
 
         StorageBatchIter {
             data: operations.data.into_values(),
             aux: operations.aux.into_values(),
             meta: operations.meta.into_values(),
             roots: operations.roots.into_values(),
+            deleted_prefixes: operations.deleted_prefixes.into_iter(),
         }
     }
 }
@@ -572,6 +888,8 @@ pub(crate) enum AbstractBatchOperation {
         key: Vec<u8>,
         cost_info: Option<KeyValueStorageCost>,
     },
+    /// Deferred deletion of all records of a subtree prefix
+    DeletePrefix { prefix: Vec<u8> },
 }
 
 impl std::fmt::Debug for AbstractBatchOperation {
@@ -601,6 +919,11 @@ impl std::fmt::Debug for AbstractBatchOperation {
                 visualize_to_vec(&mut key_buf, key.as_slice());
                 fmt.field("key", &String::from_utf8_lossy(&key_buf));
             }
+            AbstractBatchOperation::DeletePrefix { prefix } => {
+                key_buf.clear();
+                visualize_to_vec(&mut key_buf, prefix.as_slice());
+                fmt.field("prefix", &String::from_utf8_lossy(&key_buf));
+            }
         }
 
         fmt.finish()
@@ -609,6 +932,8 @@ impl std::fmt::Debug for AbstractBatchOperation {
 
 #[cfg(test)]
 mod tests {
+    use grovedb_costs::storage_cost::removal::StorageRemovedBytes::BasicStorageRemoval;
+
     use super::*;
 
     #[test]
@@ -632,4 +957,91 @@ mod tests {
             "DeleteRoot { key: \"[hex: 6b657931, str: key1]\" }"
         );
     }
+
+    #[test]
+    fn test_delete_prefix_drops_previous_operations() {
+        let batch = StorageBatch::new();
+        batch.put(b"pkey1".to_vec(), b"value".to_vec(), None, None);
+        batch.put_aux(b"pkey2".to_vec(), b"value".to_vec(), None);
+        batch.delete(b"other".to_vec(), None);
+        batch.delete_prefix(b"p".to_vec());
+        batch.put_meta(b"pkey3".to_vec(), b"value".to_vec(), None);
+
+        let operations: Vec<_> = batch.into_iter().map(|op| format!("{op:?}")).collect();
+        assert_eq!(
+            operations,
+            vec![
+                "DeletePrefix { prefix: \"[hex: 70, str: p]\" }",
+                "PutMeta { key: \"[hex: 706b657933, str: pkey3]\", value: \"[hex: 76616c7565, \
+                 str: value]\" }",
+                "Delete { key: \"[hex: 6f74686572, str: other]\" }",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_data_storage_costs() {
+        let batch = StorageBatch::new();
+        batch.put(b"key".to_vec(), b"value".to_vec(), None, None);
+        batch.put_aux(b"aux".to_vec(), b"value".to_vec(), None);
+        batch.delete(b"unknown".to_vec(), None);
+        batch.delete(
+            b"removed".to_vec(),
+            Some(KeyValueStorageCost {
+                value_storage_cost: StorageCost {
+                    removed_bytes: BasicStorageRemoval(10),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+        );
+
+        let costs = batch.data_storage_costs().expect("expected costs");
+        assert_eq!(costs.len(), 2);
+        assert_eq!(costs[b"key".as_ref()].added_bytes, 3 + 5 + 1 + 1);
+        assert_eq!(
+            costs[b"removed".as_ref()].removed_bytes,
+            BasicStorageRemoval(10)
+        );
+    }
+
+    #[test]
+    fn test_data_value_after_commit() {
+        let batch = StorageBatch::new();
+        batch.put(b"pkey1".to_vec(), b"old".to_vec(), None, None);
+        batch.delete_prefix(b"p".to_vec());
+        batch.put(b"pkey2".to_vec(), b"value".to_vec(), None, None);
+        batch.delete(b"other".to_vec(), None);
+
+        assert_eq!(batch.data_value_after_commit(b"pkey1"), Some(None));
+        assert_eq!(
+            batch.data_value_after_commit(b"pkey2"),
+            Some(Some(b"value".to_vec()))
+        );
+        assert_eq!(batch.data_value_after_commit(b"other"), Some(None));
+        assert_eq!(batch.data_value_after_commit(b"unknown"), None);
+    }
+
+    #[test]
+    fn test_append_previous_records() {
+        let mut records = PreviousRecords {
+            data: vec![(b"a".to_vec(), None), (b"b".to_vec(), Some(b"1".to_vec()))],
+            ..Default::default()
+        };
+        records.append(PreviousRecords {
+            data: vec![(b"b".to_vec(), Some(b"2".to_vec())), (b"c".to_vec(), None)],
+            meta: vec![(b"m".to_vec(), Some(b"3".to_vec()))],
+            ..Default::default()
+        });
+
+        assert_eq!(
+            records.data,
+            vec![
+                (b"a".to_vec(), None),
+                (b"b".to_vec(), Some(b"1".to_vec())),
+                (b"c".to_vec(), None),
+            ]
+        );
+        assert_eq!(records.meta, vec![(b"m".to_vec(), Some(b"3".to_vec()))]);
+    }
 }
@@ -0,0 +1,154 @@
+// MIT LICENSE
+//
+// Copyright (c) 2023 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Elements
+//!
+//! Decoding of the elements stored in GroveDB, compatible with the bincode
+//! encoding `grovedb` uses to serialize them.
+
+use alloc::vec::Vec;
+
+use crate::{encoding::Reader, Error};
+
+/// Flags stored alongside an element
+pub type ElementFlags = Vec<u8>;
+
+/// Maximum amount of hops a reference may follow
+pub type MaxReferenceHop = Option<u8>;
+
+/// Sum value
+pub type SumValue = i64;
+
+/// Reference path variants
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ReferencePathType {
+    /// Holds the absolute path to the element the reference points to
+    AbsolutePathReference(Vec<Vec<u8>>),
+    /// Takes the first n elements from the current path and appends a new path
+    UpstreamRootHeightReference(u8, Vec<Vec<u8>>),
+    /// Discards the last n elements from the current path and appends a new
+    /// path
+    UpstreamFromElementHeightReference(u8, Vec<Vec<u8>>),
+    /// Swaps the immediate parent of the stored path with a provided key
+    CousinReference(Vec<u8>),
+    /// Swaps the immediate parent of the stored path with a path
+    RemovedCousinReference(Vec<Vec<u8>>),
+    /// Swaps the key with a new value
+    SiblingReference(Vec<u8>),
+}
+
+/// Variants of GroveDB stored entities
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Element {
+    /// An ordinary value
+    Item(Vec<u8>, Option<ElementFlags>),
+    /// A reference to an object by its path
+    Reference(ReferencePathType, MaxReferenceHop, Option<ElementFlags>),
+    /// A subtree, contains the root key of the subtree
+    Tree(Option<Vec<u8>>, Option<ElementFlags>),
+    /// Signed integer value that can be totaled in a sum tree
+    SumItem(SumValue, Option<ElementFlags>),
+    /// Same as `Element::Tree` but the subtree sums the values of its
+    /// summable nodes
+    SumTree(Option<Vec<u8>>, SumValue, Option<ElementFlags>),
+}
+
+impl Element {
+    /// Deserializes an element from the bytes stored in GroveDB
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(bytes);
+        let element = match reader.read_bincode_varint()? {
+            0 => Element::Item(reader.read_bincode_bytes()?, read_flags(&mut reader)?),
+            1 => {
+                let reference_path = read_reference_path(&mut reader)?;
+                let max_hop = if reader.read_bincode_option_tag()? {
+                    Some(reader.read_u8()?)
+                } else {
+                    None
+                };
+                Element::Reference(reference_path, max_hop, read_flags(&mut reader)?)
+            }
+            2 => Element::Tree(read_optional_bytes(&mut reader)?, read_flags(&mut reader)?),
+            3 => Element::SumItem(
+                reader.read_bincode_signed_varint()?,
+                read_flags(&mut reader)?,
+            ),
+            4 => Element::SumTree(
+                read_optional_bytes(&mut reader)?,
+                reader.read_bincode_signed_varint()?,
+                read_flags(&mut reader)?,
+            ),
+            _ => return Err(Error::CorruptedData("unknown element type")),
+        };
+        if !reader.is_empty() {
+            return Err(Error::CorruptedData("trailing bytes after element"));
+        }
+        Ok(element)
+    }
+
+    /// Is the element a tree or a sum tree
+    pub const fn is_tree(&self) -> bool {
+        matches!(self, Element::Tree(..) | Element::SumTree(..))
+    }
+}
+
+fn read_optional_bytes(reader: &mut Reader) -> Result<Option<Vec<u8>>, Error> {
+    if reader.read_bincode_option_tag()? {
+        reader.read_bincode_bytes().map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+fn read_flags(reader: &mut Reader) -> Result<Option<ElementFlags>, Error> {
+    read_optional_bytes(reader)
+}
+
+fn read_path(reader: &mut Reader) -> Result<Vec<Vec<u8>>, Error> {
+    let len = reader.read_bincode_varint()?;
+    let mut path = Vec::new();
+    for _ in 0..len {
+        path.push(reader.read_bincode_bytes()?);
+    }
+    Ok(path)
+}
+
+fn read_reference_path(reader: &mut Reader) -> Result<ReferencePathType, Error> {
+    Ok(match reader.read_bincode_varint()? {
+        0 => ReferencePathType::AbsolutePathReference(read_path(reader)?),
+        1 => ReferencePathType::UpstreamRootHeightReference(reader.read_u8()?, read_path(reader)?),
+        2 => ReferencePathType::UpstreamFromElementHeightReference(
+            reader.read_u8()?,
+            read_path(reader)?,
+        ),
+        3 => ReferencePathType::CousinReference(reader.read_bincode_bytes()?),
+        4 => ReferencePathType::RemovedCousinReference(read_path(reader)?),
+        5 => ReferencePathType::SiblingReference(reader.read_bincode_bytes()?),
+        _ => return Err(Error::CorruptedData("unknown reference path type")),
+    })
+}
@@ -0,0 +1,180 @@
+// MIT LICENSE
+//
+// Copyright (c) 2023 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Byte readers for the encodings found in GroveDB proofs
+
+use alloc::vec::Vec;
+
+use crate::{
+    hash::{CryptoHash, HASH_LENGTH},
+    Error,
+};
+
+/// Reads values from the front of a byte slice, consuming them
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) const fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    /// Bytes that have not been read yet
+    pub(crate) const fn remaining(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    pub(crate) const fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8, Error> {
+        let (byte, rest) = self
+            .bytes
+            .split_first()
+            .ok_or(Error::CorruptedData("unexpected end of data"))?;
+        self.bytes = rest;
+        Ok(*byte)
+    }
+
+    pub(crate) fn read_slice(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.bytes.len() < len {
+            return Err(Error::CorruptedData("unexpected end of data"));
+        }
+        let (slice, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(slice)
+    }
+
+    pub(crate) fn read_vec(&mut self, len: usize) -> Result<Vec<u8>, Error> {
+        self.read_slice(len).map(|slice| slice.to_vec())
+    }
+
+    pub(crate) fn read_hash(&mut self) -> Result<CryptoHash, Error> {
+        let mut hash = CryptoHash::default();
+        hash.copy_from_slice(self.read_slice(HASH_LENGTH)?);
+        Ok(hash)
+    }
+
+    pub(crate) fn read_u16_be(&mut self) -> Result<u16, Error> {
+        let bytes = self.read_slice(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Reads an unsigned LEB128 varint, as written by `integer-encoding`
+    pub(crate) fn read_varint(&mut self) -> Result<u64, Error> {
+        let mut result = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_u8()?;
+            if shift == 63 && byte > 1 {
+                return Err(Error::CorruptedData("varint overflow"));
+            }
+            result |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+            shift += 7;
+            if shift > 63 {
+                return Err(Error::CorruptedData("varint overflow"));
+            }
+        }
+    }
+
+    /// Reads an unsigned LEB128 varint that has to fit into a `usize`
+    pub(crate) fn read_varint_usize(&mut self) -> Result<usize, Error> {
+        usize::try_from(self.read_varint()?)
+            .map_err(|_| Error::CorruptedData("length does not fit into usize"))
+    }
+
+    /// Reads a zigzag encoded signed LEB128 varint
+    pub(crate) fn read_signed_varint(&mut self) -> Result<i64, Error> {
+        Ok(zigzag_decode(self.read_varint()?))
+    }
+
+    /// Reads an unsigned integer in bincode's varint encoding
+    pub(crate) fn read_bincode_varint(&mut self) -> Result<u64, Error> {
+        match self.read_u8()? {
+            byte @ 0..=250 => Ok(u64::from(byte)),
+            251 => {
+                let bytes = self.read_slice(2)?;
+                Ok(u64::from(u16::from_le_bytes([bytes[0], bytes[1]])))
+            }
+            252 => {
+                let mut bytes = [0; 4];
+                bytes.copy_from_slice(self.read_slice(4)?);
+                Ok(u64::from(u32::from_le_bytes(bytes)))
+            }
+            253 => {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(self.read_slice(8)?);
+                Ok(u64::from_le_bytes(bytes))
+            }
+            _ => Err(Error::CorruptedData("unsupported bincode varint")),
+        }
+    }
+
+    /// Reads a signed integer in bincode's zigzag varint encoding
+    pub(crate) fn read_bincode_signed_varint(&mut self) -> Result<i64, Error> {
+        Ok(zigzag_decode(self.read_bincode_varint()?))
+    }
+
+    /// Reads a bincode length prefixed byte vector
+    pub(crate) fn read_bincode_bytes(&mut self) -> Result<Vec<u8>, Error> {
+        let len = usize::try_from(self.read_bincode_varint()?)
+            .map_err(|_| Error::CorruptedData("length does not fit into usize"))?;
+        self.read_vec(len)
+    }
+
+    /// Reads a bincode option tag
+    pub(crate) fn read_bincode_option_tag(&mut self) -> Result<bool, Error> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::CorruptedData("invalid option tag")),
+        }
+    }
+}
+
+const fn zigzag_decode(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+/// Writes `value` as an unsigned LEB128 varint into `buf`, returning the used
+/// part of the buffer
+pub(crate) fn encode_varint(mut value: u64, buf: &mut [u8; 10]) -> &[u8] {
+    let mut i = 0;
+    while value >= 0x80 {
+        buf[i] = (value as u8) | 0x80;
+        value >>= 7;
+        i += 1;
+    }
+    buf[i] = value as u8;
+    &buf[..=i]
+}
@@ -0,0 +1,62 @@
+// MIT LICENSE
+//
+// Copyright (c) 2023 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Errors
+
+use core::fmt;
+
+/// Errors returned while verifying a proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The proof is malformed or does not prove the query
+    InvalidProof(&'static str),
+    /// Data inside the proof could not be decoded
+    CorruptedData(&'static str),
+    /// Invalid input
+    InvalidInput(&'static str),
+    /// The operation is not supported for the given query
+    NotSupported(&'static str),
+    /// More terminal keys than allowed would be generated
+    RequestAmountExceeded(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidProof(e) => write!(f, "invalid proof: {e}"),
+            Error::CorruptedData(e) => write!(f, "data corruption error: {e}"),
+            Error::InvalidInput(e) => write!(f, "invalid input: {e}"),
+            Error::NotSupported(e) => write!(f, "not supported: {e}"),
+            Error::RequestAmountExceeded(max) => {
+                write!(f, "terminal keys limit exceeded, set max is {max}")
+            }
+        }
+    }
+}
+
+impl core::error::Error for Error {}
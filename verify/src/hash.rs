@@ -0,0 +1,90 @@
+// MIT LICENSE
+//
+// Copyright (c) 2023 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Merk hash functions
+
+use crate::encoding::encode_varint;
+
+/// The length of a `Hash` (in bytes).
+pub const HASH_LENGTH: usize = 32;
+
+/// A zero-filled `Hash`.
+pub const NULL_HASH: CryptoHash = [0; HASH_LENGTH];
+
+/// A cryptographic hash digest.
+pub type CryptoHash = [u8; HASH_LENGTH];
+
+fn update_with_length_prefix(hasher: &mut blake3::Hasher, bytes: &[u8]) {
+    let mut buf = [0; 10];
+    hasher.update(encode_varint(bytes.len() as u64, &mut buf));
+    hasher.update(bytes);
+}
+
+fn finalize(hasher: blake3::Hasher) -> CryptoHash {
+    *hasher.finalize().as_bytes()
+}
+
+/// Hashes a value
+pub fn value_hash(value: &[u8]) -> CryptoHash {
+    let mut hasher = blake3::Hasher::new();
+    update_with_length_prefix(&mut hasher, value);
+    finalize(hasher)
+}
+
+/// Hashes a key/value pair.
+///
+/// The result is Hash(key_len, key, Hash(value_len, value))
+pub fn kv_hash(key: &[u8], value: &[u8]) -> CryptoHash {
+    kv_digest_to_kv_hash(key, &value_hash(value))
+}
+
+/// Computes the kv hash given a kv digest
+pub fn kv_digest_to_kv_hash(key: &[u8], value_hash: &CryptoHash) -> CryptoHash {
+    let mut hasher = blake3::Hasher::new();
+    update_with_length_prefix(&mut hasher, key);
+    hasher.update(value_hash);
+    finalize(hasher)
+}
+
+/// Hashes a node based on the hash of its key/value pair, the hash of its left
+/// child (if any), and the hash of its right child (if any).
+pub fn node_hash(kv: &CryptoHash, left: &CryptoHash, right: &CryptoHash) -> CryptoHash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(kv);
+    hasher.update(left);
+    hasher.update(right);
+    finalize(hasher)
+}
+
+/// Combines two hash values into one
+pub fn combine_hash(hash_one: &CryptoHash, hash_two: &CryptoHash) -> CryptoHash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(hash_one);
+    hasher.update(hash_two);
+    finalize(hasher)
+}
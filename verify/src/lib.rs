@@ -0,0 +1,61 @@
+// MIT LICENSE
+//
+// Copyright (c) 2023 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! GroveDB proof verifier for `no_std` environments.
+//!
+//! Checks proofs generated by `grovedb` without depending on `std`, only on
+//! `alloc`, so that they can be verified in the browser through
+//! `wasm32-unknown-unknown` or on bare-metal devices.
+//!
+//! Queries are expressed with the [`PathQuery`], [`SizedQuery`] and [`Query`]
+//! types of this crate, which mirror the ones of `grovedb`. A proof verifies
+//! against a query that was built the same way as the one it was generated
+//! for.
+
+#![cfg_attr(not(test), no_std)]
+#![deny(missing_docs)]
+
+extern crate alloc;
+
+mod element;
+mod encoding;
+mod error;
+pub mod hash;
+mod merk;
+pub mod query;
+mod verify;
+
+pub use element::{Element, ElementFlags, MaxReferenceHop, ReferencePathType, SumValue};
+pub use error::Error;
+pub use hash::CryptoHash;
+pub use query::{PathQuery, Query, QueryItem, SizedQuery, SubqueryBranch};
+pub use verify::{
+    verify_query, verify_query_raw, verify_query_with_absence_proof, verify_subset_query,
+    verify_subset_query_raw, verify_subset_query_with_absence_proof, PathKeyOptionalElementTrio,
+    ProvedPathKeyValue,
+};
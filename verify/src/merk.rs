@@ -0,0 +1,449 @@
+// MIT LICENSE
+//
+// Copyright (c) 2023 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Merk proofs
+//!
+//! Decoding and execution of the proofs of a single Merk tree.
+
+use alloc::vec::Vec;
+
+use crate::{
+    encoding::Reader,
+    hash::{combine_hash, kv_digest_to_kv_hash, kv_hash, node_hash, value_hash, NULL_HASH},
+    query::Query,
+    CryptoHash, Error,
+};
+
+/// A selected piece of data about a single tree node
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Node {
+    Hash(CryptoHash),
+    KVHash(CryptoHash),
+    KVDigest(Vec<u8>, CryptoHash),
+    KV(Vec<u8>, Vec<u8>),
+    KVValueHash(Vec<u8>, Vec<u8>, CryptoHash),
+    KVValueHashFeatureType(Vec<u8>, Vec<u8>, CryptoHash),
+    KVRefValueHash(Vec<u8>, Vec<u8>, CryptoHash),
+}
+
+impl Node {
+    /// Key of nodes that carry one
+    fn key(&self) -> Option<&[u8]> {
+        match self {
+            Node::KV(key, _)
+            | Node::KVValueHashFeatureType(key, ..)
+            | Node::KVRefValueHash(key, ..) => Some(key),
+            _ => None,
+        }
+    }
+
+    fn kv_hash(&self) -> Option<CryptoHash> {
+        match self {
+            Node::Hash(_) => None,
+            Node::KVHash(kv_hash) => Some(*kv_hash),
+            Node::KV(key, value) => Some(kv_hash(key, value)),
+            Node::KVValueHash(key, _, value_hash)
+            | Node::KVValueHashFeatureType(key, _, value_hash)
+            | Node::KVDigest(key, value_hash) => Some(kv_digest_to_kv_hash(key, value_hash)),
+            Node::KVRefValueHash(key, referenced_value, node_value_hash) => {
+                let combined_value_hash =
+                    combine_hash(node_value_hash, &value_hash(referenced_value));
+                Some(kv_digest_to_kv_hash(key, &combined_value_hash))
+            }
+        }
+    }
+}
+
+/// A proof operator
+enum Op {
+    Push(Node),
+    PushInverted(Node),
+    Parent,
+    Child,
+    ParentInverted,
+    ChildInverted,
+}
+
+fn decode_op(reader: &mut Reader) -> Result<Op, Error> {
+    let variant = reader.read_u8()?;
+    let node = match variant {
+        0x10 => return Ok(Op::Parent),
+        0x11 => return Ok(Op::Child),
+        0x12 => return Ok(Op::ParentInverted),
+        0x13 => return Ok(Op::ChildInverted),
+        0x01 | 0x08 => Node::Hash(reader.read_hash()?),
+        0x02 | 0x09 => Node::KVHash(reader.read_hash()?),
+        0x05 | 0x0c => {
+            let key_len = reader.read_u8()?;
+            Node::KVDigest(reader.read_vec(key_len.into())?, reader.read_hash()?)
+        }
+        0x03 | 0x04 | 0x06 | 0x07 | 0x0a | 0x0b | 0x0d | 0x0e => {
+            let key_len = reader.read_u8()?;
+            let key = reader.read_vec(key_len.into())?;
+            let value_len = reader.read_u16_be()?;
+            let value = reader.read_vec(value_len.into())?;
+            match variant {
+                0x03 | 0x0a => Node::KV(key, value),
+                0x04 | 0x0b => Node::KVValueHash(key, value, reader.read_hash()?),
+                0x06 | 0x0d => Node::KVRefValueHash(key, value, reader.read_hash()?),
+                _ => {
+                    let value_hash = reader.read_hash()?;
+                    // the feature type does not contribute to the node hash
+                    match reader.read_u8()? {
+                        0 => {}
+                        1 => {
+                            reader.read_signed_varint()?;
+                        }
+                        _ => return Err(Error::CorruptedData("unknown tree feature type")),
+                    }
+                    Node::KVValueHashFeatureType(key, value, value_hash)
+                }
+            }
+        }
+        _ => return Err(Error::CorruptedData("unknown proof op")),
+    };
+    if variant < 0x08 {
+        Ok(Op::Push(node))
+    } else {
+        Ok(Op::PushInverted(node))
+    }
+}
+
+/// Tree built while executing a proof, children are collapsed into hashes
+enum ProofTree {
+    Hash(CryptoHash),
+    Node {
+        kv_hash: CryptoHash,
+        left: Option<CryptoHash>,
+        right: Option<CryptoHash>,
+    },
+}
+
+impl ProofTree {
+    fn new(node: &Node) -> Self {
+        match node.kv_hash() {
+            Some(kv_hash) => ProofTree::Node {
+                kv_hash,
+                left: None,
+                right: None,
+            },
+            None => match node {
+                Node::Hash(hash) => ProofTree::Hash(*hash),
+                _ => unreachable!("only hash nodes have no kv hash"),
+            },
+        }
+    }
+
+    fn hash(&self) -> CryptoHash {
+        match self {
+            ProofTree::Hash(hash) => *hash,
+            ProofTree::Node {
+                kv_hash,
+                left,
+                right,
+            } => node_hash(
+                kv_hash,
+                &left.unwrap_or(NULL_HASH),
+                &right.unwrap_or(NULL_HASH),
+            ),
+        }
+    }
+
+    fn attach(&mut self, left: bool, child: ProofTree) -> Result<(), Error> {
+        let slot = match self {
+            ProofTree::Hash(_) => {
+                return Err(Error::InvalidProof(
+                    "tried to attach a child to a hash node",
+                ))
+            }
+            ProofTree::Node { left: slot, .. } if left => slot,
+            ProofTree::Node { right: slot, .. } => slot,
+        };
+        if slot.is_some() {
+            return Err(Error::InvalidProof(
+                "tried to attach a child to an occupied slot",
+            ));
+        }
+        *slot = Some(child.hash());
+        Ok(())
+    }
+}
+
+/// Executes the proof ops, calling `visit_node` for every pushed node, and
+/// returns the root hash
+fn execute<F>(bytes: &[u8], mut visit_node: F) -> Result<CryptoHash, Error>
+where
+    F: FnMut(&Node) -> Result<(), Error>,
+{
+    let mut reader = Reader::new(bytes);
+    let mut stack: Vec<ProofTree> = Vec::with_capacity(32);
+    let mut maybe_last_key: Option<Vec<u8>> = None;
+
+    fn try_pop(stack: &mut Vec<ProofTree>) -> Result<ProofTree, Error> {
+        stack.pop().ok_or(Error::InvalidProof("stack underflow"))
+    }
+
+    while !reader.is_empty() {
+        match decode_op(&mut reader)? {
+            Op::Parent => {
+                let (mut parent, child) = (try_pop(&mut stack)?, try_pop(&mut stack)?);
+                parent.attach(true, child)?;
+                stack.push(parent);
+            }
+            Op::Child => {
+                let (child, mut parent) = (try_pop(&mut stack)?, try_pop(&mut stack)?);
+                parent.attach(false, child)?;
+                stack.push(parent);
+            }
+            Op::ParentInverted => {
+                let (mut parent, child) = (try_pop(&mut stack)?, try_pop(&mut stack)?);
+                parent.attach(false, child)?;
+                stack.push(parent);
+            }
+            Op::ChildInverted => {
+                let (child, mut parent) = (try_pop(&mut stack)?, try_pop(&mut stack)?);
+                parent.attach(true, child)?;
+                stack.push(parent);
+            }
+            Op::Push(node) => {
+                if let (Some(key), Some(last_key)) = (node.key(), &maybe_last_key) {
+                    if key <= last_key.as_slice() {
+                        return Err(Error::InvalidProof("incorrect key ordering"));
+                    }
+                }
+                if let Some(key) = node.key() {
+                    maybe_last_key = Some(key.to_vec());
+                }
+                visit_node(&node)?;
+                stack.push(ProofTree::new(&node));
+            }
+            Op::PushInverted(node) => {
+                if let (Some(key), Some(last_key)) = (node.key(), &maybe_last_key) {
+                    if key >= last_key.as_slice() {
+                        return Err(Error::InvalidProof("incorrect key ordering inverted"));
+                    }
+                }
+                if let Some(key) = node.key() {
+                    maybe_last_key = Some(key.to_vec());
+                }
+                visit_node(&node)?;
+                stack.push(ProofTree::new(&node));
+            }
+        }
+    }
+
+    if stack.len() != 1 {
+        return Err(Error::InvalidProof(
+            "expected proof to result in exactly one stack item",
+        ));
+    }
+
+    Ok(stack.pop().expect("stack has one item").hash())
+}
+
+/// Proved key-value
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ProvedKeyValue {
+    /// Key
+    pub key: Vec<u8>,
+    /// Value
+    pub value: Vec<u8>,
+    /// Value hash
+    pub proof: CryptoHash,
+}
+
+/// Result of executing a Merk proof against a query
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ProofVerificationResult {
+    pub result_set: Vec<ProvedKeyValue>,
+    pub limit: Option<u16>,
+    pub offset: Option<u16>,
+}
+
+/// Is the node one that proves the keys around it
+const fn is_kv_node(node: Option<&Node>) -> bool {
+    matches!(
+        node,
+        Some(Node::KV(..))
+            | Some(Node::KVDigest(..))
+            | Some(Node::KVRefValueHash(..))
+            | Some(Node::KVValueHash(..))
+    )
+}
+
+/// Executes the encoded proof with the given query, checking that every
+/// queried key is either proven or proven to be absent. Returns the root hash
+/// of the Merk together with the proven values and the limit and offset left
+/// over.
+pub(crate) fn execute_proof(
+    bytes: &[u8],
+    query: &Query,
+    limit: Option<u16>,
+    offset: Option<u16>,
+    left_to_right: bool,
+) -> Result<(CryptoHash, ProofVerificationResult), Error> {
+    let mut output = Vec::with_capacity(query.items.len());
+    let mut last_push: Option<Node> = None;
+    let mut query_items: Vec<_> = if left_to_right {
+        query.items.iter().collect()
+    } else {
+        query.items.iter().rev().collect()
+    };
+    query_items.reverse();
+    let mut in_range = false;
+    let mut current_limit = limit;
+    let mut current_offset = offset;
+
+    let root_hash = execute(bytes, |node| {
+        let mut execute_node = |key: &Vec<u8>,
+                                value: Option<&Vec<u8>>,
+                                value_hash: CryptoHash|
+         -> Result<_, Error> {
+            while let Some(query_item) = query_items.last().copied() {
+                let (lower_bound, start_non_inclusive) = query_item.lower_bound();
+                let (upper_bound, end_inclusive) = query_item.upper_bound();
+
+                // terminate if we encounter a node before the current query item
+                let terminate = if left_to_right {
+                    !query_item.lower_unbounded()
+                        && (lower_bound > Some(key.as_slice())
+                            || (start_non_inclusive && lower_bound == Some(key.as_slice())))
+                } else {
+                    !query_item.upper_unbounded()
+                        && (upper_bound < Some(key.as_slice())
+                            || (!end_inclusive && upper_bound == Some(key.as_slice())))
+                };
+                if terminate {
+                    break;
+                }
+
+                if !in_range {
+                    // this is the first data we have encountered for this query item, the
+                    // bound has to be proven by an exact match, by being the first node, or
+                    // by a preceding node that is not abridged
+                    let bound = if left_to_right {
+                        lower_bound
+                    } else {
+                        upper_bound
+                    };
+                    if Some(key.as_slice()) != bound
+                        && last_push.is_some()
+                        && !is_kv_node(last_push.as_ref())
+                    {
+                        return Err(Error::InvalidProof(if left_to_right {
+                            "cannot verify lower bound of queried range"
+                        } else {
+                            "cannot verify upper bound of queried range"
+                        }));
+                    }
+                }
+
+                let reached_end = if left_to_right {
+                    upper_bound.is_some() && Some(key.as_slice()) >= upper_bound
+                } else {
+                    lower_bound.is_some() && Some(key.as_slice()) <= lower_bound
+                };
+                if reached_end {
+                    // at or past the end of the item, advance to the next one
+                    query_items.pop();
+                    in_range = false;
+                } else {
+                    // more values are expected to be proven in the range
+                    in_range = true;
+                }
+
+                if query_item.contains(key) {
+                    // nodes covered by the offset are pushed as digests
+                    if let Some(offset) = current_offset {
+                        if offset > 0 && value.is_none() {
+                            current_offset = Some(offset - 1);
+                            break;
+                        } else if offset > 0 && value.is_some() {
+                            return Err(Error::InvalidProof(
+                                "proof returns data before offset is exhausted",
+                            ));
+                        }
+                    }
+
+                    let value =
+                        value.ok_or(Error::InvalidProof("proof is missing data for query"))?;
+                    if let Some(limit) = current_limit {
+                        if limit == 0 {
+                            return Err(Error::InvalidProof("proof returns more data than limit"));
+                        }
+                        current_limit = Some(limit - 1);
+                        if current_limit == Some(0) {
+                            in_range = false;
+                        }
+                    }
+                    output.push(ProvedKeyValue {
+                        key: key.clone(),
+                        value: value.clone(),
+                        proof: value_hash,
+                    });
+                    break;
+                }
+            }
+            Ok(())
+        };
+
+        match node {
+            Node::KV(key, value) => execute_node(key, Some(value), value_hash(value))?,
+            Node::KVValueHash(key, value, value_hash)
+            | Node::KVRefValueHash(key, value, value_hash) => {
+                execute_node(key, Some(value), *value_hash)?
+            }
+            Node::KVDigest(key, value_hash) => execute_node(key, None, *value_hash)?,
+            _ if in_range => {
+                // the proof is abridged inside of a queried range
+                return Err(Error::InvalidProof(
+                    "proof is missing data for query for range",
+                ));
+            }
+            _ => {}
+        }
+
+        last_push = Some(node.clone());
+
+        Ok(())
+    })?;
+
+    // remaining query items have to be proven absent by the edge of the tree
+    if !query_items.is_empty() && current_limit != Some(0) && !is_kv_node(last_push.as_ref()) {
+        return Err(Error::InvalidProof("proof is missing data for query"));
+    }
+
+    Ok((
+        root_hash,
+        ProofVerificationResult {
+            result_set: output,
+            limit: current_limit,
+            offset: current_offset,
+        },
+    ))
+}
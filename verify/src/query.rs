@@ -0,0 +1,663 @@
+// MIT LICENSE
+//
+// Copyright (c) 2023 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Queries
+//!
+//! These mirror the query types of `grovedb` and `grovedb-merk`. A query has
+//! to be built the same way as the one the proof was generated for, items are
+//! kept sorted and merged exactly like the full implementation does.
+
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::{
+    cmp::{max, min, Ordering},
+    ops::{Range, RangeFrom, RangeFull, RangeInclusive, RangeTo, RangeToInclusive},
+};
+
+use crate::Error;
+
+/// Key
+pub type Key = Vec<u8>;
+/// Path
+pub type Path = Vec<Vec<u8>>;
+/// Path and key
+pub type PathKey = (Path, Key);
+
+/// A `QueryItem` represents a key or range of keys to be included in a proof.
+#[derive(Clone, Debug)]
+pub enum QueryItem {
+    /// A single key
+    Key(Vec<u8>),
+    /// `start..end`
+    Range(Range<Vec<u8>>),
+    /// `start..=end`
+    RangeInclusive(RangeInclusive<Vec<u8>>),
+    /// `..`
+    RangeFull(RangeFull),
+    /// `start..`
+    RangeFrom(RangeFrom<Vec<u8>>),
+    /// `..end`
+    RangeTo(RangeTo<Vec<u8>>),
+    /// `..=end`
+    RangeToInclusive(RangeToInclusive<Vec<u8>>),
+    /// Everything after `start`, `start` excluded
+    RangeAfter(RangeFrom<Vec<u8>>),
+    /// Everything after `start` until `end`, both excluded
+    RangeAfterTo(Range<Vec<u8>>),
+    /// Everything after `start` until `end`, `start` excluded
+    RangeAfterToInclusive(RangeInclusive<Vec<u8>>),
+}
+
+/// Boundary of a query item, used to order and merge items
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum RangeSetItem<'a> {
+    UnboundedStart,
+    UnboundedEnd,
+    Inclusive(&'a [u8]),
+    ExclusiveStart(&'a [u8]),
+    ExclusiveEnd(&'a [u8]),
+}
+
+impl PartialOrd for RangeSetItem<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RangeSetItem<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        use RangeSetItem::*;
+        match (self, other) {
+            (UnboundedStart, UnboundedStart) => Ordering::Equal,
+            (UnboundedEnd, UnboundedEnd) => Ordering::Equal,
+            (UnboundedStart, _) => Ordering::Less,
+            (_, UnboundedStart) => Ordering::Greater,
+            (UnboundedEnd, _) => Ordering::Greater,
+            (_, UnboundedEnd) => Ordering::Less,
+
+            (Inclusive(v1), Inclusive(v2))
+            | (ExclusiveStart(v1), ExclusiveStart(v2))
+            | (ExclusiveEnd(v1), ExclusiveEnd(v2)) => v1.cmp(v2),
+
+            (Inclusive(v1), ExclusiveStart(v2)) | (ExclusiveEnd(v1), Inclusive(v2)) => {
+                match v1.cmp(v2) {
+                    Ordering::Equal | Ordering::Less => Ordering::Less,
+                    _ => Ordering::Greater,
+                }
+            }
+            (Inclusive(v1), ExclusiveEnd(v2)) | (ExclusiveStart(v1), Inclusive(v2)) => {
+                match v1.cmp(v2) {
+                    Ordering::Less => Ordering::Less,
+                    _ => Ordering::Greater,
+                }
+            }
+            (ExclusiveStart(v1), ExclusiveEnd(v2)) | (ExclusiveEnd(v2), ExclusiveStart(v1)) => {
+                // an exclusive end stops just before its value, so it is the
+                // smaller one when both values are equal
+                match v1.cmp(v2) {
+                    Ordering::Equal | Ordering::Greater => Ordering::Greater,
+                    _ => Ordering::Less,
+                }
+            }
+        }
+    }
+}
+
+impl QueryItem {
+    /// Lower bound of the item and whether it is excluded
+    pub fn lower_bound(&self) -> (Option<&[u8]>, bool) {
+        match self {
+            QueryItem::Key(key) => (Some(key.as_slice()), false),
+            QueryItem::Range(range) => (Some(range.start.as_ref()), false),
+            QueryItem::RangeInclusive(range) => (Some(range.start().as_ref()), false),
+            QueryItem::RangeFull(_) => (None, false),
+            QueryItem::RangeFrom(range) => (Some(range.start.as_ref()), false),
+            QueryItem::RangeTo(_) => (None, false),
+            QueryItem::RangeToInclusive(_) => (None, false),
+            QueryItem::RangeAfter(range) => (Some(range.start.as_ref()), true),
+            QueryItem::RangeAfterTo(range) => (Some(range.start.as_ref()), true),
+            QueryItem::RangeAfterToInclusive(range) => (Some(range.start().as_ref()), true),
+        }
+    }
+
+    /// Is the item unbounded to the left
+    pub const fn lower_unbounded(&self) -> bool {
+        matches!(
+            self,
+            QueryItem::RangeFull(_) | QueryItem::RangeTo(_) | QueryItem::RangeToInclusive(_)
+        )
+    }
+
+    /// Upper bound of the item and whether it is included
+    pub fn upper_bound(&self) -> (Option<&[u8]>, bool) {
+        match self {
+            QueryItem::Key(key) => (Some(key.as_slice()), true),
+            QueryItem::Range(range) => (Some(range.end.as_ref()), false),
+            QueryItem::RangeInclusive(range) => (Some(range.end().as_ref()), true),
+            QueryItem::RangeFull(_) => (None, true),
+            QueryItem::RangeFrom(_) => (None, true),
+            QueryItem::RangeTo(range) => (Some(range.end.as_ref()), false),
+            QueryItem::RangeToInclusive(range) => (Some(range.end.as_ref()), true),
+            QueryItem::RangeAfter(_) => (None, true),
+            QueryItem::RangeAfterTo(range) => (Some(range.end.as_ref()), false),
+            QueryItem::RangeAfterToInclusive(range) => (Some(range.end().as_ref()), true),
+        }
+    }
+
+    /// Is the item unbounded to the right
+    pub const fn upper_unbounded(&self) -> bool {
+        matches!(
+            self,
+            QueryItem::RangeFull(_) | QueryItem::RangeFrom(_) | QueryItem::RangeAfter(_)
+        )
+    }
+
+    /// Does the item contain the key
+    pub fn contains(&self, key: &[u8]) -> bool {
+        let (lower_bound, lower_bound_non_inclusive) = self.lower_bound();
+        let (upper_bound, upper_bound_inclusive) = self.upper_bound();
+        (self.lower_unbounded()
+            || Some(key) > lower_bound
+            || (Some(key) == lower_bound && !lower_bound_non_inclusive))
+            && (self.upper_unbounded()
+                || Some(key) < upper_bound
+                || (Some(key) == upper_bound && upper_bound_inclusive))
+    }
+
+    /// Is the item a range that can not be expanded to distinct keys
+    pub const fn is_unbounded_range(&self) -> bool {
+        !matches!(
+            self,
+            QueryItem::Key(_) | QueryItem::Range(_) | QueryItem::RangeInclusive(_)
+        )
+    }
+
+    /// Distinct keys of the item, only available for keys and one byte ranges
+    pub fn keys(&self) -> Result<Vec<Vec<u8>>, Error> {
+        let (start, end, inclusive) = match self {
+            QueryItem::Key(key) => return Ok(vec![key.clone()]),
+            QueryItem::Range(Range { start, end }) => (start, end, false),
+            QueryItem::RangeInclusive(range) => (range.start(), range.end(), true),
+            _ => {
+                return Err(Error::NotSupported(
+                    "distinct keys are not available for unbounded ranges",
+                ))
+            }
+        };
+        if start.len() > 1 || end.len() != 1 {
+            return Err(Error::NotSupported(
+                "distinct keys are not available for ranges using more or less than 1 byte",
+            ));
+        }
+        let mut keys = vec![];
+        let start = match start.first() {
+            Some(start) => *start,
+            None => {
+                keys.push(vec![]);
+                0
+            }
+        };
+        let end = end[0];
+        if inclusive {
+            keys.extend((start..=end).map(|i| vec![i]));
+        } else {
+            keys.extend((start..end).map(|i| vec![i]));
+        }
+        Ok(keys)
+    }
+
+    fn range_set(&self) -> (RangeSetItem<'_>, RangeSetItem<'_>) {
+        let start = match self.lower_bound() {
+            (None, _) => RangeSetItem::UnboundedStart,
+            (Some(start), false) => RangeSetItem::Inclusive(start),
+            (Some(start), true) => RangeSetItem::ExclusiveStart(start),
+        };
+        let end = match self.upper_bound() {
+            (None, _) => RangeSetItem::UnboundedEnd,
+            (Some(end), true) => RangeSetItem::Inclusive(end),
+            (Some(end), false) => RangeSetItem::ExclusiveEnd(end),
+        };
+        (start, end)
+    }
+
+    /// Do the two items share at least one key
+    pub fn collides_with(&self, other: &Self) -> bool {
+        let (self_start, self_end) = self.range_set();
+        let (other_start, other_end) = other.range_set();
+        !(self_end < other_start || other_end < self_start)
+    }
+
+    fn merge(&self, other: &Self) -> Self {
+        let lower_unbounded = self.lower_unbounded() || other.lower_unbounded();
+        let upper_unbounded = self.upper_unbounded() || other.upper_unbounded();
+
+        let (start, start_non_inclusive) = min(self.lower_bound(), other.lower_bound());
+        let (end, end_inclusive) = max(self.upper_bound(), other.upper_bound());
+        let start = || start.expect("start should be bounded").to_vec();
+        let end = || end.expect("end should be bounded").to_vec();
+
+        if start_non_inclusive {
+            return if upper_unbounded {
+                Self::RangeAfter(start()..)
+            } else if end_inclusive {
+                Self::RangeAfterToInclusive(start()..=end())
+            } else {
+                Self::RangeAfterTo(start()..end())
+            };
+        }
+
+        if lower_unbounded {
+            return if upper_unbounded {
+                Self::RangeFull(..)
+            } else if end_inclusive {
+                Self::RangeToInclusive(..=end())
+            } else {
+                Self::RangeTo(..end())
+            };
+        }
+
+        if upper_unbounded {
+            Self::RangeFrom(start()..)
+        } else if end_inclusive {
+            Self::RangeInclusive(start()..=end())
+        } else {
+            Self::Range(start()..end())
+        }
+    }
+}
+
+impl PartialEq for QueryItem {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueryItem {}
+
+impl Ord for QueryItem {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (self_start, self_end) = self.range_set();
+        let (other_start, other_end) = other.range_set();
+        self_start
+            .cmp(&other_start)
+            .then_with(|| self_end.cmp(&other_end))
+    }
+}
+
+impl PartialOrd for QueryItem {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl From<Vec<u8>> for QueryItem {
+    fn from(key: Vec<u8>) -> Self {
+        Self::Key(key)
+    }
+}
+
+/// Subquery branch
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SubqueryBranch {
+    /// Subquery path
+    pub subquery_path: Option<Path>,
+    /// Subquery
+    pub subquery: Option<Box<Query>>,
+}
+
+/// `Query` represents one or more keys or ranges of keys, which can be used to
+/// resolve a proof which will include all of the requested values.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Query {
+    /// Items, sorted and without overlaps
+    pub items: Vec<QueryItem>,
+    /// Default subquery branch
+    pub default_subquery_branch: SubqueryBranch,
+    /// Conditional subquery branches, in the order they were added
+    pub conditional_subquery_branches: Option<Vec<(QueryItem, SubqueryBranch)>>,
+    /// Left to right?
+    pub left_to_right: bool,
+}
+
+impl Default for Query {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Query {
+    /// Creates a new query which contains no items.
+    pub fn new() -> Self {
+        Self::new_with_direction(true)
+    }
+
+    /// Creates a new query which contains no items with the specified
+    /// direction.
+    pub fn new_with_direction(left_to_right: bool) -> Self {
+        Self {
+            items: Vec::new(),
+            default_subquery_branch: SubqueryBranch::default(),
+            conditional_subquery_branches: None,
+            left_to_right,
+        }
+    }
+
+    /// Creates a new query which contains only one key.
+    pub fn new_single_key(key: Vec<u8>) -> Self {
+        Self::new_single_query_item(QueryItem::Key(key))
+    }
+
+    /// Creates a new query which contains only one item.
+    pub fn new_single_query_item(query_item: QueryItem) -> Self {
+        Self {
+            items: vec![query_item],
+            ..Self::new()
+        }
+    }
+
+    /// Adds an individual key to the query.
+    pub fn insert_key(&mut self, key: Vec<u8>) {
+        self.insert_item(QueryItem::Key(key));
+    }
+
+    /// Adds multiple individual keys to the query.
+    pub fn insert_keys(&mut self, keys: Vec<Vec<u8>>) {
+        for key in keys {
+            self.insert_key(key);
+        }
+    }
+
+    /// Adds a range to the query.
+    pub fn insert_range(&mut self, range: Range<Vec<u8>>) {
+        self.insert_item(QueryItem::Range(range));
+    }
+
+    /// Adds an inclusive range to the query.
+    pub fn insert_range_inclusive(&mut self, range: RangeInclusive<Vec<u8>>) {
+        self.insert_item(QueryItem::RangeInclusive(range));
+    }
+
+    /// Adds a range until a certain included value to the query.
+    pub fn insert_range_to_inclusive(&mut self, range: RangeToInclusive<Vec<u8>>) {
+        self.insert_item(QueryItem::RangeToInclusive(range));
+    }
+
+    /// Adds a range from a certain included value to the query.
+    pub fn insert_range_from(&mut self, range: RangeFrom<Vec<u8>>) {
+        self.insert_item(QueryItem::RangeFrom(range));
+    }
+
+    /// Adds a range until a certain non included value to the query.
+    pub fn insert_range_to(&mut self, range: RangeTo<Vec<u8>>) {
+        self.insert_item(QueryItem::RangeTo(range));
+    }
+
+    /// Adds a range after the first value to the query.
+    pub fn insert_range_after(&mut self, range: RangeFrom<Vec<u8>>) {
+        self.insert_item(QueryItem::RangeAfter(range));
+    }
+
+    /// Adds a range after the first value, until a certain non included value
+    /// to the query.
+    pub fn insert_range_after_to(&mut self, range: Range<Vec<u8>>) {
+        self.insert_item(QueryItem::RangeAfterTo(range));
+    }
+
+    /// Adds a range after the first value, until a certain included value to
+    /// the query.
+    pub fn insert_range_after_to_inclusive(&mut self, range: RangeInclusive<Vec<u8>>) {
+        self.insert_item(QueryItem::RangeAfterToInclusive(range));
+    }
+
+    /// Adds a range of all potential values to the query.
+    pub fn insert_all(&mut self) {
+        self.insert_item(QueryItem::RangeFull(RangeFull));
+    }
+
+    /// Adds the `QueryItem` to the query, merging it with every item it
+    /// collides with so that items never overlap.
+    pub fn insert_item(&mut self, mut item: QueryItem) {
+        self.items.retain(|our_item| {
+            if our_item.collides_with(&item) {
+                item = item.merge(our_item);
+                false
+            } else {
+                true
+            }
+        });
+        let pos = self.items.partition_point(|our_item| our_item < &item);
+        self.items.insert(pos, item);
+    }
+
+    /// Performs an insert_item on each item in the vector.
+    pub fn insert_items(&mut self, items: Vec<QueryItem>) {
+        for item in items {
+            self.insert_item(item)
+        }
+    }
+
+    /// Sets the subquery_path for the query with one key.
+    pub fn set_subquery_key(&mut self, key: Key) {
+        self.default_subquery_branch.subquery_path = Some(vec![key]);
+    }
+
+    /// Sets the subquery_path for the query.
+    pub fn set_subquery_path(&mut self, path: Path) {
+        self.default_subquery_branch.subquery_path = Some(path);
+    }
+
+    /// Sets the subquery for the query.
+    pub fn set_subquery(&mut self, subquery: Self) {
+        self.default_subquery_branch.subquery = Some(Box::new(subquery));
+    }
+
+    /// Adds a conditional subquery. If multiple conditional subquery items
+    /// match a key, the first one that was added is used. Adding an item that
+    /// is equal to an existing one replaces its branch.
+    pub fn add_conditional_subquery(
+        &mut self,
+        item: QueryItem,
+        subquery_path: Option<Path>,
+        subquery: Option<Self>,
+    ) {
+        let branch = SubqueryBranch {
+            subquery_path,
+            subquery: subquery.map(Box::new),
+        };
+        let branches = self
+            .conditional_subquery_branches
+            .get_or_insert_with(Vec::new);
+        match branches.iter_mut().find(|(existing, _)| existing == &item) {
+            Some((_, existing_branch)) => *existing_branch = branch,
+            None => branches.push((item, branch)),
+        }
+    }
+
+    /// Subquery path and subquery applying to the given key
+    pub(crate) fn subquery_branch_for_key(&self, key: &[u8]) -> &SubqueryBranch {
+        self.conditional_subquery_branches
+            .iter()
+            .flatten()
+            .find(|(query_item, _)| query_item.contains(key))
+            .map(|(_, branch)| branch)
+            .unwrap_or(&self.default_subquery_branch)
+    }
+
+    /// Pushes terminal key paths and keys to `result`, no more than
+    /// `max_results`. Returns the number of terminal keys added.
+    fn terminal_keys(
+        &self,
+        current_path: Path,
+        max_results: usize,
+        result: &mut Vec<PathKey>,
+    ) -> Result<usize, Error> {
+        let start_len = result.len();
+        let mut conditional_keys: Vec<Key> = vec![];
+        for (conditional_query_item, subquery_branch) in
+            self.conditional_subquery_branches.iter().flatten()
+        {
+            if conditional_query_item.is_unbounded_range() {
+                return Err(Error::NotSupported(
+                    "terminal keys are not supported with conditional unbounded ranges",
+                ));
+            }
+            for key in conditional_query_item.keys()? {
+                conditional_keys.push(key.clone());
+                Self::terminal_keys_for_branch(
+                    subquery_branch,
+                    &current_path,
+                    key,
+                    max_results,
+                    result,
+                )?;
+            }
+        }
+        for item in self.items.iter() {
+            if item.is_unbounded_range() {
+                return Err(Error::NotSupported(
+                    "terminal keys are not supported with unbounded ranges",
+                ));
+            }
+            for key in item.keys()? {
+                if conditional_keys.contains(&key) {
+                    continue;
+                }
+                Self::terminal_keys_for_branch(
+                    &self.default_subquery_branch,
+                    &current_path,
+                    key,
+                    max_results,
+                    result,
+                )?;
+            }
+        }
+        Ok(result.len() - start_len)
+    }
+
+    fn terminal_keys_for_branch(
+        subquery_branch: &SubqueryBranch,
+        current_path: &Path,
+        key: Key,
+        max_results: usize,
+        result: &mut Vec<PathKey>,
+    ) -> Result<(), Error> {
+        let mut path = current_path.clone();
+        match (&subquery_branch.subquery_path, &subquery_branch.subquery) {
+            (Some(subquery_path), Some(subquery)) => {
+                path.push(key);
+                path.extend(subquery_path.iter().cloned());
+                subquery.terminal_keys(path, max_results, result)?;
+            }
+            (Some(subquery_path), None) => {
+                let (last_key, front_keys) = subquery_path.split_last().ok_or(
+                    Error::InvalidInput("subquery_path set but doesn't contain any values"),
+                )?;
+                path.push(key);
+                path.extend(front_keys.iter().cloned());
+                Self::push_terminal_key(result, (path, last_key.clone()), max_results)?;
+            }
+            (None, Some(subquery)) => {
+                path.push(key);
+                subquery.terminal_keys(path, max_results, result)?;
+            }
+            (None, None) => {
+                Self::push_terminal_key(result, (path, key), max_results)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn push_terminal_key(
+        result: &mut Vec<PathKey>,
+        path_key: PathKey,
+        max_results: usize,
+    ) -> Result<(), Error> {
+        if result.len() >= max_results {
+            return Err(Error::RequestAmountExceeded(max_results));
+        }
+        result.push(path_key);
+        Ok(())
+    }
+}
+
+/// Holds a query to apply to a tree and an optional limit/offset value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SizedQuery {
+    /// Query
+    pub query: Query,
+    /// Limit
+    pub limit: Option<u16>,
+    /// Offset
+    pub offset: Option<u16>,
+}
+
+impl SizedQuery {
+    /// New sized query
+    pub const fn new(query: Query, limit: Option<u16>, offset: Option<u16>) -> Self {
+        Self {
+            query,
+            limit,
+            offset,
+        }
+    }
+}
+
+/// Path query
+///
+/// Represents a path to a specific GroveDB tree and a corresponding query to
+/// apply to the given tree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathQuery {
+    /// Path
+    pub path: Path,
+    /// Query
+    pub query: SizedQuery,
+}
+
+impl PathQuery {
+    /// New path query
+    pub const fn new(path: Path, query: SizedQuery) -> Self {
+        Self { path, query }
+    }
+
+    /// New path query with a query and no limit or offset
+    pub const fn new_unsized(path: Path, query: Query) -> Self {
+        Self {
+            path,
+            query: SizedQuery::new(query, None, None),
+        }
+    }
+
+    /// Gets the path of all terminal keys
+    pub fn terminal_keys(&self, max_results: usize) -> Result<Vec<PathKey>, Error> {
+        let mut result = vec![];
+        self.query
+            .query
+            .terminal_keys(self.path.clone(), max_results, &mut result)?;
+        Ok(result)
+    }
+}
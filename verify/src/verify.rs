@@ -0,0 +1,804 @@
+// MIT LICENSE
+//
+// Copyright (c) 2023 Dash Core Group
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! GroveDB proof verification
+
+use alloc::{borrow::Cow, collections::BTreeMap, vec, vec::Vec};
+
+use crate::{
+    element::Element,
+    encoding::Reader,
+    hash::{combine_hash, value_hash, CryptoHash},
+    merk::{self, ProvedKeyValue},
+    query::{Key, Path, PathKey, PathQuery, Query, SizedQuery},
+    Error,
+};
+
+/// Root hash of an empty tree
+const EMPTY_TREE_HASH: CryptoHash = [0; 32];
+
+/// Path, key and the element if it exists
+pub type PathKeyOptionalElementTrio = (Path, Key, Option<Element>);
+
+/// Proved path-key-value
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProvedPathKeyValue {
+    /// Path
+    pub path: Path,
+    /// Key
+    pub key: Key,
+    /// Serialized element
+    pub value: Vec<u8>,
+    /// Value hash
+    pub proof: CryptoHash,
+}
+
+impl ProvedPathKeyValue {
+    fn from_proved_key_value(path: Path, proved_key_value: ProvedKeyValue) -> Self {
+        Self {
+            path,
+            key: proved_key_value.key,
+            value: proved_key_value.value,
+            proof: proved_key_value.proof,
+        }
+    }
+
+    fn from_proved_key_values(path: Path, proved_key_values: Vec<ProvedKeyValue>) -> Vec<Self> {
+        proved_key_values
+            .into_iter()
+            .map(|pkv| Self::from_proved_key_value(path.clone(), pkv))
+            .collect()
+    }
+}
+
+impl TryFrom<ProvedPathKeyValue> for PathKeyOptionalElementTrio {
+    type Error = Error;
+
+    fn try_from(proved_path_key_value: ProvedPathKeyValue) -> Result<Self, Self::Error> {
+        let element = Element::deserialize(proved_path_key_value.value.as_slice())?;
+        Ok((
+            proved_path_key_value.path,
+            proved_path_key_value.key,
+            Some(element),
+        ))
+    }
+}
+
+/// Proof layer types, the byte values match the ones written by `grovedb`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProofTokenType {
+    Merk,
+    SizedMerk,
+    EmptyTree,
+    AbsentPath,
+}
+
+impl ProofTokenType {
+    const PATH_INFO: u8 = 0x06;
+
+    fn from_byte(byte: u8) -> Result<Self, Error> {
+        match byte {
+            0x01 => Ok(ProofTokenType::Merk),
+            0x02 => Ok(ProofTokenType::SizedMerk),
+            0x04 => Ok(ProofTokenType::EmptyTree),
+            0x05 => Ok(ProofTokenType::AbsentPath),
+            _ => Err(Error::InvalidProof("expected merk or sized merk proof")),
+        }
+    }
+}
+
+/// Reads the layers of a GroveDB proof
+struct ProofReader<'a> {
+    reader: Reader<'a>,
+    is_verbose: bool,
+}
+
+/// Type of a proof layer, its merk proof and the key of its subtree if verbose
+type ProofTokenInfo<'a> = (ProofTokenType, &'a [u8], Option<&'a [u8]>);
+
+impl<'a> ProofReader<'a> {
+    fn new(proof: &'a [u8], is_verbose: bool) -> Self {
+        Self {
+            reader: Reader::new(proof),
+            is_verbose,
+        }
+    }
+
+    /// Reads the next proof layer, verbose layers also carry the key of the
+    /// subtree they belong to
+    fn read_proof(&mut self) -> Result<ProofTokenInfo<'a>, Error> {
+        let proof_token_type = ProofTokenType::from_byte(self.reader.read_u8()?)?;
+        if matches!(
+            proof_token_type,
+            ProofTokenType::EmptyTree | ProofTokenType::AbsentPath
+        ) {
+            if self.is_verbose {
+                return Err(Error::InvalidProof(
+                    "key must exist for verbose merk proofs",
+                ));
+            }
+            return Ok((proof_token_type, &[], None));
+        }
+        let key = if self.is_verbose {
+            let key_length = self.reader.read_varint_usize()?;
+            Some(self.reader.read_slice(key_length)?)
+        } else {
+            None
+        };
+        let proof_length = self.reader.read_varint_usize()?;
+        let proof = self.reader.read_slice(proof_length)?;
+        Ok((proof_token_type, proof, key))
+    }
+
+    /// For non verbose proofs reads the immediate next layer, for verbose
+    /// proofs reads the first layer that belongs to the given key
+    fn read_next_proof(&mut self, key: &[u8]) -> Result<(ProofTokenType, &'a [u8]), Error> {
+        loop {
+            let (proof_token_type, proof, proof_key) = self.read_proof()?;
+            if !self.is_verbose || proof_key == Some(key) {
+                return Ok((proof_token_type, proof));
+            }
+        }
+    }
+
+    /// Reads the path the verbose proof was generated for
+    fn read_path_info(&mut self) -> Result<Path, Error> {
+        if self.reader.read_u8()? != ProofTokenType::PATH_INFO {
+            return Err(Error::InvalidProof("wrong data_type, expected path_info"));
+        }
+        let path_len = self.reader.read_varint_usize()?;
+        let mut path = vec![];
+        for _ in 0..path_len {
+            let key_len = self.reader.read_varint_usize()?;
+            path.push(self.reader.read_vec(key_len)?);
+        }
+        Ok(path)
+    }
+}
+
+/// Reduces the offset, or if there is no offset left the limit, by `n`.
+/// Returns true if the offset was reduced.
+fn reduce_limit_and_offset_by(limit: &mut Option<u16>, offset: &mut Option<u16>, n: u16) -> bool {
+    if let Some(offset_value) = *offset {
+        if offset_value > 0 {
+            *offset = Some(offset_value.saturating_sub(n));
+            return true;
+        }
+    }
+    if let Some(limit_value) = *limit {
+        *limit = Some(limit_value.saturating_sub(n));
+    }
+    false
+}
+
+/// Verify proof given a path query
+/// Returns the root hash + deserialized elements
+pub fn verify_query(
+    proof: &[u8],
+    query: &PathQuery,
+) -> Result<(CryptoHash, Vec<PathKeyOptionalElementTrio>), Error> {
+    let (root_hash, proved_path_key_values) = verify_query_raw(proof, query)?;
+    Ok((root_hash, into_elements(proved_path_key_values)?))
+}
+
+/// Verify proof for a given path query returns serialized elements
+pub fn verify_query_raw(
+    proof: &[u8],
+    query: &PathQuery,
+) -> Result<(CryptoHash, Vec<ProvedPathKeyValue>), Error> {
+    let mut verifier = ProofVerifier::new(query);
+    let hash = verifier.execute_proof(proof, query, false)?;
+    Ok((hash, verifier.result_set))
+}
+
+/// Given a verbose proof, we can verify it with a subset path query.
+/// Returning the root hash and the deserialized result set.
+pub fn verify_subset_query(
+    proof: &[u8],
+    query: &PathQuery,
+) -> Result<(CryptoHash, Vec<PathKeyOptionalElementTrio>), Error> {
+    let (root_hash, proved_path_key_values) = verify_subset_query_raw(proof, query)?;
+    Ok((root_hash, into_elements(proved_path_key_values)?))
+}
+
+/// Given a verbose proof, we can verify it with a subset path query.
+/// Returning the root hash and the serialized result set.
+pub fn verify_subset_query_raw(
+    proof: &[u8],
+    query: &PathQuery,
+) -> Result<(CryptoHash, Vec<ProvedPathKeyValue>), Error> {
+    let mut verifier = ProofVerifier::new(query);
+    let hash = verifier.execute_proof(proof, query, true)?;
+    Ok((hash, verifier.result_set))
+}
+
+/// Verify non subset query return the absence proof
+/// Returns all possible keys within the Path Query with an optional Element
+/// Value Element is set to None if absent
+pub fn verify_query_with_absence_proof(
+    proof: &[u8],
+    query: &PathQuery,
+) -> Result<(CryptoHash, Vec<PathKeyOptionalElementTrio>), Error> {
+    verify_with_absence_proof(proof, query, verify_query)
+}
+
+/// Verify subset query return the absence proof
+/// Returns all possible keys within the Path Query with an optional Element
+/// Value Element is set to None if absent
+pub fn verify_subset_query_with_absence_proof(
+    proof: &[u8],
+    query: &PathQuery,
+) -> Result<(CryptoHash, Vec<PathKeyOptionalElementTrio>), Error> {
+    verify_with_absence_proof(proof, query, verify_subset_query)
+}
+
+fn into_elements(
+    proved_path_key_values: Vec<ProvedPathKeyValue>,
+) -> Result<Vec<PathKeyOptionalElementTrio>, Error> {
+    proved_path_key_values
+        .into_iter()
+        .map(|pkv| pkv.try_into())
+        .collect()
+}
+
+/// Verifies the proof and returns both elements in the result set and the
+/// terminal keys of the query that are not in state.
+fn verify_with_absence_proof<T>(
+    proof: &[u8],
+    query: &PathQuery,
+    verification_fn: T,
+) -> Result<(CryptoHash, Vec<PathKeyOptionalElementTrio>), Error>
+where
+    T: Fn(&[u8], &PathQuery) -> Result<(CryptoHash, Vec<PathKeyOptionalElementTrio>), Error>,
+{
+    let max_results = query.query.limit.ok_or(Error::NotSupported(
+        "limits must be set in verify_query_with_absence_proof",
+    ))? as usize;
+
+    if query.query.offset.is_some() {
+        return Err(Error::NotSupported(
+            "offsets are not supported for verify_query_with_absence_proof",
+        ));
+    }
+
+    let terminal_keys = query.terminal_keys(max_results)?;
+
+    let (root_hash, result_set) = verification_fn(proof, query)?;
+
+    let mut result_set_as_map: BTreeMap<PathKey, Option<Element>> = result_set
+        .into_iter()
+        .map(|(path, key, element)| ((path, key), element))
+        .collect();
+
+    let result_set_with_absence = terminal_keys
+        .into_iter()
+        .map(|terminal_key| {
+            let element = result_set_as_map.remove(&terminal_key).flatten();
+            (terminal_key.0, terminal_key.1, element)
+        })
+        .collect();
+
+    Ok((root_hash, result_set_with_absence))
+}
+
+/// Proof verifier
+struct ProofVerifier {
+    limit: Option<u16>,
+    offset: Option<u16>,
+    result_set: Vec<ProvedPathKeyValue>,
+}
+
+impl ProofVerifier {
+    fn new(query: &PathQuery) -> Self {
+        ProofVerifier {
+            limit: query.query.limit,
+            offset: query.query.offset,
+            result_set: vec![],
+        }
+    }
+
+    fn execute_proof(
+        &mut self,
+        proof: &[u8],
+        query: &PathQuery,
+        is_verbose: bool,
+    ) -> Result<CryptoHash, Error> {
+        let mut reader = Reader::new(proof);
+        // the proof version is read but not interpreted yet
+        reader.read_varint()?;
+        let mut proof_reader = ProofReader::new(reader.remaining(), is_verbose);
+
+        let mut query = Cow::Borrowed(query);
+
+        // verbose proofs start with the path they were generated for, the
+        // subset query is rewritten to be relative to that path
+        if is_verbose {
+            let original_path = proof_reader.read_path_info()?;
+            if original_path != query.path {
+                query = Cow::Owned(Self::subset_query_from_original_path(
+                    original_path,
+                    &query,
+                )?);
+            }
+        }
+
+        let (proof_token_type, proof, _) = proof_reader.read_proof()?;
+
+        if proof_token_type == ProofTokenType::AbsentPath {
+            return self.verify_absent_path(&mut proof_reader, &query.path);
+        }
+
+        let mut last_subtree_root_hash = self.execute_subquery_proof(
+            proof_token_type,
+            proof,
+            &mut proof_reader,
+            query.as_ref(),
+            query.path.clone(),
+        )?;
+
+        self.verify_path_to_root(
+            query.as_ref(),
+            &query.path,
+            &mut proof_reader,
+            &mut last_subtree_root_hash,
+        )
+    }
+
+    fn subset_query_from_original_path(
+        original_path: Path,
+        query: &PathQuery,
+    ) -> Result<PathQuery, Error> {
+        if original_path.len() > query.path.len() {
+            return Err(Error::InvalidProof(
+                "original path query path must not be greater than the subset path len",
+            ));
+        }
+        if !original_path.iter().all(|key| query.path.contains(key)) {
+            return Err(Error::InvalidProof(
+                "the original path should be a subset of the subset path",
+            ));
+        }
+
+        let mut path_iter = query.path[original_path.len()..].iter();
+        let mut new_query = Query::new();
+        if let Some(first_key) = path_iter.next() {
+            new_query.insert_key(first_key.clone());
+        }
+        new_query.set_subquery_path(path_iter.cloned().collect());
+        new_query.set_subquery(query.query.query.clone());
+
+        Ok(PathQuery::new(
+            original_path,
+            SizedQuery::new(new_query, query.query.limit, query.query.offset),
+        ))
+    }
+
+    fn execute_subquery_proof(
+        &mut self,
+        proof_token_type: ProofTokenType,
+        proof: &[u8],
+        proof_reader: &mut ProofReader,
+        query: &PathQuery,
+        path: Path,
+    ) -> Result<CryptoHash, Error> {
+        match proof_token_type {
+            ProofTokenType::SizedMerk => {
+                let (root_hash, _) = self.execute_merk_proof(
+                    ProofTokenType::SizedMerk,
+                    proof,
+                    &query.query.query,
+                    query.query.query.left_to_right,
+                    path,
+                )?;
+                Ok(root_hash)
+            }
+            ProofTokenType::Merk => {
+                // for non leaf subtrees, all the queried keys have an
+                // accompanying proof as long as the limit is non zero and their
+                // child subtree is not empty
+                let (root_hash, children) = self.execute_merk_proof(
+                    ProofTokenType::Merk,
+                    proof,
+                    &query.query.query,
+                    query.query.query.left_to_right,
+                    path,
+                )?;
+                let children = children.ok_or(Error::InvalidProof(
+                    "MERK_PROOF always returns a result set",
+                ))?;
+
+                for child in children {
+                    if self.limit == Some(0) {
+                        break;
+                    }
+                    self.verify_child(child, proof_reader, query)?;
+                }
+                Ok(root_hash)
+            }
+            ProofTokenType::EmptyTree => Ok(EMPTY_TREE_HASH),
+            ProofTokenType::AbsentPath => Err(Error::InvalidProof("wrong proof type")),
+        }
+    }
+
+    /// Verifies a key proven in a non leaf subtree, either adding it to the
+    /// result set or descending into its subquery
+    fn verify_child(
+        &mut self,
+        proved_path_key_value: ProvedPathKeyValue,
+        proof_reader: &mut ProofReader,
+        query: &PathQuery,
+    ) -> Result<(), Error> {
+        let element = Element::deserialize(&proved_path_key_value.value)?;
+        let expected_root_key = match &element {
+            Element::Tree(root_key, _) | Element::SumTree(root_key, ..) => root_key,
+            _ => {
+                // a subquery can't be applied to non tree items
+                self.push_result(proved_path_key_value);
+                return Ok(());
+            }
+        };
+
+        let subquery_branch = query
+            .query
+            .query
+            .subquery_branch_for_key(&proved_path_key_value.key);
+        let subquery_path = subquery_branch.subquery_path.clone();
+        let subquery = subquery_branch.subquery.as_deref().cloned();
+
+        if subquery.is_none() && subquery_path.is_none() {
+            self.push_result(proved_path_key_value);
+            return Ok(());
+        }
+
+        if expected_root_key.is_none() {
+            // the child tree is empty
+            return Ok(());
+        }
+
+        let ProvedPathKeyValue {
+            path,
+            key,
+            value,
+            proof: element_value_hash,
+        } = proved_path_key_value;
+        let mut expected_combined_child_hash = element_value_hash;
+        let mut current_value_bytes = value;
+        let mut new_path = path;
+        new_path.push(key);
+
+        if let Some(mut subquery_path) = subquery_path.filter(|path| !path.is_empty()) {
+            if subquery.is_none() {
+                self.verify_subquery_path(
+                    proof_reader,
+                    ProofTokenType::SizedMerk,
+                    &mut subquery_path,
+                    &mut expected_combined_child_hash,
+                    &mut current_value_bytes,
+                    &mut new_path,
+                )?;
+                return Ok(());
+            }
+
+            let (_, result_set, encountered_absence) = self.verify_subquery_path(
+                proof_reader,
+                ProofTokenType::Merk,
+                &mut subquery_path,
+                &mut expected_combined_child_hash,
+                &mut current_value_bytes,
+                &mut new_path,
+            )?;
+            if encountered_absence {
+                return Ok(());
+            }
+            let result_set = result_set.ok_or(Error::InvalidProof(
+                "expected unsized proof for subquery path as subquery value exists",
+            ))?;
+            if result_set.is_empty() {
+                // the subquery path is proven to be absent
+                return Ok(());
+            }
+            Self::update_root_key_from_subquery_path_element(
+                &mut expected_combined_child_hash,
+                &mut current_value_bytes,
+                &result_set,
+            )?;
+        }
+
+        let Some(subquery) = subquery else {
+            return Ok(());
+        };
+        let new_path_query = PathQuery::new_unsized(vec![], subquery);
+
+        let (child_proof_token_type, child_proof) =
+            proof_reader.read_next_proof(new_path.last().map(Vec::as_slice).unwrap_or_default())?;
+
+        let child_hash = self.execute_subquery_proof(
+            child_proof_token_type,
+            child_proof,
+            proof_reader,
+            &new_path_query,
+            new_path,
+        )?;
+
+        let combined_child_hash = combine_hash(&value_hash(&current_value_bytes), &child_hash);
+        if combined_child_hash != expected_combined_child_hash {
+            return Err(Error::InvalidProof(
+                "child hash doesn't match the expected hash",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Adds a proven item to the result set unless it is covered by the offset
+    fn push_result(&mut self, proved_path_key_value: ProvedPathKeyValue) {
+        let skip_limit = reduce_limit_and_offset_by(&mut self.limit, &mut self.offset, 1);
+        if !skip_limit {
+            self.result_set.push(proved_path_key_value);
+        }
+    }
+
+    /// Updates the expected hash and element value from the tree element the
+    /// subquery path points to
+    fn update_root_key_from_subquery_path_element(
+        expected_child_hash: &mut CryptoHash,
+        current_value_bytes: &mut Vec<u8>,
+        subquery_path_result_set: &[ProvedPathKeyValue],
+    ) -> Result<(), Error> {
+        let proved = &subquery_path_result_set[0];
+        if !Element::deserialize(&proved.value)?.is_tree() {
+            return Err(Error::InvalidProof(
+                "subquery path cannot point to non tree element",
+            ));
+        }
+        *expected_child_hash = proved.proof;
+        *current_value_bytes = proved.value.clone();
+        Ok(())
+    }
+
+    /// Checks that a valid proof showing the existence or absence of the
+    /// subquery path is present
+    fn verify_subquery_path(
+        &mut self,
+        proof_reader: &mut ProofReader,
+        expected_proof_token_type: ProofTokenType,
+        subquery_path: &mut Path,
+        expected_root_hash: &mut CryptoHash,
+        current_value_bytes: &mut Vec<u8>,
+        current_path: &mut Path,
+    ) -> Result<(CryptoHash, Option<Vec<ProvedPathKeyValue>>, bool), Error> {
+        let last_key = subquery_path
+            .pop()
+            .ok_or(Error::InvalidProof("subquery path must not be empty"))?;
+
+        for subquery_key in subquery_path.iter() {
+            let (proof_token_type, subkey_proof) = proof_reader
+                .read_next_proof(current_path.last().map(Vec::as_slice).unwrap_or_default())?;
+            // intermediate proofs are all unsized merk proofs
+            if proof_token_type != ProofTokenType::Merk {
+                return Err(Error::InvalidProof(
+                    "expected MERK proof type for intermediate subquery path keys",
+                ));
+            }
+            let key_as_query = Query::new_single_key(subquery_key.clone());
+            current_path.push(subquery_key.clone());
+
+            let (proof_root_hash, result_set) = self.execute_merk_proof(
+                proof_token_type,
+                subkey_proof,
+                &key_as_query,
+                key_as_query.left_to_right,
+                current_path.clone(),
+            )?;
+            let result_set = result_set.unwrap_or_default();
+
+            // an empty result set proves the absence of the key
+            if result_set.is_empty() {
+                return Ok((proof_root_hash, None, true));
+            }
+
+            // the subquery path elements have to be linked by their hashes
+            let combined_child_hash =
+                combine_hash(&value_hash(current_value_bytes), &proof_root_hash);
+            if combined_child_hash != *expected_root_hash {
+                return Err(Error::InvalidProof(
+                    "child hash doesn't match the expected hash",
+                ));
+            }
+
+            Self::update_root_key_from_subquery_path_element(
+                expected_root_hash,
+                current_value_bytes,
+                &result_set,
+            )?;
+        }
+
+        let (proof_token_type, subkey_proof) = proof_reader
+            .read_next_proof(current_path.last().map(Vec::as_slice).unwrap_or_default())?;
+        if proof_token_type != expected_proof_token_type {
+            return Err(Error::InvalidProof(
+                "unexpected proof type for subquery path",
+            ));
+        }
+
+        let key_as_query = Query::new_single_key(last_key.clone());
+        current_path.push(last_key);
+
+        let (root_hash, result_set) = self.execute_merk_proof(
+            proof_token_type,
+            subkey_proof,
+            &key_as_query,
+            key_as_query.left_to_right,
+            current_path.clone(),
+        )?;
+
+        Ok((root_hash, result_set, false))
+    }
+
+    fn verify_absent_path(
+        &mut self,
+        proof_reader: &mut ProofReader,
+        path: &[Vec<u8>],
+    ) -> Result<CryptoHash, Error> {
+        let mut root_key_hash = None;
+        let mut expected_child_hash = None;
+        let mut last_result_set: Vec<ProvedPathKeyValue> = vec![];
+
+        for key in path {
+            let (proof_token_type, merk_proof, _) = proof_reader.read_proof()?;
+            if proof_token_type != ProofTokenType::Merk {
+                return Err(Error::InvalidProof("expected a merk proof for absent path"));
+            }
+
+            let child_query = Query::new_single_key(key.clone());
+            let (proof_root_hash, result_set) = self.execute_merk_proof(
+                ProofTokenType::Merk,
+                merk_proof,
+                &child_query,
+                true,
+                Vec::new(),
+            )?;
+
+            match expected_child_hash {
+                None => root_key_hash = Some(proof_root_hash),
+                Some(expected_child_hash) => {
+                    let combined_hash = combine_hash(
+                        &value_hash(last_result_set[0].value.as_slice()),
+                        &proof_root_hash,
+                    );
+                    if combined_hash != expected_child_hash {
+                        return Err(Error::InvalidProof("proof invalid: invalid parent"));
+                    }
+                }
+            }
+
+            last_result_set = result_set.unwrap_or_default();
+            let Some(proved) = last_result_set.first() else {
+                // the absence point is reached
+                break;
+            };
+
+            if !Element::deserialize(&proved.value)?.is_tree() {
+                return Err(Error::InvalidProof(
+                    "intermediate proofs should be for trees",
+                ));
+            }
+            expected_child_hash = Some(proved.proof);
+        }
+
+        match (last_result_set.is_empty(), root_key_hash) {
+            (true, Some(hash)) => Ok(hash),
+            (true, None) => Err(Error::InvalidProof("proof invalid: no non root tree found")),
+            (false, _) => Err(Error::InvalidProof("proof invalid: path not absent")),
+        }
+    }
+
+    /// Verifies that the correct proof was provided to confirm the path in
+    /// query
+    fn verify_path_to_root(
+        &mut self,
+        query: &PathQuery,
+        path: &[Vec<u8>],
+        proof_reader: &mut ProofReader,
+        expected_root_hash: &mut CryptoHash,
+    ) -> Result<CryptoHash, Error> {
+        let mut split_path = path.split_last();
+        while let Some((key, parent_path)) = split_path {
+            // every subtree needs a proof in its parent showing it is a child
+            let (proof_token_type, parent_merk_proof) = proof_reader
+                .read_next_proof(parent_path.last().map(Vec::as_slice).unwrap_or_default())?;
+            if proof_token_type != ProofTokenType::Merk {
+                return Err(Error::InvalidProof("wrong data_type expected merk proof"));
+            }
+
+            let parent_query = Query::new_single_key(key.clone());
+            let (proof_root_hash, result_set) = self.execute_merk_proof(
+                ProofTokenType::Merk,
+                parent_merk_proof,
+                &parent_query,
+                query.query.query.left_to_right,
+                Vec::new(),
+            )?;
+
+            let result_set = result_set.unwrap_or_default();
+            let proved = match result_set.first() {
+                Some(proved) if &proved.key == key => proved,
+                _ => return Err(Error::InvalidProof("proof invalid: invalid parent")),
+            };
+
+            if !Element::deserialize(&proved.value)?.is_tree() {
+                return Err(Error::InvalidProof(
+                    "intermediate proofs should be for trees",
+                ));
+            }
+
+            let combined_root_hash = combine_hash(&value_hash(&proved.value), expected_root_hash);
+            if proved.proof != combined_root_hash {
+                return Err(Error::InvalidProof(
+                    "bad path: tree hash does not have expected hash",
+                ));
+            }
+
+            *expected_root_hash = proof_root_hash;
+            split_path = parent_path.split_last();
+        }
+
+        Ok(*expected_root_hash)
+    }
+
+    /// Execute a merk proof, sized proofs consume the limit and offset and
+    /// add to the result set
+    fn execute_merk_proof(
+        &mut self,
+        proof_token_type: ProofTokenType,
+        proof: &[u8],
+        query: &Query,
+        left_to_right: bool,
+        path: Path,
+    ) -> Result<(CryptoHash, Option<Vec<ProvedPathKeyValue>>), Error> {
+        let is_sized_proof = proof_token_type == ProofTokenType::SizedMerk;
+        let (limit, offset) = if is_sized_proof {
+            (self.limit, self.offset)
+        } else {
+            (None, None)
+        };
+
+        let (hash, result) = merk::execute_proof(proof, query, limit, offset, left_to_right)?;
+
+        let proved_path_key_values =
+            ProvedPathKeyValue::from_proved_key_values(path, result.result_set);
+
+        if is_sized_proof {
+            self.limit = result.limit;
+            self.offset = result.offset;
+            self.result_set.extend(proved_path_key_values);
+            Ok((hash, None))
+        } else {
+            Ok((hash, Some(proved_path_key_values)))
+        }
+    }
+}